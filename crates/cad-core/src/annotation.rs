@@ -0,0 +1,85 @@
+//! Measurements stored on the model.
+
+use crate::ObjectId;
+use serde::{Deserialize, Serialize};
+
+pub type AnnotationId = u64;
+
+/// A point a measurement is taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Anchor {
+    /// Fixed point in world space.
+    World([f32; 3]),
+    /// Point in a body's local space; follows the body when it moves.
+    Object { object: ObjectId, local: [f32; 3] },
+}
+
+impl Anchor {
+    pub fn object(&self) -> Option<ObjectId> {
+        match *self {
+            Self::World(_) => None,
+            Self::Object { object, .. } => Some(object),
+        }
+    }
+
+    pub(crate) fn scale_lengths(&mut self, factor: f32) {
+        let (Self::World(p) | Self::Object { local: p, .. }) = self;
+        *p = p.map(|c| c * factor);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Measurement {
+    Distance {
+        a: Anchor,
+        b: Anchor,
+    },
+    /// Angle at `vertex` between the rays towards `a` and `b`, in radians.
+    Angle {
+        vertex: Anchor,
+        a: Anchor,
+        b: Anchor,
+    },
+    /// Radius of a round primitive (cylinder, tube or prism circumradius).
+    Radius {
+        object: ObjectId,
+    },
+}
+
+impl Measurement {
+    /// Bodies the measurement depends on.
+    pub fn objects(&self) -> Vec<ObjectId> {
+        match self {
+            Self::Distance { a, b } => [a, b].iter().filter_map(|a| a.object()).collect(),
+            Self::Angle { vertex, a, b } => {
+                [vertex, a, b].iter().filter_map(|a| a.object()).collect()
+            }
+            Self::Radius { object } => vec![*object],
+        }
+    }
+
+    pub(crate) fn scale_lengths(&mut self, factor: f32) {
+        match self {
+            Self::Distance { a, b } => {
+                a.scale_lengths(factor);
+                b.scale_lengths(factor);
+            }
+            Self::Angle { vertex, a, b } => {
+                for anchor in [vertex, a, b] {
+                    anchor.scale_lengths(factor);
+                }
+            }
+            Self::Radius { .. } => {}
+        }
+    }
+}
+
+/// A persistent measurement, re-evaluated against the current geometry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Annotation {
+    pub id: AnnotationId,
+    pub measurement: Measurement,
+}
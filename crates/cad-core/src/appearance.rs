@@ -0,0 +1,48 @@
+//! How bodies are drawn in the viewport.
+
+use serde::{Deserialize, Serialize};
+
+/// Per-object display material.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Appearance {
+    /// Linear RGBA, each channel in `0..=1`.
+    pub color: [f32; 4],
+    /// Microfacet roughness in `0..=1`; low values give sharp highlights.
+    pub roughness: f32,
+    /// `0` for dielectrics, `1` for bare metal.
+    pub metalness: f32,
+    /// Draw the body's edges only instead of shaded faces.
+    #[serde(default)]
+    pub wireframe: bool,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            color: [0.78, 0.8, 0.84, 1.0],
+            roughness: 0.5,
+            metalness: 0.0,
+            wireframe: false,
+        }
+    }
+}
+
+impl Appearance {
+    /// Copy with every value clamped to its valid range.
+    pub fn clamped(self) -> Self {
+        let unit = |v: f32| {
+            if v.is_finite() {
+                v.clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        };
+        Self {
+            color: self.color.map(unit),
+            roughness: unit(self.roughness),
+            metalness: unit(self.metalness),
+            wireframe: self.wireframe,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Typed object properties and the bill of materials built from them.
+
+use crate::{Model, ObjectId};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Property keys the bill of materials understands.
+pub mod keys {
+    /// Groups bodies into one BOM line; defaults to the body name.
+    pub const PART_NUMBER: &str = "part_number";
+    /// Count contributed by the body (1 when unset).
+    pub const QUANTITY: &str = "quantity";
+    pub const MATERIAL: &str = "material";
+    pub const SUPPLIER: &str = "supplier";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum PropertyValue {
+    Text(String),
+    Integer(i64),
+    Number(f64),
+    Bool(bool),
+}
+
+impl PropertyValue {
+    /// Whole-number view, for counts such as [`keys::QUANTITY`].
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            Self::Integer(value) => Some(value),
+            Self::Number(value) if value.is_finite() && value.fract() == 0.0 => Some(value as i64),
+            Self::Text(ref text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PropertyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(text) => f.write_str(text),
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::Number(value) => write!(f, "{value}"),
+            Self::Bool(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl From<&str> for PropertyValue {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<String> for PropertyValue {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<i64> for PropertyValue {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<f64> for PropertyValue {
+    fn from(value: f64) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<bool> for PropertyValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+/// One line of the bill of materials: identical parts counted together.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BomLine {
+    pub part: String,
+    pub material: Option<String>,
+    pub supplier: Option<String>,
+    pub quantity: i64,
+    pub objects: Vec<ObjectId>,
+}
+
+impl Model {
+    /// Sets a typed property, returning the previous value.
+    pub fn set_property(
+        &mut self,
+        id: ObjectId,
+        key: impl Into<String>,
+        value: impl Into<PropertyValue>,
+    ) -> Option<Option<PropertyValue>> {
+        let obj = self.objects.iter_mut().find(|obj| obj.id == id)?;
+        Some(obj.properties.insert(key.into(), value.into()))
+    }
+
+    pub fn remove_property(&mut self, id: ObjectId, key: &str) -> Option<PropertyValue> {
+        let obj = self.objects.iter_mut().find(|obj| obj.id == id)?;
+        obj.properties.remove(key)
+    }
+
+    /// Aggregates bodies by part number (or name), material and supplier,
+    /// summing their quantities. Lines are sorted by part.
+    pub fn bill_of_materials(&self) -> Vec<BomLine> {
+        let mut lines: BTreeMap<(String, Option<String>, Option<String>), BomLine> =
+            BTreeMap::new();
+        for obj in &self.objects {
+            let text = |key: &str| obj.properties.get(key).map(ToString::to_string);
+            let part = text(keys::PART_NUMBER).unwrap_or_else(|| obj.display_name());
+            let (material, supplier) = (text(keys::MATERIAL), text(keys::SUPPLIER));
+            let quantity = obj
+                .properties
+                .get(keys::QUANTITY)
+                .and_then(PropertyValue::as_integer)
+                .unwrap_or(1);
+            let line = lines
+                .entry((part.clone(), material.clone(), supplier.clone()))
+                .or_insert_with(|| BomLine {
+                    part,
+                    material,
+                    supplier,
+                    quantity: 0,
+                    objects: Vec::new(),
+                });
+            line.quantity += quantity;
+            line.objects.push(obj.id);
+        }
+        lines.into_values().collect()
+    }
+}
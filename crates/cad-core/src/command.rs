@@ -0,0 +1,273 @@
+//! Invertible model edits and an undo/redo stack built on them.
+
+use crate::{Appearance, ComponentId, Feature, LayerId, Model, ObjectId, ObjectKind, Transform};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A single edit of the model. Applying a command returns its inverse,
+/// which is what undo applies later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ModelCommand {
+    AddObject {
+        kind: ObjectKind,
+    },
+    /// Moves an object to the trash.
+    Delete {
+        id: ObjectId,
+    },
+    /// Takes an object out of the trash; the inverse of
+    /// [`ModelCommand::Delete`].
+    Restore {
+        id: ObjectId,
+    },
+    SetTransform {
+        id: ObjectId,
+        transform: Transform,
+    },
+    SetKind {
+        id: ObjectId,
+        kind: ObjectKind,
+    },
+    SetName {
+        id: ObjectId,
+        name: String,
+    },
+    SetAppearance {
+        id: ObjectId,
+        appearance: Appearance,
+    },
+    SetVisible {
+        id: ObjectId,
+        visible: bool,
+    },
+    SetLocked {
+        id: ObjectId,
+        locked: bool,
+    },
+    SetLayer {
+        id: ObjectId,
+        layer: Option<LayerId>,
+    },
+    SetParent {
+        id: ObjectId,
+        parent: Option<ComponentId>,
+    },
+    /// Replaces an object's whole feature history.
+    SetFeatures {
+        id: ObjectId,
+        features: Vec<Feature>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    UnknownObject(ObjectId),
+    UnknownLayer(LayerId),
+    UnknownComponent(ComponentId),
+    /// A restored object's id is taken.
+    IdInUse(ObjectId),
+    InvalidPrimitive(&'static str),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownObject(id) => write!(f, "unknown object {id}"),
+            Self::UnknownLayer(id) => write!(f, "unknown layer {id}"),
+            Self::UnknownComponent(id) => write!(f, "unknown component {id}"),
+            Self::IdInUse(id) => write!(f, "object id {id} is already in use"),
+            Self::InvalidPrimitive(reason) => write!(f, "invalid primitive: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Something commands can be applied to: the bare [`Model`], or a scene
+/// that keeps derived data (solids, meshes) in step with it.
+pub trait CommandTarget {
+    type Error;
+
+    /// Applies `command`, returning the command that reverts it.
+    fn apply(&mut self, command: ModelCommand) -> Result<ModelCommand, Self::Error>;
+}
+
+impl CommandTarget for Model {
+    type Error = CommandError;
+
+    fn apply(&mut self, command: ModelCommand) -> Result<ModelCommand, CommandError> {
+        use ModelCommand as C;
+        let known = |model: &Model, id| {
+            model
+                .object(id)
+                .cloned()
+                .ok_or(CommandError::UnknownObject(id))
+        };
+        Ok(match command {
+            C::AddObject { kind } => {
+                kind.validate().map_err(CommandError::InvalidPrimitive)?;
+                C::Delete {
+                    id: self.add_object(kind),
+                }
+            }
+            C::Delete { id } => {
+                if !self.delete(id) {
+                    return Err(CommandError::UnknownObject(id));
+                }
+                C::Restore { id }
+            }
+            C::Restore { id } => {
+                if self.object(id).is_some() {
+                    return Err(CommandError::IdInUse(id));
+                }
+                if !self.restore(id) {
+                    return Err(CommandError::UnknownObject(id));
+                }
+                C::Delete { id }
+            }
+            C::SetTransform { id, transform } => {
+                let old = known(self, id)?.transform;
+                self.set_transform(id, transform);
+                C::SetTransform { id, transform: old }
+            }
+            C::SetKind { id, kind } => {
+                kind.validate().map_err(CommandError::InvalidPrimitive)?;
+                let old = known(self, id)?.kind;
+                self.set_kind(id, kind);
+                C::SetKind { id, kind: old }
+            }
+            C::SetName { id, name } => {
+                let old = known(self, id)?.name;
+                self.set_name(id, name);
+                C::SetName { id, name: old }
+            }
+            C::SetAppearance { id, appearance } => {
+                let old = known(self, id)?.appearance;
+                self.set_appearance(id, appearance);
+                C::SetAppearance {
+                    id,
+                    appearance: old,
+                }
+            }
+            C::SetVisible { id, visible } => {
+                let old = known(self, id)?.visible;
+                self.set_visible(id, visible);
+                C::SetVisible { id, visible: old }
+            }
+            C::SetLocked { id, locked } => {
+                let old = known(self, id)?.locked;
+                self.set_locked(id, locked);
+                C::SetLocked { id, locked: old }
+            }
+            C::SetLayer { id, layer } => {
+                let old = known(self, id)?.layer;
+                if !self.set_object_layer(id, layer) {
+                    return Err(CommandError::UnknownLayer(layer.unwrap_or_default()));
+                }
+                C::SetLayer { id, layer: old }
+            }
+            C::SetParent { id, parent } => {
+                let old = known(self, id)?.parent;
+                if !self.set_object_parent(id, parent) {
+                    return Err(CommandError::UnknownComponent(parent.unwrap_or_default()));
+                }
+                C::SetParent { id, parent: old }
+            }
+            C::SetFeatures { id, features } => {
+                let old = known(self, id)?.features;
+                self.set_features(id, features);
+                C::SetFeatures { id, features: old }
+            }
+        })
+    }
+}
+
+/// Undo and redo history of applied commands.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoStack {
+    undo: Vec<ModelCommand>,
+    redo: Vec<ModelCommand>,
+    /// Oldest entries are dropped beyond this many undo steps.
+    limit: usize,
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl UndoStack {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    /// Applies a new command, recording its inverse and clearing redo.
+    pub fn execute<T: CommandTarget>(
+        &mut self,
+        target: &mut T,
+        command: ModelCommand,
+    ) -> Result<(), T::Error> {
+        let inverse = target.apply(command)?;
+        self.redo.clear();
+        self.push_undo(inverse);
+        Ok(())
+    }
+
+    /// Reverts the last command. Returns `false` when there is nothing to
+    /// undo. A failing step is dropped from the history.
+    pub fn undo<T: CommandTarget>(&mut self, target: &mut T) -> Result<bool, T::Error> {
+        let Some(command) = self.undo.pop() else {
+            return Ok(false);
+        };
+        self.redo.push(target.apply(command)?);
+        Ok(true)
+    }
+
+    /// Re-applies the last undone command. Returns `false` when there is
+    /// nothing to redo. A failing step is dropped from the history.
+    pub fn redo<T: CommandTarget>(&mut self, target: &mut T) -> Result<bool, T::Error> {
+        let Some(command) = self.redo.pop() else {
+            return Ok(false);
+        };
+        let inverse = target.apply(command)?;
+        self.push_undo(inverse);
+        Ok(true)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// The command [`UndoStack::undo`] would apply next: the inverse of the
+    /// last edit.
+    pub fn next_undo(&self) -> Option<&ModelCommand> {
+        self.undo.last()
+    }
+
+    /// The command [`UndoStack::redo`] would apply next.
+    pub fn next_redo(&self) -> Option<&ModelCommand> {
+        self.redo.last()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn push_undo(&mut self, command: ModelCommand) {
+        if self.undo.len() == self.limit {
+            self.undo.remove(0);
+        }
+        self.undo.push(command);
+    }
+}
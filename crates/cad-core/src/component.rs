@@ -0,0 +1,19 @@
+//! Components: named nodes that own bodies and other components.
+
+use crate::Transform;
+use serde::{Deserialize, Serialize};
+
+pub type ComponentId = u64;
+
+/// A node of the assembly tree. Its transform places its children relative
+/// to its own parent, so placements compose from the root down.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Component {
+    pub id: ComponentId,
+    pub name: String,
+    pub transform: Transform,
+    /// `None` for top-level components.
+    #[serde(default)]
+    pub parent: Option<ComponentId>,
+}
@@ -0,0 +1,138 @@
+//! Datums: reference planes, axes and points placed in the model.
+
+use crate::{Model, SketchPlane, Transform};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+pub type DatumId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum DatumKind {
+    /// The XY plane of the datum's frame.
+    Plane,
+    /// The Z axis of the datum's frame.
+    Axis,
+    /// The origin of the datum's frame.
+    Point,
+}
+
+/// Construction geometry that sketches and features can be placed against.
+/// The transform places a frame in world space; the kind says which part of
+/// that frame the datum stands for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Datum {
+    pub id: DatumId,
+    pub name: String,
+    pub kind: DatumKind,
+    pub transform: Transform,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+impl Datum {
+    pub fn origin(&self) -> [f32; 3] {
+        self.transform.translation
+    }
+
+    /// The plane of a [`DatumKind::Plane`] datum.
+    pub fn plane(&self) -> Option<SketchPlane> {
+        if self.kind != DatumKind::Plane {
+            return None;
+        }
+        let rotation = self.transform.rotation_quat();
+        Some(SketchPlane::new(
+            self.origin(),
+            (rotation * Vec3::X).to_array(),
+            (rotation * Vec3::Y).to_array(),
+        ))
+    }
+
+    /// Origin and unit direction of a [`DatumKind::Axis`] datum.
+    pub fn axis(&self) -> Option<([f32; 3], [f32; 3])> {
+        if self.kind != DatumKind::Axis {
+            return None;
+        }
+        let direction = self.transform.rotation_quat() * Vec3::Z;
+        Some((self.origin(), direction.to_array()))
+    }
+}
+
+impl Model {
+    pub fn datums(&self) -> &[Datum] {
+        &self.datums
+    }
+
+    pub fn datum(&self, id: DatumId) -> Option<&Datum> {
+        self.datums.iter().find(|datum| datum.id == id)
+    }
+
+    pub fn add_datum(
+        &mut self,
+        name: impl Into<String>,
+        kind: DatumKind,
+        transform: Transform,
+    ) -> DatumId {
+        let id = self.next_datum_id;
+        self.next_datum_id += 1;
+        self.datums.push(Datum {
+            id,
+            name: name.into(),
+            kind,
+            transform,
+            visible: true,
+        });
+        id
+    }
+
+    pub fn remove_datum(&mut self, id: DatumId) -> Option<Datum> {
+        let idx = self.datums.iter().position(|datum| datum.id == id)?;
+        Some(self.datums.remove(idx))
+    }
+
+    pub fn rename_datum(&mut self, id: DatumId, name: impl Into<String>) -> bool {
+        self.update_datum(id, |datum| datum.name = name.into())
+    }
+
+    pub fn set_datum_transform(&mut self, id: DatumId, transform: Transform) -> bool {
+        self.update_datum(id, |datum| datum.transform = transform)
+    }
+
+    pub fn set_datum_visible(&mut self, id: DatumId, visible: bool) -> bool {
+        self.update_datum(id, |datum| datum.visible = visible)
+    }
+
+    /// Planes a sketch can be placed on, by name: the three origin planes,
+    /// then every plane datum.
+    pub fn sketch_planes(&self) -> Vec<(String, SketchPlane)> {
+        let origin = [
+            ("XY Plane", SketchPlane::XY),
+            ("XZ Plane", SketchPlane::XZ),
+            ("YZ Plane", SketchPlane::YZ),
+        ];
+        origin
+            .into_iter()
+            .map(|(name, plane)| (name.to_string(), plane))
+            .chain(
+                self.datums
+                    .iter()
+                    .filter_map(|datum| Some((datum.name.clone(), datum.plane()?))),
+            )
+            .collect()
+    }
+
+    fn update_datum(&mut self, id: DatumId, f: impl FnOnce(&mut Datum)) -> bool {
+        match self.datums.iter_mut().find(|datum| datum.id == id) {
+            Some(datum) => {
+                f(datum);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Object-level differences between two model states.
+
+use crate::{CommandError, Model, ModelObject, ObjectId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The object changes turning one model state into another, for syncing
+/// peers and writing incremental autosaves. Only bodies are covered; mates
+/// and annotations follow their bodies on removal as in [`Model::remove`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ModelDelta {
+    /// New objects with their position in the target model's object list.
+    pub added: Vec<(usize, ModelObject)>,
+    pub removed: Vec<ObjectId>,
+    /// Full replacement for each object that differs.
+    pub changed: Vec<ModelObject>,
+    /// The target model's next object id, so both sides keep minting the
+    /// same ids.
+    pub next_id: ObjectId,
+}
+
+impl ModelDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Model {
+    /// Delta that turns `self` into `newer` when applied to `self`.
+    pub fn diff(&self, newer: &Model) -> ModelDelta {
+        let removed = self
+            .objects
+            .iter()
+            .filter(|obj| newer.object(obj.id).is_none())
+            .map(|obj| obj.id)
+            .collect();
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for (index, obj) in newer.objects.iter().enumerate() {
+            match self.object(obj.id) {
+                None => added.push((index, obj.clone())),
+                Some(old) if old != obj => changed.push(obj.clone()),
+                Some(_) => {}
+            }
+        }
+        ModelDelta {
+            added,
+            removed,
+            changed,
+            next_id: newer.next_id,
+        }
+    }
+
+    /// Applies a delta: removals first, then replacements, then additions
+    /// at their recorded positions. The model is left untouched if the
+    /// delta does not fit it.
+    pub fn apply_delta(&mut self, delta: &ModelDelta) -> Result<(), CommandError> {
+        let removed: HashSet<ObjectId> = delta.removed.iter().copied().collect();
+        for &id in &delta.removed {
+            if self.object(id).is_none() {
+                return Err(CommandError::UnknownObject(id));
+            }
+        }
+        for obj in &delta.changed {
+            if self.object(obj.id).is_none() || removed.contains(&obj.id) {
+                return Err(CommandError::UnknownObject(obj.id));
+            }
+        }
+        let mut seen = HashSet::new();
+        for (_, obj) in &delta.added {
+            let taken = self.object(obj.id).is_some() && !removed.contains(&obj.id);
+            if taken || !seen.insert(obj.id) {
+                return Err(CommandError::IdInUse(obj.id));
+            }
+        }
+
+        for &id in &delta.removed {
+            self.remove(id);
+        }
+        for obj in &delta.changed {
+            if let Some(slot) = self.objects.iter_mut().find(|slot| slot.id == obj.id) {
+                *slot = obj.clone();
+            }
+        }
+        let mut added: Vec<&(usize, ModelObject)> = delta.added.iter().collect();
+        added.sort_by_key(|(index, _)| *index);
+        for (index, obj) in added {
+            let index = (*index).min(self.objects.len());
+            self.objects.insert(index, obj.clone());
+            self.next_id = self.next_id.max(obj.id.saturating_add(1));
+        }
+        self.next_id = self.next_id.max(delta.next_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! Bodies that are not primitives: stored meshes and solids computed from
+//! other bodies or sketches.
+
+use crate::{BooleanOp, Model, ObjectId, ObjectKind, SketchId};
+use serde::{Deserialize, Serialize};
+
+pub type MeshHandle = u64;
+
+/// A triangle mesh stored with the document (e.g. an imported STL), drawn
+/// by [`ObjectKind::Mesh`] bodies. Positions are in body-local space.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MeshAsset {
+    pub handle: MeshHandle,
+    pub name: String,
+    pub positions: Vec<[f32; 3]>,
+    /// Counter-clockwise triangles, three indices each.
+    pub indices: Vec<u32>,
+}
+
+/// An input of a [`ObjectKind::Derived`] body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Operand {
+    Object(ObjectId),
+    Sketch(SketchId),
+}
+
+/// How a [`ObjectKind::Derived`] body is computed from its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum DerivedOp {
+    /// Folds `op` over two or more object operands, in order.
+    Boolean(BooleanOp),
+    /// Extrudes the closed loops of one sketch along its normal.
+    Extrude { distance: f32 },
+    /// Revolves one sketch by `angle` radians around an in-plane axis.
+    Revolve {
+        axis_origin: [f32; 2],
+        axis_dir: [f32; 2],
+        angle: f32,
+    },
+}
+
+impl DerivedOp {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Boolean(BooleanOp::Union) => "Union",
+            Self::Boolean(BooleanOp::Subtract) => "Subtract",
+            Self::Boolean(BooleanOp::Intersect) => "Intersect",
+            Self::Extrude { .. } => "Extrusion",
+            Self::Revolve { .. } => "Revolve",
+        }
+    }
+
+    /// Checks the parameters and the number and type of operands.
+    pub(crate) fn validate(&self, operands: &[Operand]) -> Result<(), &'static str> {
+        let objects = operands
+            .iter()
+            .filter(|operand| matches!(operand, Operand::Object(_)))
+            .count();
+        match *self {
+            Self::Boolean(_) => {
+                if objects < 2 || objects != operands.len() {
+                    return Err("boolean needs two or more body operands");
+                }
+            }
+            Self::Extrude { distance } => {
+                if !(distance.is_finite() && distance != 0.0) {
+                    return Err("extrusion distance must be non-zero");
+                }
+            }
+            Self::Revolve { angle, .. } => {
+                if !(angle.is_finite() && angle != 0.0) {
+                    return Err("revolve angle must be non-zero");
+                }
+            }
+        }
+        if !matches!(self, Self::Boolean(_)) && !matches!(operands, [Operand::Sketch(_)]) {
+            return Err("extrusions and revolves take a single sketch operand");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn scale_lengths(&mut self, factor: f32) {
+        match self {
+            Self::Boolean(_) => {}
+            Self::Extrude { distance } => *distance *= factor,
+            Self::Revolve { axis_origin, .. } => *axis_origin = axis_origin.map(|c| c * factor),
+        }
+    }
+}
+
+impl Model {
+    pub fn mesh_assets(&self) -> &[MeshAsset] {
+        &self.mesh_assets
+    }
+
+    pub fn mesh_asset(&self, handle: MeshHandle) -> Option<&MeshAsset> {
+        self.mesh_assets.iter().find(|asset| asset.handle == handle)
+    }
+
+    /// Stores a triangle mesh for [`ObjectKind::Mesh`] bodies to draw.
+    pub fn add_mesh_asset(
+        &mut self,
+        name: impl Into<String>,
+        positions: Vec<[f32; 3]>,
+        indices: Vec<u32>,
+    ) -> MeshHandle {
+        let handle = self.next_mesh_handle;
+        self.next_mesh_handle = self.next_mesh_handle.saturating_add(1);
+        self.mesh_assets.push(MeshAsset {
+            handle,
+            name: name.into(),
+            positions,
+            indices,
+        });
+        handle
+    }
+
+    /// Removes a mesh asset no body draws anymore.
+    pub fn remove_mesh_asset(&mut self, handle: MeshHandle) -> Option<MeshAsset> {
+        let in_use = self
+            .objects
+            .iter()
+            .any(|obj| obj.kind == ObjectKind::Mesh { handle });
+        if in_use {
+            return None;
+        }
+        let idx = self
+            .mesh_assets
+            .iter()
+            .position(|asset| asset.handle == handle)?;
+        Some(self.mesh_assets.remove(idx))
+    }
+
+    /// Adds a body drawing a stored mesh.
+    pub fn add_mesh(&mut self, handle: MeshHandle) -> Option<ObjectId> {
+        self.mesh_asset(handle)?;
+        Some(self.add_object(ObjectKind::Mesh { handle }))
+    }
+
+    /// Adds a body computed from `operands`. Operands are not checked to
+    /// exist; a body whose operand is removed fails to regenerate.
+    pub fn add_derived(&mut self, op: DerivedOp, operands: Vec<Operand>) -> ObjectId {
+        self.add_object(ObjectKind::Derived { op, operands })
+    }
+}
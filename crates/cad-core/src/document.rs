@@ -0,0 +1,31 @@
+//! Document-level information saved with the model.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct DocumentInfo {
+    pub title: String,
+    pub author: String,
+    pub description: String,
+    /// Unix time in milliseconds; `0` when unknown.
+    pub created_ms: u64,
+    /// Unix time in milliseconds of the last recorded change.
+    pub modified_ms: u64,
+    /// Counts recorded changes; see [`crate::Model::touch`].
+    pub revision: u64,
+}
+
+impl Default for DocumentInfo {
+    fn default() -> Self {
+        Self {
+            title: "Untitled".to_string(),
+            author: String::new(),
+            description: String::new(),
+            created_ms: 0,
+            modified_ms: 0,
+            revision: 0,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Per-body feature history.
+
+use crate::ObjectId;
+use serde::{Deserialize, Serialize};
+
+pub type FeatureId = u64;
+
+/// How a [`crate::DerivedOp::Boolean`] body combines its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum BooleanOp {
+    Union,
+    Subtract,
+    Intersect,
+}
+
+/// An operation replayed on top of a body's base primitive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum FeatureOp {
+    /// Cut another body's solid out of this one, where the two are placed
+    /// in the world.
+    Subtract { tool: ObjectId },
+    /// Repeat the current solid `count` times along `direction`.
+    LinearPattern {
+        direction: [f32; 3],
+        spacing: f32,
+        count: u32,
+    },
+    /// Move the planar face through `point` facing along `normal` by
+    /// `distance`, extending the faces around it. Body-local space.
+    PushPull {
+        point: [f32; 3],
+        normal: [f32; 3],
+        distance: f32,
+    },
+}
+
+impl FeatureOp {
+    /// The other body whose solid this feature reads, if any.
+    pub fn tool(&self) -> Option<ObjectId> {
+        match *self {
+            Self::Subtract { tool } => Some(tool),
+            Self::LinearPattern { .. } | Self::PushPull { .. } => None,
+        }
+    }
+
+    pub(crate) fn scale_lengths(&mut self, factor: f32) {
+        match self {
+            Self::Subtract { .. } => {}
+            Self::LinearPattern { spacing, .. } => *spacing *= factor,
+            Self::PushPull {
+                point, distance, ..
+            } => {
+                *point = point.map(|c| c * factor);
+                *distance *= factor;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Feature {
+    pub id: FeatureId,
+    pub op: FeatureOp,
+    /// Suppressed features are kept in the history but skipped on replay.
+    #[serde(default)]
+    pub suppressed: bool,
+}
@@ -0,0 +1,68 @@
+//! Grouping bodies under a component without moving them.
+
+use crate::{ComponentId, Model, ObjectId, Transform};
+use glam::Mat4;
+
+impl Model {
+    /// Local-to-world matrix of a component chain (identity for `None`).
+    pub fn parent_matrix(&self, parent: Option<ComponentId>) -> Mat4 {
+        self.component_ancestors(parent)
+            .fold(Mat4::IDENTITY, |m, component| {
+                component.transform.to_mat4() * m
+            })
+    }
+
+    /// Puts `objects` into a new component with an identity transform,
+    /// keeping their world placement; moving the group then moves them all.
+    /// The group sits under the objects' common parent, or at the top level
+    /// if they have none. Returns `None` for an empty or unknown selection.
+    pub fn group(&mut self, name: impl Into<String>, objects: &[ObjectId]) -> Option<ComponentId> {
+        let parents = objects
+            .iter()
+            .map(|&id| self.object(id).map(|obj| obj.parent))
+            .collect::<Option<Vec<_>>>()?;
+        let first = *parents.first()?;
+        let parent = first.filter(|_| parents.iter().all(|p| *p == first));
+        let group = self.add_component(name, parent)?;
+        for &id in objects {
+            self.reparent_in_place(id, Some(group));
+        }
+        Some(group)
+    }
+
+    /// Dissolves a group: its bodies and sub-components move up to its
+    /// parent with the group's transform baked into their own, so nothing
+    /// moves on screen. Returns the bodies that were in the group.
+    pub fn ungroup(&mut self, group: ComponentId) -> Option<Vec<ObjectId>> {
+        let removed = self.component(group)?.clone();
+        let members: Vec<ObjectId> = self
+            .objects
+            .iter()
+            .filter(|obj| obj.parent == Some(group))
+            .map(|obj| obj.id)
+            .collect();
+        for &id in &members {
+            self.reparent_in_place(id, removed.parent);
+        }
+        for component in &mut self.components {
+            if component.parent == Some(group) {
+                component.transform = removed.transform.compose(&component.transform);
+            }
+        }
+        self.remove_component(group);
+        Some(members)
+    }
+
+    /// Moves an object under `parent`, adjusting its transform so its world
+    /// placement stays the same.
+    fn reparent_in_place(&mut self, id: ObjectId, parent: Option<ComponentId>) {
+        let Some(obj) = self.object(id) else {
+            return;
+        };
+        let world = self.parent_matrix(obj.parent) * obj.transform.to_mat4();
+        let local = Transform::from_mat4(self.parent_matrix(parent).inverse() * world);
+        if self.set_object_parent(id, parent) {
+            self.set_transform(id, local);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Identities that stay unique across documents and clients.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Identifies the client (editor session or tool) that created an object.
+/// Client `0` is reserved for documents saved before stable ids existed.
+pub type ClientId = u64;
+
+/// A random client id, different on every call and never
+/// [`StableId::LEGACY_CLIENT`].
+pub fn fresh_client_id() -> ClientId {
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+    // Randomly keyed per process; the count tells calls apart.
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(CALLS.fetch_add(1, Ordering::Relaxed));
+    hasher.finish().max(1)
+}
+
+/// A body's identity across documents: the creating client plus that
+/// client's sequence number. Unlike [`crate::ObjectId`], which is a
+/// per-document handle, two documents (or two collaborating clients with
+/// distinct client ids) never mint the same `StableId`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct StableId {
+    pub client: ClientId,
+    pub seq: u64,
+}
+
+impl StableId {
+    pub const LEGACY_CLIENT: ClientId = 0;
+
+    /// The identity given to objects from saves without stable ids.
+    pub fn legacy(seq: u64) -> Self {
+        Self {
+            client: Self::LEGACY_CLIENT,
+            seq,
+        }
+    }
+}
+
+/// Formats as `<client hex>-<seq>`, e.g. `00000000deadbeef-12`.
+impl fmt::Display for StableId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}-{}", self.client, self.seq)
+    }
+}
+
+impl FromStr for StableId {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (client, seq) = s.split_once('-').ok_or("expected <client>-<seq>")?;
+        Ok(Self {
+            client: ClientId::from_str_radix(client, 16).map_err(|_| "invalid client id")?,
+            seq: seq.parse().map_err(|_| "invalid sequence number")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Model, StableId};
+
+    #[test]
+    fn separate_models_never_mint_the_same_id() {
+        let mut a = Model::default();
+        let mut b = Model::default();
+        let (id_a, id_b) = (a.add_box(1.0, 1.0, 1.0), b.add_box(1.0, 1.0, 1.0));
+        // Object ids are per document; stable ids are not.
+        assert_eq!(id_a, id_b);
+        let a = a.object(id_a).unwrap().uid();
+        let b = b.object(id_b).unwrap().uid();
+        assert_ne!(a.client, StableId::LEGACY_CLIENT);
+        assert_ne!(b.client, StableId::LEGACY_CLIENT);
+        assert_ne!(a, b);
+    }
+}
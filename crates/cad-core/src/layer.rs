@@ -0,0 +1,19 @@
+//! Named layers for organizing bodies.
+
+use serde::{Deserialize, Serialize};
+
+pub type LayerId = u64;
+
+/// A layer groups bodies so they can be shown, hidden or locked together.
+/// Bodies without a layer sit on the implicit default layer, which is always
+/// visible and unlocked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Layer {
+    pub id: LayerId,
+    pub name: String,
+    /// Linear RGBA used to tag the layer in the UI.
+    pub color: [f32; 4],
+    pub visible: bool,
+    pub locked: bool,
+}
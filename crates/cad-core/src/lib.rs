@@ -1,14 +1,72 @@
 //! Core model types shared by client and server.
 
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+mod annotation;
+mod appearance;
+mod bom;
+mod command;
+mod component;
+mod datum;
+mod delta;
+mod derived;
+mod document;
+mod feature;
+mod group;
+mod identity;
+mod layer;
+mod mate;
+mod parameter;
+mod point_cloud;
+mod query;
+mod reference;
+mod schema;
+mod selection;
+mod sketch;
+mod snapshot;
+mod stats;
+mod transform;
+mod trash;
+mod units;
+mod validate;
+
+pub use annotation::{Anchor, Annotation, AnnotationId, Measurement};
+pub use appearance::Appearance;
+pub use bom::{keys as property_keys, BomLine, PropertyValue};
+pub use command::{CommandError, CommandTarget, ModelCommand, UndoStack};
+pub use component::{Component, ComponentId};
+pub use datum::{Datum, DatumId, DatumKind};
+pub use delta::ModelDelta;
+pub use derived::{DerivedOp, MeshAsset, MeshHandle, Operand};
+pub use document::DocumentInfo;
+pub use feature::{BooleanOp, Feature, FeatureId, FeatureOp};
+pub use identity::{fresh_client_id, ClientId, StableId};
+pub use layer::{Layer, LayerId};
+pub use mate::{Mate, MateAxis, MateId, MateKind, MatePlane};
+pub use parameter::{ParamError, Parameter};
+pub use point_cloud::{PointCloud, PointCloudId};
+pub use query::ObjectQuery;
+pub use reference::{ReferenceError, ResolvedReference};
+pub use schema::{LoadError, SCHEMA_VERSION};
+pub use selection::{SelectionSet, SelectionSetId};
+pub use sketch::{Sketch, SketchChain, SketchEntity, SketchId, SketchPlane};
+pub use snapshot::{ModelSnapshot, SharedModel};
+pub use stats::ModelStats;
+pub use units::LengthUnit;
+pub use validate::ModelIssue;
 
 pub type ObjectId = u64;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Transform {
     pub translation: [f32; 3],
     /// Quaternion `[x, y, z, w]`.
     pub rotation: [f32; 4],
+    /// Per-axis scale, applied before rotation.
+    #[serde(default = "unit_scale")]
+    pub scale: [f32; 3],
 }
 
 impl Default for Transform {
@@ -16,27 +74,339 @@ impl Default for Transform {
         Self {
             translation: [0.0, 0.0, 0.0],
             rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: unit_scale(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn unit_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ObjectKind {
-    Box { w: f32, h: f32, d: f32 },
-    Cylinder { r: f32, h: f32 },
+    Box {
+        w: f32,
+        h: f32,
+        d: f32,
+    },
+    Cylinder {
+        r: f32,
+        h: f32,
+    },
+    /// Box whose top slopes from full height at `-X` down to zero at `+X`.
+    Wedge {
+        w: f32,
+        h: f32,
+        d: f32,
+    },
+    /// Hollow cylinder along Y.
+    Tube {
+        outer_r: f32,
+        inner_r: f32,
+        h: f32,
+    },
+    /// Regular `sides`-gon prism along Y; `r` is the circumradius.
+    Prism {
+        sides: u32,
+        r: f32,
+        h: f32,
+    },
+    /// Stored triangle mesh, e.g. an import; see [`Model::add_mesh_asset`].
+    Mesh {
+        handle: MeshHandle,
+    },
+    /// Solid computed from other bodies or sketches, placed relative to
+    /// them in world space.
+    Derived {
+        op: DerivedOp,
+        operands: Vec<Operand>,
+    },
+    /// Body `object` of the document saved at `document`; its shape comes
+    /// from [`Model::resolve_references`].
+    Reference {
+        document: String,
+        object: StableId,
+    },
+}
+
+impl ObjectKind {
+    /// Human-readable primitive name, used for default object names.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Box { .. } => "Box",
+            Self::Cylinder { .. } => "Cylinder",
+            Self::Wedge { .. } => "Wedge",
+            Self::Tube { .. } => "Tube",
+            Self::Prism { .. } => "Prism",
+            Self::Mesh { .. } => "Mesh",
+            Self::Derived { op, .. } => op.label(),
+            Self::Reference { .. } => "Reference",
+        }
+    }
+
+    /// Checks that the dimensions describe a non-degenerate solid.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        match *self {
+            Self::Mesh { .. } => {}
+            Self::Reference { ref document, .. } => {
+                if document.is_empty() {
+                    return Err("reference needs a document");
+                }
+            }
+            Self::Derived {
+                ref op,
+                ref operands,
+            } => op.validate(operands)?,
+            Self::Box { w, h, d } | Self::Wedge { w, h, d } => {
+                if !(positive(w) && positive(h) && positive(d)) {
+                    return Err("width, height and depth must be positive");
+                }
+            }
+            Self::Cylinder { r, h } => {
+                if !(positive(r) && positive(h)) {
+                    return Err("radius and height must be positive");
+                }
+            }
+            Self::Tube {
+                outer_r,
+                inner_r,
+                h,
+            } => {
+                if !(positive(outer_r) && positive(h)) {
+                    return Err("radius and height must be positive");
+                }
+                if !(inner_r.is_finite() && (0.0..outer_r).contains(&inner_r)) {
+                    return Err("inner radius must be smaller than the outer radius");
+                }
+            }
+            Self::Prism { sides, r, h } => {
+                if sides < 3 {
+                    return Err("prism needs at least three sides");
+                }
+                if !(positive(r) && positive(h)) {
+                    return Err("radius and height must be positive");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the dimension called `name` (the field name: `w`, `h`, `d`,
+    /// `r`, `outer_r`, `inner_r` or `sides`, or `distance` and `angle` of
+    /// extrusions and revolves), returning `false` if this kind has no such
+    /// dimension. `sides` is rounded to the nearest count.
+    pub fn set_dimension(&mut self, name: &str, value: f32) -> bool {
+        let field = match (self, name) {
+            (Self::Box { w, .. } | Self::Wedge { w, .. }, "w") => w,
+            (
+                Self::Box { h, .. }
+                | Self::Wedge { h, .. }
+                | Self::Cylinder { h, .. }
+                | Self::Tube { h, .. }
+                | Self::Prism { h, .. },
+                "h",
+            ) => h,
+            (Self::Box { d, .. } | Self::Wedge { d, .. }, "d") => d,
+            (Self::Cylinder { r, .. } | Self::Prism { r, .. }, "r") => r,
+            (Self::Tube { outer_r, .. }, "outer_r") => outer_r,
+            (Self::Tube { inner_r, .. }, "inner_r") => inner_r,
+            (
+                Self::Derived {
+                    op: DerivedOp::Extrude { distance },
+                    ..
+                },
+                "distance",
+            ) => distance,
+            (
+                Self::Derived {
+                    op: DerivedOp::Revolve { angle, .. },
+                    ..
+                },
+                "angle",
+            ) => angle,
+            (Self::Prism { sides, .. }, "sides") => {
+                *sides = value.round().max(0.0) as u32;
+                return true;
+            }
+            _ => return false,
+        };
+        *field = value;
+        true
+    }
+
+    pub(crate) fn scale_lengths(&mut self, factor: f32) {
+        match self {
+            Self::Box { w, h, d } | Self::Wedge { w, h, d } => {
+                *w *= factor;
+                *h *= factor;
+                *d *= factor;
+            }
+            Self::Cylinder { r, h } | Self::Prism { r, h, .. } => {
+                *r *= factor;
+                *h *= factor;
+            }
+            Self::Tube {
+                outer_r,
+                inner_r,
+                h,
+            } => {
+                *outer_r *= factor;
+                *inner_r *= factor;
+                *h *= factor;
+            }
+            Self::Mesh { .. } | Self::Reference { .. } => {}
+            Self::Derived { op, .. } => op.scale_lengths(factor),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ModelObject {
     pub id: ObjectId,
+    /// Missing in saves that predate stable ids; see [`ModelObject::uid`].
+    #[serde(default)]
+    pub stable_id: Option<StableId>,
     pub kind: ObjectKind,
     pub transform: Transform,
+    /// Features replayed on top of `kind`, in order.
+    #[serde(default)]
+    pub features: Vec<Feature>,
+    #[serde(default)]
+    pub name: String,
+    /// Free-form user properties (part number, material note, ...).
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Typed properties read by [`Model::bill_of_materials`]; see
+    /// [`property_keys`] for the keys it understands.
+    #[serde(default)]
+    pub properties: HashMap<String, PropertyValue>,
+    #[serde(default)]
+    pub appearance: Appearance,
+    /// Hidden objects are left out of the viewport mesh and picking.
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    /// Locked objects are drawn but cannot be picked.
+    #[serde(default)]
+    pub locked: bool,
+    /// `None` places the object on the default layer.
+    #[serde(default)]
+    pub layer: Option<LayerId>,
+    /// Owning component; `transform` is relative to it. `None` for bodies
+    /// at the top of the tree.
+    #[serde(default)]
+    pub parent: Option<ComponentId>,
+    /// Dimensions of `kind` driven by parameter expressions, keyed by
+    /// dimension name; see [`Model::bind_dimension`].
+    #[serde(default)]
+    pub bindings: HashMap<String, String>,
+    /// Free-form labels for search and [`Model::query`].
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+}
+
+fn default_visible() -> bool {
+    true
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+impl ModelObject {
+    /// Cross-document identity. Objects loaded from older saves get a
+    /// legacy id derived from their [`ObjectId`].
+    pub fn uid(&self) -> StableId {
+        self.stable_id.unwrap_or(StableId::legacy(self.id))
+    }
+
+    /// Whether the object can be picked in the viewport.
+    pub fn is_pickable(&self) -> bool {
+        self.visible && !self.locked
+    }
+
+    /// The user name, or "Body N" for objects saved without one.
+    pub fn display_name(&self) -> String {
+        if self.name.is_empty() {
+            format!("Body {}", self.id.saturating_add(1))
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// Everything [`Model::remove`] detached from the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Removed {
+    pub object: ModelObject,
+    /// Former position in [`Model::objects`].
+    pub index: usize,
+    pub mates: Vec<Mate>,
+    pub annotations: Vec<Annotation>,
+    /// Features of other bodies suppressed because they used the object as
+    /// a boolean tool, as `(owner, feature)`.
+    pub suppressed_features: Vec<(ObjectId, FeatureId)>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Model {
+    #[serde(default)]
+    info: DocumentInfo,
     objects: Vec<ModelObject>,
     next_id: ObjectId,
+    #[serde(default)]
+    next_feature_id: FeatureId,
+    #[serde(default)]
+    sketches: Vec<Sketch>,
+    #[serde(default)]
+    next_sketch_id: SketchId,
+    #[serde(default)]
+    unit: LengthUnit,
+    #[serde(default)]
+    point_clouds: Vec<PointCloud>,
+    #[serde(default)]
+    next_point_cloud_id: PointCloudId,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+    #[serde(default)]
+    next_annotation_id: AnnotationId,
+    #[serde(default)]
+    mates: Vec<Mate>,
+    #[serde(default)]
+    next_mate_id: MateId,
+    #[serde(default)]
+    layers: Vec<Layer>,
+    #[serde(default)]
+    next_layer_id: LayerId,
+    #[serde(default)]
+    components: Vec<Component>,
+    #[serde(default)]
+    next_component_id: ComponentId,
+    #[serde(default)]
+    parameters: Vec<Parameter>,
+    #[serde(default)]
+    mesh_assets: Vec<MeshAsset>,
+    #[serde(default)]
+    next_mesh_handle: MeshHandle,
+    #[serde(default)]
+    selection_sets: Vec<SelectionSet>,
+    #[serde(default)]
+    next_selection_set_id: SelectionSetId,
+    #[serde(default)]
+    trash: Vec<Removed>,
+    #[serde(default)]
+    datums: Vec<Datum>,
+    #[serde(default)]
+    next_datum_id: DatumId,
+    /// Cache filled by [`Model::resolve_references`]; rebuilt after load.
+    #[serde(skip)]
+    resolved_references: HashMap<(String, StableId), ResolvedReference>,
+    /// Client minting stable ids for new objects; not saved with the
+    /// document, since every session should use its own. Picked at random
+    /// on the first mint if nobody set one.
+    #[serde(skip)]
+    client_id: ClientId,
 }
 
 impl Model {
@@ -44,10 +414,127 @@ impl Model {
         &self.objects
     }
 
+    pub fn info(&self) -> &DocumentInfo {
+        &self.info
+    }
+
+    pub fn set_info(&mut self, info: DocumentInfo) {
+        self.info = info;
+    }
+
+    /// Records a change made at `now_ms` (Unix milliseconds): bumps the
+    /// revision and modification time, and fills in the creation time of
+    /// documents that have none.
+    pub fn touch(&mut self, now_ms: u64) {
+        if self.info.created_ms == 0 {
+            self.info.created_ms = now_ms;
+        }
+        self.info.modified_ms = self.info.modified_ms.max(now_ms);
+        self.info.revision += 1;
+    }
+
+    pub fn client_id(&self) -> ClientId {
+        self.client_id
+    }
+
+    /// Sets the client stamped into the stable ids of objects created from
+    /// now on. Every editing session should pick a fresh random non-zero
+    /// id, so ids minted in different documents or by different
+    /// collaborators never meet.
+    pub fn set_client_id(&mut self, client: ClientId) {
+        self.client_id = client;
+    }
+
+    pub fn object_by_uid(&self, uid: StableId) -> Option<&ModelObject> {
+        self.objects.iter().find(|obj| obj.uid() == uid)
+    }
+
+    /// Writes explicit stable ids for objects loaded from older saves, so
+    /// they survive being renumbered (e.g. when merged into another
+    /// document). Returns how many objects were upgraded.
+    pub fn assign_stable_ids(&mut self) -> usize {
+        let mut upgraded = 0;
+        for obj in &mut self.objects {
+            if obj.stable_id.is_none() {
+                obj.stable_id = Some(obj.uid());
+                upgraded += 1;
+            }
+        }
+        upgraded
+    }
+
     pub fn object(&self, id: ObjectId) -> Option<&ModelObject> {
         self.objects.iter().find(|obj| obj.id == id)
     }
 
+    pub fn unit(&self) -> LengthUnit {
+        self.unit
+    }
+
+    /// Relabels the document unit without touching any lengths.
+    pub fn set_unit(&mut self, unit: LengthUnit) {
+        self.unit = unit;
+    }
+
+    /// Switches the document unit, rescaling every stored length so the
+    /// geometry keeps its physical size.
+    pub fn convert_units(&mut self, unit: LengthUnit) {
+        let factor = self.unit.convert(1.0, unit);
+        self.unit = unit;
+        if factor == 1.0 {
+            return;
+        }
+        let trashed = self.trash.iter_mut().map(|removed| &mut removed.object);
+        for obj in self.objects.iter_mut().chain(trashed) {
+            obj.kind.scale_lengths(factor);
+            for t in &mut obj.transform.translation {
+                *t *= factor;
+            }
+            for feature in &mut obj.features {
+                feature.op.scale_lengths(factor);
+            }
+        }
+        for asset in &mut self.mesh_assets {
+            for p in &mut asset.positions {
+                *p = p.map(|c| c * factor);
+            }
+        }
+        let datums = self.datums.iter_mut().map(|datum| &mut datum.transform);
+        for transform in self
+            .components
+            .iter_mut()
+            .map(|c| &mut c.transform)
+            .chain(datums)
+        {
+            for t in &mut transform.translation {
+                *t *= factor;
+            }
+        }
+        for sketch in &mut self.sketches {
+            for o in &mut sketch.plane.origin {
+                *o *= factor;
+            }
+            for entity in sketch.entities.iter_mut().chain(&mut sketch.references) {
+                entity.scale_lengths(factor);
+            }
+        }
+        for cloud in &mut self.point_clouds {
+            for p in cloud
+                .points
+                .iter_mut()
+                .chain([&mut cloud.transform.translation])
+            {
+                *p = p.map(|c| c * factor);
+            }
+        }
+        for annotation in &mut self.annotations {
+            annotation.measurement.scale_lengths(factor);
+        }
+        for mate in &mut self.mates {
+            mate.kind.scale_lengths(factor);
+        }
+    }
+
     pub fn set_transform(&mut self, id: ObjectId, transform: Transform) -> bool {
         if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) {
             obj.transform = transform;
@@ -57,6 +544,546 @@ impl Model {
         }
     }
 
+    pub fn set_name(&mut self, id: ObjectId, name: impl Into<String>) -> bool {
+        if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) {
+            obj.name = name.into();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Sets a metadata entry, returning the previous value.
+    pub fn set_metadata(
+        &mut self,
+        id: ObjectId,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Option<Option<String>> {
+        let obj = self.objects.iter_mut().find(|obj| obj.id == id)?;
+        Some(obj.metadata.insert(key.into(), value.into()))
+    }
+
+    pub fn remove_metadata(&mut self, id: ObjectId, key: &str) -> Option<String> {
+        let obj = self.objects.iter_mut().find(|obj| obj.id == id)?;
+        obj.metadata.remove(key)
+    }
+
+    /// Replaces an object's appearance; values are clamped to their ranges.
+    pub fn set_appearance(&mut self, id: ObjectId, appearance: Appearance) -> bool {
+        if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) {
+            obj.appearance = appearance.clamped();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_visible(&mut self, id: ObjectId, visible: bool) -> bool {
+        if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) {
+            obj.visible = visible;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_locked(&mut self, id: ObjectId, locked: bool) -> bool {
+        if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) {
+            obj.locked = locked;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_kind(&mut self, id: ObjectId, kind: ObjectKind) -> bool {
+        if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) {
+            obj.kind = kind;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Appends a feature to an object's history.
+    pub fn add_feature(&mut self, id: ObjectId, op: FeatureOp) -> Option<FeatureId> {
+        let feature_id = self.next_feature_id;
+        let obj = self.objects.iter_mut().find(|obj| obj.id == id)?;
+        obj.features.push(Feature {
+            id: feature_id,
+            op,
+            suppressed: false,
+        });
+        self.next_feature_id = self.next_feature_id.saturating_add(1);
+        Some(feature_id)
+    }
+
+    /// Replaces an object's features, keeping later ids clear of theirs.
+    pub fn set_features(&mut self, id: ObjectId, features: Vec<Feature>) -> bool {
+        let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) else {
+            return false;
+        };
+        if let Some(last) = features.iter().map(|f| f.id).max() {
+            self.next_feature_id = self.next_feature_id.max(last.saturating_add(1));
+        }
+        obj.features = features;
+        true
+    }
+
+    pub fn remove_feature(&mut self, id: ObjectId, feature: FeatureId) -> Option<Feature> {
+        let obj = self.objects.iter_mut().find(|obj| obj.id == id)?;
+        let idx = obj.features.iter().position(|f| f.id == feature)?;
+        Some(obj.features.remove(idx))
+    }
+
+    pub fn set_feature_suppressed(
+        &mut self,
+        id: ObjectId,
+        feature: FeatureId,
+        suppressed: bool,
+    ) -> bool {
+        let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) else {
+            return false;
+        };
+        match obj.features.iter_mut().find(|f| f.id == feature) {
+            Some(f) => {
+                f.suppressed = suppressed;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn sketches(&self) -> &[Sketch] {
+        &self.sketches
+    }
+
+    pub fn sketch(&self, id: SketchId) -> Option<&Sketch> {
+        self.sketches.iter().find(|sketch| sketch.id == id)
+    }
+
+    pub fn add_sketch(
+        &mut self,
+        name: impl Into<String>,
+        plane: SketchPlane,
+        entities: Vec<SketchEntity>,
+    ) -> SketchId {
+        let id = self.next_sketch_id;
+        self.next_sketch_id = self.next_sketch_id.saturating_add(1);
+        self.sketches.push(Sketch {
+            id,
+            name: name.into(),
+            plane,
+            entities,
+            references: Vec::new(),
+        });
+        id
+    }
+
+    pub fn add_sketch_references(&mut self, id: SketchId, references: Vec<SketchEntity>) -> bool {
+        if let Some(sketch) = self.sketches.iter_mut().find(|sketch| sketch.id == id) {
+            sketch.references.extend(references);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_sketch_entities(&mut self, id: SketchId, entities: Vec<SketchEntity>) -> bool {
+        if let Some(sketch) = self.sketches.iter_mut().find(|sketch| sketch.id == id) {
+            sketch.entities = entities;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn remove_sketch(&mut self, id: SketchId) -> Option<Sketch> {
+        let idx = self.sketches.iter().position(|sketch| sketch.id == id)?;
+        Some(self.sketches.remove(idx))
+    }
+
+    pub fn point_clouds(&self) -> &[PointCloud] {
+        &self.point_clouds
+    }
+
+    pub fn point_cloud(&self, id: PointCloudId) -> Option<&PointCloud> {
+        self.point_clouds.iter().find(|cloud| cloud.id == id)
+    }
+
+    pub fn add_point_cloud(
+        &mut self,
+        name: impl Into<String>,
+        points: Vec<[f32; 3]>,
+        colors: Vec<[u8; 3]>,
+    ) -> PointCloudId {
+        let id = self.next_point_cloud_id;
+        self.next_point_cloud_id += 1;
+        self.point_clouds.push(PointCloud {
+            id,
+            name: name.into(),
+            points,
+            colors,
+            transform: Transform::default(),
+        });
+        id
+    }
+
+    pub fn set_point_cloud_transform(&mut self, id: PointCloudId, transform: Transform) -> bool {
+        if let Some(cloud) = self.point_clouds.iter_mut().find(|cloud| cloud.id == id) {
+            cloud.transform = transform;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn remove_point_cloud(&mut self, id: PointCloudId) -> Option<PointCloud> {
+        let idx = self.point_clouds.iter().position(|cloud| cloud.id == id)?;
+        Some(self.point_clouds.remove(idx))
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    pub fn annotation(&self, id: AnnotationId) -> Option<&Annotation> {
+        self.annotations
+            .iter()
+            .find(|annotation| annotation.id == id)
+    }
+
+    pub fn add_annotation(&mut self, measurement: Measurement) -> AnnotationId {
+        let id = self.next_annotation_id;
+        self.next_annotation_id += 1;
+        self.annotations.push(Annotation { id, measurement });
+        id
+    }
+
+    pub fn remove_annotation(&mut self, id: AnnotationId) -> Option<Annotation> {
+        let idx = self
+            .annotations
+            .iter()
+            .position(|annotation| annotation.id == id)?;
+        Some(self.annotations.remove(idx))
+    }
+
+    pub fn mates(&self) -> &[Mate] {
+        &self.mates
+    }
+
+    pub fn add_mate(&mut self, kind: MateKind) -> MateId {
+        let id = self.next_mate_id;
+        self.next_mate_id += 1;
+        self.mates.push(Mate {
+            id,
+            kind,
+            suppressed: false,
+        });
+        id
+    }
+
+    pub fn remove_mate(&mut self, id: MateId) -> Option<Mate> {
+        let idx = self.mates.iter().position(|mate| mate.id == id)?;
+        Some(self.mates.remove(idx))
+    }
+
+    pub fn set_mate_suppressed(&mut self, id: MateId, suppressed: bool) -> bool {
+        if let Some(mate) = self.mates.iter_mut().find(|mate| mate.id == id) {
+            mate.suppressed = suppressed;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn layer(&self, id: LayerId) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.id == id)
+    }
+
+    /// Adds a visible, unlocked layer.
+    pub fn add_layer(&mut self, name: impl Into<String>, color: [f32; 4]) -> LayerId {
+        let id = self.next_layer_id;
+        self.next_layer_id += 1;
+        self.layers.push(Layer {
+            id,
+            name: name.into(),
+            color,
+            visible: true,
+            locked: false,
+        });
+        id
+    }
+
+    pub fn rename_layer(&mut self, id: LayerId, name: impl Into<String>) -> bool {
+        self.update_layer(id, |layer| layer.name = name.into())
+    }
+
+    pub fn set_layer_color(&mut self, id: LayerId, color: [f32; 4]) -> bool {
+        self.update_layer(id, |layer| layer.color = color)
+    }
+
+    pub fn set_layer_visible(&mut self, id: LayerId, visible: bool) -> bool {
+        self.update_layer(id, |layer| layer.visible = visible)
+    }
+
+    pub fn set_layer_locked(&mut self, id: LayerId, locked: bool) -> bool {
+        self.update_layer(id, |layer| layer.locked = locked)
+    }
+
+    /// Removes a layer; its objects move to the default layer.
+    pub fn remove_layer(&mut self, id: LayerId) -> Option<Layer> {
+        let idx = self.layers.iter().position(|layer| layer.id == id)?;
+        for obj in &mut self.objects {
+            if obj.layer == Some(id) {
+                obj.layer = None;
+            }
+        }
+        Some(self.layers.remove(idx))
+    }
+
+    /// Moves an object to `layer` (`None` for the default layer). Fails for
+    /// unknown objects or layers.
+    pub fn set_object_layer(&mut self, id: ObjectId, layer: Option<LayerId>) -> bool {
+        if layer.is_some_and(|layer| self.layer(layer).is_none()) {
+            return false;
+        }
+        if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) {
+            obj.layer = layer;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether an object is shown, taking its layer into account.
+    pub fn is_visible(&self, obj: &ModelObject) -> bool {
+        obj.visible && self.object_layer(obj).is_none_or(|layer| layer.visible)
+    }
+
+    /// Whether an object can be picked, taking its layer into account.
+    pub fn is_pickable(&self, obj: &ModelObject) -> bool {
+        obj.is_pickable()
+            && self
+                .object_layer(obj)
+                .is_none_or(|layer| layer.visible && !layer.locked)
+    }
+
+    fn object_layer(&self, obj: &ModelObject) -> Option<&Layer> {
+        obj.layer.and_then(|id| self.layer(id))
+    }
+
+    fn update_layer(&mut self, id: LayerId, f: impl FnOnce(&mut Layer)) -> bool {
+        if let Some(layer) = self.layers.iter_mut().find(|layer| layer.id == id) {
+            f(layer);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    pub fn component(&self, id: ComponentId) -> Option<&Component> {
+        self.components.iter().find(|component| component.id == id)
+    }
+
+    /// Adds an empty component under `parent`, or `None` if the parent does
+    /// not exist.
+    pub fn add_component(
+        &mut self,
+        name: impl Into<String>,
+        parent: Option<ComponentId>,
+    ) -> Option<ComponentId> {
+        if parent.is_some_and(|parent| self.component(parent).is_none()) {
+            return None;
+        }
+        let id = self.next_component_id;
+        self.next_component_id += 1;
+        self.components.push(Component {
+            id,
+            name: name.into(),
+            transform: Transform::default(),
+            parent,
+        });
+        Some(id)
+    }
+
+    pub fn rename_component(&mut self, id: ComponentId, name: impl Into<String>) -> bool {
+        if let Some(component) = self.components.iter_mut().find(|c| c.id == id) {
+            component.name = name.into();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_component_transform(&mut self, id: ComponentId, transform: Transform) -> bool {
+        if let Some(component) = self.components.iter_mut().find(|c| c.id == id) {
+            component.transform = transform;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Moves a component under `parent`. Fails for unknown ids and for moves
+    /// that would make a component its own ancestor.
+    pub fn set_component_parent(&mut self, id: ComponentId, parent: Option<ComponentId>) -> bool {
+        if self.component(id).is_none() {
+            return false;
+        }
+        if let Some(parent) = parent {
+            if self.component(parent).is_none()
+                || self.component_ancestors(Some(parent)).any(|c| c.id == id)
+            {
+                return false;
+            }
+        }
+        if let Some(component) = self.components.iter_mut().find(|c| c.id == id) {
+            component.parent = parent;
+        }
+        true
+    }
+
+    /// Moves an object under `parent` (`None` for the top level). The
+    /// object's transform is kept, so it is now read relative to the new
+    /// parent.
+    pub fn set_object_parent(&mut self, id: ObjectId, parent: Option<ComponentId>) -> bool {
+        if parent.is_some_and(|parent| self.component(parent).is_none()) {
+            return false;
+        }
+        if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) {
+            obj.parent = parent;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes a component. Its children, bodies and components alike, move
+    /// up to its parent with their own transforms unchanged.
+    pub fn remove_component(&mut self, id: ComponentId) -> Option<Component> {
+        let idx = self.components.iter().position(|c| c.id == id)?;
+        let removed = self.components.remove(idx);
+        for component in &mut self.components {
+            if component.parent == Some(id) {
+                component.parent = removed.parent;
+            }
+        }
+        for obj in &mut self.objects {
+            if obj.parent == Some(id) {
+                obj.parent = removed.parent;
+            }
+        }
+        Some(removed)
+    }
+
+    /// `start` and the components above it, nearest first.
+    pub fn component_ancestors(
+        &self,
+        start: Option<ComponentId>,
+    ) -> impl Iterator<Item = &Component> + '_ {
+        let mut next = start;
+        // Bounded so a corrupted file with a parent cycle cannot hang.
+        std::iter::from_fn(move || {
+            let component = self.component(next?)?;
+            next = component.parent;
+            Some(component)
+        })
+        .take(self.components.len())
+    }
+
+    /// Removes an object and detaches everything that refers to it:
+    ///
+    /// - mates involving it are removed,
+    /// - annotations anchored to or measuring it are removed,
+    /// - boolean features of other bodies using it as a tool are suppressed
+    ///   (kept in their history, skipped on replay).
+    ///
+    /// Object ids are never reissued, so the returned record can be handed
+    /// to [`Model::restore`] to undo the removal.
+    pub fn remove(&mut self, id: ObjectId) -> Option<Removed> {
+        let index = self.objects.iter().position(|obj| obj.id == id)?;
+        let object = self.objects.remove(index);
+
+        let (mates, kept): (Vec<Mate>, Vec<Mate>) = self.mates.drain(..).partition(|mate| {
+            let (a, b) = mate.kind.objects();
+            a == id || b == id
+        });
+        self.mates = kept;
+
+        let (annotations, kept): (Vec<Annotation>, Vec<Annotation>) = self
+            .annotations
+            .drain(..)
+            .partition(|annotation| annotation.measurement.objects().contains(&id));
+        self.annotations = kept;
+
+        let mut suppressed_features = Vec::new();
+        for obj in &mut self.objects {
+            for feature in &mut obj.features {
+                let uses_tool = feature.op.tool() == Some(id);
+                if uses_tool && !feature.suppressed {
+                    feature.suppressed = true;
+                    suppressed_features.push((obj.id, feature.id));
+                }
+            }
+        }
+
+        Some(Removed {
+            object,
+            index,
+            mates,
+            annotations,
+            suppressed_features,
+        })
+    }
+
+    /// Puts back an object taken out by [`Model::remove`], reattaching its
+    /// mates and annotations and unsuppressing the features suppressed on
+    /// its behalf. Returns `false` (and changes nothing) if an object with
+    /// the same id exists.
+    pub fn reinsert(&mut self, removed: Removed) -> bool {
+        if self.object(removed.object.id).is_some() {
+            return false;
+        }
+        let index = removed.index.min(self.objects.len());
+        self.objects.insert(index, removed.object);
+        self.mates.extend(removed.mates);
+        self.mates.sort_by_key(|mate| mate.id);
+        self.annotations.extend(removed.annotations);
+        self.annotations.sort_by_key(|annotation| annotation.id);
+        for (owner, feature) in removed.suppressed_features {
+            self.set_feature_suppressed(owner, feature, false);
+        }
+        true
+    }
+
+    /// Adds a copy of an object (same kind and transform) under a new id.
+    pub fn duplicate(&mut self, id: ObjectId) -> Option<ObjectId> {
+        let source = self.object(id)?.clone();
+        let new_id = self.add_object(source.kind);
+        if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == new_id) {
+            obj.transform = source.transform;
+            obj.features = source.features;
+            obj.metadata = source.metadata;
+            obj.properties = source.properties;
+            obj.appearance = source.appearance;
+            obj.layer = source.layer;
+            obj.parent = source.parent;
+            obj.bindings = source.bindings;
+            obj.tags = source.tags;
+        }
+        Some(new_id)
+    }
+
     pub fn add_box(&mut self, w: f32, h: f32, d: f32) -> ObjectId {
         self.add_object(ObjectKind::Box { w, h, d })
     }
@@ -65,13 +1092,48 @@ impl Model {
         self.add_object(ObjectKind::Cylinder { r, h })
     }
 
+    pub fn add_wedge(&mut self, w: f32, h: f32, d: f32) -> ObjectId {
+        self.add_object(ObjectKind::Wedge { w, h, d })
+    }
+
+    pub fn add_tube(&mut self, outer_r: f32, inner_r: f32, h: f32) -> ObjectId {
+        self.add_object(ObjectKind::Tube {
+            outer_r,
+            inner_r,
+            h,
+        })
+    }
+
+    pub fn add_prism(&mut self, sides: u32, r: f32, h: f32) -> ObjectId {
+        self.add_object(ObjectKind::Prism { sides, r, h })
+    }
+
     fn add_object(&mut self, kind: ObjectKind) -> ObjectId {
+        if self.client_id == StableId::LEGACY_CLIENT {
+            // Every model would share ids minted by the legacy client.
+            self.client_id = fresh_client_id();
+        }
         let id = self.next_id;
         self.next_id = self.next_id.saturating_add(1);
         self.objects.push(ModelObject {
             id,
+            stable_id: Some(StableId {
+                client: self.client_id,
+                seq: id,
+            }),
+            name: format!("{} {}", kind.label(), id.saturating_add(1)),
             kind,
             transform: Transform::default(),
+            features: Vec::new(),
+            metadata: HashMap::new(),
+            properties: HashMap::new(),
+            appearance: Appearance::default(),
+            visible: true,
+            locked: false,
+            layer: None,
+            parent: None,
+            bindings: HashMap::new(),
+            tags: BTreeSet::new(),
         });
         id
     }
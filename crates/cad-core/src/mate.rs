@@ -0,0 +1,89 @@
+//! Relational placement constraints between bodies.
+
+use crate::ObjectId;
+use serde::{Deserialize, Serialize};
+
+pub type MateId = u64;
+
+/// A plane attached to a body, in the body's local space.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MatePlane {
+    pub object: ObjectId,
+    pub origin: [f32; 3],
+    /// Outward normal.
+    pub normal: [f32; 3],
+}
+
+/// An axis attached to a body, in the body's local space.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MateAxis {
+    pub object: ObjectId,
+    pub origin: [f32; 3],
+    pub direction: [f32; 3],
+}
+
+/// A constraint that positions the body of `b` relative to the body of `a`.
+/// Solving only ever moves `b`'s body.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum MateKind {
+    /// Planes touch, facing each other.
+    Coincident { a: MatePlane, b: MatePlane },
+    /// Axes are collinear.
+    Concentric { a: MateAxis, b: MateAxis },
+    /// Planes face each other with a gap of `distance`.
+    Distance {
+        a: MatePlane,
+        b: MatePlane,
+        distance: f32,
+    },
+    /// Plane normals make `angle` radians.
+    Angle {
+        a: MatePlane,
+        b: MatePlane,
+        angle: f32,
+    },
+}
+
+impl MateKind {
+    /// The fixed body and the body the mate moves.
+    pub fn objects(&self) -> (ObjectId, ObjectId) {
+        match self {
+            Self::Coincident { a, b } | Self::Distance { a, b, .. } | Self::Angle { a, b, .. } => {
+                (a.object, b.object)
+            }
+            Self::Concentric { a, b } => (a.object, b.object),
+        }
+    }
+
+    pub(crate) fn scale_lengths(&mut self, factor: f32) {
+        let scale = |p: &mut [f32; 3]| *p = p.map(|c| c * factor);
+        match self {
+            Self::Coincident { a, b } | Self::Angle { a, b, .. } => {
+                scale(&mut a.origin);
+                scale(&mut b.origin);
+            }
+            Self::Distance { a, b, distance } => {
+                scale(&mut a.origin);
+                scale(&mut b.origin);
+                *distance *= factor;
+            }
+            Self::Concentric { a, b } => {
+                scale(&mut a.origin);
+                scale(&mut b.origin);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Mate {
+    pub id: MateId,
+    pub kind: MateKind,
+    /// Suppressed mates are kept but ignored by the solver.
+    #[serde(default)]
+    pub suppressed: bool,
+}
@@ -0,0 +1,526 @@
+//! Named design parameters and the expressions that drive dimensions.
+
+use crate::{Model, ObjectId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A named value defined by an expression such as `40` or `width / 2`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Parameter {
+    pub name: String,
+    pub expression: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamError {
+    /// The expression does not parse.
+    Syntax(String),
+    /// Parameter names must start with a letter or `_` and continue with
+    /// letters, digits or `_`; function names are reserved.
+    InvalidName(String),
+    UnknownParameter(String),
+    /// The parameter depends on itself, directly or through others.
+    Cycle(String),
+    /// Still referenced by another parameter or a bound dimension.
+    InUse(String),
+    UnknownObject(ObjectId),
+    /// The object's kind has no dimension of this name.
+    UnknownDimension(String),
+    /// The evaluated values make a body degenerate.
+    InvalidDimension(ObjectId, &'static str),
+    /// Evaluation produced infinity or NaN (e.g. division by zero).
+    NotFinite,
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(reason) => write!(f, "syntax error: {reason}"),
+            Self::InvalidName(name) => write!(f, "invalid parameter name `{name}`"),
+            Self::UnknownParameter(name) => write!(f, "unknown parameter `{name}`"),
+            Self::Cycle(name) => write!(f, "parameter `{name}` depends on itself"),
+            Self::InUse(name) => write!(f, "parameter `{name}` is still in use"),
+            Self::UnknownObject(id) => write!(f, "unknown object {id}"),
+            Self::UnknownDimension(dim) => write!(f, "unknown dimension `{dim}`"),
+            Self::InvalidDimension(id, reason) => write!(f, "object {id}: {reason}"),
+            Self::NotFinite => write!(f, "expression does not evaluate to a finite number"),
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+/// Parsed expression tree.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    Number(f64),
+    Name(String),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Function {
+    Sqrt,
+    Abs,
+    Min,
+    Max,
+    Round,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sqrt" => Self::Sqrt,
+            "abs" => Self::Abs,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "round" => Self::Round,
+            _ => return None,
+        })
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Self::Min | Self::Max => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl Expr {
+    /// Parses `+ - * / ^`, parentheses, unary minus, numbers, parameter
+    /// names, `pi` and the functions `sqrt abs min max round`.
+    pub(crate) fn parse(source: &str) -> Result<Self, ParamError> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let expr = parser.sum()?;
+        parser.skip_ws();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(expr)
+    }
+
+    /// Names of the parameters the expression reads.
+    pub(crate) fn names(&self, out: &mut Vec<String>) {
+        match self {
+            Self::Number(_) => {}
+            Self::Name(name) => out.push(name.clone()),
+            Self::Neg(inner) => inner.names(out),
+            Self::Binary(_, a, b) => {
+                a.names(out);
+                b.names(out);
+            }
+            Self::Call(_, args) => args.iter().for_each(|arg| arg.names(out)),
+        }
+    }
+
+    fn eval(
+        &self,
+        lookup: &mut dyn FnMut(&str) -> Result<f64, ParamError>,
+    ) -> Result<f64, ParamError> {
+        Ok(match self {
+            Self::Number(value) => *value,
+            Self::Name(name) => lookup(name)?,
+            Self::Neg(inner) => -inner.eval(lookup)?,
+            Self::Binary(op, a, b) => {
+                let (a, b) = (a.eval(lookup)?, b.eval(lookup)?);
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    _ => a.powf(b),
+                }
+            }
+            Self::Call(function, args) => {
+                let values = args
+                    .iter()
+                    .map(|arg| arg.eval(lookup))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match function {
+                    Function::Sqrt => values[0].sqrt(),
+                    Function::Abs => values[0].abs(),
+                    Function::Min => values[0].min(values[1]),
+                    Function::Max => values[0].max(values[1]),
+                    Function::Round => values[0].round(),
+                }
+            }
+        })
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, reason: &str) -> ParamError {
+        ParamError::Syntax(format!("{reason} at column {}", self.pos + 1))
+    }
+
+    fn skip_ws(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.chars.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, ParamError> {
+        let mut expr = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                '+'
+            } else if self.eat('-') {
+                '-'
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, ParamError> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                '*'
+            } else if self.eat('/') {
+                '/'
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParamError> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    /// Right-associative, binding tighter than unary minus: `-2^2 = -4`.
+    fn power(&mut self) -> Result<Expr, ParamError> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Ok(Expr::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParamError> {
+        self.skip_ws();
+        if self.eat('(') {
+            let inner = self.sum()?;
+            if !self.eat(')') {
+                return Err(self.error("expected `)`"));
+            }
+            return Ok(inner);
+        }
+        let start = self.pos;
+        match self.chars.get(self.pos) {
+            Some(c) if c.is_ascii_digit() || *c == '.' => {
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+                {
+                    self.pos += 1;
+                }
+                // Optional exponent, e.g. `1.5e-3`.
+                if matches!(self.chars.get(self.pos), Some('e' | 'E')) {
+                    let mark = self.pos;
+                    self.pos += 1;
+                    if matches!(self.chars.get(self.pos), Some('+' | '-')) {
+                        self.pos += 1;
+                    }
+                    if self.chars.get(self.pos).is_some_and(char::is_ascii_digit) {
+                        while self.chars.get(self.pos).is_some_and(char::is_ascii_digit) {
+                            self.pos += 1;
+                        }
+                    } else {
+                        self.pos = mark;
+                    }
+                }
+                let text: String = self.chars[start..self.pos].iter().collect();
+                text.parse()
+                    .map(Expr::Number)
+                    .map_err(|_| ParamError::Syntax(format!("invalid number `{text}`")))
+            }
+            Some(c) if is_name_start(*c) => {
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| is_name_continue(*c))
+                {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                if let Some(function) = Function::from_name(&name) {
+                    if !self.eat('(') {
+                        return Err(self.error("expected `(` after function name"));
+                    }
+                    let mut args = vec![self.sum()?];
+                    while self.eat(',') {
+                        args.push(self.sum()?);
+                    }
+                    if !self.eat(')') {
+                        return Err(self.error("expected `)`"));
+                    }
+                    if args.len() != function.arity() {
+                        return Err(ParamError::Syntax(format!(
+                            "`{name}` takes {} argument(s)",
+                            function.arity()
+                        )));
+                    }
+                    return Ok(Expr::Call(function, args));
+                }
+                if name == "pi" {
+                    return Ok(Expr::Number(std::f64::consts::PI));
+                }
+                Ok(Expr::Name(name))
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_name_continue(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_name_start)
+        && chars.all(is_name_continue)
+        && name != "pi"
+        && Function::from_name(name).is_none()
+}
+
+impl Model {
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&Parameter> {
+        self.parameters.iter().find(|param| param.name == name)
+    }
+
+    /// Defines or redefines a parameter, then re-evaluates every bound
+    /// dimension. Returns the objects whose kind changed, which need
+    /// regenerating. On error the model is left untouched.
+    pub fn set_parameter(
+        &mut self,
+        name: impl Into<String>,
+        expression: impl Into<String>,
+    ) -> Result<Vec<ObjectId>, ParamError> {
+        let name = name.into();
+        let expression = expression.into();
+        if !is_valid_name(&name) {
+            return Err(ParamError::InvalidName(name));
+        }
+        Expr::parse(&expression)?;
+        let previous = self.parameters.clone();
+        match self.parameters.iter_mut().find(|param| param.name == name) {
+            Some(param) => param.expression = expression,
+            None => self.parameters.push(Parameter { name, expression }),
+        }
+        let result = self
+            .parameter_values()
+            .and_then(|_| self.resolve_bindings());
+        if result.is_err() {
+            self.parameters = previous;
+        }
+        result
+    }
+
+    /// Removes a parameter nothing else refers to.
+    pub fn remove_parameter(&mut self, name: &str) -> Result<Parameter, ParamError> {
+        let idx = self
+            .parameters
+            .iter()
+            .position(|param| param.name == name)
+            .ok_or_else(|| ParamError::UnknownParameter(name.to_string()))?;
+        let expressions = self
+            .parameters
+            .iter()
+            .map(|param| &param.expression)
+            .chain(self.objects.iter().flat_map(|obj| obj.bindings.values()));
+        for expression in expressions {
+            let mut names = Vec::new();
+            if let Ok(expr) = Expr::parse(expression) {
+                expr.names(&mut names);
+            }
+            if names.iter().any(|used| used == name) {
+                return Err(ParamError::InUse(name.to_string()));
+            }
+        }
+        Ok(self.parameters.remove(idx))
+    }
+
+    /// Current value of a parameter.
+    pub fn parameter_value(&self, name: &str) -> Result<f64, ParamError> {
+        self.parameter_values()?
+            .remove(name)
+            .ok_or_else(|| ParamError::UnknownParameter(name.to_string()))
+    }
+
+    /// Evaluates an expression against the model's parameters.
+    pub fn evaluate(&self, expression: &str) -> Result<f64, ParamError> {
+        let values = self.parameter_values()?;
+        evaluate_with(&Expr::parse(expression)?, &values)
+    }
+
+    /// Drives a dimension of an object's kind (named as in
+    /// [`crate::ObjectKind::set_dimension`]) by an expression. Returns
+    /// whether the kind changed.
+    pub fn bind_dimension(
+        &mut self,
+        id: ObjectId,
+        dimension: &str,
+        expression: impl Into<String>,
+    ) -> Result<bool, ParamError> {
+        let expression = expression.into();
+        let obj = self.object(id).ok_or(ParamError::UnknownObject(id))?;
+        if !obj.kind.clone().set_dimension(dimension, 0.0) {
+            return Err(ParamError::UnknownDimension(dimension.to_string()));
+        }
+        let expr = Expr::parse(&expression)?;
+        evaluate_with(&expr, &self.parameter_values()?)?;
+        let obj = self
+            .objects
+            .iter_mut()
+            .find(|obj| obj.id == id)
+            .ok_or(ParamError::UnknownObject(id))?;
+        let previous = obj.bindings.insert(dimension.to_string(), expression);
+        match self.resolve_bindings() {
+            Ok(changed) => Ok(changed.contains(&id)),
+            Err(err) => {
+                if let Some(obj) = self.objects.iter_mut().find(|obj| obj.id == id) {
+                    match previous {
+                        Some(expression) => obj.bindings.insert(dimension.to_string(), expression),
+                        None => obj.bindings.remove(dimension),
+                    };
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Stops driving a dimension; it keeps its last evaluated value.
+    pub fn unbind_dimension(&mut self, id: ObjectId, dimension: &str) -> Option<String> {
+        self.objects
+            .iter_mut()
+            .find(|obj| obj.id == id)?
+            .bindings
+            .remove(dimension)
+    }
+
+    /// Writes the evaluated bindings into the kinds of their objects,
+    /// returning the ids whose kind changed. Nothing is written unless
+    /// every binding evaluates to a valid kind.
+    pub(crate) fn resolve_bindings(&mut self) -> Result<Vec<ObjectId>, ParamError> {
+        let values = self.parameter_values()?;
+        let mut updates = Vec::new();
+        for obj in self.objects.iter().filter(|obj| !obj.bindings.is_empty()) {
+            let mut kind = obj.kind.clone();
+            for (dimension, expression) in &obj.bindings {
+                let value = evaluate_with(&Expr::parse(expression)?, &values)?;
+                if !kind.set_dimension(dimension, value as f32) {
+                    return Err(ParamError::UnknownDimension(dimension.clone()));
+                }
+            }
+            kind.validate()
+                .map_err(|reason| ParamError::InvalidDimension(obj.id, reason))?;
+            if kind != obj.kind {
+                updates.push((obj.id, kind));
+            }
+        }
+        let changed = updates.iter().map(|(id, _)| *id).collect();
+        for (id, kind) in updates {
+            self.set_kind(id, kind);
+        }
+        Ok(changed)
+    }
+
+    /// Values of every parameter, failing on unknown names and cycles.
+    fn parameter_values(&self) -> Result<HashMap<String, f64>, ParamError> {
+        let exprs = self
+            .parameters
+            .iter()
+            .map(|param| Ok((param.name.as_str(), Expr::parse(&param.expression)?)))
+            .collect::<Result<HashMap<_, _>, ParamError>>()?;
+        let mut values = HashMap::new();
+        for param in &self.parameters {
+            resolve(&param.name, &exprs, &mut values, &mut Vec::new())?;
+        }
+        Ok(values)
+    }
+}
+
+/// Evaluates `name` and the parameters it reads, memoizing into `values`;
+/// `pending` holds the names being evaluated further up, to catch cycles.
+fn resolve(
+    name: &str,
+    exprs: &HashMap<&str, Expr>,
+    values: &mut HashMap<String, f64>,
+    pending: &mut Vec<String>,
+) -> Result<f64, ParamError> {
+    if let Some(value) = values.get(name) {
+        return Ok(*value);
+    }
+    if pending.iter().any(|open| open == name) {
+        return Err(ParamError::Cycle(name.to_string()));
+    }
+    let expr = exprs
+        .get(name)
+        .ok_or_else(|| ParamError::UnknownParameter(name.to_string()))?;
+    pending.push(name.to_string());
+    let value = expr.eval(&mut |used| resolve(used, exprs, values, pending))?;
+    pending.pop();
+    if !value.is_finite() {
+        return Err(ParamError::NotFinite);
+    }
+    values.insert(name.to_string(), value);
+    Ok(value)
+}
+
+fn evaluate_with(expr: &Expr, values: &HashMap<String, f64>) -> Result<f64, ParamError> {
+    let value = expr.eval(&mut |name| {
+        values
+            .get(name)
+            .copied()
+            .ok_or_else(|| ParamError::UnknownParameter(name.to_string()))
+    })?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(ParamError::NotFinite)
+    }
+}
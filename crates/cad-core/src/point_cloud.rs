@@ -0,0 +1,19 @@
+//! Scanned point data kept as modeling reference.
+
+use crate::Transform;
+use serde::{Deserialize, Serialize};
+
+pub type PointCloudId = u64;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PointCloud {
+    pub id: PointCloudId,
+    pub name: String,
+    pub points: Vec<[f32; 3]>,
+    /// Per-point RGB, either empty or parallel to `points`.
+    #[serde(default)]
+    pub colors: Vec<[u8; 3]>,
+    #[serde(default)]
+    pub transform: Transform,
+}
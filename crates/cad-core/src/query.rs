@@ -0,0 +1,101 @@
+//! Object tags and queries over tags, kinds, layers and names.
+
+use crate::{LayerId, Model, ModelObject, ObjectId};
+use std::collections::BTreeSet;
+
+/// Which objects [`Model::query`] returns. Text comparisons ignore ASCII
+/// case.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectQuery {
+    Tag(String),
+    /// Matches [`crate::ObjectKind::label`], e.g. `"box"` or `"extrusion"`.
+    Kind(String),
+    /// `None` matches objects on the default layer.
+    Layer(Option<LayerId>),
+    /// Substring of the display name.
+    Name(String),
+    /// Objects matching every query; matches everything when empty.
+    All(Vec<ObjectQuery>),
+    /// Objects matching any query; matches nothing when empty.
+    Any(Vec<ObjectQuery>),
+}
+
+impl ObjectQuery {
+    pub fn matches(&self, obj: &ModelObject) -> bool {
+        match self {
+            Self::Tag(tag) => obj.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            Self::Kind(kind) => obj.kind.label().eq_ignore_ascii_case(kind),
+            Self::Layer(layer) => obj.layer == *layer,
+            Self::Name(text) => obj
+                .display_name()
+                .to_ascii_lowercase()
+                .contains(&text.to_ascii_lowercase()),
+            Self::All(queries) => queries.iter().all(|q| q.matches(obj)),
+            Self::Any(queries) => queries.iter().any(|q| q.matches(obj)),
+        }
+    }
+}
+
+impl Model {
+    /// Tags an object; tags are trimmed and must not be empty. Returns
+    /// `false` for unknown objects, empty tags and tags already present.
+    pub fn add_tag(&mut self, id: ObjectId, tag: &str) -> bool {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return false;
+        }
+        match self.objects.iter_mut().find(|obj| obj.id == id) {
+            Some(obj) => obj.tags.insert(tag.to_string()),
+            None => false,
+        }
+    }
+
+    pub fn remove_tag(&mut self, id: ObjectId, tag: &str) -> bool {
+        match self.objects.iter_mut().find(|obj| obj.id == id) {
+            Some(obj) => obj.tags.remove(tag.trim()),
+            None => false,
+        }
+    }
+
+    /// Every tag used by some object, sorted.
+    pub fn tags(&self) -> BTreeSet<&str> {
+        self.objects
+            .iter()
+            .flat_map(|obj| obj.tags.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Ids of the objects matching `query`, in model order.
+    pub fn query(&self, query: &ObjectQuery) -> Vec<ObjectId> {
+        self.objects
+            .iter()
+            .filter(|obj| query.matches(obj))
+            .map(|obj| obj.id)
+            .collect()
+    }
+
+    /// Parses search-box text into a query: whitespace-separated terms that
+    /// must all match, each `tag:NAME`, `kind:LABEL`, `layer:NAME` (or
+    /// `layer:default`) or a bare word matched against names.
+    pub fn parse_query(&self, text: &str) -> ObjectQuery {
+        let terms = text
+            .split_whitespace()
+            .map(|term| match term.split_once(':') {
+                Some(("tag", tag)) => ObjectQuery::Tag(tag.to_string()),
+                Some(("kind", kind)) => ObjectQuery::Kind(kind.to_string()),
+                Some(("layer", name)) if name.eq_ignore_ascii_case("default") => {
+                    ObjectQuery::Layer(None)
+                }
+                Some(("layer", name)) => ObjectQuery::Any(
+                    self.layers
+                        .iter()
+                        .filter(|layer| layer.name.eq_ignore_ascii_case(name))
+                        .map(|layer| ObjectQuery::Layer(Some(layer.id)))
+                        .collect(),
+                ),
+                _ => ObjectQuery::Name(term.to_string()),
+            })
+            .collect();
+        ObjectQuery::All(terms)
+    }
+}
@@ -0,0 +1,156 @@
+//! Bodies referencing parts saved in other documents.
+
+use crate::{MeshAsset, Model, ObjectId, ObjectKind, StableId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// What a [`ObjectKind::Reference`] body stands for once its document has
+/// been loaded: the referenced body's kind in this document's unit, plus
+/// the mesh it draws when that kind is [`ObjectKind::Mesh`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedReference {
+    pub kind: ObjectKind,
+    pub mesh: Option<MeshAsset>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReferenceError {
+    /// The document could not be loaded; carries the loader's message.
+    Load {
+        document: String,
+        reason: String,
+    },
+    MissingObject {
+        document: String,
+        object: StableId,
+    },
+    /// The referenced body cannot be rebuilt on its own (it is derived,
+    /// has active features, or is itself a reference).
+    Unsupported {
+        document: String,
+        object: StableId,
+    },
+}
+
+impl fmt::Display for ReferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Load { document, reason } => write!(f, "cannot load `{document}`: {reason}"),
+            Self::MissingObject { document, object } => {
+                write!(f, "`{document}` has no object {object}")
+            }
+            Self::Unsupported { document, object } => write!(
+                f,
+                "object {object} of `{document}` depends on other bodies and cannot be referenced"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReferenceError {}
+
+impl Model {
+    /// Adds a body standing for `object` of the document at `document`.
+    /// It has no shape until [`Model::resolve_references`] runs.
+    pub fn add_reference(&mut self, document: impl Into<String>, object: StableId) -> ObjectId {
+        self.add_object(ObjectKind::Reference {
+            document: document.into(),
+            object,
+        })
+    }
+
+    pub fn resolved_reference(
+        &self,
+        document: &str,
+        object: StableId,
+    ) -> Option<&ResolvedReference> {
+        self.resolved_references
+            .get(&(document.to_string(), object))
+    }
+
+    /// Loads every referenced document once through `load` and caches
+    /// what each reference body stands for. Run after loading a document
+    /// and whenever referenced files change. Returns the reference bodies
+    /// that could not be resolved; they keep their previous resolution, if
+    /// any.
+    pub fn resolve_references<E: fmt::Display>(
+        &mut self,
+        mut load: impl FnMut(&str) -> Result<Model, E>,
+    ) -> Vec<(ObjectId, ReferenceError)> {
+        let mut documents: HashMap<String, Result<Model, String>> = HashMap::new();
+        let mut failures = Vec::new();
+        let references: Vec<(ObjectId, String, StableId)> = self
+            .objects
+            .iter()
+            .filter_map(|obj| match &obj.kind {
+                ObjectKind::Reference { document, object } => {
+                    Some((obj.id, document.clone(), *object))
+                }
+                _ => None,
+            })
+            .collect();
+        for (id, document, object) in references {
+            let loaded = documents.entry(document.clone()).or_insert_with(|| {
+                load(&document)
+                    .map(|mut model| {
+                        model.convert_units(self.unit);
+                        model
+                    })
+                    .map_err(|err| err.to_string())
+            });
+            let resolved = match loaded {
+                Ok(model) => resolve_in(model, &document, object),
+                Err(reason) => Err(ReferenceError::Load {
+                    document: document.clone(),
+                    reason: reason.clone(),
+                }),
+            };
+            match resolved {
+                Ok(resolved) => {
+                    self.resolved_references
+                        .insert((document, object), resolved);
+                }
+                Err(err) => failures.push((id, err)),
+            }
+        }
+        failures
+    }
+}
+
+fn resolve_in(
+    model: &Model,
+    document: &str,
+    object: StableId,
+) -> Result<ResolvedReference, ReferenceError> {
+    let obj = model
+        .object_by_uid(object)
+        .ok_or_else(|| ReferenceError::MissingObject {
+            document: document.to_string(),
+            object,
+        })?;
+    let standalone = !matches!(
+        obj.kind,
+        ObjectKind::Derived { .. } | ObjectKind::Reference { .. }
+    ) && obj.features.iter().all(|feature| feature.suppressed);
+    if !standalone {
+        return Err(ReferenceError::Unsupported {
+            document: document.to_string(),
+            object,
+        });
+    }
+    let mesh = match obj.kind {
+        ObjectKind::Mesh { handle } => {
+            Some(model.mesh_asset(handle).cloned().ok_or_else(|| {
+                ReferenceError::MissingObject {
+                    document: document.to_string(),
+                    object,
+                }
+            })?)
+        }
+        _ => None,
+    };
+    Ok(ResolvedReference {
+        kind: obj.kind.clone(),
+        mesh,
+    })
+}
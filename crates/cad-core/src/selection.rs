@@ -0,0 +1,93 @@
+//! Named selection sets for re-selecting groups of bodies.
+
+use crate::{Model, ObjectId};
+use serde::{Deserialize, Serialize};
+
+pub type SelectionSetId = u64;
+
+/// A saved list of bodies, e.g. "all fasteners". Ids of removed bodies are
+/// kept, so the set comes back whole if they are restored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SelectionSet {
+    pub id: SelectionSetId,
+    pub name: String,
+    pub objects: Vec<ObjectId>,
+}
+
+impl Model {
+    pub fn selection_sets(&self) -> &[SelectionSet] {
+        &self.selection_sets
+    }
+
+    pub fn selection_set(&self, id: SelectionSetId) -> Option<&SelectionSet> {
+        self.selection_sets.iter().find(|set| set.id == id)
+    }
+
+    /// Saves `objects` (duplicates and unknown ids dropped) under `name`.
+    pub fn add_selection_set(
+        &mut self,
+        name: impl Into<String>,
+        objects: &[ObjectId],
+    ) -> SelectionSetId {
+        let id = self.next_selection_set_id;
+        self.next_selection_set_id += 1;
+        let objects = self.known_objects(objects);
+        self.selection_sets.push(SelectionSet {
+            id,
+            name: name.into(),
+            objects,
+        });
+        id
+    }
+
+    pub fn rename_selection_set(&mut self, id: SelectionSetId, name: impl Into<String>) -> bool {
+        match self.selection_sets.iter_mut().find(|set| set.id == id) {
+            Some(set) => {
+                set.name = name.into();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the bodies of a set, e.g. after the user edits the selection.
+    pub fn set_selection_set_objects(&mut self, id: SelectionSetId, objects: &[ObjectId]) -> bool {
+        let objects = self.known_objects(objects);
+        match self.selection_sets.iter_mut().find(|set| set.id == id) {
+            Some(set) => {
+                set.objects = objects;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_selection_set(&mut self, id: SelectionSetId) -> Option<SelectionSet> {
+        let idx = self.selection_sets.iter().position(|set| set.id == id)?;
+        Some(self.selection_sets.remove(idx))
+    }
+
+    /// The bodies of a set that can be selected right now: present in the
+    /// model and pickable (see [`Model::is_pickable`]), in model order.
+    pub fn apply_selection_set(&self, id: SelectionSetId) -> Vec<ObjectId> {
+        let Some(set) = self.selection_set(id) else {
+            return Vec::new();
+        };
+        self.objects
+            .iter()
+            .filter(|obj| set.objects.contains(&obj.id) && self.is_pickable(obj))
+            .map(|obj| obj.id)
+            .collect()
+    }
+
+    fn known_objects(&self, ids: &[ObjectId]) -> Vec<ObjectId> {
+        let mut known: Vec<ObjectId> = Vec::with_capacity(ids.len());
+        for &id in ids {
+            if self.object(id).is_some() && !known.contains(&id) {
+                known.push(id);
+            }
+        }
+        known
+    }
+}
@@ -0,0 +1,331 @@
+//! 2D sketches drawn on a plane.
+
+use serde::{Deserialize, Serialize};
+
+pub type SketchId = u64;
+
+/// Plane with an orthonormal `(u, v)` frame; `normal = u x v`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SketchPlane {
+    pub origin: [f32; 3],
+    pub normal: [f32; 3],
+    pub u: [f32; 3],
+    pub v: [f32; 3],
+}
+
+impl SketchPlane {
+    pub const XY: Self = Self {
+        origin: [0.0, 0.0, 0.0],
+        normal: [0.0, 0.0, 1.0],
+        u: [1.0, 0.0, 0.0],
+        v: [0.0, 1.0, 0.0],
+    };
+    pub const XZ: Self = Self {
+        origin: [0.0, 0.0, 0.0],
+        normal: [0.0, -1.0, 0.0],
+        u: [1.0, 0.0, 0.0],
+        v: [0.0, 0.0, 1.0],
+    };
+    pub const YZ: Self = Self {
+        origin: [0.0, 0.0, 0.0],
+        normal: [1.0, 0.0, 0.0],
+        u: [0.0, 1.0, 0.0],
+        v: [0.0, 0.0, 1.0],
+    };
+
+    /// Builds a plane from its in-plane axes; the normal is `u x v`.
+    pub fn new(origin: [f32; 3], u: [f32; 3], v: [f32; 3]) -> Self {
+        let normal = [
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ];
+        Self {
+            origin,
+            normal,
+            u,
+            v,
+        }
+    }
+
+    /// Maps plane coordinates to world space.
+    pub fn to_world(&self, p: [f32; 2]) -> [f32; 3] {
+        std::array::from_fn(|i| self.origin[i] + self.u[i] * p[0] + self.v[i] * p[1])
+    }
+
+    /// Projects a world point onto the plane, returning plane coordinates.
+    pub fn to_plane(&self, p: [f32; 3]) -> [f32; 2] {
+        let rel: [f32; 3] = std::array::from_fn(|i| p[i] - self.origin[i]);
+        [dot(rel, self.u), dot(rel, self.v)]
+    }
+}
+
+/// Sketch geometry in plane coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum SketchEntity {
+    Line {
+        a: [f32; 2],
+        b: [f32; 2],
+    },
+    /// Counter-clockwise for positive `sweep` (radians).
+    Arc {
+        center: [f32; 2],
+        radius: f32,
+        start_angle: f32,
+        sweep: f32,
+    },
+    Circle {
+        center: [f32; 2],
+        radius: f32,
+    },
+    /// Interpolating cubic B-spline through `points`. A closed spline joins
+    /// the last point back to the first.
+    Spline {
+        points: Vec<[f32; 2]>,
+        #[serde(default)]
+        closed: bool,
+    },
+}
+
+impl SketchEntity {
+    pub fn start(&self) -> [f32; 2] {
+        match *self {
+            Self::Line { a, .. } => a,
+            Self::Arc {
+                center,
+                radius,
+                start_angle,
+                ..
+            } => polar(center, radius, start_angle),
+            Self::Circle { center, radius } => polar(center, radius, 0.0),
+            Self::Spline { ref points, .. } => points.first().copied().unwrap_or_default(),
+        }
+    }
+
+    pub fn end(&self) -> [f32; 2] {
+        match *self {
+            Self::Line { b, .. } => b,
+            Self::Arc {
+                center,
+                radius,
+                start_angle,
+                sweep,
+            } => polar(center, radius, start_angle + sweep),
+            Self::Circle { center, radius } => polar(center, radius, 0.0),
+            Self::Spline {
+                ref points,
+                closed: true,
+            } => points.first().copied().unwrap_or_default(),
+            Self::Spline { ref points, .. } => points.last().copied().unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn scale_lengths(&mut self, factor: f32) {
+        let scale = |p: &mut [f32; 2]| *p = p.map(|c| c * factor);
+        match self {
+            Self::Line { a, b } => {
+                scale(a);
+                scale(b);
+            }
+            Self::Arc { center, radius, .. } | Self::Circle { center, radius } => {
+                scale(center);
+                *radius *= factor;
+            }
+            Self::Spline { points, .. } => points.iter_mut().for_each(scale),
+        }
+    }
+
+    /// Entities that form a loop on their own.
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self,
+            Self::Circle { .. } | Self::Spline { closed: true, .. }
+        )
+    }
+
+    /// Cubic Bézier spans `[p0, c0, c1, p1]` of a spline, in order; empty for
+    /// other entities and for splines with too few points.
+    ///
+    /// Open splines use natural end conditions (zero curvature at the ends);
+    /// closed splines are periodic.
+    pub fn spline_spans(&self) -> Vec<[[f32; 2]; 4]> {
+        let Self::Spline { points, closed } = self else {
+            return Vec::new();
+        };
+        let n = points.len();
+        if n < 2 || (*closed && n < 3) {
+            return Vec::new();
+        }
+
+        // B-spline de Boor points satisfy d[i-1] + 4 d[i] + d[i+1] = 6 p[i];
+        // the system is diagonally dominant so Gauss-Seidel converges fast.
+        let interior = if *closed { 0..n } else { 1..n - 1 };
+        let mut d = points.clone();
+        for _ in 0..SPLINE_ITERATIONS {
+            for i in interior.clone() {
+                let prev = d[(i + n - 1) % n];
+                let next = d[(i + 1) % n];
+                d[i] = std::array::from_fn(|k| (6.0 * points[i][k] - prev[k] - next[k]) / 4.0);
+            }
+        }
+
+        let spans = if *closed { n } else { n - 1 };
+        (0..spans)
+            .map(|i| {
+                let j = (i + 1) % n;
+                [
+                    points[i],
+                    std::array::from_fn(|k| (2.0 * d[i][k] + d[j][k]) / 3.0),
+                    std::array::from_fn(|k| (d[i][k] + 2.0 * d[j][k]) / 3.0),
+                    points[j],
+                ]
+            })
+            .collect()
+    }
+}
+
+/// A chain of entities joined end to end.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SketchChain {
+    /// Entity index and whether it is traversed end-to-start.
+    pub entities: Vec<(usize, bool)>,
+    pub closed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Sketch {
+    pub id: SketchId,
+    pub name: String,
+    pub plane: SketchPlane,
+    pub entities: Vec<SketchEntity>,
+    /// Geometry projected from other bodies, used for snapping and display
+    /// but not for profiles.
+    #[serde(default)]
+    pub references: Vec<SketchEntity>,
+}
+
+impl Sketch {
+    /// Groups entities into chains by matching endpoints within `tolerance`.
+    pub fn chains(&self, tolerance: f32) -> Vec<SketchChain> {
+        let mut used = vec![false; self.entities.len()];
+        let mut chains = Vec::new();
+
+        for (first, entity) in self.entities.iter().enumerate() {
+            if used[first] {
+                continue;
+            }
+            used[first] = true;
+            if entity.is_closed() {
+                chains.push(SketchChain {
+                    entities: vec![(first, false)],
+                    closed: true,
+                });
+                continue;
+            }
+
+            let start = entity.start();
+            let mut cursor = entity.end();
+            let mut chain = vec![(first, false)];
+            let closed = loop {
+                let can_close = chain.len() > 1 || !matches!(entity, SketchEntity::Line { .. });
+                if can_close && distance(cursor, start) <= tolerance {
+                    break true;
+                }
+                let next = self.entities.iter().enumerate().find_map(|(idx, other)| {
+                    if used[idx] || other.is_closed() {
+                        None
+                    } else if distance(other.start(), cursor) <= tolerance {
+                        Some((idx, false))
+                    } else if distance(other.end(), cursor) <= tolerance {
+                        Some((idx, true))
+                    } else {
+                        None
+                    }
+                });
+                let Some((idx, reversed)) = next else {
+                    break false;
+                };
+                used[idx] = true;
+                chain.push((idx, reversed));
+                let other = &self.entities[idx];
+                cursor = if reversed { other.start() } else { other.end() };
+            };
+
+            // An open chain may have started mid-way; grow it backwards too.
+            if !closed {
+                let mut head = start;
+                loop {
+                    let prev = self.entities.iter().enumerate().find_map(|(idx, other)| {
+                        if used[idx] || other.is_closed() {
+                            None
+                        } else if distance(other.end(), head) <= tolerance {
+                            Some((idx, false))
+                        } else if distance(other.start(), head) <= tolerance {
+                            Some((idx, true))
+                        } else {
+                            None
+                        }
+                    });
+                    let Some((idx, reversed)) = prev else {
+                        break;
+                    };
+                    used[idx] = true;
+                    chain.insert(0, (idx, reversed));
+                    let other = &self.entities[idx];
+                    head = if reversed { other.end() } else { other.start() };
+                }
+            }
+
+            chains.push(SketchChain {
+                entities: chain,
+                closed,
+            });
+        }
+
+        chains
+    }
+
+    /// First and last point of a chain, following traversal direction.
+    pub fn chain_endpoints(&self, chain: &SketchChain) -> Option<([f32; 2], [f32; 2])> {
+        let &(first, first_rev) = chain.entities.first()?;
+        let &(last, last_rev) = chain.entities.last()?;
+        let first = &self.entities[first];
+        let last = &self.entities[last];
+        Some((
+            if first_rev {
+                first.end()
+            } else {
+                first.start()
+            },
+            if last_rev { last.start() } else { last.end() },
+        ))
+    }
+
+    pub fn closed_loops(&self, tolerance: f32) -> Vec<SketchChain> {
+        self.chains(tolerance)
+            .into_iter()
+            .filter(|chain| chain.closed)
+            .collect()
+    }
+}
+
+/// Gauss-Seidel sweeps when solving for spline control points.
+const SPLINE_ITERATIONS: usize = 32;
+
+fn polar(center: [f32; 2], radius: f32, angle: f32) -> [f32; 2] {
+    [
+        center[0] + radius * angle.cos(),
+        center[1] + radius * angle.sin(),
+    ]
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
@@ -0,0 +1,83 @@
+//! Read-only snapshots of a model that stay consistent while it is edited.
+
+use crate::Model;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// An immutable view of a model at one point in time. Cloning is cheap, and
+/// it can be sent to other threads (e.g. export jobs).
+#[derive(Debug, Clone)]
+pub struct ModelSnapshot(Arc<Model>);
+
+impl ModelSnapshot {
+    /// The document revision the snapshot was taken at.
+    pub fn revision(&self) -> u64 {
+        self.0.info().revision
+    }
+
+    /// Takes the model out, cloning it only if other snapshots share it.
+    pub fn into_model(self) -> Model {
+        Arc::try_unwrap(self.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+}
+
+impl Deref for ModelSnapshot {
+    type Target = Model;
+
+    fn deref(&self) -> &Model {
+        &self.0
+    }
+}
+
+impl From<Model> for ModelSnapshot {
+    fn from(model: Model) -> Self {
+        Self(Arc::new(model))
+    }
+}
+
+impl Model {
+    /// Copies the model into a snapshot. Owners that snapshot often should
+    /// hold a [`SharedModel`] instead, which only copies on the next edit.
+    pub fn snapshot(&self) -> ModelSnapshot {
+        ModelSnapshot(Arc::new(self.clone()))
+    }
+}
+
+/// A model with copy-on-write snapshots: taking one is O(1), and the first
+/// edit after it copies the model once, leaving the snapshot untouched.
+#[derive(Debug, Clone, Default)]
+pub struct SharedModel(Arc<Model>);
+
+impl SharedModel {
+    pub fn new(model: Model) -> Self {
+        Self(Arc::new(model))
+    }
+
+    pub fn snapshot(&self) -> ModelSnapshot {
+        ModelSnapshot(Arc::clone(&self.0))
+    }
+
+    /// Mutable access for an edit, copying the model first if a snapshot
+    /// still shares it.
+    pub fn edit(&mut self) -> &mut Model {
+        Arc::make_mut(&mut self.0)
+    }
+
+    pub fn into_model(self) -> Model {
+        ModelSnapshot(self.0).into_model()
+    }
+}
+
+impl Deref for SharedModel {
+    type Target = Model;
+
+    fn deref(&self) -> &Model {
+        &self.0
+    }
+}
+
+impl From<Model> for SharedModel {
+    fn from(model: Model) -> Self {
+        Self::new(model)
+    }
+}
@@ -0,0 +1,131 @@
+//! Document size figures for status displays and server reports.
+
+use crate::{DerivedOp, FeatureOp, LayerId, Model, ModelObject, ObjectKind, Operand, SketchEntity};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Segments assumed per full turn of a curved surface when estimating.
+const ESTIMATE_SEGMENTS: usize = 32;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ModelStats {
+    pub objects: usize,
+    /// Objects left out of the viewport, by their own flag or their layer.
+    pub hidden: usize,
+    /// Object count per kind label (see [`ObjectKind::label`]).
+    pub kinds: BTreeMap<String, usize>,
+    pub features: usize,
+    pub sketches: usize,
+    pub components: usize,
+    pub layers: usize,
+    /// Object count per layer, the default layer (`None`) first, then the
+    /// layers in model order.
+    pub objects_by_layer: Vec<(Option<LayerId>, usize)>,
+    /// Rough triangle count of the tessellated scene, without meshing it.
+    pub triangles: usize,
+}
+
+impl Model {
+    pub fn stats(&self) -> ModelStats {
+        let mut kinds = BTreeMap::new();
+        for obj in &self.objects {
+            *kinds.entry(obj.kind.label().to_string()).or_default() += 1;
+        }
+        let on_layer =
+            |layer: Option<LayerId>| self.objects.iter().filter(|obj| obj.layer == layer).count();
+        let objects_by_layer = std::iter::once(None)
+            .chain(self.layers.iter().map(|layer| Some(layer.id)))
+            .map(|layer| (layer, on_layer(layer)))
+            .collect();
+        ModelStats {
+            objects: self.objects.len(),
+            hidden: self
+                .objects
+                .iter()
+                .filter(|obj| !self.is_visible(obj))
+                .count(),
+            kinds,
+            features: self.objects.iter().map(|obj| obj.features.len()).sum(),
+            sketches: self.sketches.len(),
+            components: self.components.len(),
+            layers: self.layers.len(),
+            objects_by_layer,
+            triangles: self
+                .objects
+                .iter()
+                .map(|obj| self.triangle_estimate(obj, true))
+                .sum(),
+        }
+    }
+
+    /// Triangles expected from tessellating `obj`. Boolean operands are
+    /// counted one level deep only, which keeps cycles harmless.
+    fn triangle_estimate(&self, obj: &ModelObject, follow_operands: bool) -> usize {
+        let copies: usize = obj
+            .features
+            .iter()
+            .filter(|feature| !feature.suppressed)
+            .map(|feature| match feature.op {
+                FeatureOp::LinearPattern { count, .. } => count.max(1) as usize,
+                _ => 1,
+            })
+            .product();
+        self.kind_triangles(&obj.kind, follow_operands) * copies
+    }
+
+    fn kind_triangles(&self, kind: &ObjectKind, follow_operands: bool) -> usize {
+        let n = ESTIMATE_SEGMENTS;
+        match kind {
+            ObjectKind::Box { .. } => 12,
+            ObjectKind::Wedge { .. } => 8,
+            // Side quads plus two fans of caps.
+            ObjectKind::Cylinder { .. } => 2 * n + 2 * (n - 2),
+            ObjectKind::Tube { .. } => 4 * n + 4 * n,
+            ObjectKind::Prism { sides, .. } => {
+                let sides = *sides as usize;
+                2 * sides + 2 * sides.saturating_sub(2)
+            }
+            ObjectKind::Mesh { handle } => self
+                .mesh_asset(*handle)
+                .map_or(0, |asset| asset.indices.len() / 3),
+            ObjectKind::Derived { op, operands } => match op {
+                DerivedOp::Boolean(_) if follow_operands => operands
+                    .iter()
+                    .filter_map(|operand| match operand {
+                        Operand::Object(id) => self.object(*id),
+                        Operand::Sketch(_) => None,
+                    })
+                    .map(|operand| self.triangle_estimate(operand, false))
+                    .sum(),
+                DerivedOp::Boolean(_) => 0,
+                // Walls plus caps for extrusions; one band per profile
+                // segment for revolves.
+                DerivedOp::Extrude { .. } => 4 * self.profile_segments(operands),
+                DerivedOp::Revolve { .. } => 2 * n * self.profile_segments(operands),
+            },
+            ObjectKind::Reference { document, object } => self
+                .resolved_reference(document, *object)
+                .map_or(0, |resolved| match &resolved.mesh {
+                    Some(mesh) => mesh.indices.len() / 3,
+                    None => self.kind_triangles(&resolved.kind, false),
+                }),
+        }
+    }
+
+    fn profile_segments(&self, operands: &[Operand]) -> usize {
+        operands
+            .iter()
+            .filter_map(|operand| match operand {
+                Operand::Sketch(id) => self.sketch(*id),
+                Operand::Object(_) => None,
+            })
+            .flat_map(|sketch| &sketch.entities)
+            .map(|entity| match entity {
+                SketchEntity::Line { .. } => 1,
+                SketchEntity::Arc { .. } | SketchEntity::Circle { .. } => ESTIMATE_SEGMENTS,
+                SketchEntity::Spline { points, .. } => 4 * points.len(),
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,80 @@
+//! Matrix and quaternion helpers for [`Transform`].
+
+use crate::Transform;
+use glam::{Mat4, Quat, Vec3};
+
+impl Transform {
+    pub fn from_translation(translation: [f32; 3]) -> Self {
+        Self {
+            translation,
+            ..Self::default()
+        }
+    }
+
+    /// Splits an affine matrix into scale, rotation and translation. Shear
+    /// cannot be represented and is lost.
+    pub fn from_mat4(m: Mat4) -> Self {
+        let (scale, rotation, translation) = m.to_scale_rotation_translation();
+        Self {
+            translation: translation.to_array(),
+            rotation: rotation.normalize().to_array(),
+            scale: scale.to_array(),
+        }
+    }
+
+    /// Scale, then rotation, then translation.
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(
+            Vec3::from_array(self.scale),
+            self.rotation_quat(),
+            Vec3::from_array(self.translation),
+        )
+    }
+
+    /// The rotation as a unit quaternion.
+    pub fn rotation_quat(&self) -> Quat {
+        Quat::from_array(self.rotation).normalize()
+    }
+
+    /// `child` placed in the space of `self`: applying the result equals
+    /// applying `child`, then `self`. Exact unless a non-uniform scale of
+    /// `self` meets a rotated `child`, which would need shear.
+    pub fn compose(&self, child: &Transform) -> Transform {
+        Self::from_mat4(self.to_mat4() * child.to_mat4())
+    }
+
+    /// The transform undoing this one, with the same shear caveat as
+    /// [`Transform::compose`].
+    pub fn inverse(&self) -> Transform {
+        Self::from_mat4(self.to_mat4().inverse())
+    }
+
+    /// Interpolates translation and scale linearly and rotation along the
+    /// shortest arc; `t = 0` gives `self`, `t = 1` gives `other`.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        let mix =
+            |a: [f32; 3], b: [f32; 3]| Vec3::from_array(a).lerp(Vec3::from_array(b), t).to_array();
+        Self {
+            translation: mix(self.translation, other.translation),
+            rotation: self
+                .rotation_quat()
+                .slerp(other.rotation_quat(), t)
+                .normalize()
+                .to_array(),
+            scale: mix(self.scale, other.scale),
+        }
+    }
+
+    pub fn transform_point(&self, p: [f32; 3]) -> [f32; 3] {
+        self.to_mat4()
+            .transform_point3(Vec3::from_array(p))
+            .to_array()
+    }
+
+    /// Transforms a direction: rotation and scale, no translation.
+    pub fn transform_vector(&self, v: [f32; 3]) -> [f32; 3] {
+        self.to_mat4()
+            .transform_vector3(Vec3::from_array(v))
+            .to_array()
+    }
+}
@@ -0,0 +1,52 @@
+//! Recoverable deletion: deleted objects wait in the trash until they are
+//! restored or purged.
+
+use crate::{Model, ObjectId, Removed};
+
+impl Model {
+    /// Deleted objects, oldest first, with everything detached alongside
+    /// them.
+    pub fn trash(&self) -> &[Removed] {
+        &self.trash
+    }
+
+    pub fn deleted(&self, id: ObjectId) -> Option<&Removed> {
+        self.trash.iter().find(|removed| removed.object.id == id)
+    }
+
+    /// Moves an object to the trash, like [`Model::remove`] but keeping what
+    /// was detached for [`Model::restore`].
+    pub fn delete(&mut self, id: ObjectId) -> bool {
+        match self.remove(id) {
+            Some(removed) => {
+                self.trash.push(removed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Takes an object out of the trash and puts it back where it was.
+    /// Returns `false` (and changes nothing) if it is not in the trash or
+    /// its id is taken.
+    pub fn restore(&mut self, id: ObjectId) -> bool {
+        let Some(idx) = self.trash.iter().position(|r| r.object.id == id) else {
+            return false;
+        };
+        if self.object(id).is_some() {
+            return false;
+        }
+        let removed = self.trash.remove(idx);
+        self.reinsert(removed)
+    }
+
+    /// Drops an object from the trash for good.
+    pub fn purge(&mut self, id: ObjectId) -> Option<Removed> {
+        let idx = self.trash.iter().position(|r| r.object.id == id)?;
+        Some(self.trash.remove(idx))
+    }
+
+    pub fn empty_trash(&mut self) -> Vec<Removed> {
+        std::mem::take(&mut self.trash)
+    }
+}
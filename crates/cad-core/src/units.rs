@@ -0,0 +1,46 @@
+//! Document length units.
+
+use serde::{Deserialize, Serialize};
+
+/// What a length of `1.0` means in a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum LengthUnit {
+    #[default]
+    Millimeter,
+    Centimeter,
+    Meter,
+    Inch,
+}
+
+impl LengthUnit {
+    pub const ALL: [Self; 4] = [Self::Millimeter, Self::Centimeter, Self::Meter, Self::Inch];
+
+    pub fn meters_per_unit(self) -> f64 {
+        match self {
+            Self::Millimeter => 0.001,
+            Self::Centimeter => 0.01,
+            Self::Meter => 1.0,
+            Self::Inch => 0.0254,
+        }
+    }
+
+    /// Short label for status bars and dimension text.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Millimeter => "mm",
+            Self::Centimeter => "cm",
+            Self::Meter => "m",
+            Self::Inch => "in",
+        }
+    }
+
+    /// Factor turning lengths in `self` into lengths in `to`.
+    pub fn factor_to(self, to: Self) -> f64 {
+        self.meters_per_unit() / to.meters_per_unit()
+    }
+
+    pub fn convert(self, value: f32, to: Self) -> f32 {
+        (value as f64 * self.factor_to(to)) as f32
+    }
+}
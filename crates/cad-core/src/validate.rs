@@ -0,0 +1,257 @@
+//! Consistency checks run before a document is trusted or persisted.
+
+use crate::{
+    AnnotationId, ComponentId, DatumId, FeatureId, LayerId, MateId, MeshHandle, Model, ObjectId,
+    ObjectKind, StableId, Transform,
+};
+use std::collections::HashSet;
+use std::fmt;
+
+/// A problem found by [`Model::validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelIssue {
+    DuplicateObjectId(ObjectId),
+    DuplicateStableId(StableId),
+    DuplicateComponentId(ComponentId),
+    DuplicateLayerId(LayerId),
+    /// An object id at or above the counter, which a later add would reuse.
+    IdAboveCounter(ObjectId),
+    UnknownParent {
+        object: ObjectId,
+        parent: ComponentId,
+    },
+    UnknownComponentParent {
+        component: ComponentId,
+        parent: ComponentId,
+    },
+    /// The component is its own ancestor.
+    ComponentCycle(ComponentId),
+    UnknownLayer {
+        object: ObjectId,
+        layer: LayerId,
+    },
+    UnknownMeshAsset {
+        object: ObjectId,
+        handle: MeshHandle,
+    },
+    /// An active boolean feature whose tool body is gone.
+    UnknownTool {
+        object: ObjectId,
+        feature: FeatureId,
+        tool: ObjectId,
+    },
+    DanglingMate {
+        mate: MateId,
+        object: ObjectId,
+    },
+    DanglingAnnotation {
+        annotation: AnnotationId,
+        object: ObjectId,
+    },
+    NonFiniteTransform(ObjectId),
+    NonFiniteComponentTransform(ComponentId),
+    NonFiniteDatumTransform(DatumId),
+    InvalidKind {
+        object: ObjectId,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for ModelIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateObjectId(id) => write!(f, "object id {id} is used twice"),
+            Self::DuplicateStableId(uid) => write!(f, "stable id {uid} is used twice"),
+            Self::DuplicateComponentId(id) => write!(f, "component id {id} is used twice"),
+            Self::DuplicateLayerId(id) => write!(f, "layer id {id} is used twice"),
+            Self::IdAboveCounter(id) => write!(f, "object id {id} is ahead of the id counter"),
+            Self::UnknownParent { object, parent } => {
+                write!(f, "object {object} is owned by unknown component {parent}")
+            }
+            Self::UnknownComponentParent { component, parent } => {
+                write!(
+                    f,
+                    "component {component} is owned by unknown component {parent}"
+                )
+            }
+            Self::ComponentCycle(id) => write!(f, "component {id} is its own ancestor"),
+            Self::UnknownLayer { object, layer } => {
+                write!(f, "object {object} is on unknown layer {layer}")
+            }
+            Self::UnknownMeshAsset { object, handle } => {
+                write!(f, "object {object} draws unknown mesh asset {handle}")
+            }
+            Self::UnknownTool {
+                object,
+                feature,
+                tool,
+            } => write!(
+                f,
+                "feature {feature} of object {object} uses unknown tool {tool}"
+            ),
+            Self::DanglingMate { mate, object } => {
+                write!(f, "mate {mate} references unknown object {object}")
+            }
+            Self::DanglingAnnotation { annotation, object } => {
+                write!(
+                    f,
+                    "annotation {annotation} references unknown object {object}"
+                )
+            }
+            Self::NonFiniteTransform(id) => write!(f, "object {id} has a non-finite transform"),
+            Self::NonFiniteComponentTransform(id) => {
+                write!(f, "component {id} has a non-finite transform")
+            }
+            Self::NonFiniteDatumTransform(id) => write!(f, "datum {id} has a non-finite transform"),
+            Self::InvalidKind { object, reason } => write!(f, "object {object}: {reason}"),
+        }
+    }
+}
+
+impl Model {
+    /// Checks the document for dangling references, non-finite transforms
+    /// and duplicate ids. An empty list means the model is consistent.
+    pub fn validate(&self) -> Vec<ModelIssue> {
+        let mut issues = Vec::new();
+
+        let mut objects = HashSet::new();
+        let mut stable_ids = HashSet::new();
+        for obj in &self.objects {
+            if !objects.insert(obj.id) {
+                issues.push(ModelIssue::DuplicateObjectId(obj.id));
+            }
+            if obj.id >= self.next_id {
+                issues.push(ModelIssue::IdAboveCounter(obj.id));
+            }
+            if let Some(uid) = obj.stable_id {
+                if !stable_ids.insert(uid) {
+                    issues.push(ModelIssue::DuplicateStableId(uid));
+                }
+            }
+        }
+        let mut components = HashSet::new();
+        for component in &self.components {
+            if !components.insert(component.id) {
+                issues.push(ModelIssue::DuplicateComponentId(component.id));
+            }
+        }
+        let mut layers = HashSet::new();
+        for layer in &self.layers {
+            if !layers.insert(layer.id) {
+                issues.push(ModelIssue::DuplicateLayerId(layer.id));
+            }
+        }
+
+        for component in &self.components {
+            if !transform_is_finite(&component.transform) {
+                issues.push(ModelIssue::NonFiniteComponentTransform(component.id));
+            }
+            match component.parent {
+                Some(parent) if !components.contains(&parent) => {
+                    issues.push(ModelIssue::UnknownComponentParent {
+                        component: component.id,
+                        parent,
+                    });
+                }
+                _ if self.component_in_cycle(component.id) => {
+                    issues.push(ModelIssue::ComponentCycle(component.id));
+                }
+                _ => {}
+            }
+        }
+
+        for datum in &self.datums {
+            if !transform_is_finite(&datum.transform) {
+                issues.push(ModelIssue::NonFiniteDatumTransform(datum.id));
+            }
+        }
+
+        for obj in &self.objects {
+            if !transform_is_finite(&obj.transform) {
+                issues.push(ModelIssue::NonFiniteTransform(obj.id));
+            }
+            if let Err(reason) = obj.kind.validate() {
+                issues.push(ModelIssue::InvalidKind {
+                    object: obj.id,
+                    reason,
+                });
+            }
+            if let ObjectKind::Mesh { handle } = obj.kind {
+                if self.mesh_asset(handle).is_none() {
+                    issues.push(ModelIssue::UnknownMeshAsset {
+                        object: obj.id,
+                        handle,
+                    });
+                }
+            }
+            if let Some(parent) = obj.parent.filter(|p| !components.contains(p)) {
+                issues.push(ModelIssue::UnknownParent {
+                    object: obj.id,
+                    parent,
+                });
+            }
+            if let Some(layer) = obj.layer.filter(|l| !layers.contains(l)) {
+                issues.push(ModelIssue::UnknownLayer {
+                    object: obj.id,
+                    layer,
+                });
+            }
+            for feature in obj.features.iter().filter(|f| !f.suppressed) {
+                if let Some(tool) = feature.op.tool() {
+                    if !objects.contains(&tool) {
+                        issues.push(ModelIssue::UnknownTool {
+                            object: obj.id,
+                            feature: feature.id,
+                            tool,
+                        });
+                    }
+                }
+            }
+        }
+
+        for mate in &self.mates {
+            let (a, b) = mate.kind.objects();
+            for object in [a, b] {
+                if !objects.contains(&object) {
+                    issues.push(ModelIssue::DanglingMate {
+                        mate: mate.id,
+                        object,
+                    });
+                }
+            }
+        }
+        for annotation in &self.annotations {
+            for object in annotation.measurement.objects() {
+                if !objects.contains(&object) {
+                    issues.push(ModelIssue::DanglingAnnotation {
+                        annotation: annotation.id,
+                        object,
+                    });
+                }
+            }
+        }
+        issues
+    }
+
+    /// Whether following parents up from `id` comes back to it.
+    fn component_in_cycle(&self, id: ComponentId) -> bool {
+        let mut current = self.component(id).and_then(|c| c.parent);
+        for _ in 0..self.components.len() {
+            match current {
+                Some(parent) if parent == id => return true,
+                Some(parent) => current = self.component(parent).and_then(|c| c.parent),
+                None => return false,
+            }
+        }
+        false
+    }
+}
+
+fn transform_is_finite(transform: &Transform) -> bool {
+    transform
+        .translation
+        .iter()
+        .chain(&transform.rotation)
+        .chain(&transform.scale)
+        .all(|c| c.is_finite())
+}
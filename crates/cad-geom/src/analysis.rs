@@ -0,0 +1,129 @@
+//! Per-vertex surface analysis channels for color-mapped inspection.
+
+use crate::{position_key, GeomError, GeomScene, TriMesh};
+use glam::Vec3;
+use std::collections::{HashMap, HashSet};
+
+/// Scalar quantity written to [`TriMesh::scalars`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Analysis {
+    /// Estimated mean curvature (1/length); positive where convex.
+    Curvature,
+    /// Draft angle in radians against a pull direction: positive on faces
+    /// turned towards it, zero on walls parallel to it, negative on undercuts.
+    Draft { pull: [f32; 3] },
+}
+
+impl TriMesh {
+    /// Mean curvature per vertex, averaged from the normal curvature along
+    /// each edge leaving it. Coincident vertices are treated as one point,
+    /// so sharp body edges show up as curvature spikes.
+    pub fn curvature(&self) -> Vec<f32> {
+        let keys: Vec<[i32; 3]> = self
+            .positions
+            .iter()
+            .map(|p| position_key(Vec3::from_array(*p)))
+            .collect();
+        let mut neighbours: HashMap<[i32; 3], HashSet<[i32; 3]>> = HashMap::new();
+        let mut points: HashMap<[i32; 3], Vec3> = HashMap::new();
+        for tri in self.indices.chunks_exact(3) {
+            for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+                let (a, b) = (tri[i] as usize, tri[j] as usize);
+                if keys[a] == keys[b] {
+                    continue;
+                }
+                neighbours.entry(keys[a]).or_default().insert(keys[b]);
+                neighbours.entry(keys[b]).or_default().insert(keys[a]);
+                points.insert(keys[a], Vec3::from_array(self.positions[a]));
+                points.insert(keys[b], Vec3::from_array(self.positions[b]));
+            }
+        }
+
+        (0..self.positions.len())
+            .map(|idx| {
+                let (Some(normal), Some(around)) =
+                    (self.normals.get(idx), neighbours.get(&keys[idx]))
+                else {
+                    return 0.0;
+                };
+                let normal = Vec3::from_array(*normal);
+                let p = Vec3::from_array(self.positions[idx]);
+                let sum: f32 = around
+                    .iter()
+                    .map(|key| {
+                        let d = p - points[key];
+                        2.0 * normal.dot(d) / d.length_squared()
+                    })
+                    .sum();
+                sum / around.len() as f32
+            })
+            .collect()
+    }
+
+    /// Draft angle per vertex relative to `pull`; see [`Analysis::Draft`].
+    pub fn draft_angles(&self, pull: [f32; 3]) -> Vec<f32> {
+        let pull = Vec3::from_array(pull).normalize_or_zero();
+        self.normals
+            .iter()
+            .map(|n| Vec3::from_array(*n).dot(pull).clamp(-1.0, 1.0).asin())
+            .collect()
+    }
+
+    /// Smallest and largest value of the scalar channel, for color ramps.
+    pub fn scalar_range(&self) -> Option<(f32, f32)> {
+        let mut values = self.scalars.iter().copied().filter(|v| v.is_finite());
+        let first = values.next()?;
+        Some(values.fold((first, first), |(lo, hi), v| (lo.min(v), hi.max(v))))
+    }
+}
+
+impl GeomScene {
+    /// The world-space scene mesh with `analysis` in its scalar channel.
+    pub fn analysis_mesh(&mut self, analysis: Analysis) -> Result<TriMesh, GeomError> {
+        let mut mesh = self.mesh()?;
+        mesh.scalars = match analysis {
+            Analysis::Curvature => mesh.curvature(),
+            Analysis::Draft { pull } => mesh.draft_angles(pull),
+        };
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn draft_and_curvature_channels() {
+        let mut scene = GeomScene::new();
+        scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let mesh = scene
+            .analysis_mesh(Analysis::Draft {
+                pull: [0.0, 1.0, 0.0],
+            })
+            .unwrap();
+        assert_eq!(mesh.scalars.len(), mesh.positions.len());
+        let (lo, hi) = mesh.scalar_range().unwrap();
+        assert!((lo + FRAC_PI_2).abs() < 1.0e-3 && (hi - FRAC_PI_2).abs() < 1.0e-3);
+
+        // The convex outer wall of a tube curves more than its concave bore.
+        let mut scene = GeomScene::new();
+        scene.add_tube(1.0, 0.5, 1.0).unwrap();
+        let mesh = scene.analysis_mesh(Analysis::Curvature).unwrap();
+        let wall_mean = |outer: bool| {
+            let values: Vec<f32> = mesh
+                .positions
+                .iter()
+                .zip(&mesh.normals)
+                .zip(&mesh.scalars)
+                .filter(|((p, n), _)| {
+                    n[1].abs() < 0.5 && (p[0] * n[0] + p[2] * n[2] > 0.0) == outer
+                })
+                .map(|(_, k)| *k)
+                .collect();
+            values.iter().sum::<f32>() / values.len() as f32
+        };
+        assert!(wall_mean(true) > wall_mean(false));
+    }
+}
@@ -0,0 +1,412 @@
+//! Tight bounding volumes: minimal spheres and oriented boxes.
+
+use crate::{position_key, Aabb, GeomScene, TriMesh};
+use cad_core::ObjectId;
+use glam::{Mat3, Mat4, Vec3};
+use std::collections::HashSet;
+
+/// Relative slack when testing whether a point lies inside a sphere.
+const SPHERE_EPS: f32 = 1.0e-5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BoundingSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Smallest sphere enclosing all points (Welzl's algorithm, iterative
+    /// move-to-front form over a shuffled copy of the points).
+    pub fn from_points(points: &[[f32; 3]]) -> Self {
+        let mut pts = unique_points(points);
+        let Some(&first) = pts.first() else {
+            return Self::default();
+        };
+        shuffle(&mut pts);
+
+        let mut s = Sphere {
+            center: first,
+            radius: 0.0,
+        };
+        for i in 1..pts.len() {
+            if s.contains(pts[i]) {
+                continue;
+            }
+            s = Sphere::enclosing(&[pts[i]]);
+            for j in 0..i {
+                if s.contains(pts[j]) {
+                    continue;
+                }
+                s = Sphere::enclosing(&[pts[i], pts[j]]);
+                for k in 0..j {
+                    if s.contains(pts[k]) {
+                        continue;
+                    }
+                    s = Sphere::enclosing(&[pts[i], pts[j], pts[k]]);
+                    for l in 0..k {
+                        if !s.contains(pts[l]) {
+                            s = Sphere::enclosing(&[pts[i], pts[j], pts[k], pts[l]]);
+                        }
+                    }
+                }
+            }
+        }
+        Self {
+            center: s.center.to_array(),
+            radius: s.radius,
+        }
+    }
+
+    /// Smallest sphere containing both spheres.
+    pub fn merge(self, other: Self) -> Self {
+        let (c0, c1) = (
+            Vec3::from_array(self.center),
+            Vec3::from_array(other.center),
+        );
+        let dist = c0.distance(c1);
+        if dist + other.radius <= self.radius {
+            return self;
+        }
+        if dist + self.radius <= other.radius {
+            return other;
+        }
+        let radius = (dist + self.radius + other.radius) * 0.5;
+        let center = c0 + (c1 - c0) * ((radius - self.radius) / dist);
+        Self {
+            center: center.to_array(),
+            radius,
+        }
+    }
+
+    /// Encloses the transformed sphere; exact for uniform scale.
+    pub(crate) fn transformed(&self, transform: Mat4) -> Self {
+        let max_scale = [transform.x_axis, transform.y_axis, transform.z_axis]
+            .iter()
+            .map(|axis| axis.truncate().length())
+            .fold(0.0, f32::max);
+        Self {
+            center: transform
+                .transform_point3(Vec3::from_array(self.center))
+                .to_array(),
+            radius: self.radius * max_scale,
+        }
+    }
+
+    pub(crate) fn intersects_ray(&self, ray_o: Vec3, ray_d: Vec3) -> bool {
+        let to_center = Vec3::from_array(self.center) - ray_o;
+        let along = to_center.dot(ray_d);
+        let closest_sq = to_center.length_squared() - along * along;
+        let r_sq = self.radius * self.radius * (1.0 + SPHERE_EPS);
+        closest_sq <= r_sq && (along >= 0.0 || to_center.length_squared() <= r_sq)
+    }
+}
+
+/// Box with arbitrary orthonormal axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obb {
+    pub center: [f32; 3],
+    /// Unit axes, right-handed.
+    pub axes: [[f32; 3]; 3],
+    pub half_extents: [f32; 3],
+}
+
+impl Obb {
+    /// Fits a box along the principal axes of the points, falling back to
+    /// the world axes when that is tighter (e.g. for axis-aligned boxes,
+    /// whose corners skew the principal axes).
+    pub fn from_points(points: &[[f32; 3]]) -> Self {
+        let pts = unique_points(points);
+        let aligned = Self::along(&pts, Mat3::IDENTITY);
+        if pts.len() < 4 {
+            return aligned;
+        }
+        let (_, cov) = covariance(&pts);
+        let principal = Self::along(&pts, symmetric_eigenvectors(cov));
+        if principal.volume() < aligned.volume() {
+            principal
+        } else {
+            aligned
+        }
+    }
+
+    fn along(points: &[Vec3], axes: Mat3) -> Self {
+        let axes = [axes.x_axis, axes.y_axis, axes.z_axis];
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+        for p in points {
+            let local = Vec3::new(p.dot(axes[0]), p.dot(axes[1]), p.dot(axes[2]));
+            min = min.min(local);
+            max = max.max(local);
+        }
+        if points.is_empty() {
+            (min, max) = (Vec3::ZERO, Vec3::ZERO);
+        }
+        let mid = (min + max) * 0.5;
+        let center = axes[0] * mid.x + axes[1] * mid.y + axes[2] * mid.z;
+        Self {
+            center: center.to_array(),
+            axes: axes.map(|axis| axis.to_array()),
+            half_extents: ((max - min) * 0.5).to_array(),
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        8.0 * self.half_extents.iter().product::<f32>()
+    }
+
+    pub fn corners(&self) -> [[f32; 3]; 8] {
+        let center = Vec3::from_array(self.center);
+        let axes = self.axes.map(Vec3::from_array);
+        std::array::from_fn(|k| {
+            let offset = (0..3)
+                .map(|axis| {
+                    let sign = if k & (1 << axis) == 0 { -1.0 } else { 1.0 };
+                    axes[axis] * self.half_extents[axis] * sign
+                })
+                .sum::<Vec3>();
+            (center + offset).to_array()
+        })
+    }
+}
+
+impl From<Aabb> for Obb {
+    fn from(aabb: Aabb) -> Self {
+        let (min, max) = (Vec3::from_array(aabb.min), Vec3::from_array(aabb.max));
+        Self {
+            center: ((min + max) * 0.5).to_array(),
+            axes: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            half_extents: ((max - min) * 0.5).to_array(),
+        }
+    }
+}
+
+impl GeomScene {
+    /// Tight bounding sphere of an object in world space.
+    pub fn object_bounding_sphere(&self, id: ObjectId) -> Option<BoundingSphere> {
+        let idx = self.model.objects().iter().position(|obj| obj.id == id)?;
+        let transform = self.world_mat(&self.model.objects()[idx]);
+        Some(self.local_spheres.get(idx)?.transformed(transform))
+    }
+
+    /// Sphere around every object, e.g. for fitting the camera.
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        self.model
+            .objects()
+            .iter()
+            .zip(&self.local_spheres)
+            .map(|(obj, sphere)| sphere.transformed(self.world_mat(obj)))
+            .reduce(BoundingSphere::merge)
+    }
+
+    /// Oriented bounding box of an object in world space.
+    pub fn object_obb(&self, id: ObjectId) -> Option<Obb> {
+        let idx = self.model.objects().iter().position(|obj| obj.id == id)?;
+        let transform = self.world_mat(&self.model.objects()[idx]);
+        let mesh = self.local_meshes.get(idx)?;
+        let points: Vec<[f32; 3]> = mesh
+            .positions
+            .iter()
+            .map(|p| transform.transform_point3(Vec3::from_array(*p)).to_array())
+            .collect();
+        Some(Obb::from_points(&points))
+    }
+}
+
+pub(crate) fn mesh_bounding_sphere(mesh: &TriMesh) -> BoundingSphere {
+    BoundingSphere::from_points(&mesh.positions)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sphere {
+    center: Vec3,
+    radius: f32,
+}
+
+impl Sphere {
+    fn contains(&self, p: Vec3) -> bool {
+        self.center.distance(p) <= self.radius + SPHERE_EPS * self.radius.max(1.0)
+    }
+
+    /// Smallest sphere enclosing up to four points, trying every subset as
+    /// the boundary so degenerate (collinear, coplanar) sets still work.
+    fn enclosing(points: &[Vec3]) -> Self {
+        let n = points.len();
+        let mut best: Option<Self> = None;
+        for mask in 1u32..(1 << n) {
+            let subset: Vec<Vec3> = (0..n)
+                .filter(|&i| mask & (1 << i) != 0)
+                .map(|i| points[i])
+                .collect();
+            let Some(candidate) = Self::through(&subset) else {
+                continue;
+            };
+            let better = best.is_none_or(|b| candidate.radius < b.radius);
+            if better && points.iter().all(|&p| candidate.contains(p)) {
+                best = Some(candidate);
+            }
+        }
+        best.unwrap_or(Self {
+            center: points[0],
+            radius: 0.0,
+        })
+    }
+
+    /// Sphere with all points on its boundary and its center in their span.
+    fn through(points: &[Vec3]) -> Option<Self> {
+        let p0 = *points.first()?;
+        let center = match points[1..] {
+            [] => p0,
+            [p1] => (p0 + p1) * 0.5,
+            [p1, p2] => {
+                let (a, b) = (p1 - p0, p2 - p0);
+                let axb = a.cross(b);
+                let denom = 2.0 * axb.length_squared();
+                if denom < 1.0e-12 {
+                    return None;
+                }
+                p0 + (b * a.length_squared() - a * b.length_squared()).cross(axb) / denom
+            }
+            [p1, p2, p3] => {
+                let rows = Mat3::from_cols(p1 - p0, p2 - p0, p3 - p0).transpose();
+                if rows.determinant().abs() < 1.0e-12 {
+                    return None;
+                }
+                let rhs = Vec3::new(
+                    (p1 - p0).length_squared(),
+                    (p2 - p0).length_squared(),
+                    (p3 - p0).length_squared(),
+                ) * 0.5;
+                p0 + rows.inverse() * rhs
+            }
+            _ => return None,
+        };
+        Some(Self {
+            center,
+            radius: points
+                .iter()
+                .map(|p| center.distance(*p))
+                .fold(0.0, f32::max),
+        })
+    }
+}
+
+fn unique_points(points: &[[f32; 3]]) -> Vec<Vec3> {
+    let mut seen = HashSet::new();
+    points
+        .iter()
+        .map(|p| Vec3::from_array(*p))
+        .filter(|p| seen.insert(position_key(*p)))
+        .collect()
+}
+
+/// Deterministic Fisher-Yates shuffle (xorshift), so results are stable.
+fn shuffle(points: &mut [Vec3]) {
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    for i in (1..points.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        points.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
+
+/// Principal axes of a symmetric 3x3 matrix as the columns of a
+/// right-handed rotation, largest eigenvalue first.
+fn symmetric_eigenvectors(a: [[f32; 3]; 3]) -> Mat3 {
+    let [(_, x), (_, y), _] = symmetric_eigen(a);
+    if x == Vec3::ZERO || y == Vec3::ZERO {
+        return Mat3::IDENTITY;
+    }
+    Mat3::from_cols(x, y, x.cross(y))
+}
+
+/// Eigenvalues and unit eigenvectors of a symmetric 3x3 matrix (cyclic
+/// Jacobi), sorted by decreasing eigenvalue.
+pub(crate) fn symmetric_eigen(mut a: [[f32; 3]; 3]) -> [(f32, Vec3); 3] {
+    let mut v = [[1.0f32, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..16 {
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q].abs() < 1.0e-12 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            for row in &mut a {
+                let (akp, akq) = (row[p], row[q]);
+                row[p] = c * akp - s * akq;
+                row[q] = s * akp + c * akq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            a[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+            a[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+            for row in &mut v {
+                let (vp, vq) = (row[p], row[q]);
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+    let mut pairs: [(f32, Vec3); 3] = std::array::from_fn(|i| {
+        (
+            a[i][i],
+            Vec3::new(v[0][i], v[1][i], v[2][i]).normalize_or_zero(),
+        )
+    });
+    pairs.sort_by(|l, r| r.0.total_cmp(&l.0));
+    pairs
+}
+
+/// Covariance of points about their mean, with the mean.
+pub(crate) fn covariance(points: &[Vec3]) -> (Vec3, [[f32; 3]; 3]) {
+    let mean = points.iter().copied().sum::<Vec3>() / points.len().max(1) as f32;
+    let mut cov = [[0.0f32; 3]; 3];
+    for p in points {
+        let d = (*p - mean).to_array();
+        for (r, row) in cov.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value += d[r] * d[c];
+            }
+        }
+    }
+    (mean, cov)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sphere_and_box_fit_off_center_points() {
+        let cube: Vec<[f32; 3]> = (0..8)
+            .map(|k| {
+                [
+                    10.0 + (k & 1) as f32,
+                    ((k >> 1) & 1) as f32,
+                    ((k >> 2) & 1) as f32,
+                ]
+            })
+            .collect();
+        let sphere = BoundingSphere::from_points(&cube);
+        let expected = 3.0f32.sqrt() / 2.0;
+        assert!((sphere.radius - expected).abs() < 1.0e-4);
+        assert!(Vec3::from_array(sphere.center).distance(Vec3::new(10.5, 0.5, 0.5)) < 1.0e-4);
+
+        // A long diagonal rod: the oriented box is much thinner than the AABB.
+        let rod: Vec<[f32; 3]> = (0..=20)
+            .flat_map(|i| {
+                let t = i as f32;
+                [
+                    [t, t, 0.0],
+                    [t + 0.1, t, 0.0],
+                    [t, t, 0.1],
+                    [t + 0.1, t, 0.1],
+                ]
+            })
+            .collect();
+        let obb = Obb::from_points(&rod);
+        assert!(obb.volume() < 1.0);
+        assert!(Obb::from_points(&cube).volume() - 1.0 < 1.0e-4);
+    }
+}
@@ -0,0 +1,184 @@
+//! Applying model commands while keeping solids and meshes in step.
+
+use crate::{check_primitive, GeomError, GeomScene};
+use cad_core::{CommandTarget, ModelCommand};
+use glam::Mat4;
+
+impl CommandTarget for GeomScene {
+    type Error = GeomError;
+
+    fn apply(&mut self, command: ModelCommand) -> Result<ModelCommand, GeomError> {
+        match command {
+            ModelCommand::AddObject { kind } => {
+                let solid = self.kind_solid(&kind, Mat4::IDENTITY)?;
+                let inverse = self.model.apply(ModelCommand::AddObject { kind })?;
+                self.push_solid(solid);
+                Ok(inverse)
+            }
+            ModelCommand::Delete { id } => {
+                if !self.delete_object(id) {
+                    return Err(GeomError::UnknownObject(id));
+                }
+                Ok(ModelCommand::Restore { id })
+            }
+            ModelCommand::Restore { id } => {
+                self.restore_deleted(id)?;
+                Ok(ModelCommand::Delete { id })
+            }
+            ModelCommand::SetKind { id, kind } => {
+                check_primitive(&kind)?;
+                let inverse = self.model.apply(ModelCommand::SetKind { id, kind })?;
+                self.regenerate(id)?;
+                Ok(inverse)
+            }
+            ModelCommand::SetFeatures { id, features } => {
+                let inverse = self
+                    .model
+                    .apply(ModelCommand::SetFeatures { id, features })?;
+                if let Err(err) = self.regenerate(id) {
+                    self.model.apply(inverse)?;
+                    self.regenerate(id)?;
+                    return Err(err);
+                }
+                Ok(inverse)
+            }
+            other => {
+                let inverse = self.model.apply(other)?;
+                self.mesh_cache = None;
+                Ok(inverse)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cad_core::{Feature, FeatureOp, Model, ObjectKind, Transform, UndoStack};
+
+    #[test]
+    fn undoes_and_redoes_scene_edits() {
+        let mut scene = GeomScene::new();
+        let mut history = UndoStack::default();
+        history
+            .execute(
+                &mut scene,
+                ModelCommand::AddObject {
+                    kind: ObjectKind::Box {
+                        w: 1.0,
+                        h: 1.0,
+                        d: 1.0,
+                    },
+                },
+            )
+            .unwrap();
+        let id = scene.model().objects()[0].id;
+        let moved = Transform {
+            translation: [2.0, 0.0, 0.0],
+            ..Transform::default()
+        };
+        history
+            .execute(
+                &mut scene,
+                ModelCommand::SetTransform {
+                    id,
+                    transform: moved,
+                },
+            )
+            .unwrap();
+        history
+            .execute(&mut scene, ModelCommand::Delete { id })
+            .unwrap();
+        assert!(scene.mesh().is_err());
+        assert_eq!(scene.model().deleted(id).unwrap().object.transform, moved);
+
+        assert!(history.undo(&mut scene).unwrap());
+        assert_eq!(scene.object_transform(id), Some(moved));
+        assert!(scene.model().trash().is_empty());
+        assert!(!scene.mesh().unwrap().indices.is_empty());
+        assert!(history.undo(&mut scene).unwrap());
+        assert_eq!(scene.object_transform(id), Some(Transform::default()));
+        assert!(history.undo(&mut scene).unwrap());
+        assert!(scene.model().objects().is_empty());
+        assert!(!history.undo(&mut scene).unwrap());
+
+        assert!(history.redo(&mut scene).unwrap());
+        assert_eq!(scene.model().objects()[0].id, id);
+        assert!(history
+            .execute(
+                &mut scene,
+                ModelCommand::SetKind {
+                    id,
+                    kind: ObjectKind::Cylinder { r: -1.0, h: 1.0 },
+                },
+            )
+            .is_err());
+        assert!(history.can_redo());
+
+        let feature = |op| Feature {
+            id: 1,
+            op,
+            suppressed: false,
+        };
+        let cut = feature(FeatureOp::Subtract { tool: id });
+        assert!(history
+            .execute(
+                &mut scene,
+                ModelCommand::SetFeatures {
+                    id,
+                    features: vec![cut],
+                },
+            )
+            .is_err());
+        assert!(scene.model().object(id).unwrap().features.is_empty());
+        let single = scene.mesh().unwrap().indices.len();
+        let pattern = feature(FeatureOp::LinearPattern {
+            direction: [1.0, 0.0, 0.0],
+            spacing: 2.0,
+            count: 2,
+        });
+        history
+            .execute(
+                &mut scene,
+                ModelCommand::SetFeatures {
+                    id,
+                    features: vec![pattern],
+                },
+            )
+            .unwrap();
+        assert_eq!(scene.mesh().unwrap().indices.len(), 2 * single);
+        assert!(history.undo(&mut scene).unwrap());
+        assert_eq!(scene.mesh().unwrap().indices.len(), single);
+    }
+
+    #[test]
+    fn syncs_scenes_through_deltas() {
+        let mut local = GeomScene::new();
+        let mut remote = GeomScene::new();
+        let kept = local.add_box(1.0, 1.0, 1.0).unwrap();
+        let gone = local.add_cylinder(0.5, 1.0).unwrap();
+        remote
+            .apply_delta(&Model::default().diff(local.model()))
+            .unwrap();
+
+        let before = local.model().clone();
+        local.set_object_name(kept, "Base");
+        local.remove_object(gone);
+        let added = local.add_wedge(1.0, 1.0, 1.0).unwrap();
+        let delta = before.diff(local.model());
+        assert_eq!((delta.added.len(), delta.removed.len()), (1, 1));
+        assert_eq!(delta.changed.len(), 1);
+
+        remote.apply_delta(&delta).unwrap();
+        assert!(remote.model().diff(local.model()).is_empty());
+        assert_eq!(
+            remote.model().object(added).unwrap().display_name(),
+            "Wedge 3"
+        );
+        assert_eq!(
+            remote.mesh().unwrap().indices.len(),
+            local.mesh().unwrap().indices.len()
+        );
+        assert!(remote.apply_delta(&delta).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! Datum edits and features placed against datums.
+
+use crate::{GeomError, GeomScene};
+use cad_core::{Datum, DatumId, DatumKind, FeatureId, FeatureOp, ObjectId, Transform};
+use glam::Vec3;
+
+impl GeomScene {
+    pub fn add_datum(
+        &mut self,
+        name: impl Into<String>,
+        kind: DatumKind,
+        transform: Transform,
+    ) -> DatumId {
+        self.model.add_datum(name, kind, transform)
+    }
+
+    pub fn remove_datum(&mut self, id: DatumId) -> Option<Datum> {
+        self.model.remove_datum(id)
+    }
+
+    pub fn set_datum_transform(&mut self, id: DatumId, transform: Transform) -> bool {
+        self.model.set_datum_transform(id, transform)
+    }
+
+    /// Adds a linear pattern to a body, repeating it along an axis datum.
+    pub fn add_pattern_along(
+        &mut self,
+        id: ObjectId,
+        axis: DatumId,
+        spacing: f32,
+        count: u32,
+    ) -> Result<FeatureId, GeomError> {
+        let (_, direction) = self
+            .model
+            .datum(axis)
+            .and_then(Datum::axis)
+            .ok_or(GeomError::InvalidFeature("pattern needs an axis datum"))?;
+        let obj = self.model.object(id).ok_or(GeomError::UnknownObject(id))?;
+        // Features work in body-local space.
+        let local = self
+            .world_mat(obj)
+            .inverse()
+            .transform_vector3(Vec3::from_array(direction));
+        let op = FeatureOp::LinearPattern {
+            direction: local.normalize_or_zero().to_array(),
+            spacing,
+            count,
+        };
+        self.add_feature(id, op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Quat;
+
+    #[test]
+    fn datums_place_sketches_and_patterns() {
+        let mut scene = GeomScene::new();
+        let tilted = Transform {
+            translation: [0.0, 0.0, 2.0],
+            rotation: Quat::from_rotation_x(std::f32::consts::FRAC_PI_2).to_array(),
+            ..Transform::default()
+        };
+        let plane = scene.add_datum("Top", DatumKind::Plane, tilted);
+        let axis = scene.add_datum("Rail", DatumKind::Axis, tilted);
+
+        let planes = scene.model().sketch_planes();
+        assert_eq!(planes.len(), 4);
+        let (name, top) = &planes[3];
+        assert_eq!(name, "Top");
+        assert_eq!(Some(*top), scene.model().datum(plane).unwrap().plane());
+        assert!((Vec3::from_array(top.normal) - Vec3::NEG_Y).length() < 1.0e-5);
+
+        // The rail runs along -Y; a body turned a quarter about Z sees it
+        // along its own -X.
+        let id = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let turned = Transform {
+            rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_2).to_array(),
+            ..Transform::default()
+        };
+        scene.set_object_transform(id, turned);
+        scene.add_pattern_along(id, axis, 2.0, 3).unwrap();
+        let aabb = scene.local_aabb(id).unwrap();
+        assert!((aabb.max[0] - aabb.min[0] - 5.0).abs() < 1.0e-4);
+        assert!(scene.add_pattern_along(id, plane, 2.0, 3).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! Solids of stored-mesh, derived and reference bodies, which depend on the
+//! scene.
+
+use crate::{
+    boolean_subtract, extrude_sketch, make_solid, mat4_to_truck, revolve_sketch, GeomError,
+    GeomScene,
+};
+use cad_core::{
+    BooleanOp, DerivedOp, Model, ObjectId, ObjectKind, Operand, ReferenceError, Sketch, StableId,
+};
+use glam::{Mat4, Vec3};
+use std::collections::HashMap;
+use std::fmt;
+use truck_modeling::{builder, Point3, Solid, Wire};
+
+impl GeomScene {
+    /// Stores a closed triangle mesh with the document and adds a body
+    /// drawing it.
+    pub fn add_mesh(
+        &mut self,
+        name: impl Into<String>,
+        positions: Vec<[f32; 3]>,
+        indices: Vec<u32>,
+    ) -> Result<ObjectId, GeomError> {
+        let solid = mesh_solid(&positions, &indices)?;
+        let handle = self.model.add_mesh_asset(name, positions, indices);
+        let id = self
+            .model
+            .add_mesh(handle)
+            .ok_or(GeomError::InvalidPrimitive("unknown mesh asset"))?;
+        self.push_solid(solid);
+        Ok(id)
+    }
+
+    /// Adds a body computed from other bodies or a sketch.
+    pub fn add_derived(
+        &mut self,
+        op: DerivedOp,
+        operands: Vec<Operand>,
+    ) -> Result<ObjectId, GeomError> {
+        let kind = ObjectKind::Derived {
+            op,
+            operands: operands.clone(),
+        };
+        let solid = self.kind_solid(&kind, Mat4::IDENTITY)?;
+        let id = self.model.add_derived(op, operands);
+        self.push_solid(solid);
+        Ok(id)
+    }
+
+    /// Adds a body standing for a body of another saved document, resolving
+    /// it through `load`.
+    pub fn add_reference<E: fmt::Display>(
+        &mut self,
+        document: impl Into<String>,
+        object: StableId,
+        load: impl FnMut(&str) -> Result<Model, E>,
+    ) -> Result<ObjectId, GeomError> {
+        let id = self.model.add_reference(document, object);
+        let failures = self.model.resolve_references(load);
+        let solid = match failures.into_iter().find(|(failed, _)| *failed == id) {
+            Some((_, err)) => Err(GeomError::Import(err.to_string())),
+            None => self.kind_solid(
+                &self.model.objects()[self.solids.len()].kind,
+                Mat4::IDENTITY,
+            ),
+        };
+        match solid {
+            Ok(solid) => {
+                self.push_solid(solid);
+                Ok(id)
+            }
+            Err(err) => {
+                self.model.remove(id);
+                Err(err)
+            }
+        }
+    }
+
+    /// Re-reads referenced documents through `load` and rebuilds every
+    /// reference body; see [`Model::resolve_references`]. Bodies that fail
+    /// keep their previous shape.
+    pub fn resolve_references<E: fmt::Display>(
+        &mut self,
+        load: impl FnMut(&str) -> Result<Model, E>,
+    ) -> Vec<(ObjectId, ReferenceError)> {
+        let failures = self.model.resolve_references(load);
+        let references: Vec<ObjectId> = self
+            .model
+            .objects()
+            .iter()
+            .filter(|obj| matches!(obj.kind, ObjectKind::Reference { .. }))
+            .map(|obj| obj.id)
+            .filter(|id| !failures.iter().any(|(failed, _)| failed == id))
+            .collect();
+        for id in references {
+            // Resolved kinds were already valid in their own document.
+            let _ = self.regenerate(id);
+        }
+        failures
+    }
+
+    /// Builds the base solid of `kind` for a body placed at `world`.
+    /// Derived bodies take their operands as currently built, so they are
+    /// only as fresh as those bodies.
+    pub(crate) fn kind_solid(&self, kind: &ObjectKind, world: Mat4) -> Result<Solid, GeomError> {
+        match kind {
+            ObjectKind::Mesh { handle } => {
+                let asset = self
+                    .model
+                    .mesh_asset(*handle)
+                    .ok_or(GeomError::InvalidPrimitive("unknown mesh asset"))?;
+                mesh_solid(&asset.positions, &asset.indices)
+            }
+            ObjectKind::Derived { op, operands } => {
+                kind.validate().map_err(GeomError::InvalidPrimitive)?;
+                let to_local = world.inverse();
+                match *op {
+                    DerivedOp::Boolean(op) => {
+                        let mut solids = operands.iter().map(|operand| {
+                            let Operand::Object(id) = *operand else {
+                                return Err(GeomError::InvalidPrimitive(
+                                    "boolean operands must be bodies",
+                                ));
+                            };
+                            let idx = self
+                                .model
+                                .objects()
+                                .iter()
+                                .position(|obj| obj.id == id)
+                                .ok_or(GeomError::UnknownObject(id))?;
+                            let placed = to_local * self.world_mat(&self.model.objects()[idx]);
+                            Ok(builder::transformed(
+                                &self.solids[idx],
+                                mat4_to_truck(placed),
+                            ))
+                        });
+                        let first = solids.next().ok_or(GeomError::EmptyScene)??;
+                        solids.try_fold(first, |acc, tool| match op {
+                            BooleanOp::Subtract => boolean_subtract(&acc, &tool?),
+                            BooleanOp::Union => Err(GeomError::NotImplemented("boolean_union")),
+                            BooleanOp::Intersect => {
+                                Err(GeomError::NotImplemented("boolean_intersect"))
+                            }
+                        })
+                    }
+                    DerivedOp::Extrude { distance } => {
+                        let solid = extrude_sketch(self.sketch_operand(operands)?, distance)?;
+                        Ok(builder::transformed(&solid, mat4_to_truck(to_local)))
+                    }
+                    DerivedOp::Revolve {
+                        axis_origin,
+                        axis_dir,
+                        angle,
+                    } => {
+                        let sketch = self.sketch_operand(operands)?;
+                        let solid = revolve_sketch(sketch, axis_origin, axis_dir, angle)?;
+                        Ok(builder::transformed(&solid, mat4_to_truck(to_local)))
+                    }
+                }
+            }
+            ObjectKind::Reference { document, object } => {
+                let resolved = self
+                    .model
+                    .resolved_reference(document, *object)
+                    .ok_or(GeomError::InvalidPrimitive("unresolved reference"))?;
+                match &resolved.mesh {
+                    Some(mesh) => mesh_solid(&mesh.positions, &mesh.indices),
+                    None => make_solid(&resolved.kind),
+                }
+            }
+            _ => make_solid(kind),
+        }
+    }
+
+    fn sketch_operand(&self, operands: &[Operand]) -> Result<&Sketch, GeomError> {
+        match *operands {
+            [Operand::Sketch(id)] => self
+                .model
+                .sketch(id)
+                .ok_or(GeomError::InvalidPrimitive("unknown sketch operand")),
+            _ => Err(GeomError::InvalidPrimitive(
+                "extrusions and revolves take a single sketch operand",
+            )),
+        }
+    }
+}
+
+/// Builds a solid with one planar face per triangle. Triangles sharing an
+/// edge share its topology, so the mesh must be closed and consistently
+/// wound; zero-area triangles are skipped.
+pub fn mesh_solid(positions: &[[f32; 3]], indices: &[u32]) -> Result<Solid, GeomError> {
+    if indices.iter().any(|&i| i as usize >= positions.len()) {
+        return Err(GeomError::Import("mesh index out of range".into()));
+    }
+    let vertices: Vec<_> = positions
+        .iter()
+        .map(|p| builder::vertex(Point3::new(p[0] as f64, p[1] as f64, p[2] as f64)))
+        .collect();
+    let mut edges = HashMap::new();
+    let mut faces = Vec::new();
+    for tri in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| Vec3::from_array(positions[tri[k] as usize]));
+        if (b - a).cross(c - a).length_squared() <= f32::EPSILON * f32::EPSILON {
+            continue;
+        }
+        let wire: Wire = [(tri[0], tri[1]), (tri[1], tri[2]), (tri[2], tri[0])]
+            .into_iter()
+            .map(|(from, to)| {
+                let key = (from.min(to), from.max(to));
+                let edge = edges.entry(key).or_insert_with(|| {
+                    builder::line(&vertices[key.0 as usize], &vertices[key.1 as usize])
+                });
+                if from == key.0 {
+                    edge.clone()
+                } else {
+                    edge.inverse()
+                }
+            })
+            .collect();
+        let face =
+            builder::try_attach_plane(&[wire]).map_err(|err| GeomError::Kernel(err.to_string()))?;
+        faces.push(face);
+    }
+    if faces.is_empty() {
+        return Err(GeomError::Import("mesh has no triangles".into()));
+    }
+    Solid::try_new(vec![faces.into_iter().collect()])
+        .map_err(|err| GeomError::Kernel(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cad_core::{Model, SketchEntity, SketchPlane};
+
+    #[test]
+    fn builds_mesh_and_derived_bodies() {
+        let mut scene = GeomScene::new();
+        let positions = vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        let indices = vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3];
+        let tetra = scene.add_mesh("tetra", positions, indices).unwrap();
+        assert_eq!(scene.local_meshes[0].indices.len(), 12);
+        assert!(scene
+            .add_mesh("open", vec![[0.0; 3]; 3], vec![0, 1])
+            .is_err());
+
+        let square = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let entities = (0..4)
+            .map(|i| SketchEntity::Line {
+                a: square[i],
+                b: square[(i + 1) % 4],
+            })
+            .collect();
+        let sketch = scene.add_sketch("base", SketchPlane::XY, entities);
+        let block = scene
+            .add_derived(
+                DerivedOp::Extrude { distance: 2.0 },
+                vec![Operand::Sketch(sketch)],
+            )
+            .unwrap();
+        let aabb = scene.local_aabb(block).unwrap();
+        assert!((aabb.max[2] - aabb.min[2] - 2.0).abs() < 1.0e-4);
+        assert_eq!(scene.model().object(block).unwrap().name, "Extrusion 2");
+
+        // Both save forms keep the new kinds.
+        let json = Model::from_json(&scene.model().to_json().unwrap()).unwrap();
+        let bytes = Model::from_bytes(&scene.model().to_bytes().unwrap()).unwrap();
+        for model in [json, bytes] {
+            assert_eq!(model.objects(), scene.model().objects());
+            assert_eq!(model.mesh_assets(), scene.model().mesh_assets());
+        }
+
+        assert!(scene
+            .add_derived(
+                DerivedOp::Boolean(BooleanOp::Subtract),
+                vec![Operand::Object(tetra)],
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn resolves_external_references() {
+        let mut part = GeomScene::new();
+        let id = part.add_box(1.0, 2.0, 3.0).unwrap();
+        let stable = part.model().object(id).unwrap().uid();
+        let mut saved = part.model().to_json().unwrap();
+        let load = |saved: &str, doc: &str| match doc {
+            "part.json" => Model::from_json(saved).map_err(|err| err.to_string()),
+            _ => Err("no such document".to_string()),
+        };
+
+        let mut scene = GeomScene::new();
+        let reference = scene
+            .add_reference("part.json", stable, |doc| load(&saved, doc))
+            .unwrap();
+        let aabb = scene.local_aabb(reference).unwrap();
+        assert!((aabb.max[2] - aabb.min[2] - 3.0).abs() < 1.0e-4);
+        assert!(scene
+            .add_reference("gone.json", stable, |doc| load(&saved, doc))
+            .is_err());
+        assert_eq!(scene.model().objects().len(), 1);
+
+        // Resolving again picks up edits saved in the other document.
+        part.update_primitive(
+            id,
+            ObjectKind::Box {
+                w: 1.0,
+                h: 2.0,
+                d: 5.0,
+            },
+        )
+        .unwrap();
+        saved = part.model().to_json().unwrap();
+        assert!(scene.resolve_references(|doc| load(&saved, doc)).is_empty());
+        let aabb = scene.local_aabb(reference).unwrap();
+        assert!((aabb.max[2] - aabb.min[2] - 5.0).abs() < 1.0e-4);
+    }
+}
@@ -0,0 +1,180 @@
+//! Edge extraction from triangle meshes.
+
+use crate::{position_key, GeomScene, TriMesh};
+use cad_core::{ObjectId, SketchEntity, SketchId, SketchPlane};
+use glam::Vec3;
+use std::collections::{HashMap, HashSet};
+
+/// Dihedral angle above which a mesh edge counts as a body edge when projecting.
+const PROJECT_CREASE_ANGLE: f32 = 30.0_f32.to_radians();
+
+/// An undirected mesh edge with the normals of the triangles using it.
+#[derive(Debug, Clone)]
+pub(crate) struct MeshEdge {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub normals: Vec<Vec3>,
+}
+
+/// Collects undirected edges, welding coincident vertices by position.
+pub(crate) fn mesh_edges(mesh: &TriMesh) -> Vec<MeshEdge> {
+    let mut edges: HashMap<([i32; 3], [i32; 3]), MeshEdge> = HashMap::new();
+    for tri in mesh.indices.chunks_exact(3) {
+        let p = [0, 1, 2].map(|k| Vec3::from_array(mesh.positions[tri[k] as usize]));
+        let normal = (p[1] - p[0]).cross(p[2] - p[0]).normalize_or_zero();
+        if normal == Vec3::ZERO {
+            continue;
+        }
+        for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+            let (ka, kb) = (position_key(p[i]), position_key(p[j]));
+            let (key, a, b) = if ka < kb {
+                ((ka, kb), p[i], p[j])
+            } else {
+                ((kb, ka), p[j], p[i])
+            };
+            edges
+                .entry(key)
+                .or_insert_with(|| MeshEdge {
+                    a,
+                    b,
+                    normals: Vec::new(),
+                })
+                .normals
+                .push(normal);
+        }
+    }
+    edges.into_values().collect()
+}
+
+/// Where a view is looked at from, for view-dependent edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewPoint {
+    /// Perspective camera at this position.
+    Eye([f32; 3]),
+    /// Orthographic camera looking along this direction.
+    Direction([f32; 3]),
+}
+
+impl ViewPoint {
+    /// Whether a surface with `normal` at `point` faces the viewer.
+    fn faces(&self, point: Vec3, normal: Vec3) -> bool {
+        match *self {
+            Self::Eye(eye) => normal.dot(Vec3::from_array(eye) - point) > 0.0,
+            Self::Direction(dir) => normal.dot(Vec3::from_array(dir)) < 0.0,
+        }
+    }
+}
+
+/// Boundary edges plus edges whose dihedral angle exceeds
+/// `dihedral_threshold` (radians), as line segments.
+pub fn feature_edges(mesh: &TriMesh, dihedral_threshold: f32) -> Vec<[[f32; 3]; 2]> {
+    let cos_threshold = dihedral_threshold.cos();
+    mesh_edges(mesh)
+        .into_iter()
+        .filter(|edge| match edge.normals[..] {
+            [n0, n1] => n0.dot(n1) < cos_threshold,
+            _ => true,
+        })
+        .map(|edge| [edge.a.to_array(), edge.b.to_array()])
+        .collect()
+}
+
+/// Edges between a front-facing and a back-facing triangle, i.e. the
+/// outline of the mesh as seen from `view`.
+pub fn silhouette_edges(mesh: &TriMesh, view: ViewPoint) -> Vec<[[f32; 3]; 2]> {
+    mesh_edges(mesh)
+        .into_iter()
+        .filter(|edge| is_silhouette(edge, view))
+        .map(|edge| [edge.a.to_array(), edge.b.to_array()])
+        .collect()
+}
+
+fn is_silhouette(edge: &MeshEdge, view: ViewPoint) -> bool {
+    let mid = (edge.a + edge.b) * 0.5;
+    match edge.normals[..] {
+        [n0, n1] => view.faces(mid, n0) != view.faces(mid, n1),
+        _ => false,
+    }
+}
+
+impl GeomScene {
+    /// Feature and silhouette edges of the given bodies in world space, for
+    /// drawing crisp outlines or hidden-line views. An empty `ids` slice
+    /// means every body.
+    pub fn outline_edges(
+        &self,
+        ids: &[ObjectId],
+        dihedral_threshold: f32,
+        view: ViewPoint,
+    ) -> Vec<[[f32; 3]; 2]> {
+        let cos_threshold = dihedral_threshold.cos();
+        let mut lines = Vec::new();
+        for (idx, obj) in self.model.objects().iter().enumerate() {
+            if !ids.is_empty() && !ids.contains(&obj.id) {
+                continue;
+            }
+            let Some(local) = self.local_meshes.get(idx) else {
+                continue;
+            };
+            let mut mesh = TriMesh::default();
+            mesh.append_transformed(local, self.world_mat(obj));
+
+            for edge in mesh_edges(&mesh) {
+                let keep = match edge.normals[..] {
+                    [n0, n1] => n0.dot(n1) < cos_threshold || is_silhouette(&edge, view),
+                    _ => true,
+                };
+                if keep {
+                    lines.push([edge.a.to_array(), edge.b.to_array()]);
+                }
+            }
+        }
+        lines
+    }
+
+    /// Projects boundary, crease and silhouette edges of the given bodies onto
+    /// a sketch plane, as line entities in plane coordinates.
+    ///
+    /// Silhouettes are taken as seen along the plane normal. Edges that
+    /// collapse to a point (parallel to the normal) are dropped.
+    pub fn project_edges(&self, plane: &SketchPlane, ids: &[ObjectId]) -> Vec<SketchEntity> {
+        if ids.is_empty() {
+            return Vec::new();
+        }
+        let view = ViewPoint::Direction(plane.normal);
+        let mut lines = Vec::new();
+        let mut seen = HashSet::new();
+
+        for [a, b] in self.outline_edges(ids, PROJECT_CREASE_ANGLE, view) {
+            let a = plane.to_plane(a);
+            let b = plane.to_plane(b);
+            if (a[0] - b[0]).hypot(a[1] - b[1]) < 1.0e-5 {
+                continue;
+            }
+            let key = {
+                let q =
+                    |p: [f32; 2]| [(p[0] * 1.0e4).round() as i32, (p[1] * 1.0e4).round() as i32];
+                let (qa, qb) = (q(a), q(b));
+                if qa < qb {
+                    (qa, qb)
+                } else {
+                    (qb, qa)
+                }
+            };
+            if seen.insert(key) {
+                lines.push(SketchEntity::Line { a, b });
+            }
+        }
+
+        lines
+    }
+
+    /// Projects body edges into a sketch's reference geometry.
+    pub fn project_into_sketch(&mut self, sketch: SketchId, ids: &[ObjectId]) -> bool {
+        let Some(plane) = self.model.sketch(sketch).map(|s| s.plane) else {
+            return false;
+        };
+        let lines = self.project_edges(&plane, ids);
+        self.model.add_sketch_references(sketch, lines)
+    }
+}
@@ -0,0 +1,157 @@
+//! Mesh exports: binary STL and glTF binary (`.glb`).
+
+use crate::{GeomError, GeomScene, TriMesh};
+use cad_core::ObjectId;
+
+impl GeomScene {
+    /// World-space mesh of the given bodies, or of every visible body when
+    /// `objects` is empty. Appearance is left out.
+    pub fn export_mesh(&self, objects: &[ObjectId]) -> Result<TriMesh, GeomError> {
+        if let Some(&id) = objects.iter().find(|&&id| self.model.object(id).is_none()) {
+            return Err(GeomError::UnknownObject(id));
+        }
+        let mut combined = TriMesh::default();
+        for (idx, obj) in self.model.objects().iter().enumerate() {
+            let wanted = if objects.is_empty() {
+                self.model.is_visible(obj)
+            } else {
+                objects.contains(&obj.id)
+            };
+            if wanted {
+                combined.append_transformed(&self.local_meshes[idx], self.world_mat(obj));
+            }
+        }
+        if combined.indices.is_empty() {
+            return Err(GeomError::EmptyScene);
+        }
+        Ok(combined)
+    }
+
+    /// Binary STL of [`GeomScene::export_mesh`], with facet normals.
+    pub fn export_stl(&self, objects: &[ObjectId]) -> Result<Vec<u8>, GeomError> {
+        let mesh = self.export_mesh(objects)?;
+        let triangles = mesh.indices.chunks_exact(3);
+        let mut out = Vec::with_capacity(84 + triangles.len() * 50);
+        let mut header = [0u8; 80];
+        header[..8].copy_from_slice(b"physalis");
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&(triangles.len() as u32).to_le_bytes());
+        for tri in triangles {
+            let [a, b, c] =
+                [0, 1, 2].map(|k| glam::Vec3::from_array(mesh.positions[tri[k] as usize]));
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            for v in [normal, a, b, c] {
+                for x in v.to_array() {
+                    out.extend_from_slice(&x.to_le_bytes());
+                }
+            }
+            out.extend_from_slice(&0u16.to_le_bytes());
+        }
+        Ok(out)
+    }
+
+    /// glTF 2.0 binary of [`GeomScene::export_mesh`]: one mesh with
+    /// positions, normals and indices.
+    pub fn export_glb(&self, objects: &[ObjectId]) -> Result<Vec<u8>, GeomError> {
+        let mesh = self.export_mesh(objects)?;
+        let vertices = mesh.positions.len();
+        let mut bin = Vec::with_capacity(vertices * 24 + mesh.indices.len() * 4);
+        for v in mesh.positions.iter().chain(&mesh.normals) {
+            for x in v {
+                bin.extend_from_slice(&x.to_le_bytes());
+            }
+        }
+        for i in &mesh.indices {
+            bin.extend_from_slice(&i.to_le_bytes());
+        }
+
+        let (min, max) = mesh.positions.iter().fold(
+            ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+            |(min, max), p| {
+                (
+                    std::array::from_fn(|k| min[k].min(p[k])),
+                    std::array::from_fn(|k| max[k].max(p[k])),
+                )
+            },
+        );
+        let vec3_len = vertices * 12;
+        let json = format!(
+            concat!(
+                r#"{{"asset":{{"version":"2.0","generator":"physalis"}},"#,
+                r#""scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"#,
+                r#""meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1}},"indices":2}}]}}],"#,
+                r#""accessors":["#,
+                r#"{{"bufferView":0,"componentType":5126,"count":{n},"type":"VEC3","min":{min:?},"max":{max:?}}},"#,
+                r#"{{"bufferView":1,"componentType":5126,"count":{n},"type":"VEC3"}},"#,
+                r#"{{"bufferView":2,"componentType":5125,"count":{m},"type":"SCALAR"}}],"#,
+                r#""bufferViews":["#,
+                r#"{{"buffer":0,"byteOffset":0,"byteLength":{v},"target":34962}},"#,
+                r#"{{"buffer":0,"byteOffset":{v},"byteLength":{v},"target":34962}},"#,
+                r#"{{"buffer":0,"byteOffset":{i},"byteLength":{il},"target":34963}}],"#,
+                r#""buffers":[{{"byteLength":{b}}}]}}"#,
+            ),
+            n = vertices,
+            m = mesh.indices.len(),
+            min = min,
+            max = max,
+            v = vec3_len,
+            i = vec3_len * 2,
+            il = mesh.indices.len() * 4,
+            b = bin.len(),
+        );
+
+        // Chunks are 4-byte aligned: JSON padded with spaces, binary with
+        // zeros.
+        let mut json = json.into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        bin.resize(bin.len().next_multiple_of(4), 0);
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        let mut out = Vec::with_capacity(total);
+        out.extend_from_slice(b"glTF");
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&(total as u32).to_le_bytes());
+        out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"JSON");
+        out.extend_from_slice(&json);
+        out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"BIN\0");
+        out.extend_from_slice(&bin);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_stl_and_glb() {
+        let mut scene = GeomScene::new();
+        let a = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        scene.add_box(2.0, 2.0, 2.0).unwrap();
+        let one = scene.export_mesh(&[a]).unwrap();
+        let all = scene.export_mesh(&[]).unwrap();
+        assert!(all.indices.len() > one.indices.len());
+        assert!(matches!(
+            scene.export_mesh(&[99]),
+            Err(GeomError::UnknownObject(99))
+        ));
+
+        let stl = scene.export_stl(&[a]).unwrap();
+        let count = u32::from_le_bytes(stl[80..84].try_into().unwrap()) as usize;
+        assert_eq!(count, one.indices.len() / 3);
+        assert_eq!(stl.len(), 84 + count * 50);
+        let rebuilt = GeomScene::from_model(scene.model().clone()).unwrap();
+        assert_eq!(rebuilt.export_stl(&[a]).unwrap(), stl);
+
+        let glb = scene.export_glb(&[]).unwrap();
+        assert_eq!(&glb[..4], b"glTF");
+        assert_eq!(
+            u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+            glb.len()
+        );
+        let json_len = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        let json = std::str::from_utf8(&glb[20..20 + json_len]).unwrap();
+        assert!(json.contains(&format!("\"count\":{}", all.indices.len())));
+    }
+}
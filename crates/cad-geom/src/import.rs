@@ -0,0 +1,113 @@
+//! Mesh imports.
+
+use crate::GeomError;
+use std::collections::HashMap;
+
+/// Parses a binary or ASCII STL into welded positions and triangle indices,
+/// ready for [`crate::GeomScene::add_mesh`]. Facet normals are ignored.
+pub fn parse_stl(bytes: &[u8]) -> Result<(Vec<[f32; 3]>, Vec<u32>), GeomError> {
+    let triangles = match binary_triangle_count(bytes) {
+        Some(count) => (0..count)
+            .map(|i| {
+                let facet = &bytes[84 + i * 50..];
+                let float = |at: usize| f32::from_le_bytes(facet[at..at + 4].try_into().unwrap());
+                // Skip the normal, then three vertices.
+                std::array::from_fn(|v| std::array::from_fn(|k| float(12 + v * 12 + k * 4)))
+            })
+            .collect(),
+        None => ascii_triangles(bytes)?,
+    };
+    if triangles.is_empty() {
+        return Err(GeomError::Import("STL has no triangles".to_string()));
+    }
+
+    // Facets repeat their corners; share them so the mesh is connected.
+    let mut positions = Vec::new();
+    let mut indices = Vec::with_capacity(triangles.len() * 3);
+    let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+    for tri in &triangles {
+        for p in tri {
+            if p.iter().any(|c| !c.is_finite()) {
+                return Err(GeomError::Import(
+                    "STL has non-finite coordinates".to_string(),
+                ));
+            }
+            let key = p.map(|c| (c + 0.0).to_bits());
+            let idx = *welded.entry(key).or_insert_with(|| {
+                positions.push(*p);
+                positions.len() as u32 - 1
+            });
+            indices.push(idx);
+        }
+    }
+    Ok((positions, indices))
+}
+
+/// The facet count of a binary STL, when the length matches one.
+fn binary_triangle_count(bytes: &[u8]) -> Option<usize> {
+    let count = u32::from_le_bytes(bytes.get(80..84)?.try_into().unwrap()) as usize;
+    (bytes.len() == count.checked_mul(50)?.checked_add(84)?).then_some(count)
+}
+
+fn ascii_triangles(bytes: &[u8]) -> Result<Vec<[[f32; 3]; 3]>, GeomError> {
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| GeomError::Import("not a binary or ASCII STL".to_string()))?;
+    if !text.trim_start().starts_with("solid") {
+        return Err(GeomError::Import("not a binary or ASCII STL".to_string()));
+    }
+    let mut vertices = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        if fields.next() != Some("vertex") {
+            continue;
+        }
+        let coords = fields
+            .map(str::parse::<f32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| GeomError::Import(format!("line {}: {err}", line_no + 1)))?;
+        let [x, y, z] = coords[..] else {
+            return Err(GeomError::Import(format!(
+                "line {}: expected vertex x y z",
+                line_no + 1
+            )));
+        };
+        vertices.push([x, y, z]);
+    }
+    if vertices.len() % 3 != 0 {
+        return Err(GeomError::Import(
+            "STL vertex count is not a multiple of three".to_string(),
+        ));
+    }
+    Ok(vertices
+        .chunks_exact(3)
+        .map(|tri| [tri[0], tri[1], tri[2]])
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GeomScene;
+
+    #[test]
+    fn stl_round_trips_through_a_mesh_body() {
+        let mut scene = GeomScene::new();
+        let id = scene.add_box(1.0, 2.0, 3.0).unwrap();
+        let stl = scene.export_stl(&[id]).unwrap();
+        let (positions, indices) = parse_stl(&stl).unwrap();
+        assert_eq!(
+            indices.len(),
+            u32::from_le_bytes(stl[80..84].try_into().unwrap()) as usize * 3
+        );
+        assert!(positions.len() < indices.len());
+        let mesh = scene.add_mesh("box.stl", positions, indices).unwrap();
+        let aabb = scene.local_aabb(mesh).unwrap();
+        assert!((aabb.max[2] - aabb.min[2] - 3.0).abs() < 1.0e-4);
+
+        let ascii = "solid t\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\n\
+                     vertex 0 1 0\nendloop\nendfacet\nendsolid t\n";
+        let (positions, indices) = parse_stl(ascii.as_bytes()).unwrap();
+        assert_eq!((positions.len(), indices), (3, vec![0, 1, 2]));
+        assert!(parse_stl(b"not an stl").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! Sharing tessellations between identical bodies.
+
+use crate::{tessellate_solid_with_normals, GeomScene, NormalMode, TriMesh};
+use cad_core::{ModelObject, ObjectId, ObjectKind};
+use std::sync::Arc;
+use truck_modeling::Solid;
+
+/// Identity of a body's tessellation: primitive parameters plus meshing
+/// settings, compared bit for bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct GeometryKey {
+    kind: u8,
+    params: [u32; 4],
+    normal_mode: (u8, u32),
+    tolerance: u64,
+}
+
+impl GeometryKey {
+    /// `None` for derived and reference bodies and bodies with active
+    /// features, whose shape may depend on other bodies or documents.
+    pub(crate) fn new(obj: &ModelObject, mode: NormalMode, tolerance: f64) -> Option<Self> {
+        if obj.features.iter().any(|feature| !feature.suppressed) {
+            return None;
+        }
+        let (kind, params) = match obj.kind {
+            ObjectKind::Mesh { handle } => (5, [handle as u32, (handle >> 32) as u32, 0, 0]),
+            ObjectKind::Derived { .. } | ObjectKind::Reference { .. } => return None,
+            ObjectKind::Box { w, h, d } => (0, [w.to_bits(), h.to_bits(), d.to_bits(), 0]),
+            ObjectKind::Cylinder { r, h } => (1, [r.to_bits(), h.to_bits(), 0, 0]),
+            ObjectKind::Wedge { w, h, d } => (2, [w.to_bits(), h.to_bits(), d.to_bits(), 0]),
+            ObjectKind::Tube {
+                outer_r,
+                inner_r,
+                h,
+            } => (4, [outer_r.to_bits(), inner_r.to_bits(), h.to_bits(), 0]),
+            ObjectKind::Prism { sides, r, h } => (3, [r.to_bits(), h.to_bits(), sides, 0]),
+        };
+        let normal_mode = match mode {
+            NormalMode::Flat => (0, 0),
+            NormalMode::Smooth => (1, 0),
+            NormalMode::Crease(angle) => (2, angle.to_bits()),
+        };
+        Some(Self {
+            kind,
+            params,
+            normal_mode,
+            tolerance: tolerance.to_bits(),
+        })
+    }
+}
+
+/// One shared mesh and every body drawing it, for instanced rendering.
+#[derive(Debug, Clone)]
+pub struct MeshInstances {
+    pub mesh: Arc<TriMesh>,
+    pub objects: Vec<ObjectId>,
+    /// Column-major world matrices, parallel to `objects`.
+    pub transforms: Vec<[[f32; 4]; 4]>,
+}
+
+impl GeomScene {
+    /// Number of bodies (including this one) sharing this body's mesh.
+    pub fn instance_count(&self, id: ObjectId) -> Option<usize> {
+        let idx = self.model.objects().iter().position(|obj| obj.id == id)?;
+        let mesh = self.local_meshes.get(idx)?;
+        Some(
+            self.local_meshes
+                .iter()
+                .filter(|other| Arc::ptr_eq(mesh, other))
+                .count(),
+        )
+    }
+
+    /// The body's tessellation in its local space, shared with identical
+    /// bodies.
+    pub fn object_mesh(&self, id: ObjectId) -> Option<Arc<TriMesh>> {
+        let idx = self.model.objects().iter().position(|obj| obj.id == id)?;
+        self.local_meshes.get(idx).cloned()
+    }
+
+    /// Bodies grouped by shared mesh, in object order.
+    pub fn mesh_instances(&self) -> Vec<MeshInstances> {
+        let mut groups: Vec<MeshInstances> = Vec::new();
+        for (obj, mesh) in self.model.objects().iter().zip(&self.local_meshes) {
+            let transform = self.world_mat(obj).to_cols_array_2d();
+            match groups
+                .iter_mut()
+                .find(|group| Arc::ptr_eq(&group.mesh, mesh))
+            {
+                Some(group) => {
+                    group.objects.push(obj.id);
+                    group.transforms.push(transform);
+                }
+                None => groups.push(MeshInstances {
+                    mesh: mesh.clone(),
+                    objects: vec![obj.id],
+                    transforms: vec![transform],
+                }),
+            }
+        }
+        groups
+    }
+
+    pub(crate) fn geometry_key(&self, idx: usize, mode: NormalMode) -> Option<GeometryKey> {
+        GeometryKey::new(&self.model.objects()[idx], mode, self.tolerance)
+    }
+
+    /// Tessellates the solid of object `idx`, reusing the mesh of an
+    /// identical body when there is one.
+    pub(crate) fn shared_mesh(
+        &mut self,
+        idx: usize,
+        solid: &Solid,
+        mode: NormalMode,
+    ) -> Arc<TriMesh> {
+        let key = self.geometry_key(idx, mode);
+        if let Some(mesh) = key.and_then(|key| self.mesh_pool.get(&key)) {
+            return mesh.clone();
+        }
+        let mesh = Arc::new(tessellate_solid_with_normals(solid, self.tolerance, mode));
+        if let Some(key) = key {
+            self.prune_mesh_pool();
+            self.mesh_pool.insert(key, mesh.clone());
+        }
+        mesh
+    }
+
+    /// Drops pooled meshes no body uses any more.
+    pub(crate) fn prune_mesh_pool(&mut self) {
+        self.mesh_pool.retain(|_, mesh| Arc::strong_count(mesh) > 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_primitives_share_a_mesh() {
+        let mut scene = GeomScene::new();
+        let a = scene.add_cylinder(0.5, 2.0).unwrap();
+        let b = scene.add_cylinder(0.5, 2.0).unwrap();
+        let c = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        assert_eq!(scene.instance_count(a), Some(2));
+        assert_eq!(scene.mesh_instances().len(), 2);
+
+        scene
+            .update_primitive(b, ObjectKind::Cylinder { r: 0.5, h: 3.0 })
+            .unwrap();
+        assert_eq!(scene.instance_count(a), Some(1));
+        assert_eq!(scene.instance_count(c), Some(1));
+
+        scene
+            .update_primitive(b, ObjectKind::Cylinder { r: 0.5, h: 2.0 })
+            .unwrap();
+        scene.regenerate_all().unwrap();
+        assert_eq!(scene.instance_count(b), Some(2));
+
+        let coarse = scene.object_mesh(a).unwrap().indices.len();
+        scene.set_tolerance(scene.tolerance() / 10.0);
+        let fine = scene.object_mesh(a).unwrap();
+        assert!(fine.indices.len() > coarse);
+        assert!(Arc::ptr_eq(&fine, &scene.object_mesh(b).unwrap()));
+        assert!(scene.object_mesh(99).is_none());
+        let rebuilt =
+            GeomScene::from_model_with_tolerance(scene.model().clone(), scene.tolerance()).unwrap();
+        assert_eq!(rebuilt.object_mesh(a).unwrap().indices, fine.indices);
+    }
+}
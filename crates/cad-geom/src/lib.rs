@@ -1,18 +1,99 @@
 //! Geometry layer backed by Truck.
 
-use cad_core::{Model, ObjectId, Transform};
-use glam::{Mat4, Quat, Vec3};
+use bounds::mesh_bounding_sphere;
+use cad_core::{
+    Appearance, ClientId, CommandError, ComponentId, DocumentInfo, FeatureId, FeatureOp, LayerId,
+    LengthUnit, Model, ModelDelta, ModelObject, ObjectId, ObjectKind, Operand, ParamError,
+    PropertyValue, Removed, SketchEntity, SketchId, SketchPlane, Transform,
+};
+use glam::{Mat4, Vec3};
+use instancing::GeometryKey;
+use parallel::par_map;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use thiserror::Error;
 use truck_meshalgo::{filters::*, tessellation::*};
-use truck_modeling::{builder, InnerSpace, Point3, Rad, Solid, Vector3};
+use truck_modeling::{builder, InnerSpace, Matrix4, Point3, Rad, Shell, Solid, Surface, Vector3};
 use truck_polymesh::{PolygonMesh, StandardAttributes, StandardVertex, TOLERANCE};
 
+mod analysis;
+mod bounds;
+mod commands;
+mod datum;
+mod derived;
+mod edges;
+mod export;
+mod import;
+mod instancing;
+mod mass;
+mod mates;
+mod measure;
+mod parallel;
+mod params;
+mod point_cloud;
+mod preview;
+mod push_pull;
+mod section;
+mod select;
+mod shrinkwrap;
+mod sketch;
+mod text;
+mod validate;
+
+pub use analysis::Analysis;
+pub use bounds::{BoundingSphere, Obb};
+pub use derived::mesh_solid;
+pub use edges::{feature_edges, silhouette_edges, ViewPoint};
+pub use import::parse_stl;
+pub use instancing::MeshInstances;
+pub use mass::MassProperties;
+pub use parallel::MeshParts;
+pub use point_cloud::{
+    fit_cylinder, fit_plane, parse_ply, parse_xyz, CylinderFit, PlaneFit, ScanPoints,
+};
+pub use section::{hatch_loops, section_loops, Hatch, Section};
+pub use select::RectSelection;
+pub use sketch::{
+    detect_profiles, extrude_sketch, profile_face, revolve_sketch, sketch_faces, sketch_polylines,
+    ProfileGap, ProfileReport, SketchProfile, SKETCH_TOLERANCE,
+};
+pub use text::{extrude_text, text_entities};
+pub use validate::MeshReport;
+
 #[derive(Debug, Error)]
 pub enum GeomError {
     #[error("no solids in scene")]
     EmptyScene,
     #[error("operation not implemented: {0}")]
     NotImplemented(&'static str),
+    #[error("unknown object {0}")]
+    UnknownObject(ObjectId),
+    #[error("object {object} has no feature {feature}")]
+    UnknownFeature {
+        object: ObjectId,
+        feature: FeatureId,
+    },
+    #[error("sketch has no closed profile")]
+    NoClosedProfile,
+    #[error("invalid font: {0}")]
+    Font(String),
+    #[error("import failed: {0}")]
+    Import(String),
+    #[error("modeling kernel error: {0}")]
+    Kernel(String),
+    #[error("invalid primitive: {0}")]
+    InvalidPrimitive(&'static str),
+    #[error("invalid feature: {0}")]
+    InvalidFeature(&'static str),
+    #[error(transparent)]
+    Command(#[from] CommandError),
+    #[error(transparent)]
+    Param(#[from] ParamError),
+    #[error("feature {feature} failed: {source}")]
+    FeatureFailed {
+        feature: FeatureId,
+        source: Box<GeomError>,
+    },
 }
 
 #[derive(Debug, Clone, Default)]
@@ -20,6 +101,16 @@ pub struct TriMesh {
     pub positions: Vec<[f32; 3]>,
     pub normals: Vec<[f32; 3]>,
     pub indices: Vec<u32>,
+    /// Optional per-vertex scalar channel (see [`Analysis`]), parallel to
+    /// `positions`; empty when unused.
+    pub scalars: Vec<f32>,
+    /// Optional per-vertex RGBA color from the body's [`Appearance`];
+    /// empty when unused.
+    pub colors: Vec<[f32; 4]>,
+    /// Optional per-vertex `[roughness, metalness]`, parallel to `colors`.
+    pub materials: Vec<[f32; 2]>,
+    /// Index pairs drawn as lines instead of shaded faces (wireframe bodies).
+    pub line_indices: Vec<u32>,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -28,6 +119,20 @@ pub struct Aabb {
     pub max: [f32; 3],
 }
 
+/// How vertex normals are generated when tessellating.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NormalMode {
+    /// One normal per triangle; every facet is shaded separately.
+    Flat,
+    /// Analytic surface normals from the kernel (or fully averaged normals
+    /// for meshes without a surface).
+    #[default]
+    Smooth,
+    /// Average normals of adjacent triangles only where the dihedral angle
+    /// between them is below the given crease angle (radians).
+    Crease(f32),
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct SurfaceHit {
     pub object_id: ObjectId,
@@ -39,22 +144,29 @@ pub struct SurfaceHit {
 impl TriMesh {
     pub fn append(&mut self, other: TriMesh) {
         let base = self.positions.len() as u32;
+        self.append_channels(&other);
         self.positions.extend(other.positions);
         self.normals.extend(other.normals);
         self.indices
             .extend(other.indices.into_iter().map(|idx| idx + base));
+        self.line_indices
+            .extend(other.line_indices.into_iter().map(|idx| idx + base));
     }
 
     pub fn append_transformed(&mut self, other: &TriMesh, transform: Mat4) {
         let base = self.positions.len() as u32;
+        self.append_channels(other);
         self.positions.extend(other.positions.iter().map(|p| {
             let p = Vec3::from_array(*p);
             let p = transform.transform_point3(p);
             p.to_array()
         }));
+        // Normals use the inverse transpose so non-uniform scale keeps them
+        // perpendicular to the surface.
+        let normal_mat = transform.inverse().transpose();
         self.normals.extend(other.normals.iter().map(|n| {
             let n = Vec3::from_array(*n);
-            let n = transform.transform_vector3(n);
+            let n = normal_mat.transform_vector3(n);
             if n.length_squared() > 1.0e-12 {
                 n.normalize().to_array()
             } else {
@@ -63,7 +175,124 @@ impl TriMesh {
         }));
         self.indices
             .extend(other.indices.iter().copied().map(|idx| idx + base));
+        self.line_indices
+            .extend(other.line_indices.iter().copied().map(|idx| idx + base));
+    }
+
+    /// Fills the color and material channels from `appearance`, and turns
+    /// the triangles into edge lines for wireframe appearances.
+    pub fn apply_appearance(&mut self, appearance: &Appearance) {
+        self.colors = vec![appearance.color; self.positions.len()];
+        self.materials = vec![[appearance.roughness, appearance.metalness]; self.positions.len()];
+        if appearance.wireframe {
+            for tri in self.indices.chunks_exact(3) {
+                self.line_indices
+                    .extend([tri[0], tri[1], tri[1], tri[2], tri[2], tri[0]]);
+            }
+            self.indices.clear();
+        }
+    }
+
+    /// Recomputes vertex normals from triangle geometry.
+    ///
+    /// Corners are grouped by position, so this also works on meshes where
+    /// every triangle has its own vertices (as produced by `tessellate_solid`).
+    pub fn recompute_normals(&mut self, mode: NormalMode) {
+        let face_normals: Vec<(Vec3, Vec3)> = self
+            .indices
+            .chunks_exact(3)
+            .map(|tri| {
+                let p0 = self.position(tri[0]);
+                let p1 = self.position(tri[1]);
+                let p2 = self.position(tri[2]);
+                // Unnormalized cross product, so averaging is area weighted.
+                let n = (p1 - p0).cross(p2 - p0);
+                (n, n.normalize_or_zero())
+            })
+            .collect();
+
+        let cos_limit = match mode {
+            NormalMode::Flat => None,
+            NormalMode::Smooth => Some(-1.0),
+            NormalMode::Crease(angle) => Some(angle.clamp(0.0, std::f32::consts::PI).cos()),
+        };
+
+        let mut corners_at: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+        if cos_limit.is_some() {
+            for (corner, &idx) in self.indices.iter().enumerate() {
+                corners_at
+                    .entry(position_key(self.position(idx)))
+                    .or_default()
+                    .push(corner);
+            }
+        }
+
+        let mut normals = vec![Vec3::ZERO; self.positions.len()];
+        for (corner, &idx) in self.indices.iter().enumerate() {
+            let (_, own) = face_normals[corner / 3];
+            let n = match cos_limit {
+                None => own,
+                Some(cos_limit) => corners_at[&position_key(self.position(idx))]
+                    .iter()
+                    .map(|other| face_normals[other / 3])
+                    .filter(|(_, unit)| unit.dot(own) >= cos_limit)
+                    .fold(Vec3::ZERO, |acc, (weighted, _)| acc + weighted),
+            };
+            normals[idx as usize] += n;
+        }
+
+        self.normals = normals
+            .into_iter()
+            .map(|n| {
+                if n.length_squared() > 1.0e-12 {
+                    n.normalize().to_array()
+                } else {
+                    [0.0, 1.0, 0.0]
+                }
+            })
+            .collect();
+    }
+
+    /// Keeps the optional per-vertex channels parallel to `positions` when
+    /// either side has them.
+    fn append_channels(&mut self, other: &TriMesh) {
+        let (len, other_len) = (self.positions.len(), other.positions.len());
+        let fallback = Appearance::default();
+        append_channel(&mut self.scalars, &other.scalars, len, other_len, 0.0);
+        append_channel(
+            &mut self.colors,
+            &other.colors,
+            len,
+            other_len,
+            fallback.color,
+        );
+        append_channel(
+            &mut self.materials,
+            &other.materials,
+            len,
+            other_len,
+            [fallback.roughness, fallback.metalness],
+        );
+    }
+
+    fn position(&self, idx: u32) -> Vec3 {
+        Vec3::from_array(self.positions[idx as usize])
+    }
+}
+
+fn append_channel<T: Copy>(
+    channel: &mut Vec<T>,
+    other: &[T],
+    len: usize,
+    other_len: usize,
+    fill: T,
+) {
+    if channel.is_empty() && other.is_empty() {
+        return;
     }
+    channel.resize(len, fill);
+    channel.extend(other);
+    channel.resize(len + other_len, fill);
 }
 
 /// Scene that keeps model data separate from render meshes.
@@ -71,11 +300,16 @@ impl TriMesh {
 pub struct GeomScene {
     model: Model,
     solids: Vec<Solid>,
-    local_meshes: Vec<TriMesh>,
+    local_meshes: Vec<Arc<TriMesh>>,
     bounds_radius: Vec<f32>,
     local_aabbs: Vec<Aabb>,
+    local_spheres: Vec<BoundingSphere>,
+    normal_modes: Vec<NormalMode>,
     mesh_cache: Option<TriMesh>,
+    /// Meshes of feature-less bodies, shared between identical ones.
+    mesh_pool: HashMap<GeometryKey, Arc<TriMesh>>,
     tolerance: f64,
+    normal_mode: NormalMode,
 }
 
 impl GeomScene {
@@ -86,37 +320,244 @@ impl GeomScene {
             local_meshes: Vec::new(),
             bounds_radius: Vec::new(),
             local_aabbs: Vec::new(),
+            local_spheres: Vec::new(),
+            normal_modes: Vec::new(),
             mesh_cache: None,
+            mesh_pool: HashMap::new(),
             tolerance: 0.01,
+            normal_mode: NormalMode::Smooth,
         }
     }
 
+    /// Builds a scene around an existing model, regenerating every body.
+    pub fn from_model(model: Model) -> Result<Self, GeomError> {
+        Self::from_model_with_tolerance(model, Self::new().tolerance)
+    }
+
+    /// Like [`GeomScene::from_model`], tessellating at `tolerance`; see
+    /// [`GeomScene::set_tolerance`].
+    pub fn from_model_with_tolerance(model: Model, tolerance: f64) -> Result<Self, GeomError> {
+        let mut scene = Self::ungenerated(model, tolerance);
+        scene.regenerate_all()?;
+        Ok(scene)
+    }
+
+    /// Like [`GeomScene::from_model`], but on wasm builds regenerates one
+    /// body at a time; see [`GeomScene::regenerate_all_async`].
+    pub async fn from_model_async(model: Model) -> Result<Self, GeomError> {
+        let mut scene = Self::ungenerated(model, Self::new().tolerance);
+        scene.regenerate_all_async().await?;
+        Ok(scene)
+    }
+
+    /// A scene around `model` whose bodies are all still empty.
+    fn ungenerated(model: Model, tolerance: f64) -> Self {
+        let mut scene = Self::new();
+        scene.tolerance = tolerance;
+        let count = model.objects().len();
+        scene.model = model;
+        scene.solids = (0..count).map(|_| Solid::new(Vec::new())).collect();
+        scene.local_meshes = vec![Arc::default(); count];
+        scene.bounds_radius = vec![0.0; count];
+        scene.local_aabbs = vec![Aabb::default(); count];
+        scene.local_spheres = vec![BoundingSphere::default(); count];
+        scene.normal_modes = vec![scene.normal_mode; count];
+        scene
+    }
+
     pub fn model(&self) -> &Model {
         &self.model
     }
 
+    pub fn set_document_info(&mut self, info: DocumentInfo) {
+        self.model.set_info(info);
+    }
+
+    /// See [`Model::touch`].
+    pub fn touch_document(&mut self, now_ms: u64) {
+        self.model.touch(now_ms);
+    }
+
+    /// See [`Model::set_client_id`].
+    pub fn set_client_id(&mut self, client: ClientId) {
+        self.model.set_client_id(client);
+    }
+
     pub fn object_transform(&self, id: ObjectId) -> Option<Transform> {
         self.model.object(id).map(|obj| obj.transform)
     }
 
+    /// Column-major matrix placing an object in world space, composed
+    /// through its parent components.
+    pub fn object_world_matrix(&self, id: ObjectId) -> Option<[[f32; 4]; 4]> {
+        self.model
+            .object(id)
+            .map(|obj| self.world_mat(obj).to_cols_array_2d())
+    }
+
+    /// Local-to-world matrix of an object's parent chain, without the
+    /// object's own transform.
+    pub(crate) fn parent_mat(&self, parent: Option<ComponentId>) -> Mat4 {
+        self.model.parent_matrix(parent)
+    }
+
+    pub(crate) fn world_mat(&self, obj: &ModelObject) -> Mat4 {
+        self.parent_mat(obj.parent) * obj.transform.to_mat4()
+    }
+
+    /// Radius around the object origin, including the object's scale.
     pub fn bounds_radius(&self, id: ObjectId) -> Option<f32> {
+        let idx = self.model.objects().iter().position(|obj| obj.id == id)?;
+        let world = self.world_mat(&self.model.objects()[idx]);
+        let max_scale = [world.x_axis, world.y_axis, world.z_axis]
+            .iter()
+            .fold(0.0f32, |acc, axis| acc.max(axis.truncate().length()));
+        self.bounds_radius.get(idx).map(|r| r * max_scale)
+    }
+
+    pub fn local_aabb(&self, id: ObjectId) -> Option<Aabb> {
         self.model
             .objects()
             .iter()
             .position(|obj| obj.id == id)
-            .and_then(|idx| self.bounds_radius.get(idx).copied())
+            .and_then(|idx| self.local_aabbs.get(idx).copied())
     }
 
-    pub fn local_aabb(&self, id: ObjectId) -> Option<Aabb> {
+    /// Object bounds in world space: the transformed local box, re-boxed.
+    pub fn object_world_aabb(&self, id: ObjectId) -> Option<Aabb> {
+        let idx = self.model.objects().iter().position(|obj| obj.id == id)?;
+        let transform = self.world_mat(&self.model.objects()[idx]);
+        let corners = aabb_corners(self.local_aabbs.get(idx)?, transform);
+        Some(points_aabb(&corners))
+    }
+
+    /// Bounds of every object in world space, e.g. for zoom-to-extents.
+    /// `None` for an empty scene.
+    pub fn world_aabb(&self) -> Option<Aabb> {
+        let corners: Vec<Vec3> = self
+            .model
+            .objects()
+            .iter()
+            .zip(&self.local_aabbs)
+            .flat_map(|(obj, aabb)| aabb_corners(aabb, self.world_mat(obj)))
+            .collect();
+        (!corners.is_empty()).then(|| points_aabb(&corners))
+    }
+
+    /// Chordal tolerance of the tessellation, in document units.
+    pub fn tolerance(&self) -> f64 {
+        self.tolerance
+    }
+
+    /// Re-tessellates every body at `tolerance`; smaller is finer. Bounds
+    /// keep their current values.
+    pub fn set_tolerance(&mut self, tolerance: f64) {
+        self.tolerance = tolerance;
+        let count = self.solids.len();
+        let keys: Vec<Option<GeometryKey>> = (0..count)
+            .map(|idx| self.geometry_key(idx, self.normal_modes[idx]))
+            .collect();
+        // As in `regenerate_all`: each distinct geometry once, in parallel.
+        let mut seen = HashSet::new();
+        let leaders: Vec<usize> = (0..count)
+            .filter(|&idx| match keys[idx] {
+                Some(key) => !self.mesh_pool.contains_key(&key) && seen.insert(key),
+                None => true,
+            })
+            .collect();
+        let meshes = par_map(leaders.len(), |i| {
+            let idx = leaders[i];
+            Arc::new(tessellate_solid_with_normals(
+                &self.solids[idx],
+                self.tolerance,
+                self.normal_modes[idx],
+            ))
+        });
+        for (idx, mesh) in leaders.into_iter().zip(meshes) {
+            match keys[idx] {
+                Some(key) => {
+                    self.mesh_pool.insert(key, mesh);
+                }
+                None => self.local_meshes[idx] = mesh,
+            }
+        }
+        for (mesh, key) in self.local_meshes.iter_mut().zip(&keys) {
+            if let Some(key) = key {
+                *mesh = self.mesh_pool[key].clone();
+            }
+        }
+        self.prune_mesh_pool();
+        self.mesh_cache = None;
+    }
+
+    pub fn normal_mode(&self) -> NormalMode {
+        self.normal_mode
+    }
+
+    /// Sets the normal mode used for objects created from now on.
+    pub fn set_normal_mode(&mut self, mode: NormalMode) {
+        self.normal_mode = mode;
+    }
+
+    pub fn object_normal_mode(&self, id: ObjectId) -> Option<NormalMode> {
         self.model
             .objects()
             .iter()
             .position(|obj| obj.id == id)
-            .and_then(|idx| self.local_aabbs.get(idx).copied())
+            .and_then(|idx| self.normal_modes.get(idx).copied())
+    }
+
+    /// Re-tessellates a single object with a different shading policy.
+    pub fn set_object_normal_mode(&mut self, id: ObjectId, mode: NormalMode) -> bool {
+        let Some(idx) = self.model.objects().iter().position(|obj| obj.id == id) else {
+            return false;
+        };
+        let Some(solid) = self.solids.get(idx).cloned() else {
+            return false;
+        };
+        self.local_meshes[idx] = self.shared_mesh(idx, &solid, mode);
+        self.normal_modes[idx] = mode;
+        self.mesh_cache = None;
+        true
+    }
+
+    pub fn unit(&self) -> LengthUnit {
+        self.model.unit()
+    }
+
+    /// Relabels the document unit; lengths keep their numeric values.
+    pub fn set_unit(&mut self, unit: LengthUnit) {
+        self.model.set_unit(unit);
+    }
+
+    /// Switches the document unit, keeping every body's physical size.
+    pub fn convert_units(&mut self, unit: LengthUnit) -> Result<(), GeomError> {
+        let factor = self.model.unit().factor_to(unit);
+        self.model.convert_units(unit);
+        self.tolerance *= factor;
+        self.regenerate_all()
+    }
+
+    pub fn add_sketch(
+        &mut self,
+        name: impl Into<String>,
+        plane: SketchPlane,
+        entities: Vec<SketchEntity>,
+    ) -> SketchId {
+        self.model.add_sketch(name, plane, entities)
+    }
+
+    pub fn set_sketch_entities(&mut self, id: SketchId, entities: Vec<SketchEntity>) -> bool {
+        self.model.set_sketch_entities(id, entities)
+    }
+
+    pub fn remove_sketch(&mut self, id: SketchId) -> bool {
+        self.model.remove_sketch(id).is_some()
     }
 
     pub fn set_object_transform(&mut self, id: ObjectId, transform: Transform) -> bool {
         if self.model.set_transform(id, transform) {
+            self.regenerate_moved(id);
             self.mesh_cache = None;
             true
         } else {
@@ -124,32 +565,677 @@ impl GeomScene {
         }
     }
 
-    pub fn add_box(&mut self, w: f32, h: f32, d: f32) -> ObjectId {
-        let id = self.model.add_box(w, h, d);
-        let solid = make_box(w as f64, h as f64, d as f64);
-        let mesh = tessellate_solid(&solid, self.tolerance);
-        let radius = mesh_bounds_radius(&mesh);
-        let aabb = mesh_bounds_aabb(&mesh);
-        self.solids.push(solid);
-        self.local_meshes.push(mesh);
-        self.bounds_radius.push(radius);
-        self.local_aabbs.push(aabb);
+    pub fn set_object_name(&mut self, id: ObjectId, name: impl Into<String>) -> bool {
+        self.model.set_name(id, name)
+    }
+
+    pub fn set_object_metadata(
+        &mut self,
+        id: ObjectId,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> bool {
+        self.model.set_metadata(id, key, value).is_some()
+    }
+
+    pub fn remove_object_metadata(&mut self, id: ObjectId, key: &str) -> Option<String> {
+        self.model.remove_metadata(id, key)
+    }
+
+    pub fn set_object_property(
+        &mut self,
+        id: ObjectId,
+        key: impl Into<String>,
+        value: impl Into<PropertyValue>,
+    ) -> bool {
+        self.model.set_property(id, key, value).is_some()
+    }
+
+    pub fn remove_object_property(&mut self, id: ObjectId, key: &str) -> Option<PropertyValue> {
+        self.model.remove_property(id, key)
+    }
+
+    pub fn add_object_tag(&mut self, id: ObjectId, tag: &str) -> bool {
+        self.model.add_tag(id, tag)
+    }
+
+    pub fn remove_object_tag(&mut self, id: ObjectId, tag: &str) -> bool {
+        self.model.remove_tag(id, tag)
+    }
+
+    pub fn set_object_visible(&mut self, id: ObjectId, visible: bool) -> bool {
+        if self.model.set_visible(id, visible) {
+            self.mesh_cache = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_object_locked(&mut self, id: ObjectId, locked: bool) -> bool {
+        self.model.set_locked(id, locked)
+    }
+
+    pub fn add_component(
+        &mut self,
+        name: impl Into<String>,
+        parent: Option<ComponentId>,
+    ) -> Option<ComponentId> {
+        self.model.add_component(name, parent)
+    }
+
+    pub fn rename_component(&mut self, id: ComponentId, name: impl Into<String>) -> bool {
+        self.model.rename_component(id, name)
+    }
+
+    pub fn set_component_transform(&mut self, id: ComponentId, transform: Transform) -> bool {
+        if self.model.set_component_transform(id, transform) {
+            self.mesh_cache = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_component_parent(&mut self, id: ComponentId, parent: Option<ComponentId>) -> bool {
+        if self.model.set_component_parent(id, parent) {
+            self.mesh_cache = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_object_parent(&mut self, id: ObjectId, parent: Option<ComponentId>) -> bool {
+        if self.model.set_object_parent(id, parent) {
+            self.mesh_cache = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes a component; its children move up to its parent.
+    pub fn remove_component(&mut self, id: ComponentId) -> bool {
+        if self.model.remove_component(id).is_some() {
+            self.mesh_cache = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Groups bodies under a new component without moving them.
+    pub fn group(&mut self, name: impl Into<String>, objects: &[ObjectId]) -> Option<ComponentId> {
+        let group = self.model.group(name, objects)?;
+        self.mesh_cache = None;
+        Some(group)
+    }
+
+    /// Dissolves a group, keeping its bodies where they are.
+    pub fn ungroup(&mut self, group: ComponentId) -> Option<Vec<ObjectId>> {
+        let members = self.model.ungroup(group)?;
         self.mesh_cache = None;
-        id
+        Some(members)
+    }
+
+    pub fn add_layer(&mut self, name: impl Into<String>, color: [f32; 4]) -> LayerId {
+        self.model.add_layer(name, color)
+    }
+
+    pub fn rename_layer(&mut self, id: LayerId, name: impl Into<String>) -> bool {
+        self.model.rename_layer(id, name)
+    }
+
+    pub fn set_layer_color(&mut self, id: LayerId, color: [f32; 4]) -> bool {
+        self.model.set_layer_color(id, color)
+    }
+
+    pub fn set_layer_visible(&mut self, id: LayerId, visible: bool) -> bool {
+        if self.model.set_layer_visible(id, visible) {
+            self.mesh_cache = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_layer_locked(&mut self, id: LayerId, locked: bool) -> bool {
+        self.model.set_layer_locked(id, locked)
+    }
+
+    /// Removes a layer, moving its objects to the default layer.
+    pub fn remove_layer(&mut self, id: LayerId) -> bool {
+        if self.model.remove_layer(id).is_some() {
+            self.mesh_cache = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_object_layer(&mut self, id: ObjectId, layer: Option<LayerId>) -> bool {
+        if self.model.set_object_layer(id, layer) {
+            self.mesh_cache = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_object_appearance(&mut self, id: ObjectId, appearance: Appearance) -> bool {
+        if self.model.set_appearance(id, appearance) {
+            self.mesh_cache = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes an object together with its solid, mesh and bounds.
+    pub fn remove_object(&mut self, id: ObjectId) -> bool {
+        self.take_object(id).is_some()
+    }
+
+    /// Like [`GeomScene::remove_object`], but returns what was detached so
+    /// [`GeomScene::restore_object`] can undo it. Bodies that lose a boolean
+    /// tool are regenerated without it.
+    pub fn take_object(&mut self, id: ObjectId) -> Option<Removed> {
+        let removed = self.model.remove(id)?;
+        self.detach(removed.index, &removed.suppressed_features);
+        Some(removed)
+    }
+
+    /// Puts back an object returned by [`GeomScene::take_object`] and
+    /// rebuilds it along with the bodies that used it as a tool.
+    pub fn restore_object(&mut self, removed: Removed) -> Result<(), GeomError> {
+        let id = removed.object.id;
+        let idx = removed.index.min(self.solids.len());
+        let suppressed = removed.suppressed_features.clone();
+        if !self.model.reinsert(removed) {
+            return Err(GeomError::InvalidFeature("object id already in use"));
+        }
+        self.attach(idx, id, &suppressed)
+    }
+
+    /// Moves an object to the model's trash; see [`Model::delete`].
+    pub fn delete_object(&mut self, id: ObjectId) -> bool {
+        if !self.model.delete(id) {
+            return false;
+        }
+        if let Some(removed) = self.model.deleted(id) {
+            let (idx, suppressed) = (removed.index, removed.suppressed_features.clone());
+            self.detach(idx, &suppressed);
+        }
+        true
+    }
+
+    /// Takes an object out of the trash and rebuilds it along with the
+    /// bodies that used it as a tool.
+    pub fn restore_deleted(&mut self, id: ObjectId) -> Result<(), GeomError> {
+        let removed = self.model.deleted(id).ok_or(GeomError::UnknownObject(id))?;
+        let idx = removed.index.min(self.solids.len());
+        let suppressed = removed.suppressed_features.clone();
+        if !self.model.restore(id) {
+            return Err(GeomError::InvalidFeature("object id already in use"));
+        }
+        self.attach(idx, id, &suppressed)
     }
 
-    pub fn add_cylinder(&mut self, r: f32, h: f32) -> ObjectId {
+    /// Drops the derived data of the body that was at `idx`.
+    fn detach(&mut self, idx: usize, suppressed: &[(ObjectId, FeatureId)]) {
+        self.solids.remove(idx);
+        self.local_meshes.remove(idx);
+        self.bounds_radius.remove(idx);
+        self.local_aabbs.remove(idx);
+        self.local_spheres.remove(idx);
+        self.normal_modes.remove(idx);
+        self.mesh_cache = None;
+        self.regenerate_owners(suppressed);
+        self.prune_mesh_pool();
+    }
+
+    /// Makes room for a body put back at `idx` and builds it.
+    fn attach(
+        &mut self,
+        idx: usize,
+        id: ObjectId,
+        suppressed: &[(ObjectId, FeatureId)],
+    ) -> Result<(), GeomError> {
+        self.solids.insert(idx, Solid::new(Vec::new()));
+        self.local_meshes.insert(idx, Arc::default());
+        self.bounds_radius.insert(idx, 0.0);
+        self.local_aabbs.insert(idx, Aabb::default());
+        self.local_spheres.insert(idx, BoundingSphere::default());
+        self.normal_modes.insert(idx, self.normal_mode);
+        let result = self.regenerate(id);
+        self.regenerate_owners(suppressed);
+        result
+    }
+
+    /// Applies a [`ModelDelta`], reusing the solids of bodies whose shape
+    /// did not change and rebuilding the rest, and the cuts of moved ones.
+    /// Returns the first rebuild failure, after every body has been brought
+    /// in line with the model.
+    pub fn apply_delta(&mut self, delta: &ModelDelta) -> Result<(), GeomError> {
+        let before: Vec<ModelObject> = self.model.objects().to_vec();
+        self.model.apply_delta(delta)?;
+
+        let mut solids = std::mem::take(&mut self.solids);
+        let local_meshes = std::mem::take(&mut self.local_meshes);
+        let bounds_radius = std::mem::take(&mut self.bounds_radius);
+        let local_aabbs = std::mem::take(&mut self.local_aabbs);
+        let local_spheres = std::mem::take(&mut self.local_spheres);
+        let normal_modes = std::mem::take(&mut self.normal_modes);
+        let mut stale = Vec::new();
+        let mut moved = Vec::new();
+        for obj in self.model.objects() {
+            let old = before.iter().position(|old| old.id == obj.id);
+            let reusable = old.filter(|&old| {
+                before[old].kind == obj.kind && before[old].features == obj.features
+            });
+            match reusable {
+                Some(old) => {
+                    if before[old].transform != obj.transform {
+                        moved.push(obj.id);
+                    }
+                    self.solids
+                        .push(std::mem::replace(&mut solids[old], Solid::new(Vec::new())));
+                    self.local_meshes.push(local_meshes[old].clone());
+                    self.bounds_radius.push(bounds_radius[old]);
+                    self.local_aabbs.push(local_aabbs[old]);
+                    self.local_spheres.push(local_spheres[old]);
+                    self.normal_modes.push(normal_modes[old]);
+                }
+                None => {
+                    self.solids.push(Solid::new(Vec::new()));
+                    self.local_meshes.push(Arc::default());
+                    self.bounds_radius.push(0.0);
+                    self.local_aabbs.push(Aabb::default());
+                    self.local_spheres.push(BoundingSphere::default());
+                    self.normal_modes
+                        .push(old.map_or(self.normal_mode, |old| normal_modes[old]));
+                    stale.push(obj.id);
+                }
+            }
+        }
+        self.mesh_cache = None;
+
+        let mut first_err = None;
+        for id in stale {
+            if let Err(err) = self.regenerate(id) {
+                first_err.get_or_insert(err);
+            }
+        }
+        for id in moved {
+            self.regenerate_moved(id);
+        }
+        self.prune_mesh_pool();
+        first_err.map_or(Ok(()), Err)
+    }
+
+    /// Replays the bodies whose features were toggled by a removal.
+    fn regenerate_owners(&mut self, suppressed: &[(ObjectId, FeatureId)]) {
+        let mut owners: Vec<ObjectId> = suppressed.iter().map(|(owner, _)| *owner).collect();
+        owners.dedup();
+        for owner in owners {
+            // A failing feature leaves the body rolled back to just before
+            // it, which is the best state to show.
+            let _ = self.regenerate(owner);
+        }
+    }
+
+    pub fn duplicate(&mut self, id: ObjectId) -> Option<ObjectId> {
+        self.duplicate_with_offset(id, [0.0, 0.0, 0.0])
+    }
+
+    /// Copies an object and moves the copy by `offset` in world space.
+    pub fn duplicate_with_offset(&mut self, id: ObjectId, offset: [f32; 3]) -> Option<ObjectId> {
+        let idx = self.model.objects().iter().position(|obj| obj.id == id)?;
+        let new_id = self.model.duplicate(id)?;
+        let mut transform = self.model.objects()[idx].transform;
+        for (t, o) in transform.translation.iter_mut().zip(offset) {
+            *t += o;
+        }
+        self.model.set_transform(new_id, transform);
+        self.solids.push(self.solids[idx].clone());
+        self.local_meshes.push(self.local_meshes[idx].clone());
+        self.bounds_radius.push(self.bounds_radius[idx]);
+        self.local_aabbs.push(self.local_aabbs[idx]);
+        self.local_spheres.push(self.local_spheres[idx]);
+        self.normal_modes.push(self.normal_modes[idx]);
+        self.mesh_cache = None;
+        Some(new_id)
+    }
+
+    pub fn add_box(&mut self, w: f32, h: f32, d: f32) -> Result<ObjectId, GeomError> {
+        let solid = make_box(w as f64, h as f64, d as f64)?;
+        let id = self.model.add_box(w, h, d);
+        self.push_solid(solid);
+        Ok(id)
+    }
+
+    pub fn add_cylinder(&mut self, r: f32, h: f32) -> Result<ObjectId, GeomError> {
+        let solid = make_cylinder(r as f64, h as f64)?;
         let id = self.model.add_cylinder(r, h);
-        let solid = make_cylinder(r as f64, h as f64);
-        let mesh = tessellate_solid(&solid, self.tolerance);
+        self.push_solid(solid);
+        Ok(id)
+    }
+
+    pub fn add_tube(&mut self, outer_r: f32, inner_r: f32, h: f32) -> Result<ObjectId, GeomError> {
+        let solid = make_tube(outer_r as f64, inner_r as f64, h as f64)?;
+        let id = self.model.add_tube(outer_r, inner_r, h);
+        self.push_solid(solid);
+        Ok(id)
+    }
+
+    pub fn add_wedge(&mut self, w: f32, h: f32, d: f32) -> Result<ObjectId, GeomError> {
+        let solid = make_wedge(w as f64, h as f64, d as f64)?;
+        let id = self.model.add_wedge(w, h, d);
+        self.push_solid(solid);
+        Ok(id)
+    }
+
+    pub fn add_prism(&mut self, sides: u32, r: f32, h: f32) -> Result<ObjectId, GeomError> {
+        let solid = make_prism(sides, r as f64, h as f64)?;
+        let id = self.model.add_prism(sides, r, h);
+        self.push_solid(solid);
+        Ok(id)
+    }
+
+    /// Rebuilds an object's solid and mesh from new primitive parameters,
+    /// keeping its id, transform and feature history.
+    pub fn update_primitive(&mut self, id: ObjectId, kind: ObjectKind) -> Result<(), GeomError> {
+        check_primitive(&kind)?;
+        if !self.model.set_kind(id, kind) {
+            return Err(GeomError::UnknownObject(id));
+        }
+        self.regenerate(id)
+    }
+
+    /// Appends a feature and regenerates the body. Features that fail to
+    /// apply are not kept.
+    pub fn add_feature(&mut self, id: ObjectId, op: FeatureOp) -> Result<FeatureId, GeomError> {
+        let feature = self
+            .model
+            .add_feature(id, op)
+            .ok_or(GeomError::UnknownObject(id))?;
+        if let Err(err) = self.regenerate(id) {
+            self.model.remove_feature(id, feature);
+            self.regenerate(id)?;
+            return Err(err);
+        }
+        Ok(feature)
+    }
+
+    pub fn remove_feature(&mut self, id: ObjectId, feature: FeatureId) -> Result<(), GeomError> {
+        self.model.object(id).ok_or(GeomError::UnknownObject(id))?;
+        if self.model.remove_feature(id, feature).is_none() {
+            return Err(GeomError::UnknownFeature {
+                object: id,
+                feature,
+            });
+        }
+        self.regenerate(id)
+    }
+
+    pub fn set_feature_suppressed(
+        &mut self,
+        id: ObjectId,
+        feature: FeatureId,
+        suppressed: bool,
+    ) -> Result<(), GeomError> {
+        self.model.object(id).ok_or(GeomError::UnknownObject(id))?;
+        if !self.model.set_feature_suppressed(id, feature, suppressed) {
+            return Err(GeomError::UnknownFeature {
+                object: id,
+                feature,
+            });
+        }
+        self.regenerate(id)
+    }
+
+    /// Replays an object's base primitive and features to rebuild its solid,
+    /// then those of the bodies it is a tool of.
+    ///
+    /// If a feature fails, the body is left in the state just before it (like
+    /// a rolled-back timeline) and the failure is returned.
+    pub fn regenerate(&mut self, id: ObjectId) -> Result<(), GeomError> {
+        let result = self.regenerate_body(id);
+        self.regenerate_dependents(id);
+        result
+    }
+
+    fn regenerate_body(&mut self, id: ObjectId) -> Result<(), GeomError> {
+        let idx = self
+            .model
+            .objects()
+            .iter()
+            .position(|obj| obj.id == id)
+            .ok_or(GeomError::UnknownObject(id))?;
+        let (solid, result) = self.replay(idx);
+        self.set_solid(idx, solid);
+        result
+    }
+
+    /// Replays the bodies with a feature reading `id`'s solid, and in turn
+    /// those reading theirs, after `id` changed shape or moved. Failures
+    /// leave those bodies rolled back, as [`GeomScene::regenerate`] does.
+    fn regenerate_dependents(&mut self, id: ObjectId) {
+        let mut done = HashSet::from([id]);
+        let mut changed = vec![id];
+        while let Some(tool) = changed.pop() {
+            let users: Vec<ObjectId> = self
+                .model
+                .objects()
+                .iter()
+                .filter(|obj| {
+                    obj.features
+                        .iter()
+                        .any(|f| !f.suppressed && f.op.tool() == Some(tool))
+                })
+                .map(|obj| obj.id)
+                .filter(|&user| done.insert(user))
+                .collect();
+            for user in users {
+                let _ = self.regenerate_body(user);
+                changed.push(user);
+            }
+        }
+    }
+
+    /// Replays what a move of `id` changes: its own cuts, which are placed
+    /// in the world, and the bodies it cuts.
+    fn regenerate_moved(&mut self, id: ObjectId) {
+        let cuts = self.model.object(id).is_some_and(|obj| {
+            obj.features
+                .iter()
+                .any(|f| !f.suppressed && f.op.tool().is_some())
+        });
+        if cuts {
+            let _ = self.regenerate_body(id);
+        }
+        self.regenerate_dependents(id);
+    }
+
+    /// Regenerates every body in model order, returning the first failure.
+    pub fn regenerate_all(&mut self) -> Result<(), GeomError> {
+        let count = self.model.objects().len();
+        let replayed = par_map(count, |idx| self.replay(idx));
+
+        // Tessellate each distinct geometry once; the rest share its mesh.
+        let keys: Vec<Option<GeometryKey>> = (0..count)
+            .map(|idx| self.geometry_key(idx, self.normal_modes[idx]))
+            .collect();
+        let mut seen = HashSet::new();
+        let leaders: Vec<usize> = (0..count)
+            .filter(|&idx| match keys[idx] {
+                Some(key) => !self.mesh_pool.contains_key(&key) && seen.insert(key),
+                None => true,
+            })
+            .collect();
+        let meshes = par_map(leaders.len(), |i| {
+            let idx = leaders[i];
+            let mode = self.normal_modes[idx];
+            Arc::new(tessellate_solid_with_normals(
+                &replayed[idx].0,
+                self.tolerance,
+                mode,
+            ))
+        });
+        let mut fresh = HashMap::new();
+        for (idx, mesh) in leaders.into_iter().zip(meshes) {
+            if let Some(key) = keys[idx] {
+                self.mesh_pool.insert(key, mesh.clone());
+            }
+            fresh.insert(idx, mesh);
+        }
+
+        let mut first_err = None;
+        for (idx, (solid, result)) in replayed.into_iter().enumerate() {
+            let mesh = match fresh.remove(&idx) {
+                Some(mesh) => mesh,
+                None => self.shared_mesh(idx, &solid, self.normal_modes[idx]),
+            };
+            self.store_solid(idx, solid, mesh);
+            if let Err(err) = result {
+                first_err.get_or_insert(err);
+            }
+        }
+        self.prune_mesh_pool();
+        first_err.map_or(Ok(()), Err)
+    }
+
+    /// Like [`GeomScene::regenerate_all`], but on wasm builds replays and
+    /// tessellates one body at a time and yields to the browser in between,
+    /// so opening large documents does not freeze the page.
+    pub async fn regenerate_all_async(&mut self) -> Result<(), GeomError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.regenerate_all()
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let mut first_err = None;
+            for idx in 0..self.model.objects().len() {
+                let (solid, result) = self.replay(idx);
+                self.set_solid(idx, solid);
+                if let Err(err) = result {
+                    first_err.get_or_insert(err);
+                }
+                parallel::yield_now().await;
+            }
+            first_err.map_or(Ok(()), Err)
+        }
+    }
+
+    fn replay(&self, idx: usize) -> (Solid, Result<(), GeomError>) {
+        let obj = &self.model.objects()[idx];
+        if let ObjectKind::Derived { operands, .. } = &obj.kind {
+            if operands.contains(&Operand::Object(obj.id)) {
+                let err = GeomError::InvalidPrimitive("body cannot be its own operand");
+                return (Solid::new(Vec::new()), Err(err));
+            }
+        }
+        let mut solid = match self.kind_solid(&obj.kind, self.world_mat(obj)) {
+            Ok(solid) => solid,
+            Err(err) => return (Solid::new(Vec::new()), Err(err)),
+        };
+        for feature in obj.features.iter().filter(|f| !f.suppressed) {
+            match self.apply_feature(obj, &solid, &feature.op) {
+                Ok(next) => solid = next,
+                Err(err) => {
+                    let err = GeomError::FeatureFailed {
+                        feature: feature.id,
+                        source: Box::new(err),
+                    };
+                    return (solid, Err(err));
+                }
+            }
+        }
+        (solid, Ok(()))
+    }
+
+    fn apply_feature(
+        &self,
+        obj: &ModelObject,
+        solid: &Solid,
+        op: &FeatureOp,
+    ) -> Result<Solid, GeomError> {
+        match *op {
+            FeatureOp::Subtract { tool } => {
+                if tool == obj.id {
+                    return Err(GeomError::InvalidFeature("body cannot be its own tool"));
+                }
+                let tool_idx = self
+                    .model
+                    .objects()
+                    .iter()
+                    .position(|other| other.id == tool)
+                    .ok_or(GeomError::UnknownObject(tool))?;
+                // Bring the tool into this body's local space.
+                let to_local =
+                    self.world_mat(obj).inverse() * self.world_mat(&self.model.objects()[tool_idx]);
+                let tool_solid =
+                    builder::transformed(&self.solids[tool_idx], mat4_to_truck(to_local));
+                boolean_subtract(solid, &tool_solid)
+            }
+            FeatureOp::PushPull {
+                point,
+                normal,
+                distance,
+            } => push_pull::push_pull(solid, point, normal, distance),
+            FeatureOp::LinearPattern {
+                direction,
+                spacing,
+                count,
+            } => {
+                let dir = Vec3::from_array(direction).normalize_or_zero();
+                if count == 0 || dir == Vec3::ZERO || !spacing.is_finite() {
+                    return Err(GeomError::InvalidFeature(
+                        "pattern needs a direction, spacing and count",
+                    ));
+                }
+                let step = dir * spacing;
+                let shells = (0..count)
+                    .flat_map(|i| {
+                        let offset = step * i as f32;
+                        let offset =
+                            Vector3::new(offset.x as f64, offset.y as f64, offset.z as f64);
+                        builder::translated(solid, offset).into_boundaries()
+                    })
+                    .collect();
+                Ok(Solid::new(shells))
+            }
+        }
+    }
+
+    fn set_solid(&mut self, idx: usize, solid: Solid) {
+        let mesh = self.shared_mesh(idx, &solid, self.normal_modes[idx]);
+        self.store_solid(idx, solid, mesh);
+    }
+
+    fn store_solid(&mut self, idx: usize, solid: Solid, mesh: Arc<TriMesh>) {
+        self.bounds_radius[idx] = mesh_bounds_radius(&mesh);
+        self.local_aabbs[idx] = mesh_bounds_aabb(&mesh);
+        self.local_spheres[idx] = mesh_bounding_sphere(&mesh);
+        self.solids[idx] = solid;
+        self.local_meshes[idx] = mesh;
+        self.mesh_cache = None;
+    }
+
+    fn push_solid(&mut self, solid: Solid) {
+        let mesh = self.shared_mesh(self.solids.len(), &solid, self.normal_mode);
         let radius = mesh_bounds_radius(&mesh);
         let aabb = mesh_bounds_aabb(&mesh);
+        let sphere = mesh_bounding_sphere(&mesh);
         self.solids.push(solid);
         self.local_meshes.push(mesh);
         self.bounds_radius.push(radius);
         self.local_aabbs.push(aabb);
+        self.local_spheres.push(sphere);
+        self.normal_modes.push(self.normal_mode);
         self.mesh_cache = None;
-        id
     }
 
     pub fn mesh(&mut self) -> Result<TriMesh, GeomError> {
@@ -159,15 +1245,40 @@ impl GeomScene {
         if let Some(mesh) = self.mesh_cache.clone() {
             return Ok(mesh);
         }
-        let mut combined = TriMesh::default();
-        for (idx, obj) in self.model.objects().iter().enumerate() {
-            if let Some(mesh) = self.local_meshes.get(idx) {
-                let transform = transform_mat(obj.transform);
-                combined.append_transformed(mesh, transform);
-            }
+        let mesh = self.mesh_parts()?.mesh();
+        self.mesh_cache = Some(mesh.clone());
+        Ok(mesh)
+    }
+
+    /// Like [`GeomScene::mesh`], but on wasm builds combines the bodies one
+    /// at a time and yields to the browser in between; see
+    /// [`MeshParts::mesh_async`].
+    pub async fn mesh_async(&mut self) -> Result<TriMesh, GeomError> {
+        if self.solids.is_empty() {
+            return Err(GeomError::EmptyScene);
+        }
+        if let Some(mesh) = self.mesh_cache.clone() {
+            return Ok(mesh);
         }
-        self.mesh_cache = Some(combined.clone());
-        Ok(combined)
+        let mesh = self.mesh_parts()?.mesh_async().await;
+        self.mesh_cache = Some(mesh.clone());
+        Ok(mesh)
+    }
+
+    /// The visible bodies as they stand, to combine into the scene mesh
+    /// without holding on to the scene.
+    pub fn mesh_parts(&self) -> Result<MeshParts, GeomError> {
+        if self.solids.is_empty() {
+            return Err(GeomError::EmptyScene);
+        }
+        let objects = self.model.objects();
+        let parts = objects
+            .iter()
+            .zip(&self.local_meshes)
+            .filter(|(obj, _)| self.model.is_visible(obj))
+            .map(|(obj, mesh)| (mesh.clone(), self.world_mat(obj), obj.appearance))
+            .collect();
+        Ok(MeshParts(parts))
     }
 
     pub fn pick_surface(&self, ray_origin: [f32; 3], ray_dir: [f32; 3]) -> Option<SurfaceHit> {
@@ -181,17 +1292,21 @@ impl GeomScene {
         let mut best_t = f32::INFINITY;
 
         for (idx, obj) in self.model.objects().iter().enumerate() {
+            if !self.model.is_pickable(obj) {
+                continue;
+            }
             let Some(mesh) = self.local_meshes.get(idx) else {
                 continue;
             };
-            let transform = transform_mat(obj.transform);
-            let rotation = Quat::from_xyzw(
-                obj.transform.rotation[0],
-                obj.transform.rotation[1],
-                obj.transform.rotation[2],
-                obj.transform.rotation[3],
-            )
-            .normalize();
+            let transform = self.world_mat(obj);
+            let culled = self
+                .local_spheres
+                .get(idx)
+                .is_some_and(|sphere| !sphere.transformed(transform).intersects_ray(ray_o, ray_d));
+            if culled {
+                continue;
+            }
+            let normal_mat = transform.inverse().transpose();
 
             for tri in mesh.indices.chunks_exact(3) {
                 let i0 = tri[0] as usize;
@@ -224,7 +1339,7 @@ impl GeomScene {
                     let n_local =
                         (Vec3::from_array(*n0) + Vec3::from_array(*n1) + Vec3::from_array(*n2))
                             / 3.0;
-                    (rotation * n_local).normalize_or_zero()
+                    normal_mat.transform_vector3(n_local).normalize_or_zero()
                 } else {
                     (p1 - p0).cross(p2 - p0).normalize_or_zero()
                 };
@@ -244,23 +1359,135 @@ impl GeomScene {
     }
 }
 
-pub fn make_box(w: f64, h: f64, d: f64) -> Solid {
+/// Builds the solid described by a primitive kind.
+pub fn make_solid(kind: &ObjectKind) -> Result<Solid, GeomError> {
+    match *kind {
+        ObjectKind::Mesh { .. } | ObjectKind::Derived { .. } | ObjectKind::Reference { .. } => {
+            Err(GeomError::InvalidPrimitive(
+                "mesh, derived and reference bodies are built by their scene",
+            ))
+        }
+        ObjectKind::Box { w, h, d } => make_box(w as f64, h as f64, d as f64),
+        ObjectKind::Cylinder { r, h } => make_cylinder(r as f64, h as f64),
+        ObjectKind::Wedge { w, h, d } => make_wedge(w as f64, h as f64, d as f64),
+        ObjectKind::Tube {
+            outer_r,
+            inner_r,
+            h,
+        } => make_tube(outer_r as f64, inner_r as f64, h as f64),
+        ObjectKind::Prism { sides, r, h } => make_prism(sides, r as f64, h as f64),
+    }
+}
+
+fn check_primitive(kind: &ObjectKind) -> Result<(), GeomError> {
+    kind.validate().map_err(GeomError::InvalidPrimitive)
+}
+
+pub fn make_box(w: f64, h: f64, d: f64) -> Result<Solid, GeomError> {
+    check_primitive(&ObjectKind::Box {
+        w: w as f32,
+        h: h as f32,
+        d: d as f32,
+    })?;
     let v = builder::vertex(Point3::new(-w / 2.0, -h / 2.0, -d / 2.0));
     let e = builder::tsweep(&v, Vector3::unit_x() * w);
     let f = builder::tsweep(&e, Vector3::unit_y() * h);
-    builder::tsweep(&f, Vector3::unit_z() * d)
+    Ok(builder::tsweep(&f, Vector3::unit_z() * d))
+}
+
+pub fn make_cylinder(r: f64, h: f64) -> Result<Solid, GeomError> {
+    check_primitive(&ObjectKind::Cylinder {
+        r: r as f32,
+        h: h as f32,
+    })?;
+    let disk = builder::try_attach_plane(&[base_circle(r, h)])
+        .map_err(|err| GeomError::Kernel(err.to_string()))?;
+    Ok(builder::tsweep(&disk, Vector3::new(0.0, h, 0.0)))
+}
+
+/// Annular solid centered like [`make_cylinder`], swept from a ring-shaped
+/// base face. A zero inner radius gives a plain cylinder.
+pub fn make_tube(outer_r: f64, inner_r: f64, h: f64) -> Result<Solid, GeomError> {
+    check_primitive(&ObjectKind::Tube {
+        outer_r: outer_r as f32,
+        inner_r: inner_r as f32,
+        h: h as f32,
+    })?;
+    if inner_r == 0.0 {
+        return make_cylinder(outer_r, h);
+    }
+    let ring =
+        builder::try_attach_plane(&[base_circle(outer_r, h), base_circle(inner_r, h).inverse()])
+            .map_err(|err| GeomError::Kernel(err.to_string()))?;
+    Ok(builder::tsweep(&ring, Vector3::new(0.0, h, 0.0)))
 }
 
-pub fn make_cylinder(r: f64, h: f64) -> Solid {
+/// Circle of radius `r` around the Y axis at the bottom of a body of height `h`.
+fn base_circle(r: f64, h: f64) -> truck_modeling::Wire {
     let vertex = builder::vertex(Point3::new(0.0, -h / 2.0, r));
-    let circle = builder::rsweep(
+    builder::rsweep(
         &vertex,
         Point3::new(0.0, 0.0, 0.0),
         Vector3::unit_y(),
         Rad(std::f64::consts::TAU),
-    );
-    let disk = builder::try_attach_plane(&[circle]).expect("attach disk");
-    builder::tsweep(&disk, Vector3::new(0.0, h, 0.0))
+    )
+}
+
+/// Wedge centered like [`make_box`]: a right-triangle profile in XY (full
+/// height at `-X`) extruded along Z.
+pub fn make_wedge(w: f64, h: f64, d: f64) -> Result<Solid, GeomError> {
+    check_primitive(&ObjectKind::Wedge {
+        w: w as f32,
+        h: h as f32,
+        d: d as f32,
+    })?;
+    let profile = [
+        Point3::new(-w / 2.0, -h / 2.0, -d / 2.0),
+        Point3::new(w / 2.0, -h / 2.0, -d / 2.0),
+        Point3::new(-w / 2.0, h / 2.0, -d / 2.0),
+    ];
+    extrude_polygon(&profile, Vector3::unit_z() * d)
+}
+
+/// Regular prism along Y, centered like [`make_cylinder`].
+pub fn make_prism(sides: u32, r: f64, h: f64) -> Result<Solid, GeomError> {
+    check_primitive(&ObjectKind::Prism {
+        sides,
+        r: r as f32,
+        h: h as f32,
+    })?;
+    let profile: Vec<Point3> = (0..sides)
+        .map(|k| {
+            let angle = std::f64::consts::TAU * k as f64 / sides as f64;
+            Point3::new(r * angle.sin(), -h / 2.0, r * angle.cos())
+        })
+        .collect();
+    extrude_polygon(&profile, Vector3::new(0.0, h, 0.0))
+}
+
+/// Sweeps a planar polygon along `dir`, orienting its face along the sweep.
+fn extrude_polygon(points: &[Point3], dir: Vector3) -> Result<Solid, GeomError> {
+    let vertices: Vec<_> = points.iter().map(|p| builder::vertex(*p)).collect();
+    let wire: truck_modeling::Wire = (0..vertices.len())
+        .map(|i| builder::line(&vertices[i], &vertices[(i + 1) % vertices.len()]))
+        .collect();
+    // Newell normal of the polygon.
+    let normal = (0..points.len()).fold(Vector3::new(0.0, 0.0, 0.0), |acc, i| {
+        let (a, b) = (points[i], points[(i + 1) % points.len()]);
+        acc + Vector3::new(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y),
+        )
+    });
+    let wire = if normal.dot(dir) < 0.0 {
+        wire.inverse()
+    } else {
+        wire
+    };
+    let face =
+        builder::try_attach_plane(&[wire]).map_err(|err| GeomError::Kernel(err.to_string()))?;
+    Ok(builder::tsweep(&face, dir))
 }
 
 pub fn tessellate_solid(solid: &Solid, tolerance: f64) -> TriMesh {
@@ -271,6 +1498,58 @@ pub fn tessellate_solid(solid: &Solid, tolerance: f64) -> TriMesh {
     polygon_to_trimesh(&poly)
 }
 
+pub fn tessellate_solid_with_normals(solid: &Solid, tolerance: f64, mode: NormalMode) -> TriMesh {
+    let mut mesh = tessellate_solid(solid, tolerance);
+    if mode != NormalMode::Smooth {
+        mesh.recompute_normals(mode);
+    }
+    mesh
+}
+
+/// Offsets an open sheet by `thickness` along its face normals to make a
+/// solid; a negative thickness grows the solid behind the sheet.
+///
+/// Only flat sheets (planar faces sharing one normal) are supported for now.
+pub fn thicken(shell: &Shell, thickness: f32) -> Result<Solid, GeomError> {
+    if thickness == 0.0 || !thickness.is_finite() {
+        return Err(GeomError::InvalidFeature("thickness must be non-zero"));
+    }
+    let mut normal: Option<Vector3> = None;
+    for face in shell.face_iter() {
+        let Surface::Plane(plane) = face.surface() else {
+            return Err(GeomError::NotImplemented("thickening curved surfaces"));
+        };
+        let n = if face.orientation() {
+            plane.normal()
+        } else {
+            -plane.normal()
+        };
+        match normal {
+            None => normal = Some(n),
+            Some(first) if first.dot(n) < 1.0 - 1.0e-6 => {
+                return Err(GeomError::NotImplemented("thickening folded sheets"));
+            }
+            Some(_) => {}
+        }
+    }
+    let Some(normal) = normal else {
+        return Err(GeomError::EmptyScene);
+    };
+
+    // As with extrusion, faces must point along the sweep.
+    let sheet: Shell = if thickness < 0.0 {
+        shell.face_iter().map(|face| face.inverse()).collect()
+    } else {
+        shell.clone()
+    };
+    let mut shells = Vec::new();
+    for solid in builder::tsweep(&sheet, normal * thickness as f64) {
+        let solid = solid.map_err(|err| GeomError::Kernel(err.to_string()))?;
+        shells.extend(solid.into_boundaries());
+    }
+    Ok(Solid::new(shells))
+}
+
 /// TODO: boolean subtraction backend (A - B).
 pub fn boolean_subtract(_a: &Solid, _b: &Solid) -> Result<Solid, GeomError> {
     Err(GeomError::NotImplemented("boolean_subtract"))
@@ -329,6 +1608,12 @@ fn face_normal(p0: Point3, p1: Point3, p2: Point3) -> [f32; 3] {
     }
 }
 
+/// Quantizes a position so nearly coincident corners share a normal.
+fn position_key(p: Vec3) -> [i32; 3] {
+    let q = |v: f32| (v / 1.0e-5).round() as i32;
+    [q(p.x), q(p.y), q(p.z)]
+}
+
 fn mesh_bounds_radius(mesh: &TriMesh) -> f32 {
     mesh.positions
         .iter()
@@ -336,16 +1621,39 @@ fn mesh_bounds_radius(mesh: &TriMesh) -> f32 {
         .fold(0.0, f32::max)
 }
 
-fn transform_mat(transform: Transform) -> Mat4 {
-    let t = Vec3::from_array(transform.translation);
-    let q = Quat::from_xyzw(
-        transform.rotation[0],
-        transform.rotation[1],
-        transform.rotation[2],
-        transform.rotation[3],
+/// The eight corners of a local box, transformed.
+fn aabb_corners(aabb: &Aabb, transform: Mat4) -> [Vec3; 8] {
+    std::array::from_fn(|k| {
+        let pick = |axis: usize| {
+            if k & (1 << axis) == 0 {
+                aabb.min[axis]
+            } else {
+                aabb.max[axis]
+            }
+        };
+        transform.transform_point3(Vec3::new(pick(0), pick(1), pick(2)))
+    })
+}
+
+fn points_aabb(points: &[Vec3]) -> Aabb {
+    let min = points
+        .iter()
+        .fold(Vec3::splat(f32::INFINITY), |acc, p| acc.min(*p));
+    let max = points
+        .iter()
+        .fold(Vec3::splat(f32::NEG_INFINITY), |acc, p| acc.max(*p));
+    Aabb {
+        min: min.to_array(),
+        max: max.to_array(),
+    }
+}
+
+fn mat4_to_truck(m: Mat4) -> Matrix4 {
+    let c = m.to_cols_array().map(|v| v as f64);
+    Matrix4::new(
+        c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7], c[8], c[9], c[10], c[11], c[12], c[13],
+        c[14], c[15],
     )
-    .normalize();
-    Mat4::from_translation(t) * Mat4::from_quat(q)
 }
 
 fn mesh_bounds_aabb(mesh: &TriMesh) -> Aabb {
@@ -392,3 +1700,267 @@ fn ray_triangle_intersect(ray_o: Vec3, ray_d: Vec3, v0: Vec3, v1: Vec3, v2: Vec3
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_3, FRAC_PI_4};
+
+    #[test]
+    fn box_edges_are_shaded_by_the_normal_mode() {
+        let solid = make_box(2.0, 2.0, 2.0).unwrap();
+        let shaded = |mode| {
+            let mut mesh = tessellate_solid(&solid, 0.01);
+            mesh.recompute_normals(mode);
+            mesh
+        };
+        // Every vertex of a box tessellation sits at one of its corners.
+        let corners = |mesh: &TriMesh| -> Vec<(Vec3, Vec3)> {
+            mesh.positions
+                .iter()
+                .zip(&mesh.normals)
+                .map(|(p, n)| (Vec3::from_array(*p), Vec3::from_array(*n)))
+                .filter(|(p, _)| p.abs().min_element() > 0.999)
+                .collect()
+        };
+
+        // The faces meet at 90 degrees, so a smaller crease angle keeps
+        // every corner on its own face's normal, as flat shading does.
+        for mode in [NormalMode::Flat, NormalMode::Crease(FRAC_PI_4)] {
+            let mesh = shaded(mode);
+            assert_eq!(corners(&mesh).len(), mesh.positions.len(), "{mode:?}");
+            for (p, n) in corners(&mesh) {
+                assert!(n.abs().max_element() > 0.999, "{mode:?}: {n}");
+                assert!((p.dot(n) - 1.0).abs() < 1.0e-4, "{mode:?}: {p} {n}");
+            }
+        }
+        // A larger one, or none, blends the three faces at each corner.
+        for mode in [NormalMode::Smooth, NormalMode::Crease(2.0 * FRAC_PI_3)] {
+            let mesh = shaded(mode);
+            assert_eq!(corners(&mesh).len(), mesh.positions.len(), "{mode:?}");
+            for (p, n) in corners(&mesh) {
+                assert!((n.length() - 1.0).abs() < 1.0e-4, "{mode:?}: {n}");
+                assert!((p * n).min_element() > 0.1, "{mode:?}: {p} {n}");
+            }
+        }
+    }
+
+    #[test]
+    fn scaled_objects_are_bounded_and_picked_as_drawn() {
+        let mut scene = GeomScene::new();
+        let id = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let radius = scene.bounds_radius(id).unwrap();
+        scene.set_object_transform(
+            id,
+            Transform {
+                translation: [5.0, 0.0, 0.0],
+                scale: [2.0, 1.0, 3.0],
+                ..Transform::default()
+            },
+        );
+
+        let aabb = scene.object_world_aabb(id).unwrap();
+        let close = |a: [f32; 3], b: [f32; 3]| Vec3::from(a).abs_diff_eq(Vec3::from(b), 1.0e-4);
+        assert!(close(aabb.min, [4.0, -0.5, -1.5]), "{aabb:?}");
+        assert!(close(aabb.max, [6.0, 0.5, 1.5]), "{aabb:?}");
+        let scene_aabb = scene.world_aabb().unwrap();
+        assert!(close(scene_aabb.min, aabb.min) && close(scene_aabb.max, aabb.max));
+        assert!((scene.bounds_radius(id).unwrap() - 3.0 * radius).abs() < 1.0e-4);
+
+        // Past the unscaled box's x = 5.5 face, on the stretched top face.
+        let hit = scene
+            .pick_surface([5.8, 0.0, 10.0], [0.0, 0.0, -1.0])
+            .unwrap();
+        assert_eq!(hit.object_id, id);
+        assert!(close(hit.point, [5.8, 0.0, 1.5]), "{hit:?}");
+        assert!(close(hit.normal, [0.0, 0.0, 1.0]), "{hit:?}");
+        assert!((hit.distance - 8.5).abs() < 1.0e-4);
+        assert!(scene
+            .pick_surface([6.2, 0.0, 10.0], [0.0, 0.0, -1.0])
+            .is_none());
+    }
+
+    /// A scene of cubes of the given sizes, 10 apart along x.
+    fn cubes_in_a_row(sizes: &[f32]) -> (GeomScene, Vec<ObjectId>) {
+        let mut scene = GeomScene::new();
+        let ids = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| {
+                let id = scene.add_box(size, size, size).unwrap();
+                let at = Transform::from_translation([10.0 * i as f32, 0.0, 0.0]);
+                scene.set_object_transform(id, at);
+                id
+            })
+            .collect();
+        (scene, ids)
+    }
+
+    /// The object seen looking down at `x` on the x axis.
+    fn picked_at(scene: &GeomScene, x: f32) -> Option<ObjectId> {
+        let hit = scene.pick_surface([x, 0.0, 10.0], [0.0, 0.0, -1.0]);
+        hit.map(|hit| hit.object_id)
+    }
+
+    fn ids(scene: &GeomScene) -> Vec<ObjectId> {
+        scene.model().objects().iter().map(|obj| obj.id).collect()
+    }
+
+    #[test]
+    fn removed_objects_take_their_bodies_with_them() {
+        let (mut scene, row) = cubes_in_a_row(&[1.0, 2.0, 3.0]);
+        let single = scene.object_mesh(row[0]).unwrap().positions.len();
+        assert_eq!(scene.mesh().unwrap().positions.len(), 3 * single);
+
+        assert!(scene.remove_object(row[1]));
+        assert!(!scene.remove_object(row[1]));
+        assert_eq!(ids(&scene), [row[0], row[2]]);
+        // The bodies after it moved down with their objects.
+        assert_eq!(scene.local_aabb(row[2]).unwrap().max, [1.5; 3]);
+        assert!(scene.local_aabb(row[1]).is_none());
+        assert_eq!(picked_at(&scene, 20.0), Some(row[2]));
+        assert_eq!(picked_at(&scene, 10.0), None);
+        // The combined mesh was rebuilt without it.
+        assert_eq!(scene.mesh().unwrap().positions.len(), 2 * single);
+        assert_eq!(scene.world_aabb().unwrap().max[0], 21.5);
+
+        assert!(scene.remove_object(row[0]) && scene.remove_object(row[2]));
+        assert!(matches!(scene.mesh(), Err(GeomError::EmptyScene)));
+    }
+
+    #[test]
+    fn duplicates_are_offset_copies_of_their_bodies() {
+        let (mut scene, row) = cubes_in_a_row(&[1.0, 2.0]);
+        let single = scene.object_mesh(row[0]).unwrap().positions.len();
+        assert_eq!(scene.mesh().unwrap().positions.len(), 2 * single);
+
+        let copy = scene
+            .duplicate_with_offset(row[1], [10.0, 0.0, 0.0])
+            .unwrap();
+        assert!(!row.contains(&copy));
+        assert_eq!(ids(&scene), [row[0], row[1], copy]);
+        assert_eq!(
+            scene.object_transform(copy).unwrap().translation,
+            [20.0, 0.0, 0.0]
+        );
+        assert_eq!(scene.local_aabb(copy).unwrap().max, [1.0; 3]);
+        assert_eq!(picked_at(&scene, 10.0), Some(row[1]));
+        assert_eq!(picked_at(&scene, 20.0), Some(copy));
+        assert_eq!(scene.mesh().unwrap().positions.len(), 3 * single);
+        assert_eq!(scene.world_aabb().unwrap().max[0], 21.0);
+
+        assert!(scene.duplicate_with_offset(copy + 1, [0.0; 3]).is_none());
+        assert_eq!(scene.model().objects().len(), 3);
+    }
+
+    #[test]
+    fn updated_primitives_rebuild_only_their_own_body() {
+        let (mut scene, row) = cubes_in_a_row(&[1.0, 1.0]);
+        let shared = scene.object_mesh(row[1]).unwrap();
+        assert_eq!(scene.world_aabb().unwrap().min[0], -0.5);
+        let wide = ObjectKind::Box {
+            w: 3.0,
+            h: 1.0,
+            d: 1.0,
+        };
+        let transform = scene.object_transform(row[0]);
+
+        assert_eq!(picked_at(&scene, 1.2), None);
+        scene.update_primitive(row[0], wide.clone()).unwrap();
+        assert_eq!(ids(&scene), row);
+        assert_eq!(scene.object_transform(row[0]), transform);
+        assert_eq!(scene.model().object(row[0]).unwrap().kind, wide);
+        assert_eq!(scene.local_aabb(row[0]).unwrap().max, [1.5, 0.5, 0.5]);
+        assert_eq!(picked_at(&scene, 1.2), Some(row[0]));
+        assert_eq!(scene.world_aabb().unwrap().min[0], -1.5);
+        // The other cube kept its mesh.
+        assert!(Arc::ptr_eq(&shared, &scene.object_mesh(row[1]).unwrap()));
+        assert_eq!(scene.local_aabb(row[1]).unwrap().max, [0.5; 3]);
+
+        let flat = ObjectKind::Box {
+            w: 1.0,
+            h: 0.0,
+            d: 1.0,
+        };
+        assert!(matches!(
+            scene.update_primitive(row[0], flat),
+            Err(GeomError::InvalidPrimitive(_))
+        ));
+        assert_eq!(scene.model().object(row[0]).unwrap().kind, wide);
+        assert!(matches!(
+            scene.update_primitive(row[1] + 1, wide),
+            Err(GeomError::UnknownObject(_))
+        ));
+    }
+
+    #[test]
+    fn features_replay_until_removed() {
+        let mut scene = GeomScene::new();
+        let id = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let single = scene.mesh().unwrap().indices.len();
+        let pattern = scene
+            .add_feature(
+                id,
+                FeatureOp::LinearPattern {
+                    direction: [1.0, 0.0, 0.0],
+                    spacing: 2.0,
+                    count: 3,
+                },
+            )
+            .unwrap();
+        assert_eq!(scene.mesh().unwrap().indices.len(), 3 * single);
+        assert!(matches!(
+            scene.add_feature(id, FeatureOp::Subtract { tool: id }),
+            Err(GeomError::FeatureFailed { source, .. })
+                if matches!(*source, GeomError::InvalidFeature(_))
+        ));
+        assert_eq!(scene.model().object(id).unwrap().features.len(), 1);
+
+        scene.set_feature_suppressed(id, pattern, true).unwrap();
+        assert_eq!(scene.mesh().unwrap().indices.len(), single);
+        scene.set_feature_suppressed(id, pattern, false).unwrap();
+        assert!(matches!(
+            scene.remove_feature(id, pattern + 1),
+            Err(GeomError::UnknownFeature { object, feature })
+                if object == id && feature == pattern + 1
+        ));
+        assert!(matches!(
+            scene.remove_feature(id + 1, pattern),
+            Err(GeomError::UnknownObject(missing)) if missing == id + 1
+        ));
+        scene.remove_feature(id, pattern).unwrap();
+        assert!(scene.model().object(id).unwrap().features.is_empty());
+        assert_eq!(scene.mesh().unwrap().indices.len(), single);
+    }
+
+    #[test]
+    fn moving_a_tool_regenerates_the_bodies_it_cuts() {
+        let mut scene = GeomScene::new();
+        let body = scene.add_box(2.0, 2.0, 2.0).unwrap();
+        let tool = scene.add_cylinder(0.5, 3.0).unwrap();
+        let other = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let mut cut = scene.model().clone();
+        cut.add_feature(body, FeatureOp::Subtract { tool }).unwrap();
+        // Subtraction waits on a boolean backend, so the body is left as
+        // its box; it is still replayed whenever the cut could change.
+        assert!(scene.apply_delta(&scene.model().diff(&cut)).is_err());
+        assert_eq!(scene.model().object(body).unwrap().features.len(), 1);
+
+        let replayed = scene.object_mesh(body).unwrap();
+        scene.set_object_transform(other, Transform::from_translation([5.0, 0.0, 0.0]));
+        assert!(Arc::ptr_eq(&replayed, &scene.object_mesh(body).unwrap()));
+        scene.set_object_transform(tool, Transform::from_translation([0.5, 0.0, 0.0]));
+        assert!(!Arc::ptr_eq(&replayed, &scene.object_mesh(body).unwrap()));
+
+        // So is a move arriving in a delta.
+        let mut remote = GeomScene::new();
+        assert!(remote
+            .apply_delta(&Model::default().diff(scene.model()))
+            .is_err());
+        let replayed = remote.object_mesh(body).unwrap();
+        let mut back = remote.model().clone();
+        back.set_transform(tool, Transform::default());
+        remote.apply_delta(&remote.model().diff(&back)).unwrap();
+        assert!(!Arc::ptr_eq(&replayed, &remote.object_mesh(body).unwrap()));
+    }
+}
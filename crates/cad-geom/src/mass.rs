@@ -0,0 +1,107 @@
+//! Mass properties of closed meshes, at unit density.
+
+use crate::TriMesh;
+use glam::DVec3;
+
+/// What [`TriMesh::mass_properties`] finds. Lengths are in document units;
+/// at unit density the mass equals the volume.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MassProperties {
+    pub volume: f64,
+    pub surface_area: f64,
+    /// Center of the enclosed volume; the origin when there is none.
+    pub centroid: [f64; 3],
+    /// Inertia tensor about the centroid, row by row.
+    pub inertia: [[f64; 3]; 3],
+}
+
+impl TriMesh {
+    /// Mass properties of the volume the mesh encloses, summed over the
+    /// signed tetrahedra each triangle makes with the origin. Only
+    /// meaningful for watertight, outward-wound meshes; see
+    /// [`TriMesh::validate`].
+    pub fn mass_properties(&self) -> MassProperties {
+        let mut volume = 0.0;
+        let mut surface_area = 0.0;
+        let mut moment = DVec3::ZERO;
+        // Second moments about the origin, sum of x_i x_j dV.
+        let mut second = [[0.0; 3]; 3];
+
+        for tri in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| {
+                let [x, y, z] = self.positions[tri[k] as usize];
+                DVec3::new(x as f64, y as f64, z as f64)
+            });
+            surface_area += (b - a).cross(c - a).length() / 2.0;
+            // Six times the signed volume of the tetrahedron (0, a, b, c).
+            let det = a.dot(b.cross(c));
+            volume += det / 6.0;
+            moment += det / 24.0 * (a + b + c);
+            let [a, b, c, s] = [a, b, c, a + b + c].map(|v| v.to_array());
+            for i in 0..3 {
+                for j in 0..3 {
+                    second[i][j] +=
+                        det / 120.0 * (a[i] * a[j] + b[i] * b[j] + c[i] * c[j] + s[i] * s[j]);
+                }
+            }
+        }
+
+        if volume.abs() <= f64::EPSILON {
+            return MassProperties {
+                surface_area,
+                ..MassProperties::default()
+            };
+        }
+        let centroid = (moment / volume).to_array();
+        // Parallel axis: second moments about the centroid.
+        let about = |i: usize, j: usize| second[i][j] - volume * centroid[i] * centroid[j];
+        let trace = about(0, 0) + about(1, 1) + about(2, 2);
+        let inertia = std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                if i == j {
+                    trace - about(i, i)
+                } else {
+                    0.0 - about(i, j)
+                }
+            })
+        });
+        MassProperties {
+            volume,
+            surface_area,
+            centroid,
+            inertia,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GeomScene;
+    use cad_core::Transform;
+
+    #[test]
+    fn box_mass_properties() {
+        let mut scene = GeomScene::new();
+        let id = scene.add_box(2.0, 3.0, 4.0).unwrap();
+        let transform = Transform {
+            translation: [1.0, -2.0, 5.0],
+            ..Transform::default()
+        };
+        assert!(scene.set_object_transform(id, transform));
+        let props = scene.export_mesh(&[]).unwrap().mass_properties();
+        let close = |a: f64, b: f64| (a - b).abs() < 1.0e-4 * b.abs().max(1.0);
+        assert!(close(props.volume, 24.0));
+        assert!(close(props.surface_area, 52.0));
+        for (got, want) in props.centroid.iter().zip([1.0, -2.0, 5.0]) {
+            assert!(close(*got, want), "centroid {:?}", props.centroid);
+        }
+        // m (b^2 + c^2) / 12 about each axis, no products of inertia.
+        let diagonal = [24.0 * 25.0 / 12.0, 24.0 * 20.0 / 12.0, 24.0 * 13.0 / 12.0];
+        for (i, (row, moment)) in props.inertia.iter().zip(diagonal).enumerate() {
+            for (j, &got) in row.iter().enumerate() {
+                let want = if i == j { moment } else { 0.0 };
+                assert!(close(got, want), "inertia {:?}", props.inertia);
+            }
+        }
+    }
+}
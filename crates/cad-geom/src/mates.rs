@@ -0,0 +1,259 @@
+//! Positioning bodies from assembly mates.
+
+use crate::{GeomError, GeomScene};
+use cad_core::{MateAxis, MateId, MateKind, MatePlane, ObjectId, Transform};
+use glam::{Mat4, Quat, Vec3};
+
+/// Gauss-Seidel passes over the mates before giving up.
+const MATE_ITERATIONS: usize = 32;
+/// Largest error (length or radians) accepted as solved.
+const MATE_TOLERANCE: f32 = 1.0e-5;
+
+/// A rigid move of one body: rotation about `pivot`, then translation.
+struct Correction {
+    rotation: Quat,
+    pivot: Vec3,
+    translation: Vec3,
+    /// How far the mate was from holding before the move.
+    error: f32,
+}
+
+impl GeomScene {
+    /// Adds a mate and re-solves so the moved body snaps into place.
+    pub fn add_mate(&mut self, kind: MateKind) -> Result<MateId, GeomError> {
+        let (a, b) = kind.objects();
+        for id in [a, b] {
+            if self.model.object(id).is_none() {
+                return Err(GeomError::UnknownObject(id));
+            }
+        }
+        if a == b {
+            return Err(GeomError::InvalidFeature(
+                "a body cannot be mated to itself",
+            ));
+        }
+        let id = self.model.add_mate(kind);
+        self.solve_mates()?;
+        Ok(id)
+    }
+
+    pub fn remove_mate(&mut self, id: MateId) -> bool {
+        self.model.remove_mate(id).is_some()
+    }
+
+    /// Moves bodies until every active mate holds, returning the largest
+    /// error left in the last pass (zero-ish when all mates are satisfied).
+    pub fn solve_mates(&mut self) -> Result<f32, GeomError> {
+        let mates: Vec<MateKind> = self
+            .model
+            .mates()
+            .iter()
+            .filter(|mate| !mate.suppressed)
+            .map(|mate| mate.kind)
+            .collect();
+        let mut residual = 0.0;
+        for _ in 0..MATE_ITERATIONS {
+            residual = 0.0_f32;
+            for kind in &mates {
+                let (_, moved) = kind.objects();
+                let correction = self.mate_correction(kind)?;
+                residual = residual.max(correction.error);
+                if correction.error > MATE_TOLERANCE {
+                    self.apply_correction(moved, &correction)?;
+                }
+            }
+            if residual <= MATE_TOLERANCE {
+                break;
+            }
+        }
+        Ok(residual)
+    }
+
+    fn mate_correction(&self, kind: &MateKind) -> Result<Correction, GeomError> {
+        Ok(match *kind {
+            MateKind::Coincident { a, b } => {
+                plane_correction(self.mate_plane(&a)?, self.mate_plane(&b)?, 0.0)
+            }
+            MateKind::Distance { a, b, distance } => {
+                plane_correction(self.mate_plane(&a)?, self.mate_plane(&b)?, distance)
+            }
+            MateKind::Angle { a, b, angle } => {
+                let (_, na) = self.mate_plane(&a)?;
+                let (pb, nb) = self.mate_plane(&b)?;
+                let current = na.dot(nb).clamp(-1.0, 1.0).acos();
+                let axis = na.cross(nb).try_normalize();
+                let axis = axis.unwrap_or_else(|| na.any_orthonormal_vector());
+                Correction {
+                    rotation: Quat::from_axis_angle(axis, angle - current),
+                    pivot: pb,
+                    translation: Vec3::ZERO,
+                    error: (angle - current).abs(),
+                }
+            }
+            MateKind::Concentric { a, b } => {
+                let (pa, da) = self.mate_axis(&a)?;
+                let (pb, db) = self.mate_axis(&b)?;
+                let target = if db.dot(da) >= 0.0 { da } else { -da };
+                let offset = pb - pa;
+                let off_axis = offset - da * offset.dot(da);
+                Correction {
+                    rotation: Quat::from_rotation_arc(db, target),
+                    pivot: pb,
+                    translation: -off_axis,
+                    error: db.angle_between(target).max(off_axis.length()),
+                }
+            }
+        })
+    }
+
+    fn apply_correction(&mut self, id: ObjectId, correction: &Correction) -> Result<(), GeomError> {
+        let obj = self.model.object(id).ok_or(GeomError::UnknownObject(id))?;
+        let transform = obj.transform;
+        // The correction is a world-space move; carry it into the body's
+        // parent space before applying it to the local transform.
+        let parent = self.parent_mat(obj.parent);
+        let world_move = Mat4::from_translation(correction.pivot + correction.translation)
+            * Mat4::from_quat(correction.rotation)
+            * Mat4::from_translation(-correction.pivot);
+        let local_move = parent.inverse() * world_move * parent;
+        let (_, move_rotation, _) = local_move.to_scale_rotation_translation();
+        let rotation = move_rotation * transform.rotation_quat();
+        let translation = local_move.transform_point3(Vec3::from_array(transform.translation));
+        self.set_object_transform(
+            id,
+            Transform {
+                translation: translation.to_array(),
+                rotation: rotation.normalize().to_array(),
+                ..transform
+            },
+        );
+        Ok(())
+    }
+
+    /// World-space point and unit normal of a mate plane.
+    fn mate_plane(&self, plane: &MatePlane) -> Result<(Vec3, Vec3), GeomError> {
+        let obj = self
+            .model
+            .object(plane.object)
+            .ok_or(GeomError::UnknownObject(plane.object))?;
+        let m = self.world_mat(obj);
+        let normal = m
+            .inverse()
+            .transpose()
+            .transform_vector3(Vec3::from_array(plane.normal));
+        Ok((
+            m.transform_point3(Vec3::from_array(plane.origin)),
+            normal
+                .try_normalize()
+                .ok_or(GeomError::InvalidFeature("mate plane needs a normal"))?,
+        ))
+    }
+
+    /// World-space point and unit direction of a mate axis.
+    fn mate_axis(&self, axis: &MateAxis) -> Result<(Vec3, Vec3), GeomError> {
+        let obj = self
+            .model
+            .object(axis.object)
+            .ok_or(GeomError::UnknownObject(axis.object))?;
+        let m = self.world_mat(obj);
+        let direction = m.transform_vector3(Vec3::from_array(axis.direction));
+        Ok((
+            m.transform_point3(Vec3::from_array(axis.origin)),
+            direction
+                .try_normalize()
+                .ok_or(GeomError::InvalidFeature("mate axis needs a direction"))?,
+        ))
+    }
+}
+
+/// Turns plane `b` to face plane `a` and slides it to `gap` in front of it.
+fn plane_correction((pa, na): (Vec3, Vec3), (pb, nb): (Vec3, Vec3), gap: f32) -> Correction {
+    let offset = (pb - pa).dot(na);
+    Correction {
+        rotation: Quat::from_rotation_arc(nb, -na),
+        pivot: pb,
+        translation: na * (gap - offset),
+        error: nb.angle_between(-na).max((gap - offset).abs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stacks_and_aligns_bodies() {
+        let mut scene = GeomScene::new();
+        let base = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let block = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let pin = scene.add_cylinder(0.1, 2.0).unwrap();
+        let tilted = Transform {
+            translation: [3.0, -2.0, 1.0],
+            rotation: Quat::from_rotation_z(0.7).to_array(),
+            ..Transform::default()
+        };
+        scene.set_object_transform(block, tilted);
+        scene.set_object_transform(pin, tilted);
+
+        let top = MatePlane {
+            object: base,
+            origin: [0.0, 0.5, 0.0],
+            normal: [0.0, 1.0, 0.0],
+        };
+        let bottom = MatePlane {
+            object: block,
+            origin: [0.0, -0.5, 0.0],
+            normal: [0.0, -1.0, 0.0],
+        };
+        scene
+            .add_mate(MateKind::Distance {
+                a: top,
+                b: bottom,
+                distance: 0.25,
+            })
+            .unwrap();
+        let axis = |object| MateAxis {
+            object,
+            origin: [0.0, 0.0, 0.0],
+            direction: [0.0, 1.0, 0.0],
+        };
+        scene
+            .add_mate(MateKind::Concentric {
+                a: axis(base),
+                b: axis(pin),
+            })
+            .unwrap();
+        assert!(scene.solve_mates().unwrap() <= MATE_TOLERANCE);
+
+        let (p, n) = scene.mate_plane(&bottom).unwrap();
+        assert!((p.y - 0.75).abs() < 1.0e-4 && (n.y + 1.0).abs() < 1.0e-4);
+        let (p, d) = scene.mate_axis(&axis(pin)).unwrap();
+        assert!(p.x.abs() < 1.0e-4 && p.z.abs() < 1.0e-4 && d.y.abs() > 0.9999);
+    }
+
+    #[test]
+    fn removal_detaches_and_restores_mates() {
+        let mut scene = GeomScene::new();
+        let base = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let block = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let plane = |object, y: f32| MatePlane {
+            object,
+            origin: [0.0, y, 0.0],
+            normal: [0.0, y.signum(), 0.0],
+        };
+        scene
+            .add_mate(MateKind::Coincident {
+                a: plane(base, 0.5),
+                b: plane(block, -0.5),
+            })
+            .unwrap();
+
+        let removed = scene.take_object(base).unwrap();
+        assert_eq!(removed.mates.len(), 1);
+        assert!(scene.model().mates().is_empty());
+        scene.restore_object(removed).unwrap();
+        assert_eq!(scene.model().objects()[0].id, base);
+        assert_eq!(scene.model().mates().len(), 1);
+        assert!(scene.solve_mates().unwrap() <= MATE_TOLERANCE);
+    }
+}
@@ -0,0 +1,129 @@
+//! Evaluating measurement annotations against the current geometry.
+
+use crate::{GeomScene, SurfaceHit};
+use cad_core::{Anchor, AnnotationId, Measurement, ObjectKind};
+use glam::Vec3;
+
+impl GeomScene {
+    pub fn add_annotation(&mut self, measurement: Measurement) -> AnnotationId {
+        self.model.add_annotation(measurement)
+    }
+
+    pub fn remove_annotation(&mut self, id: AnnotationId) -> bool {
+        self.model.remove_annotation(id).is_some()
+    }
+
+    /// Anchors a pick hit to the body it landed on.
+    pub fn anchor_at(&self, hit: &SurfaceHit) -> Anchor {
+        match self.model.object(hit.object_id) {
+            Some(obj) => Anchor::Object {
+                object: obj.id,
+                local: self
+                    .world_mat(obj)
+                    .inverse()
+                    .transform_point3(Vec3::from_array(hit.point))
+                    .to_array(),
+            },
+            None => Anchor::World(hit.point),
+        }
+    }
+
+    /// World position of an anchor, or `None` if its body is gone.
+    pub fn anchor_position(&self, anchor: &Anchor) -> Option<[f32; 3]> {
+        match *anchor {
+            Anchor::World(point) => Some(point),
+            Anchor::Object { object, local } => {
+                let obj = self.model.object(object)?;
+                Some(
+                    self.world_mat(obj)
+                        .transform_point3(Vec3::from_array(local))
+                        .to_array(),
+                )
+            }
+        }
+    }
+
+    /// Current value of a measurement: a length in document units or an
+    /// angle in radians. `None` when a referenced body no longer exists or
+    /// has no radius.
+    pub fn measure(&self, measurement: &Measurement) -> Option<f32> {
+        let position = |anchor| self.anchor_position(anchor).map(Vec3::from_array);
+        match measurement {
+            Measurement::Distance { a, b } => Some(position(a)?.distance(position(b)?)),
+            Measurement::Angle { vertex, a, b } => {
+                let vertex = position(vertex)?;
+                let to_a = (position(a)? - vertex).normalize_or_zero();
+                let to_b = (position(b)? - vertex).normalize_or_zero();
+                Some(to_a.dot(to_b).clamp(-1.0, 1.0).acos())
+            }
+            Measurement::Radius { object } => {
+                let obj = self.model.object(*object)?;
+                let radius = match obj.kind {
+                    ObjectKind::Cylinder { r, .. } | ObjectKind::Prism { r, .. } => r,
+                    ObjectKind::Tube { outer_r, .. } => outer_r,
+                    ObjectKind::Box { .. }
+                    | ObjectKind::Wedge { .. }
+                    | ObjectKind::Mesh { .. }
+                    | ObjectKind::Derived { .. }
+                    | ObjectKind::Reference { .. } => return None,
+                };
+                // Round bodies run along Y, so X and Z scale the radius.
+                let world = self.world_mat(obj);
+                Some(
+                    radius
+                        * world
+                            .x_axis
+                            .truncate()
+                            .length()
+                            .max(world.z_axis.truncate().length()),
+                )
+            }
+        }
+    }
+
+    pub fn annotation_value(&self, id: AnnotationId) -> Option<f32> {
+        self.measure(&self.model.annotation(id)?.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cad_core::Transform;
+
+    #[test]
+    fn measurements_follow_edits() {
+        let mut scene = GeomScene::new();
+        let block = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let rod = scene.add_cylinder(0.5, 2.0).unwrap();
+        let aside = Transform {
+            translation: [5.0, 0.0, 0.0],
+            ..Transform::default()
+        };
+        scene.set_object_transform(rod, aside);
+        let top = scene
+            .pick_surface([0.0, 5.0, 0.0], [0.0, -1.0, 0.0])
+            .unwrap();
+        let distance = scene.add_annotation(Measurement::Distance {
+            a: Anchor::World([0.0, 0.0, 0.0]),
+            b: scene.anchor_at(&top),
+        });
+        let radius = scene.add_annotation(Measurement::Radius { object: rod });
+        assert!((scene.annotation_value(distance).unwrap() - 0.5).abs() < 1.0e-4);
+
+        let lifted = Transform {
+            translation: [0.0, 2.0, 0.0],
+            ..Transform::default()
+        };
+        scene.set_object_transform(block, lifted);
+        scene
+            .update_primitive(rod, ObjectKind::Cylinder { r: 0.75, h: 2.0 })
+            .unwrap();
+        assert!((scene.annotation_value(distance).unwrap() - 2.5).abs() < 1.0e-4);
+        assert_eq!(scene.annotation_value(radius), Some(0.75));
+
+        scene.remove_object(block);
+        scene.remove_object(rod);
+        assert_eq!(scene.annotation_value(radius), None);
+    }
+}
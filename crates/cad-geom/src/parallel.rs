@@ -0,0 +1,123 @@
+//! Spreading per-object work across threads (native) or event-loop turns
+//! (wasm, where the main thread must stay responsive).
+
+use crate::TriMesh;
+use cad_core::Appearance;
+use glam::Mat4;
+use std::sync::Arc;
+
+/// Maps `f` over `0..len`, on the rayon pool where threads are available.
+pub(crate) fn par_map<T: Send>(len: usize, f: impl Fn(usize) -> T + Sync + Send) -> Vec<T> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use rayon::prelude::*;
+        (0..len).into_par_iter().map(f).collect()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        (0..len).map(f).collect()
+    }
+}
+
+/// Lets the browser handle input and paint a frame before continuing.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn yield_now() {
+    use wasm_bindgen::{JsCast, JsValue};
+
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let set_timeout = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))
+            .ok()
+            .and_then(|f| f.dyn_into::<js_sys::Function>().ok());
+        let _ = match set_timeout {
+            Some(set_timeout) => set_timeout.call2(&JsValue::NULL, &resolve, &JsValue::from(0)),
+            None => resolve.call0(&JsValue::NULL),
+        };
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// The visible bodies of a scene, placed and colored, from
+/// [`crate::GeomScene::mesh_parts`]. Cheap to take, since the bodies'
+/// meshes are shared, and combined without borrowing the scene.
+pub struct MeshParts(pub(crate) Vec<(Arc<TriMesh>, Mat4, Appearance)>);
+
+impl MeshParts {
+    /// The combined mesh, as [`crate::GeomScene::mesh`] returns it.
+    pub fn mesh(&self) -> TriMesh {
+        let parts = par_map(self.0.len(), |idx| self.part(idx));
+        let mut combined = TriMesh::default();
+        for part in parts {
+            combined.append(part);
+        }
+        combined
+    }
+
+    /// Like [`MeshParts::mesh`], but on wasm builds one body at a time,
+    /// yielding to the browser in between, so large scenes do not freeze the
+    /// page.
+    pub async fn mesh_async(&self) -> TriMesh {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.mesh()
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let mut combined = TriMesh::default();
+            for idx in 0..self.0.len() {
+                combined.append(self.part(idx));
+                yield_now().await;
+            }
+            combined
+        }
+    }
+
+    fn part(&self, idx: usize) -> TriMesh {
+        let (mesh, transform, appearance) = &self.0[idx];
+        let mut part = TriMesh::default();
+        part.append_transformed(mesh, *transform);
+        part.apply_appearance(appearance);
+        part
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{GeomScene, TriMesh};
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// Polls `future` until it is done; off wasm nothing in it ever waits.
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+                return value;
+            }
+        }
+    }
+
+    fn assert_same(a: &TriMesh, b: &TriMesh) {
+        assert_eq!(a.positions, b.positions);
+        assert_eq!(a.normals, b.normals);
+        assert_eq!(a.indices, b.indices);
+        assert_eq!(a.colors, b.colors);
+    }
+
+    #[test]
+    fn async_meshing_matches_blocking_meshing() {
+        let mut scene = GeomScene::new();
+        scene.add_box(1.0, 2.0, 3.0).unwrap();
+        let hidden = scene.add_cylinder(0.5, 1.0).unwrap();
+        scene.add_tube(1.0, 0.5, 2.0).unwrap();
+        scene.set_object_visible(hidden, false);
+        let parts = scene.mesh_parts().unwrap();
+        let mesh = scene.mesh().unwrap();
+        assert_same(&block_on(parts.mesh_async()), &mesh);
+
+        let mut opened = block_on(GeomScene::from_model_async(scene.model().clone())).unwrap();
+        assert_same(&block_on(opened.mesh_async()).unwrap(), &mesh);
+        assert!(block_on(GeomScene::new().mesh_async()).is_err());
+    }
+}
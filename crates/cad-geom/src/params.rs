@@ -0,0 +1,93 @@
+//! Parameter edits that regenerate the bodies they drive.
+
+use crate::{GeomError, GeomScene};
+use cad_core::{ObjectId, Parameter};
+
+impl GeomScene {
+    /// Defines or redefines a parameter and regenerates every body whose
+    /// bound dimensions changed with it.
+    pub fn set_parameter(
+        &mut self,
+        name: impl Into<String>,
+        expression: impl Into<String>,
+    ) -> Result<(), GeomError> {
+        let changed = self.model.set_parameter(name, expression)?;
+        self.regenerate_changed(&changed)
+    }
+
+    pub fn remove_parameter(&mut self, name: &str) -> Result<Parameter, GeomError> {
+        Ok(self.model.remove_parameter(name)?)
+    }
+
+    /// Drives a dimension of a body by an expression and regenerates it.
+    pub fn bind_dimension(
+        &mut self,
+        id: ObjectId,
+        dimension: &str,
+        expression: impl Into<String>,
+    ) -> Result<(), GeomError> {
+        if self.model.bind_dimension(id, dimension, expression)? {
+            self.regenerate(id)?;
+        }
+        Ok(())
+    }
+
+    pub fn unbind_dimension(&mut self, id: ObjectId, dimension: &str) -> Option<String> {
+        self.model.unbind_dimension(id, dimension)
+    }
+
+    /// Regenerates all of `ids`, returning the first failure.
+    fn regenerate_changed(&mut self, ids: &[ObjectId]) -> Result<(), GeomError> {
+        let mut first = Ok(());
+        for &id in ids {
+            let result = self.regenerate(id);
+            if first.is_ok() {
+                first = result;
+            }
+        }
+        first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cad_core::{ObjectKind, ParamError};
+
+    #[test]
+    fn parameters_drive_dimensions() {
+        let mut scene = GeomScene::new();
+        let plate = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        scene.set_parameter("width", "40").unwrap();
+        scene.set_parameter("height", "width / 2 - 2^2").unwrap();
+        assert_eq!(scene.model().parameter_value("height"), Ok(16.0));
+        scene.bind_dimension(plate, "w", "width").unwrap();
+        scene.bind_dimension(plate, "h", "height").unwrap();
+
+        scene.set_parameter("width", "60").unwrap();
+        assert_eq!(
+            scene.model().object(plate).unwrap().kind,
+            ObjectKind::Box {
+                w: 60.0,
+                h: 26.0,
+                d: 1.0
+            }
+        );
+        let radius = scene.bounds_radius[0];
+        assert!(radius > 30.0, "{radius}");
+
+        assert!(matches!(
+            scene.set_parameter("width", "height"),
+            Err(GeomError::Param(ParamError::Cycle(name))) if name == "width"
+        ));
+        assert!(matches!(
+            scene.set_parameter("width", "8"),
+            Err(GeomError::Param(ParamError::InvalidDimension(..)))
+        ));
+        assert_eq!(scene.model().parameter_value("width"), Ok(60.0));
+        assert!(matches!(
+            scene.remove_parameter("height"),
+            Err(GeomError::Param(ParamError::InUse(_)))
+        ));
+    }
+}
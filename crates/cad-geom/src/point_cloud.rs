@@ -0,0 +1,524 @@
+//! Point cloud import (XYZ, PLY) and primitive fitting for scanned data.
+
+use crate::bounds::{covariance, symmetric_eigen};
+use crate::{GeomError, GeomScene};
+use cad_core::PointCloudId;
+use glam::{DMat3, DVec3, Vec3};
+
+/// Points (and optional colors) read from a scan file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanPoints {
+    pub points: Vec<[f32; 3]>,
+    /// Either empty or parallel to `points`.
+    pub colors: Vec<[u8; 3]>,
+}
+
+/// Best-fit plane through a set of points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaneFit {
+    pub origin: [f32; 3],
+    pub normal: [f32; 3],
+    /// Root-mean-square distance of the points from the plane.
+    pub rms: f32,
+}
+
+/// Best-fit cylinder through a set of points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CylinderFit {
+    /// Point on the axis, at the middle of the points along it.
+    pub axis_origin: [f32; 3],
+    pub axis: [f32; 3],
+    pub radius: f32,
+    /// Root-mean-square distance of the points from the surface.
+    pub rms: f32,
+}
+
+/// Parses whitespace- or comma-separated `x y z [r g b]` lines. Colors may be
+/// 0-255 integers or 0-1 floats; `#` and `//` start comments.
+pub fn parse_xyz(text: &str) -> Result<ScanPoints, GeomError> {
+    let mut scan = ScanPoints::default();
+    let mut raw_colors = Vec::new();
+    let mut colored = true;
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        let values = line
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .filter(|field| !field.is_empty())
+            .map(str::parse::<f32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| GeomError::Import(format!("line {}: {err}", line_no + 1)))?;
+        let [x, y, z, ref rest @ ..] = values[..] else {
+            return Err(GeomError::Import(format!(
+                "line {}: expected x y z",
+                line_no + 1
+            )));
+        };
+        scan.points.push([x, y, z]);
+        match rest {
+            [r, g, b, ..] if colored => raw_colors.push([*r, *g, *b]),
+            _ => colored = false,
+        }
+    }
+    if colored {
+        let unit = raw_colors.iter().flatten().all(|c| *c <= 1.0);
+        scan.colors = raw_colors.into_iter().map(|rgb| color(rgb, unit)).collect();
+    }
+    Ok(scan)
+}
+
+/// Parses the vertex element of an ASCII or binary PLY file.
+pub fn parse_ply(bytes: &[u8]) -> Result<ScanPoints, GeomError> {
+    let err = |msg: &str| GeomError::Import(format!("ply: {msg}"));
+    let header_end = find(bytes, b"end_header").ok_or_else(|| err("missing end_header"))?;
+    let body_start = bytes[header_end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|n| header_end + n + 1)
+        .ok_or_else(|| err("truncated header"))?;
+    let header =
+        std::str::from_utf8(&bytes[..header_end]).map_err(|_| err("header is not text"))?;
+
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(err("not a PLY file"));
+    }
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            ["format", kind, _] => {
+                format = Some(match kind {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::Little,
+                    "binary_big_endian" => PlyFormat::Big,
+                    _ => return Err(err("unknown format")),
+                })
+            }
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| err("bad element count"))?,
+                props: Vec::new(),
+            }),
+            ["property", "list", ..] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| err("property before element"))?;
+                element.props.push(None);
+            }
+            ["property", ty, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| err("property before element"))?;
+                let ty = PlyType::parse(ty).ok_or_else(|| err("unknown property type"))?;
+                element.props.push(Some((ty, name.to_string())));
+            }
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| err("missing format"))?;
+
+    let mut reader = PlyReader {
+        format,
+        bytes: &bytes[body_start..],
+        pos: 0,
+        tokens: Vec::new(),
+    };
+    for element in &elements {
+        let is_vertex = element.name == "vertex";
+        let has_lists = element.props.iter().any(Option::is_none);
+        if !is_vertex && has_lists && format != PlyFormat::Ascii {
+            return Err(err("cannot skip list properties before vertices"));
+        }
+        if is_vertex && has_lists {
+            return Err(err("list properties on vertices are not supported"));
+        }
+        let index = |name: &str| {
+            element
+                .props
+                .iter()
+                .position(|prop| prop.as_ref().is_some_and(|(_, n)| n == name))
+        };
+        let xyz = [index("x"), index("y"), index("z")];
+        let rgb = [index("red"), index("green"), index("blue")];
+        let unit_rgb = rgb[0]
+            .and_then(|i| element.props[i].as_ref())
+            .is_some_and(|(ty, _)| matches!(ty, PlyType::F32 | PlyType::F64));
+        if is_vertex && xyz.iter().any(Option::is_none) {
+            return Err(err("vertices need x, y and z"));
+        }
+
+        let mut scan = ScanPoints::default();
+        let mut values = vec![0.0f64; element.props.len()];
+        for _ in 0..element.count {
+            if format == PlyFormat::Ascii {
+                reader.next_line()?;
+                if !is_vertex {
+                    continue;
+                }
+            }
+            for (value, prop) in values.iter_mut().zip(&element.props) {
+                // Lists only occur (and are skipped with the line) in ASCII.
+                if let Some((ty, _)) = prop {
+                    *value = reader.read(*ty)?;
+                }
+            }
+            if !is_vertex {
+                continue;
+            }
+            let at = |i: Option<usize>| i.map(|i| values[i]);
+            let [Some(x), Some(y), Some(z)] = xyz.map(at) else {
+                unreachable!("checked above")
+            };
+            scan.points.push([x as f32, y as f32, z as f32]);
+            if let [Some(r), Some(g), Some(b)] = rgb.map(at) {
+                scan.colors
+                    .push(color([r as f32, g as f32, b as f32], unit_rgb));
+            }
+        }
+        if is_vertex {
+            return Ok(scan);
+        }
+    }
+    Err(err("no vertex element"))
+}
+
+/// Least-squares plane through the points; `None` for fewer than three.
+pub fn fit_plane(points: &[[f32; 3]]) -> Option<PlaneFit> {
+    if points.len() < 3 {
+        return None;
+    }
+    let pts: Vec<Vec3> = points.iter().map(|p| Vec3::from_array(*p)).collect();
+    let (mean, cov) = covariance(&pts);
+    let [_, _, (_, normal)] = symmetric_eigen(cov);
+    if normal == Vec3::ZERO {
+        return None;
+    }
+    let sq: f32 = pts.iter().map(|p| (*p - mean).dot(normal).powi(2)).sum();
+    Some(PlaneFit {
+        origin: mean.to_array(),
+        normal: normal.to_array(),
+        rms: (sq / pts.len() as f32).sqrt(),
+    })
+}
+
+/// Least-squares cylinder through the points; `None` for fewer than six or
+/// degenerate input.
+///
+/// The axis starts from the principal directions of the points and is
+/// refined by a shrinking local search, with a circle fit across it at each
+/// step; works best when the scan covers a good part of the circumference.
+pub fn fit_cylinder(points: &[[f32; 3]]) -> Option<CylinderFit> {
+    if points.len() < 6 {
+        return None;
+    }
+    let pts: Vec<DVec3> = points
+        .iter()
+        .map(|p| Vec3::from_array(*p).as_dvec3())
+        .collect();
+    let (_, cov) = covariance(&pts.iter().map(|p| p.as_vec3()).collect::<Vec<_>>());
+
+    let mut best: Option<(DVec3, CircleFit)> = None;
+    for (_, dir) in symmetric_eigen(cov) {
+        let axis = dir.as_dvec3();
+        if let Some(fit) = circle_across(&pts, axis) {
+            if best.as_ref().is_none_or(|(_, b)| fit.rms < b.rms) {
+                best = Some((axis, fit));
+            }
+        }
+    }
+    let (mut axis, mut fit) = best?;
+
+    let mut step = 0.2f64;
+    while step > 1.0e-5 {
+        let (u, v) = basis(axis);
+        let improved = [u, -u, v, -v].into_iter().find_map(|dir| {
+            let candidate = (axis + dir * step.tan()).normalize();
+            circle_across(&pts, candidate)
+                .filter(|next| next.rms < fit.rms)
+                .map(|next| (candidate, next))
+        });
+        match improved {
+            Some((next_axis, next_fit)) => (axis, fit) = (next_axis, next_fit),
+            None => step *= 0.5,
+        }
+    }
+
+    Some(CylinderFit {
+        axis_origin: fit.center.as_vec3().to_array(),
+        axis: axis.as_vec3().to_array(),
+        radius: fit.radius as f32,
+        rms: fit.rms as f32,
+    })
+}
+
+impl GeomScene {
+    pub fn add_point_cloud(&mut self, name: impl Into<String>, scan: ScanPoints) -> PointCloudId {
+        self.model.add_point_cloud(name, scan.points, scan.colors)
+    }
+
+    pub fn remove_point_cloud(&mut self, id: PointCloudId) -> bool {
+        self.model.remove_point_cloud(id).is_some()
+    }
+
+    /// World-space points of a cloud.
+    pub fn point_cloud_positions(&self, id: PointCloudId) -> Option<Vec<[f32; 3]>> {
+        let cloud = self.model.point_cloud(id)?;
+        let transform = cloud.transform.to_mat4();
+        Some(
+            cloud
+                .points
+                .iter()
+                .map(|p| transform.transform_point3(Vec3::from_array(*p)).to_array())
+                .collect(),
+        )
+    }
+
+    /// Small three-axis crosses at every point, for drawing a cloud with
+    /// line overlays.
+    pub fn point_cloud_markers(&self, id: PointCloudId, size: f32) -> Vec<[[f32; 3]; 2]> {
+        let half = size * 0.5;
+        self.point_cloud_positions(id)
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|p| {
+                let p = Vec3::from_array(p);
+                [Vec3::X, Vec3::Y, Vec3::Z]
+                    .map(|axis| [(p - axis * half).to_array(), (p + axis * half).to_array()])
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CircleFit {
+    center: DVec3,
+    radius: f64,
+    rms: f64,
+}
+
+/// Algebraic (Kasa) circle fit of the points projected across `axis`.
+fn circle_across(points: &[DVec3], axis: DVec3) -> Option<CircleFit> {
+    let (u, v) = basis(axis);
+    let mean = points.iter().copied().sum::<DVec3>() / points.len() as f64;
+    let flat: Vec<(f64, f64)> = points
+        .iter()
+        .map(|p| ((*p - mean).dot(u), (*p - mean).dot(v)))
+        .collect();
+
+    // Minimise sum (x^2 + y^2 + D x + E y + F)^2 via the normal equations.
+    let mut ata = DMat3::ZERO;
+    let mut atb = DVec3::ZERO;
+    for &(x, y) in &flat {
+        let row = DVec3::new(x, y, 1.0);
+        ata += DMat3::from_cols(row * row.x, row * row.y, row * row.z);
+        atb -= row * (x * x + y * y);
+    }
+    if ata.determinant().abs() < 1.0e-12 {
+        return None;
+    }
+    let DVec3 { x: d, y: e, z: f } = ata.inverse() * atb;
+    let (cx, cy) = (-d / 2.0, -e / 2.0);
+    let r_sq = cx * cx + cy * cy - f;
+    if r_sq <= 0.0 || !r_sq.is_finite() {
+        return None;
+    }
+    let radius = r_sq.sqrt();
+    let sq: f64 = flat
+        .iter()
+        .map(|&(x, y)| ((x - cx).hypot(y - cy) - radius).powi(2))
+        .sum();
+    Some(CircleFit {
+        center: mean + u * cx + v * cy,
+        radius,
+        rms: (sq / flat.len() as f64).sqrt(),
+    })
+}
+
+fn basis(axis: DVec3) -> (DVec3, DVec3) {
+    let u = axis.any_orthonormal_vector();
+    (u, axis.cross(u))
+}
+
+/// Converts a color given as 0-255 values, or 0-1 values if `unit`.
+fn color(rgb: [f32; 3], unit: bool) -> [u8; 3] {
+    rgb.map(|c| {
+        let c = if unit { c * 255.0 } else { c };
+        c.round().clamp(0.0, 255.0) as u8
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyFormat {
+    Ascii,
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    /// Scalar properties; `None` marks a list property.
+    props: Vec<Option<(PlyType, String)>>,
+}
+
+struct PlyReader<'a> {
+    format: PlyFormat,
+    bytes: &'a [u8],
+    pos: usize,
+    /// Remaining fields of the current ASCII line, reversed.
+    tokens: Vec<&'a str>,
+}
+
+impl<'a> PlyReader<'a> {
+    fn next_line(&mut self) -> Result<(), GeomError> {
+        let rest = &self.bytes[self.pos.min(self.bytes.len())..];
+        if rest.is_empty() {
+            return Err(GeomError::Import("ply: unexpected end of data".into()));
+        }
+        let len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        let line = std::str::from_utf8(&rest[..len])
+            .map_err(|_| GeomError::Import("ply: data is not text".into()))?;
+        self.pos += len + 1;
+        self.tokens = line.split_whitespace().rev().collect();
+        Ok(())
+    }
+
+    fn read(&mut self, ty: PlyType) -> Result<f64, GeomError> {
+        let truncated = || GeomError::Import("ply: unexpected end of data".into());
+        if self.format == PlyFormat::Ascii {
+            let token = self.tokens.pop().ok_or_else(truncated)?;
+            return token
+                .parse()
+                .map_err(|_| GeomError::Import(format!("ply: bad number {token:?}")));
+        }
+        let size = ty.size();
+        let raw = self
+            .bytes
+            .get(self.pos..self.pos + size)
+            .ok_or_else(truncated)?;
+        self.pos += size;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(raw);
+        if self.format == PlyFormat::Big {
+            buf[..size].reverse();
+        }
+        Ok(match ty {
+            PlyType::I8 => buf[0] as i8 as f64,
+            PlyType::U8 => buf[0] as f64,
+            PlyType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            PlyType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            PlyType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyType::F64 => f64::from_le_bytes(buf),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_xyz_and_ascii_ply() {
+        let xyz = parse_xyz("# scan\n0 0 0 255 0 0\n1,2,3,0,128,255\n").unwrap();
+        assert_eq!(xyz.points, vec![[0.0, 0.0, 0.0], [1.0, 2.0, 3.0]]);
+        assert_eq!(xyz.colors, vec![[255, 0, 0], [0, 128, 255]]);
+
+        let ply = "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\n\
+                   property float y\nproperty float z\nelement face 0\n\
+                   property list uchar int vertex_indices\nend_header\n1 2 3\n4 5 6\n";
+        let scan = parse_ply(ply.as_bytes()).unwrap();
+        assert_eq!(scan.points, vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+        assert!(scan.colors.is_empty());
+
+        let mut binary = b"ply\nformat binary_little_endian 1.0\nelement vertex 1\n\
+                           property double x\nproperty double y\nproperty double z\n\
+                           property uchar red\nproperty uchar green\nproperty uchar blue\nend_header\n"
+            .to_vec();
+        for c in [1.5f64, -2.0, 0.25] {
+            binary.extend(c.to_le_bytes());
+        }
+        binary.extend([10u8, 20, 30]);
+        let scan = parse_ply(&binary).unwrap();
+        assert_eq!(scan.points, vec![[1.5, -2.0, 0.25]]);
+        assert_eq!(scan.colors, vec![[10, 20, 30]]);
+    }
+
+    #[test]
+    fn fits_plane_and_cylinder() {
+        let plane: Vec<[f32; 3]> = (0..100)
+            .map(|i| {
+                let (x, y) = ((i % 10) as f32, (i / 10) as f32);
+                [x, y, 0.5 * x + 2.0]
+            })
+            .collect();
+        let fit = fit_plane(&plane).unwrap();
+        let expected = Vec3::new(-0.5, 0.0, 1.0).normalize();
+        assert!(Vec3::from_array(fit.normal).dot(expected).abs() > 0.9999);
+        assert!(fit.rms < 1.0e-4);
+
+        // Half a cylinder of radius 2 around a tilted axis through (1, 2, 3).
+        let axis = Vec3::new(0.3, 0.2, 1.0).normalize();
+        let u = axis.any_orthonormal_vector();
+        let v = axis.cross(u);
+        let cylinder: Vec<[f32; 3]> = (0..200)
+            .map(|i| {
+                let angle = (i % 20) as f32 / 19.0 * std::f32::consts::PI;
+                let t = (i / 20) as f32 * 0.5;
+                let p =
+                    Vec3::new(1.0, 2.0, 3.0) + axis * t + (u * angle.cos() + v * angle.sin()) * 2.0;
+                p.to_array()
+            })
+            .collect();
+        let fit = fit_cylinder(&cylinder).unwrap();
+        assert!(Vec3::from_array(fit.axis).dot(axis).abs() > 0.999);
+        assert!((fit.radius - 2.0).abs() < 0.01);
+        assert!(fit.rms < 0.01);
+    }
+}
@@ -0,0 +1,154 @@
+//! Small pictures of a scene drawn on the CPU, for previews made where there
+//! is no GPU, such as document thumbnails on the server.
+
+use crate::{GeomScene, TriMesh};
+use glam::{Mat4, Vec3};
+
+/// Body color, sRGB.
+const BODY_COLOR: [f32; 3] = [0.62, 0.68, 0.76];
+/// Samples per pixel along each axis, averaged to smooth edges.
+const SUPERSAMPLE: u32 = 2;
+/// Empty border around the bodies, as a fraction of the picture.
+const MARGIN: f32 = 0.06;
+
+impl GeomScene {
+    /// PNG of every visible body seen from the isometric corner `(1, 1, 1)`,
+    /// Y up, flat shaded and fitted into a `size`-pixel square on a
+    /// transparent background. A scene with no bodies gives a blank picture.
+    pub fn render_preview(&self, size: u32) -> Vec<u8> {
+        let size = size.max(1);
+        let samples = size * SUPERSAMPLE;
+        let mut color = vec![[0.0f32; 4]; (samples * samples) as usize];
+        if let Ok(mesh) = self.export_mesh(&[]) {
+            rasterize(&mesh, samples, &mut color);
+        }
+
+        // Average each pixel's samples and undo the premultiplication.
+        let mut rgba = Vec::with_capacity((size * size * 4) as usize);
+        let weight = 1.0 / (SUPERSAMPLE * SUPERSAMPLE) as f32;
+        for y in 0..size {
+            for x in 0..size {
+                let mut sum = [0.0f32; 4];
+                for sy in 0..SUPERSAMPLE {
+                    for sx in 0..SUPERSAMPLE {
+                        let i = (y * SUPERSAMPLE + sy) * samples + x * SUPERSAMPLE + sx;
+                        for (s, c) in sum.iter_mut().zip(color[i as usize]) {
+                            *s += c * weight;
+                        }
+                    }
+                }
+                let alpha = sum[3];
+                for c in &sum[..3] {
+                    let straight = if alpha > 0.0 { c / alpha } else { 0.0 };
+                    rgba.push((straight.clamp(0.0, 1.0) * 255.0).round() as u8);
+                }
+                rgba.push((alpha.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, size, size);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&rgba))
+            .expect("PNG encoding into memory does not fail");
+        png
+    }
+}
+
+/// Draws `mesh` into a `samples`-wide square of premultiplied RGBA, nearest
+/// triangle first.
+fn rasterize(mesh: &TriMesh, samples: u32, color: &mut [[f32; 4]]) {
+    let view = Mat4::look_at_rh(Vec3::ONE, Vec3::ZERO, Vec3::Y);
+    let points: Vec<Vec3> = mesh
+        .positions
+        .iter()
+        .map(|&p| view.transform_point3(Vec3::from_array(p)))
+        .collect();
+    let (min, max) = points.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), &p| (min.min(p), max.max(p)),
+    );
+    let center = (min + max) / 2.0;
+    let extent = (max - min).truncate().max_element().max(f32::EPSILON);
+    let scale = samples as f32 * (1.0 - 2.0 * MARGIN) / extent;
+    let half = samples as f32 / 2.0;
+    // Screen space: x right, y down, z towards the viewer.
+    let screen: Vec<Vec3> = points
+        .iter()
+        .map(|&p| {
+            let p = p - center;
+            Vec3::new(half + p.x * scale, half - p.y * scale, p.z)
+        })
+        .collect();
+
+    // From over the viewer's left shoulder, in view space.
+    let light = Vec3::new(-0.4, 0.6, 1.0).normalize();
+    let mut depth = vec![f32::NEG_INFINITY; color.len()];
+    for tri in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| points[tri[k] as usize]);
+        // Lit from the front whichever way the triangle is wound, so open
+        // sheets shade like solids.
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        let normal = if normal.z < 0.0 { -normal } else { normal };
+        let shade = 0.3 + 0.7 * normal.dot(light).max(0.0);
+        let rgb = BODY_COLOR.map(|c| c * shade);
+
+        let [a, b, c] = [0, 1, 2].map(|k| screen[tri[k] as usize]);
+        let area = edge(a, b, c);
+        if area.abs() <= f32::EPSILON {
+            continue;
+        }
+        let lo = a.min(b).min(c).max(Vec3::ZERO);
+        let hi = a.max(b).max(c).min(Vec3::splat(samples as f32 - 1.0));
+        for y in lo.y.floor() as u32..=hi.y.ceil() as u32 {
+            for x in lo.x.floor() as u32..=hi.x.ceil() as u32 {
+                let p = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+                // Barycentric weights, all of one sign inside.
+                let [wa, wb, wc] = [edge(b, c, p), edge(c, a, p), edge(a, b, p)].map(|w| w / area);
+                if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                    continue;
+                }
+                let z = wa * a.z + wb * b.z + wc * c.z;
+                let i = (y * samples + x) as usize;
+                if z > depth[i] {
+                    depth[i] = z;
+                    color[i] = [rgb[0], rgb[1], rgb[2], 1.0];
+                }
+            }
+        }
+    }
+}
+
+/// Twice the signed area of `(a, b, p)` in the screen plane.
+fn edge(a: Vec3, b: Vec3, p: Vec3) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GeomScene;
+
+    #[test]
+    fn box_preview_is_centered() {
+        let mut scene = GeomScene::new();
+        scene.add_box(2.0, 3.0, 4.0).unwrap();
+        let png = scene.render_preview(64);
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba).unwrap();
+        assert_eq!((info.width, info.height), (64, 64));
+        let alpha = |x: usize, y: usize| rgba[(y * 64 + x) * 4 + 3];
+        assert_eq!(alpha(32, 32), 255);
+        assert_eq!(alpha(0, 0), 0);
+        assert_eq!(alpha(63, 63), 0);
+
+        let blank = GeomScene::new().render_preview(8);
+        let mut reader = png::Decoder::new(blank.as_slice()).read_info().unwrap();
+        let mut rgba = vec![1; reader.output_buffer_size()];
+        reader.next_frame(&mut rgba).unwrap();
+        assert!(rgba.iter().all(|&byte| byte == 0));
+    }
+}
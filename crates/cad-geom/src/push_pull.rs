@@ -0,0 +1,223 @@
+//! Push/pull: moving a planar face along its normal.
+
+use crate::{GeomError, GeomScene, SurfaceHit};
+use cad_core::{FeatureId, FeatureOp};
+use glam::Vec3;
+use std::collections::HashMap;
+use truck_modeling::{builder, Curve, Face, InnerSpace, Point3, Shell, Solid, Surface, Vector3};
+
+/// Moves the planar face of `solid` that faces along `normal` and passes
+/// through `point` by `distance`, sliding its vertices along the adjacent
+/// edges so the neighbouring faces stretch (or shrink) to follow.
+///
+/// Only bodies bounded by straight edges are supported.
+pub(crate) fn push_pull(
+    solid: &Solid,
+    point: [f32; 3],
+    normal: [f32; 3],
+    distance: f32,
+) -> Result<Solid, GeomError> {
+    let normal = Vector3::new(normal[0] as f64, normal[1] as f64, normal[2] as f64);
+    if !distance.is_finite() || normal.magnitude2() < 1.0e-12 {
+        return Err(GeomError::InvalidFeature(
+            "push/pull needs a face normal and a finite distance",
+        ));
+    }
+    let normal = normal.normalize();
+    let point = Point3::new(point[0] as f64, point[1] as f64, point[2] as f64);
+    let distance = distance as f64;
+    if solid
+        .edge_iter()
+        .any(|edge| !matches!(edge.curve(), Curve::Line(_)))
+    {
+        return Err(GeomError::NotImplemented("push/pull on curved bodies"));
+    }
+
+    // The face whose plane lies closest to the picked point.
+    let mut target: Option<(&Face, f64)> = None;
+    for face in solid.face_iter() {
+        let Some(n) = outward_normal(face) else {
+            continue;
+        };
+        let Some(vertex) = face.vertex_iter().next() else {
+            continue;
+        };
+        if n.dot(normal) < 1.0 - 1.0e-4 {
+            continue;
+        }
+        let gap = n.dot(point - vertex.point()).abs();
+        if target.is_none_or(|(_, best)| gap < best) {
+            target = Some((face, gap));
+        }
+    }
+    let tolerance = 1.0e-3 * (1.0 + point.to_homogeneous().truncate().magnitude());
+    let face = match target {
+        Some((face, gap)) if gap <= tolerance => face,
+        _ => {
+            return Err(GeomError::InvalidFeature(
+                "no planar face at push/pull point",
+            ))
+        }
+    };
+    let on_face: HashMap<_, _> = face.vertex_iter().map(|v| (v.id(), v.point())).collect();
+
+    // Neighbours of each face vertex that are not on the face itself.
+    let mut neighbours: HashMap<_, Vec<_>> = HashMap::new();
+    for edge in solid.edge_iter() {
+        let (front, back) = (edge.front(), edge.back());
+        for (a, b) in [(front, back), (back, front)] {
+            if on_face.contains_key(&a.id()) && !on_face.contains_key(&b.id()) {
+                let list = neighbours.entry(a.id()).or_default();
+                if !list.contains(&b.point()) {
+                    list.push(b.point());
+                }
+            }
+        }
+    }
+
+    let mut moved = HashMap::new();
+    for (&id, &p) in &on_face {
+        let target = match neighbours.get(&id).map(Vec::as_slice) {
+            // Slide along the single side edge so its faces stay flat.
+            Some(&[other]) => {
+                let along = other - p;
+                let rate = along.dot(normal);
+                if rate.abs() < 1.0e-9 {
+                    return Err(GeomError::InvalidFeature(
+                        "push/pull face has a side edge parallel to it",
+                    ));
+                }
+                let t = distance / rate;
+                if t >= 1.0 - 1.0e-6 {
+                    return Err(GeomError::InvalidFeature(
+                        "push/pull distance collapses the body",
+                    ));
+                }
+                p + along * t
+            }
+            _ => p + normal * distance,
+        };
+        moved.insert(id, target);
+    }
+
+    // Rebuild the topology with the moved vertices.
+    let mut vertices = HashMap::new();
+    for v in solid.vertex_iter() {
+        let p = moved.get(&v.id()).copied().unwrap_or(v.point());
+        vertices.entry(v.id()).or_insert_with(|| builder::vertex(p));
+    }
+    let mut edges = HashMap::new();
+    let mut shells = Vec::new();
+    for shell in solid.boundaries() {
+        let mut faces = Vec::new();
+        for face in shell.face_iter() {
+            let wires: Vec<truck_modeling::Wire> = face
+                .boundaries()
+                .iter()
+                .map(|wire| {
+                    wire.edge_iter()
+                        .map(|edge| {
+                            let (new_edge, front) = edges.entry(edge.id()).or_insert_with(|| {
+                                let line = builder::line(
+                                    &vertices[&edge.front().id()],
+                                    &vertices[&edge.back().id()],
+                                );
+                                (line, edge.front().id())
+                            });
+                            if *front == edge.front().id() {
+                                new_edge.clone()
+                            } else {
+                                new_edge.inverse()
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut new_face = builder::try_attach_plane(&wires)
+                .map_err(|_| GeomError::InvalidFeature("push/pull would bend an adjacent face"))?;
+            if let (Some(old), Some(new)) = (outward_normal(face), outward_normal(&new_face)) {
+                if old.dot(new) < 0.0 {
+                    new_face.invert();
+                }
+            }
+            faces.push(new_face);
+        }
+        shells.push(Shell::from(faces));
+    }
+    Solid::try_new(shells).map_err(|err| GeomError::Kernel(err.to_string()))
+}
+
+fn outward_normal(face: &Face) -> Option<Vector3> {
+    let Surface::Plane(plane) = face.surface() else {
+        return None;
+    };
+    Some(if face.orientation() {
+        plane.normal()
+    } else {
+        -plane.normal()
+    })
+}
+
+impl GeomScene {
+    /// Pushes (positive `distance`) or pulls the planar face under a pick
+    /// hit, recording it as a feature of the hit body.
+    pub fn push_pull(&mut self, hit: &SurfaceHit, distance: f32) -> Result<FeatureId, GeomError> {
+        let obj = self
+            .model
+            .object(hit.object_id)
+            .ok_or(GeomError::UnknownObject(hit.object_id))?;
+        let to_world = self.world_mat(obj);
+        let to_local = to_world.inverse();
+        let normal = to_world
+            .transpose()
+            .transform_vector3(Vec3::from_array(hit.normal))
+            .normalize_or_zero();
+        let offset = to_local.transform_vector3(Vec3::from_array(hit.normal) * distance);
+        let op = FeatureOp::PushPull {
+            point: to_local
+                .transform_point3(Vec3::from_array(hit.point))
+                .to_array(),
+            normal: normal.to_array(),
+            distance: offset.dot(normal),
+        };
+        self.add_feature(hit.object_id, op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::push_pull;
+    use crate::{make_wedge, tessellate_solid, GeomScene};
+
+    #[test]
+    fn pushes_and_pulls_planar_faces() {
+        let mut scene = GeomScene::new();
+        let id = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let hit = scene
+            .pick_surface([0.0, 5.0, 0.0], [0.0, -1.0, 0.0])
+            .unwrap();
+        scene.push_pull(&hit, 0.5).unwrap();
+        assert!((scene.local_aabb(id).unwrap().max[1] - 1.0).abs() < 1.0e-4);
+        let hit = scene
+            .pick_surface([0.0, 5.0, 0.0], [0.0, -1.0, 0.0])
+            .unwrap();
+        assert!(scene.push_pull(&hit, -3.0).is_err());
+
+        // Pulling the tall end of a wedge in keeps the slope of its top.
+        let wedge = push_pull(
+            &make_wedge(2.0, 1.0, 1.0).unwrap(),
+            [-1.0, 0.0, 0.0],
+            [-1.0, 0.0, 0.0],
+            -1.0,
+        )
+        .unwrap();
+        let mesh = tessellate_solid(&wedge, 0.01);
+        assert!(mesh.validate().is_printable());
+        let min_x = mesh.positions.iter().map(|p| p[0]).fold(f32::MAX, f32::min);
+        let max_y = mesh.positions.iter().map(|p| p[1]).fold(f32::MIN, f32::max);
+        assert!(
+            min_x.abs() < 1.0e-4 && max_y.abs() < 1.0e-4,
+            "{min_x} {max_y}"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Plane sections of bodies with hatched cut faces.
+
+use crate::{position_key, GeomScene, TriMesh};
+use cad_core::{ObjectId, SketchPlane};
+use glam::{Vec2, Vec3};
+use std::collections::HashMap;
+
+/// Upper bound on hatch lines per body, against tiny spacings.
+const MAX_HATCH_LINES: usize = 10_000;
+
+/// Hatch pattern drawn inside section loops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hatch {
+    /// Line direction in the section plane, radians from its `u` axis.
+    pub angle: f32,
+    /// Distance between neighbouring lines.
+    pub spacing: f32,
+}
+
+impl Default for Hatch {
+    fn default() -> Self {
+        Self {
+            angle: std::f32::consts::FRAC_PI_4,
+            spacing: 0.1,
+        }
+    }
+}
+
+/// The cut of a set of bodies by a plane.
+#[derive(Debug, Clone, Default)]
+pub struct Section {
+    /// Closed loops in world space; the first point is not repeated.
+    pub loops: Vec<Vec<[f32; 3]>>,
+    /// Hatch segments filling the loops of each body.
+    pub hatch: Vec<[[f32; 3]; 2]>,
+}
+
+impl GeomScene {
+    /// Cuts the given bodies (every body for an empty `ids` slice) with
+    /// `plane`, hatching the cut faces of each body separately.
+    pub fn section(&self, plane: &SketchPlane, ids: &[ObjectId], hatch: Hatch) -> Section {
+        let mut section = Section::default();
+        for (idx, obj) in self.model.objects().iter().enumerate() {
+            if !ids.is_empty() && !ids.contains(&obj.id) {
+                continue;
+            }
+            let Some(local) = self.local_meshes.get(idx) else {
+                continue;
+            };
+            let mut mesh = TriMesh::default();
+            mesh.append_transformed(local, self.world_mat(obj));
+            let loops = section_loops(&mesh, plane);
+            section.hatch.extend(hatch_loops(&loops, plane, hatch));
+            section.loops.extend(loops);
+        }
+        section
+    }
+}
+
+/// Closed loops where `plane` cuts a mesh. Open chains are dropped.
+pub fn section_loops(mesh: &TriMesh, plane: &SketchPlane) -> Vec<Vec<[f32; 3]>> {
+    let origin = Vec3::from_array(plane.origin);
+    let normal = Vec3::from_array(plane.normal).normalize_or_zero();
+    if normal == Vec3::ZERO {
+        return Vec::new();
+    }
+
+    let mut segments: Vec<[Vec3; 2]> = Vec::new();
+    for tri in mesh.indices.chunks_exact(3) {
+        let p = [0, 1, 2].map(|k| Vec3::from_array(mesh.positions[tri[k] as usize]));
+        let mut cut = Vec::with_capacity(2);
+        for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+            // Order the endpoints so both triangles on an edge agree exactly.
+            let (a, b) = if position_key(p[i]) < position_key(p[j]) {
+                (p[i], p[j])
+            } else {
+                (p[j], p[i])
+            };
+            let (da, db) = (normal.dot(a - origin), normal.dot(b - origin));
+            if (da >= 0.0) != (db >= 0.0) {
+                cut.push(a + (b - a) * (da / (da - db)));
+            }
+        }
+        if let [a, b] = cut[..] {
+            if position_key(a) != position_key(b) {
+                segments.push([a, b]);
+            }
+        }
+    }
+
+    let mut by_point: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+    for (idx, seg) in segments.iter().enumerate() {
+        for p in seg {
+            by_point.entry(position_key(*p)).or_default().push(idx);
+        }
+    }
+    let mut used = vec![false; segments.len()];
+    let mut loops = Vec::new();
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let [first, mut end] = segments[start];
+        let mut points = vec![first];
+        let closed = loop {
+            if position_key(end) == position_key(first) {
+                break true;
+            }
+            points.push(end);
+            let next = by_point[&position_key(end)]
+                .iter()
+                .copied()
+                .find(|&idx| !used[idx]);
+            let Some(next) = next else {
+                break false;
+            };
+            used[next] = true;
+            let [a, b] = segments[next];
+            end = if position_key(a) == position_key(end) {
+                b
+            } else {
+                a
+            };
+        };
+        if closed && points.len() >= 3 {
+            loops.push(points.iter().map(Vec3::to_array).collect());
+        }
+    }
+    loops
+}
+
+/// Hatch segments filling `loops` (even-odd) on `plane`.
+pub fn hatch_loops(
+    loops: &[Vec<[f32; 3]>],
+    plane: &SketchPlane,
+    hatch: Hatch,
+) -> Vec<[[f32; 3]; 2]> {
+    if !(hatch.spacing > 0.0 && hatch.spacing.is_finite() && hatch.angle.is_finite()) {
+        return Vec::new();
+    }
+    let origin = Vec3::from_array(plane.origin);
+    let u = Vec3::from_array(plane.u).normalize_or_zero();
+    let v = Vec3::from_array(plane.v).normalize_or_zero();
+    // Hatch frame: `s` runs along the lines, `t` across them.
+    let along = Vec2::from_angle(hatch.angle);
+    let across = along.perp();
+    let to_frame = |p: &[f32; 3]| {
+        let rel = Vec3::from_array(*p) - origin;
+        let flat = Vec2::new(rel.dot(u), rel.dot(v));
+        Vec2::new(flat.dot(along), flat.dot(across))
+    };
+    let to_world = |s: f32, t: f32| {
+        let flat = along * s + across * t;
+        (origin + u * flat.x + v * flat.y).to_array()
+    };
+
+    let polygons: Vec<Vec<Vec2>> = loops
+        .iter()
+        .map(|points| points.iter().map(to_frame).collect())
+        .collect();
+    let (t_min, t_max) = polygons
+        .iter()
+        .flatten()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), p| {
+            (lo.min(p.y), hi.max(p.y))
+        });
+    if t_min > t_max {
+        return Vec::new();
+    }
+    let first = (t_min / hatch.spacing).ceil() as i64;
+    let last = (t_max / hatch.spacing).floor() as i64;
+    if last - first >= MAX_HATCH_LINES as i64 {
+        return Vec::new();
+    }
+
+    let mut lines = Vec::new();
+    let mut crossings = Vec::new();
+    for k in first..=last {
+        let t = k as f32 * hatch.spacing;
+        crossings.clear();
+        for polygon in &polygons {
+            for (i, a) in polygon.iter().enumerate() {
+                let b = polygon[(i + 1) % polygon.len()];
+                // Half-open so a vertex on the line is counted once.
+                if (a.y <= t) != (b.y <= t) {
+                    crossings.push(a.x + (b.x - a.x) * (t - a.y) / (b.y - a.y));
+                }
+            }
+        }
+        crossings.sort_by(f32::total_cmp);
+        for pair in crossings.chunks_exact(2) {
+            lines.push([to_world(pair[0], t), to_world(pair[1], t)]);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hatches_box_section() {
+        let mut scene = GeomScene::new();
+        scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let hatch = Hatch {
+            angle: 0.0,
+            spacing: 0.3,
+        };
+        let section = scene.section(&SketchPlane::XZ, &[], hatch);
+        assert_eq!(section.loops.len(), 1);
+        assert_eq!(section.hatch.len(), 3);
+        let total: f32 = section
+            .hatch
+            .iter()
+            .map(|[a, b]| Vec3::from_array(*a).distance(Vec3::from_array(*b)))
+            .sum();
+        assert!((total - 3.0).abs() < 1.0e-4, "{total}");
+    }
+}
@@ -0,0 +1,381 @@
+//! Rectangle (window/crossing) selection.
+
+use crate::{aabb_corners, ray_triangle_intersect, GeomScene};
+use cad_core::{ObjectId, SelectionSetId};
+use glam::Vec3;
+
+/// How a selection rectangle picks bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RectSelection {
+    /// Bodies lying entirely inside the rectangle.
+    #[default]
+    Window,
+    /// Bodies inside or touching the rectangle.
+    Crossing,
+}
+
+/// Convex view volume bounded by a near plane and four side planes.
+struct Frustum {
+    /// Planes as `(normal, offset)` with `normal . p + offset >= 0` inside.
+    planes: [(Vec3, f32); 5],
+    /// The four corner rays, used as frustum edges for crossing tests.
+    rays: [(Vec3, Vec3); 4],
+}
+
+impl Frustum {
+    fn new(corners: [([f32; 3], [f32; 3]); 4]) -> Option<Self> {
+        let rays = corners.map(|(o, d)| (Vec3::from_array(o), Vec3::from_array(d)));
+        let inside = rays.iter().map(|&(o, d)| o + d).sum::<Vec3>() / 4.0;
+        let forward = rays
+            .iter()
+            .map(|&(_, d)| d)
+            .sum::<Vec3>()
+            .normalize_or_zero();
+        if forward == Vec3::ZERO {
+            return None;
+        }
+
+        let mut planes = [(forward, -forward.dot(rays[0].0)); 5];
+        for i in 0..4 {
+            let (o0, d0) = rays[i];
+            let (o1, d1) = rays[(i + 1) % 4];
+            // Works for both a shared eye (perspective) and parallel rays
+            // (orthographic).
+            let mut normal = d0.cross(o1 + d1 - o0).normalize_or_zero();
+            if normal == Vec3::ZERO {
+                return None;
+            }
+            if normal.dot(inside - o0) < 0.0 {
+                normal = -normal;
+            }
+            planes[i + 1] = (normal, -normal.dot(o0));
+        }
+        Some(Self { planes, rays })
+    }
+
+    fn contains(&self, p: Vec3) -> bool {
+        self.planes.iter().all(|&(n, d)| n.dot(p) + d >= 0.0)
+    }
+
+    /// Whether all points lie outside one plane.
+    fn excludes(&self, points: &[Vec3]) -> bool {
+        self.planes
+            .iter()
+            .any(|&(n, d)| points.iter().all(|&p| n.dot(p) + d < 0.0))
+    }
+
+    /// Clips the segment `a..b` against every plane.
+    fn clips_segment(&self, a: Vec3, b: Vec3) -> bool {
+        let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+        for &(n, d) in &self.planes {
+            let (da, db) = (n.dot(a) + d, n.dot(b) + d);
+            if da < 0.0 && db < 0.0 {
+                return false;
+            }
+            if da < 0.0 {
+                t0 = t0.max(da / (da - db));
+            } else if db < 0.0 {
+                t1 = t1.min(da / (da - db));
+            }
+        }
+        t0 <= t1
+    }
+
+    fn touches_triangle(&self, p: [Vec3; 3]) -> bool {
+        if self.excludes(&p) {
+            return false;
+        }
+        if (0..3).any(|i| self.clips_segment(p[i], p[(i + 1) % 3])) {
+            return true;
+        }
+        // The triangle may cover the whole rectangle: test the frustum's
+        // side edges and near edges against it.
+        self.rays.iter().enumerate().any(|(i, &(o, d))| {
+            let next = self.rays[(i + 1) % 4].0;
+            ray_triangle_intersect(o, d, p[0], p[1], p[2]).is_some()
+                || (next != o
+                    && ray_triangle_intersect(o, next - o, p[0], p[1], p[2])
+                        .is_some_and(|t| t <= 1.0))
+        })
+    }
+}
+
+impl GeomScene {
+    /// Returns the bodies selected by a screen rectangle.
+    ///
+    /// `corners` are the picking rays `(origin, direction)` through the four
+    /// rectangle corners, in order around the rectangle.
+    pub fn pick_in_frustum(
+        &self,
+        corners: [([f32; 3], [f32; 3]); 4],
+        mode: RectSelection,
+    ) -> Vec<ObjectId> {
+        let Some(frustum) = Frustum::new(corners) else {
+            return Vec::new();
+        };
+
+        let mut picked = Vec::new();
+        for (idx, obj) in self.model.objects().iter().enumerate() {
+            if !self.model.is_pickable(obj) {
+                continue;
+            }
+            let (Some(mesh), Some(aabb)) = (self.local_meshes.get(idx), self.local_aabbs.get(idx))
+            else {
+                continue;
+            };
+            let transform = self.world_mat(obj);
+            let box_corners = aabb_corners(aabb, transform);
+            if frustum.excludes(&box_corners) {
+                continue;
+            }
+            if box_corners.iter().all(|&p| frustum.contains(p)) {
+                picked.push(obj.id);
+                continue;
+            }
+
+            let world = |i: u32| {
+                mesh.positions
+                    .get(i as usize)
+                    .map(|p| transform.transform_point3(Vec3::from_array(*p)))
+            };
+            let hit = match mode {
+                RectSelection::Window => (0..mesh.positions.len() as u32)
+                    .filter_map(world)
+                    .all(|p| frustum.contains(p)),
+                RectSelection::Crossing => mesh.indices.chunks_exact(3).any(|tri| {
+                    match (world(tri[0]), world(tri[1]), world(tri[2])) {
+                        (Some(p0), Some(p1), Some(p2)) => frustum.touches_triangle([p0, p1, p2]),
+                        _ => false,
+                    }
+                }),
+            };
+            if hit {
+                picked.push(obj.id);
+            }
+        }
+        picked
+    }
+
+    /// Saves the given bodies as a named selection set.
+    pub fn add_selection_set(
+        &mut self,
+        name: impl Into<String>,
+        objects: &[ObjectId],
+    ) -> SelectionSetId {
+        self.model.add_selection_set(name, objects)
+    }
+
+    pub fn rename_selection_set(&mut self, id: SelectionSetId, name: impl Into<String>) -> bool {
+        self.model.rename_selection_set(id, name)
+    }
+
+    pub fn set_selection_set_objects(&mut self, id: SelectionSetId, objects: &[ObjectId]) -> bool {
+        self.model.set_selection_set_objects(id, objects)
+    }
+
+    pub fn remove_selection_set(&mut self, id: SelectionSetId) -> bool {
+        self.model.remove_selection_set(id).is_some()
+    }
+
+    /// The bodies of a selection set that can be selected right now.
+    pub fn apply_selection_set(&self, id: SelectionSetId) -> Vec<ObjectId> {
+        self.model.apply_selection_set(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cad_core::{ObjectQuery, Transform};
+
+    /// Orthographic rays looking down -Z through an XY rectangle.
+    fn rect(min: [f32; 2], max: [f32; 2]) -> [([f32; 3], [f32; 3]); 4] {
+        [
+            [min[0], min[1]],
+            [max[0], min[1]],
+            [max[0], max[1]],
+            [min[0], max[1]],
+        ]
+        .map(|[x, y]| ([x, y, 10.0], [0.0, 0.0, -1.0]))
+    }
+
+    #[test]
+    fn window_and_crossing_selection() {
+        let mut scene = GeomScene::new();
+        let near = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let far = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        scene.set_object_transform(
+            far,
+            Transform {
+                translation: [5.0, 0.0, 0.0],
+                ..Transform::default()
+            },
+        );
+
+        let partial = rect([-1.0, -1.0], [5.0, 1.0]);
+        assert_eq!(
+            scene.pick_in_frustum(partial, RectSelection::Window),
+            vec![near]
+        );
+        assert_eq!(
+            scene.pick_in_frustum(partial, RectSelection::Crossing),
+            vec![near, far]
+        );
+        // A rectangle inside a single face still crosses it.
+        let inner = rect([4.9, -0.1], [5.1, 0.1]);
+        assert_eq!(
+            scene.pick_in_frustum(inner, RectSelection::Crossing),
+            vec![far]
+        );
+        assert!(scene
+            .pick_in_frustum(inner, RectSelection::Window)
+            .is_empty());
+
+        // Hidden and locked bodies are skipped.
+        let both = scene.mesh().unwrap().indices.len();
+        scene.set_object_visible(near, false);
+        scene.set_object_locked(far, true);
+        assert!(scene
+            .pick_in_frustum(partial, RectSelection::Crossing)
+            .is_empty());
+        assert!(scene
+            .pick_surface([5.0, 0.0, 10.0], [0.0, 0.0, -1.0])
+            .is_none());
+        assert_eq!(scene.mesh().unwrap().indices.len(), both / 2);
+
+        // So are bodies on hidden layers, until the layer goes away.
+        scene.set_object_locked(far, false);
+        let layer = scene.add_layer("Hidden", [1.0; 4]);
+        assert!(scene.set_object_layer(far, Some(layer)));
+        scene.set_layer_visible(layer, false);
+        assert!(scene
+            .pick_surface([5.0, 0.0, 10.0], [0.0, 0.0, -1.0])
+            .is_none());
+        assert!(scene.remove_layer(layer));
+        assert_eq!(
+            scene.pick_in_frustum(partial, RectSelection::Crossing),
+            vec![far]
+        );
+    }
+
+    #[test]
+    fn reapplies_selection_sets() {
+        let mut scene = GeomScene::new();
+        let bolt = scene.add_cylinder(0.1, 1.0).unwrap();
+        let plate = scene.add_box(1.0, 0.1, 1.0).unwrap();
+        let nut = scene.add_prism(6, 0.2, 0.1).unwrap();
+        let set = scene.add_selection_set("Fasteners", &[nut, bolt, nut, 99]);
+        assert_eq!(
+            scene.model().selection_set(set).unwrap().objects,
+            [nut, bolt]
+        );
+        assert_eq!(scene.apply_selection_set(set), [bolt, nut]);
+
+        // Removed and locked bodies drop out until they come back.
+        let removed = scene.take_object(bolt).unwrap();
+        scene.set_object_locked(nut, true);
+        assert!(scene.apply_selection_set(set).is_empty());
+        scene.restore_object(removed).unwrap();
+        scene.set_object_locked(nut, false);
+        assert_eq!(scene.apply_selection_set(set), [bolt, nut]);
+
+        assert!(scene.set_selection_set_objects(set, &[plate]));
+        assert_eq!(scene.apply_selection_set(set), [plate]);
+        assert!(scene.remove_selection_set(set));
+        assert!(scene.apply_selection_set(set).is_empty());
+    }
+
+    #[test]
+    fn groups_move_their_bodies() {
+        let mut scene = GeomScene::new();
+        let a = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let b = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        scene.set_object_transform(
+            b,
+            Transform {
+                translation: [3.0, 0.0, 0.0],
+                ..Transform::default()
+            },
+        );
+        let group = scene.group("Pair", &[a, b]).unwrap();
+        let window = rect([2.0, -1.0], [4.0, 1.0]);
+        assert_eq!(scene.pick_in_frustum(window, RectSelection::Window), [b]);
+
+        scene.set_component_transform(
+            group,
+            Transform {
+                translation: [0.0, 5.0, 0.0],
+                ..Transform::default()
+            },
+        );
+        let moved = rect([2.0, 4.0], [4.0, 6.0]);
+        assert_eq!(scene.pick_in_frustum(moved, RectSelection::Window), [b]);
+
+        // Ungrouping bakes the group placement into the bodies.
+        assert_eq!(scene.ungroup(group).unwrap(), [a, b]);
+        assert!(scene.model().components().is_empty());
+        assert_eq!(scene.pick_in_frustum(moved, RectSelection::Window), [b]);
+        let t = scene.object_transform(b).unwrap();
+        assert!((t.translation[1] - 5.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn resolves_transforms_through_components() {
+        let mut scene = GeomScene::new();
+        let outer = scene.add_component("Outer", None).unwrap();
+        let inner = scene.add_component("Inner", Some(outer)).unwrap();
+        let shifted = |x| Transform {
+            translation: [x, 0.0, 0.0],
+            ..Transform::default()
+        };
+        scene.set_component_transform(outer, shifted(4.0));
+        scene.set_component_transform(inner, shifted(1.0));
+        let body = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        assert!(scene.set_object_parent(body, Some(inner)));
+        assert!(!scene.set_component_parent(outer, Some(inner)));
+
+        let hit = scene
+            .pick_surface([5.0, 0.0, 10.0], [0.0, 0.0, -1.0])
+            .unwrap();
+        assert_eq!(hit.object_id, body);
+        assert_eq!(
+            scene.pick_in_frustum(rect([4.0, -1.0], [6.0, 1.0]), RectSelection::Window),
+            vec![body]
+        );
+
+        // Removing a component hands its children to its parent.
+        scene.remove_component(inner);
+        assert_eq!(scene.model().object(body).unwrap().parent, Some(outer));
+        assert!(scene
+            .pick_surface([4.0, 0.0, 10.0], [0.0, 0.0, -1.0])
+            .is_some());
+    }
+
+    #[test]
+    fn queries_tags_kinds_and_layers() {
+        let mut scene = GeomScene::new();
+        let bolt = scene.add_cylinder(0.2, 1.0).unwrap();
+        let plate = scene.add_box(2.0, 0.2, 2.0).unwrap();
+        let nut = scene.add_prism(6, 0.3, 0.2).unwrap();
+        assert!(scene.add_object_tag(bolt, " fastener "));
+        assert!(scene.add_object_tag(nut, "fastener"));
+        assert!(!scene.add_object_tag(nut, "fastener"));
+        let steel = scene.add_layer("Steel", [0.5, 0.5, 0.5, 1.0]);
+        scene.set_object_layer(plate, Some(steel));
+
+        let model = scene.model();
+        let tagged = ObjectQuery::Tag("Fastener".into());
+        assert_eq!(model.query(&tagged), vec![bolt, nut]);
+        assert_eq!(model.query(&ObjectQuery::Kind("box".into())), vec![plate]);
+        assert_eq!(model.query(&ObjectQuery::Layer(None)), vec![bolt, nut]);
+        assert_eq!(model.query(&model.parse_query("layer:steel")), vec![plate]);
+        assert_eq!(
+            model.query(&model.parse_query("tag:fastener kind:prism")),
+            vec![nut]
+        );
+        assert!(model.query(&model.parse_query("layer:wood")).is_empty());
+
+        assert!(scene.remove_object_tag(bolt, "fastener"));
+        assert_eq!(scene.model().query(&tagged), vec![nut]);
+    }
+}
@@ -0,0 +1,129 @@
+//! Lightweight single-mesh stand-ins for sets of bodies.
+
+use crate::{ray_triangle_intersect, GeomError, GeomScene, NormalMode, TriMesh};
+use cad_core::ObjectId;
+use glam::Vec3;
+use std::collections::{HashMap, HashSet};
+
+/// Crease angle for the normals of the wrapped mesh.
+const SHRINKWRAP_CREASE_ANGLE: f32 = 30.0_f32.to_radians();
+
+/// Skewed so inside tests rarely graze edges or vertices exactly.
+const INSIDE_RAY: Vec3 = Vec3::new(0.5773, 0.5774, 0.5775);
+
+impl GeomScene {
+    /// Merges the given bodies (every body for an empty `ids` slice) into one
+    /// world-space mesh of their outer shell: triangles buried inside another
+    /// body are dropped, then vertices closer than `cell_size` are clustered
+    /// together to simplify the result. A non-positive `cell_size` only
+    /// welds coincident vertices.
+    pub fn shrinkwrap(&self, ids: &[ObjectId], cell_size: f32) -> Result<TriMesh, GeomError> {
+        let bodies: Vec<TriMesh> = self
+            .model
+            .objects()
+            .iter()
+            .zip(&self.local_meshes)
+            .filter(|(obj, _)| ids.is_empty() || ids.contains(&obj.id))
+            .map(|(obj, local)| {
+                let mut mesh = TriMesh::default();
+                mesh.append_transformed(local, self.world_mat(obj));
+                mesh
+            })
+            .collect();
+        if bodies.is_empty() {
+            return Err(GeomError::EmptyScene);
+        }
+
+        let mut triangles: Vec<[Vec3; 3]> = Vec::new();
+        for (idx, body) in bodies.iter().enumerate() {
+            for tri in body.indices.chunks_exact(3) {
+                let p = [0, 1, 2].map(|k| Vec3::from_array(body.positions[tri[k] as usize]));
+                let centroid = (p[0] + p[1] + p[2]) / 3.0;
+                let buried = bodies
+                    .iter()
+                    .enumerate()
+                    .any(|(other, mesh)| other != idx && contains_point(mesh, centroid));
+                if !buried {
+                    triangles.push(p);
+                }
+            }
+        }
+
+        let cell = if cell_size > 0.0 && cell_size.is_finite() {
+            cell_size
+        } else {
+            1.0e-5
+        };
+        let mut mesh = TriMesh::default();
+        let mut clusters: HashMap<[i64; 3], (u32, Vec3, u32)> = HashMap::new();
+        let mut seen = HashSet::new();
+        for tri in &triangles {
+            let ids = tri.map(|p| {
+                let key = (p / cell).round().as_i64vec3().to_array();
+                let next = clusters.len() as u32;
+                let entry = clusters.entry(key).or_insert((next, Vec3::ZERO, 0));
+                entry.1 += p;
+                entry.2 += 1;
+                entry.0
+            });
+            if ids[0] == ids[1] || ids[1] == ids[2] || ids[2] == ids[0] {
+                continue;
+            }
+            // Drop repeats of the same triangle regardless of winding start.
+            let start = (0..3).min_by_key(|&k| ids[k]).unwrap_or(0);
+            if seen.insert([ids[start], ids[(start + 1) % 3], ids[(start + 2) % 3]]) {
+                mesh.indices.extend(ids);
+            }
+        }
+        // Each cluster sits at the mean of the points merged into it.
+        mesh.positions = vec![[0.0; 3]; clusters.len()];
+        for (idx, sum, count) in clusters.into_values() {
+            mesh.positions[idx as usize] = (sum / count as f32).to_array();
+        }
+        mesh.recompute_normals(NormalMode::Crease(SHRINKWRAP_CREASE_ANGLE));
+        Ok(mesh)
+    }
+}
+
+/// Parity test: whether `point` lies inside the closed mesh.
+fn contains_point(mesh: &TriMesh, point: Vec3) -> bool {
+    let (min, max) = mesh.positions.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(lo, hi), p| (lo.min(Vec3::from_array(*p)), hi.max(Vec3::from_array(*p))),
+    );
+    if point.cmplt(min).any() || point.cmpgt(max).any() {
+        return false;
+    }
+    let ray = INSIDE_RAY.normalize();
+    let crossings = mesh
+        .indices
+        .chunks_exact(3)
+        .filter(|tri| {
+            let [a, b, c] = [0, 1, 2].map(|k| Vec3::from_array(mesh.positions[tri[k] as usize]));
+            ray_triangle_intersect(point, ray, a, b, c).is_some_and(|t| t > 1.0e-5)
+        })
+        .count();
+    crossings % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_buried_bodies_and_clusters_vertices() {
+        let mut scene = GeomScene::new();
+        let outer = scene.add_box(2.0, 2.0, 2.0).unwrap();
+        scene.add_box(1.0, 1.0, 1.0).unwrap();
+        let wrapped = scene.shrinkwrap(&[], 0.0).unwrap();
+        assert_eq!(wrapped.indices.len() / 3, 12);
+        assert_eq!(wrapped.positions.len(), 8);
+        assert!(wrapped.validate().is_watertight());
+
+        scene.remove_object(outer);
+        scene.add_cylinder(0.5, 1.0).unwrap();
+        let fine = scene.shrinkwrap(&[], 0.0).unwrap();
+        let coarse = scene.shrinkwrap(&[], 0.2).unwrap();
+        assert!(coarse.indices.len() < fine.indices.len());
+    }
+}
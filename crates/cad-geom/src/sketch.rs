@@ -0,0 +1,595 @@
+//! Turning sketches into faces and solids.
+
+use crate::GeomError;
+use cad_core::{Sketch, SketchChain, SketchEntity};
+use truck_modeling::{builder, Edge, Face, InnerSpace, Point3, Rad, Solid, Vector3, Vertex, Wire};
+
+/// Endpoint matching tolerance in sketch units.
+pub const SKETCH_TOLERANCE: f32 = 1.0e-4;
+
+/// Samples per cubic span when flattening splines.
+const SPLINE_SAMPLES: usize = 16;
+
+/// A closed outer loop with the loops nested directly inside it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SketchProfile {
+    pub outer: SketchChain,
+    pub holes: Vec<SketchChain>,
+}
+
+/// Distance between a dangling chain end and the nearest other dangling end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfileGap {
+    pub from: [f32; 2],
+    pub to: [f32; 2],
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileReport {
+    pub profiles: Vec<SketchProfile>,
+    pub open_chains: Vec<SketchChain>,
+    pub gaps: Vec<ProfileGap>,
+}
+
+/// Sorts sketch segments into face profiles.
+///
+/// Loops nested at an odd depth become holes of the loop around them; loops
+/// at an even depth (islands inside holes) start new profiles.
+pub fn detect_profiles(sketch: &Sketch) -> ProfileReport {
+    let (closed, open_chains): (Vec<_>, Vec<_>) = sketch
+        .chains(SKETCH_TOLERANCE)
+        .into_iter()
+        .partition(|chain| chain.closed);
+
+    let polygons: Vec<Vec<[f32; 2]>> = closed
+        .iter()
+        .map(|chain| chain_points(sketch, chain))
+        .collect();
+    let areas: Vec<f32> = polygons.iter().map(|p| signed_area(p).abs()).collect();
+    let contains = |outer: usize, inner: usize| {
+        outer != inner
+            && areas[outer] > areas[inner]
+            && polygons[inner]
+                .first()
+                .is_some_and(|p| point_in_polygon(*p, &polygons[outer]))
+    };
+
+    // Immediate parent = smallest loop containing this one.
+    let parents: Vec<Option<usize>> = (0..closed.len())
+        .map(|inner| {
+            (0..closed.len())
+                .filter(|&outer| contains(outer, inner))
+                .min_by(|&a, &b| areas[a].total_cmp(&areas[b]))
+        })
+        .collect();
+    let depth = |mut idx: usize| {
+        let mut depth = 0;
+        while let Some(parent) = parents[idx] {
+            depth += 1;
+            idx = parent;
+        }
+        depth
+    };
+
+    let profiles = (0..closed.len())
+        .filter(|&idx| depth(idx) % 2 == 0)
+        .map(|outer| SketchProfile {
+            outer: closed[outer].clone(),
+            holes: (0..closed.len())
+                .filter(|&hole| parents[hole] == Some(outer))
+                .map(|hole| closed[hole].clone())
+                .collect(),
+        })
+        .collect();
+
+    let ends: Vec<[f32; 2]> = open_chains
+        .iter()
+        .filter_map(|chain| sketch.chain_endpoints(chain))
+        .flat_map(|(a, b)| [a, b])
+        .collect();
+    let mut gaps: Vec<ProfileGap> = Vec::new();
+    for (i, &from) in ends.iter().enumerate() {
+        let nearest = ends
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, &to)| (to, distance(from, to)))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let Some((to, distance)) = nearest else {
+            continue;
+        };
+        let duplicate = gaps.iter().any(|gap| gap.from == to && gap.to == from);
+        if !duplicate {
+            gaps.push(ProfileGap { from, to, distance });
+        }
+    }
+
+    ProfileReport {
+        profiles,
+        open_chains,
+        gaps,
+    }
+}
+
+/// Builds one planar face per profile, with holes cut out.
+///
+/// Outer loops are oriented counter-clockwise around the sketch normal (and
+/// holes clockwise), so the faces point along `plane.normal`.
+pub fn sketch_faces(sketch: &Sketch) -> Result<Vec<Face>, GeomError> {
+    let report = detect_profiles(sketch);
+    if report.profiles.is_empty() {
+        return Err(GeomError::NoClosedProfile);
+    }
+    report
+        .profiles
+        .iter()
+        .map(|profile| profile_face(sketch, profile))
+        .collect()
+}
+
+/// Builds the face for one profile.
+pub fn profile_face(sketch: &Sketch, profile: &SketchProfile) -> Result<Face, GeomError> {
+    let oriented = |chain: &SketchChain, ccw: bool| {
+        let wire = chain_wire(sketch, chain);
+        if (signed_area(&chain_points(sketch, chain)) > 0.0) == ccw {
+            wire
+        } else {
+            wire.inverse()
+        }
+    };
+    let mut wires = vec![oriented(&profile.outer, true)];
+    wires.extend(profile.holes.iter().map(|hole| oriented(hole, false)));
+    builder::try_attach_plane(&wires).map_err(|err| GeomError::Kernel(err.to_string()))
+}
+
+/// Extrudes every closed loop of the sketch along its normal.
+pub fn extrude_sketch(sketch: &Sketch, distance: f32) -> Result<Solid, GeomError> {
+    if distance == 0.0 || !distance.is_finite() {
+        return Err(GeomError::InvalidFeature(
+            "extrude distance must be non-zero",
+        ));
+    }
+    let n = sketch.plane.normal;
+    let dir = Vector3::new(n[0] as f64, n[1] as f64, n[2] as f64) * distance as f64;
+    let shells = sketch_faces(sketch)?
+        .into_iter()
+        .flat_map(|face| {
+            // Truck expects the swept face to point along the sweep.
+            let face = if distance < 0.0 { face.inverse() } else { face };
+            builder::tsweep(&face, dir).into_boundaries()
+        })
+        .collect();
+    Ok(Solid::new(shells))
+}
+
+/// Revolves every closed loop of the sketch around an in-plane axis.
+pub fn revolve_sketch(
+    sketch: &Sketch,
+    axis_origin: [f32; 2],
+    axis_dir: [f32; 2],
+    angle: f32,
+) -> Result<Solid, GeomError> {
+    let len = (axis_dir[0] * axis_dir[0] + axis_dir[1] * axis_dir[1]).sqrt();
+    if len < 1.0e-6 || angle == 0.0 || !angle.is_finite() {
+        return Err(GeomError::InvalidFeature(
+            "revolve needs an axis and a non-zero angle",
+        ));
+    }
+    let plane = sketch.plane;
+    let origin = to_point(plane.to_world(axis_origin));
+    let tip = to_point(plane.to_world([
+        axis_origin[0] + axis_dir[0] / len,
+        axis_origin[1] + axis_dir[1] / len,
+    ]));
+    let axis = (tip - origin).normalize();
+    let n = plane.normal;
+    let normal = Vector3::new(n[0] as f64, n[1] as f64, n[2] as f64);
+    let profiles = detect_profiles(sketch).profiles;
+    if profiles.is_empty() {
+        return Err(GeomError::NoClosedProfile);
+    }
+    let faces = profiles
+        .iter()
+        .map(|profile| Ok((profile_face(sketch, profile)?, &profile.outer)))
+        .collect::<Result<Vec<_>, GeomError>>()?;
+    let shells = faces
+        .into_iter()
+        .flat_map(|(face, outer)| {
+            // As with extrusion, the face must point the way it travels.
+            let points = chain_points(sketch, outer);
+            let centroid = points.iter().fold([0.0, 0.0], |acc, p| {
+                [
+                    acc[0] + p[0] / points.len() as f32,
+                    acc[1] + p[1] / points.len() as f32,
+                ]
+            });
+            let travel = axis.cross(to_point(plane.to_world(centroid)) - origin);
+            let face = if normal.dot(travel) * (angle as f64) < 0.0 {
+                face.inverse()
+            } else {
+                face
+            };
+            builder::rsweep(&face, origin, axis, Rad(angle as f64)).into_boundaries()
+        })
+        .collect();
+    Ok(Solid::new(shells))
+}
+
+pub(crate) fn chain_wire(sketch: &Sketch, chain: &SketchChain) -> Wire {
+    let plane = sketch.plane;
+    let world = |p: [f32; 2]| to_point(plane.to_world(p));
+
+    // A loop made of a single curve is split in two so its edge does not
+    // start and end on the same vertex. Splines are split into spans below.
+    let single = match chain.entities[..] {
+        [(idx, _)] => match sketch.entities[idx] {
+            SketchEntity::Circle { center, radius } => Some((center, radius, 0.0, 1.0)),
+            SketchEntity::Arc {
+                center,
+                radius,
+                start_angle,
+                sweep,
+            } => Some((center, radius, start_angle, sweep.signum())),
+            SketchEntity::Spline { .. } => None,
+            SketchEntity::Line { .. } => return Wire::new(),
+        },
+        _ => None,
+    };
+    if let Some((center, radius, start, sign)) = single {
+        let at = |angle: f32| world(polar(center, radius, angle));
+        let v0 = builder::vertex(at(start));
+        let v1 = builder::vertex(at(start + sign * std::f32::consts::PI));
+        let quarter = sign * std::f32::consts::FRAC_PI_2;
+        return vec![
+            builder::circle_arc(&v0, &v1, at(start + quarter)),
+            builder::circle_arc(&v1, &v0, at(start + 3.0 * quarter)),
+        ]
+        .into();
+    }
+
+    let vertices: Vec<Vertex> = chain
+        .entities
+        .iter()
+        .map(|&(idx, reversed)| {
+            let entity = &sketch.entities[idx];
+            builder::vertex(world(if reversed {
+                entity.end()
+            } else {
+                entity.start()
+            }))
+        })
+        .collect();
+
+    chain
+        .entities
+        .iter()
+        .enumerate()
+        .flat_map(|(k, &(idx, reversed))| {
+            let v0 = &vertices[k];
+            let v1 = &vertices[(k + 1) % vertices.len()];
+            match sketch.entities[idx] {
+                SketchEntity::Arc {
+                    center,
+                    radius,
+                    start_angle,
+                    sweep,
+                } => vec![builder::circle_arc(
+                    v0,
+                    v1,
+                    world(polar(center, radius, start_angle + sweep / 2.0)),
+                )],
+                ref spline @ SketchEntity::Spline { .. } => {
+                    spline_edges(spline, reversed, v0, v1, &world)
+                }
+                _ => vec![builder::line(v0, v1)],
+            }
+        })
+        .collect()
+}
+
+/// One Bézier edge per spline span, joined through fresh vertices.
+fn spline_edges(
+    spline: &SketchEntity,
+    reversed: bool,
+    v0: &Vertex,
+    v1: &Vertex,
+    world: &impl Fn([f32; 2]) -> Point3,
+) -> Vec<Edge> {
+    let mut spans = spline.spline_spans();
+    if reversed {
+        spans.reverse();
+        for span in &mut spans {
+            span.reverse();
+        }
+    }
+    let mut joints = vec![v0.clone()];
+    joints.extend(
+        spans
+            .iter()
+            .skip(1)
+            .map(|span| builder::vertex(world(span[0]))),
+    );
+    joints.push(v1.clone());
+    spans
+        .iter()
+        .enumerate()
+        .map(|(i, span)| {
+            builder::bezier(
+                &joints[i],
+                &joints[i + 1],
+                vec![world(span[1]), world(span[2])],
+            )
+        })
+        .collect()
+}
+
+/// Samples a chain as a polyline in plane coordinates.
+pub(crate) fn chain_points(sketch: &Sketch, chain: &SketchChain) -> Vec<[f32; 2]> {
+    let mut points = Vec::new();
+    for &(idx, reversed) in &chain.entities {
+        let mut samples = entity_points(&sketch.entities[idx]);
+        if reversed {
+            samples.reverse();
+        }
+        // Drop the end point; the next entity starts there.
+        samples.pop();
+        points.extend(samples);
+    }
+    points
+}
+
+pub(crate) fn entity_points(entity: &SketchEntity) -> Vec<[f32; 2]> {
+    match *entity {
+        SketchEntity::Line { a, b } => vec![a, b],
+        SketchEntity::Arc {
+            center,
+            radius,
+            start_angle,
+            sweep,
+        } => arc_points(center, radius, start_angle, sweep),
+        SketchEntity::Circle { center, radius } => {
+            arc_points(center, radius, 0.0, std::f32::consts::TAU)
+        }
+        SketchEntity::Spline { .. } => {
+            let spans = entity.spline_spans();
+            let mut points: Vec<[f32; 2]> = spans.first().map(|span| span[0]).into_iter().collect();
+            for [p0, p1, p2, p3] in spans {
+                points.extend((1..=SPLINE_SAMPLES).map(|i| {
+                    let t = i as f32 / SPLINE_SAMPLES as f32;
+                    let s = 1.0 - t;
+                    std::array::from_fn(|k| {
+                        s * s * s * p0[k]
+                            + 3.0 * s * s * t * p1[k]
+                            + 3.0 * s * t * t * p2[k]
+                            + t * t * t * p3[k]
+                    })
+                }));
+            }
+            points
+        }
+    }
+}
+
+/// Flattens every sketch entity to a polyline in plane coordinates, e.g. for
+/// drawing the sketch as an overlay.
+pub fn sketch_polylines(sketch: &Sketch) -> Vec<Vec<[f32; 2]>> {
+    sketch.entities.iter().map(entity_points).collect()
+}
+
+fn arc_points(center: [f32; 2], radius: f32, start: f32, sweep: f32) -> Vec<[f32; 2]> {
+    let steps = ((sweep.abs() / std::f32::consts::TAU) * 32.0)
+        .ceil()
+        .max(2.0) as usize;
+    (0..=steps)
+        .map(|i| polar(center, radius, start + sweep * i as f32 / steps as f32))
+        .collect()
+}
+
+/// Shoelace area; positive for counter-clockwise polygons.
+pub(crate) fn signed_area(points: &[[f32; 2]]) -> f32 {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let a = points[i];
+            let b = points[(i + 1) % n];
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum::<f32>()
+        * 0.5
+}
+
+fn point_in_polygon(p: [f32; 2], poly: &[[f32; 2]]) -> bool {
+    let mut inside = false;
+    let mut j = poly.len().wrapping_sub(1);
+    for i in 0..poly.len() {
+        let (a, b) = (poly[i], poly[j]);
+        if (a[1] > p[1]) != (b[1] > p[1])
+            && p[0] < (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1]) + a[0]
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+}
+
+fn polar(center: [f32; 2], radius: f32, angle: f32) -> [f32; 2] {
+    [
+        center[0] + radius * angle.cos(),
+        center[1] + radius * angle.sin(),
+    ]
+}
+
+fn to_point(p: [f32; 3]) -> Point3 {
+    Point3::new(p[0] as f64, p[1] as f64, p[2] as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tessellate_solid;
+    use cad_core::SketchPlane;
+
+    fn sketch(entities: Vec<SketchEntity>) -> Sketch {
+        Sketch {
+            id: 0,
+            name: "test".to_string(),
+            plane: SketchPlane::XZ,
+            entities,
+            references: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn extrudes_clockwise_rectangle_and_circle() {
+        // Clockwise, with one segment drawn backwards.
+        let rect = sketch(vec![
+            SketchEntity::Line {
+                a: [0.0, 0.0],
+                b: [0.0, 1.0],
+            },
+            SketchEntity::Line {
+                a: [0.0, 1.0],
+                b: [2.0, 1.0],
+            },
+            SketchEntity::Line {
+                a: [2.0, 0.0],
+                b: [2.0, 1.0],
+            },
+            SketchEntity::Line {
+                a: [2.0, 0.0],
+                b: [0.0, 0.0],
+            },
+        ]);
+        let circle = sketch(vec![SketchEntity::Circle {
+            center: [0.0, 0.0],
+            radius: 0.5,
+        }]);
+        for (sketch, distance) in [(rect, 0.5), (circle, -1.0)] {
+            let mesh = tessellate_solid(&extrude_sketch(&sketch, distance).unwrap(), 0.01);
+            let report = mesh.validate();
+            assert!(report.is_printable(), "{report:?}");
+            assert!(signed_volume(&mesh) > 0.0);
+        }
+    }
+
+    #[test]
+    fn revolves_outward_in_both_directions() {
+        let ring = sketch(vec![SketchEntity::Circle {
+            center: [2.0, 0.0],
+            radius: 0.5,
+        }]);
+        for angle in [std::f32::consts::PI, -1.0, std::f32::consts::TAU] {
+            let mesh = tessellate_solid(
+                &revolve_sketch(&ring, [0.0, 0.0], [0.0, 1.0], angle).unwrap(),
+                0.01,
+            );
+            assert!(mesh.validate().is_printable());
+            assert!(signed_volume(&mesh) > 0.0);
+        }
+    }
+
+    fn signed_volume(mesh: &crate::TriMesh) -> f32 {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|tri| {
+                let p = |i: u32| glam::Vec3::from_array(mesh.positions[i as usize]);
+                p(tri[0]).dot(p(tri[1]).cross(p(tri[2]))) / 6.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn nested_loops_become_holes_and_islands() {
+        let square = |half: f32| {
+            let c = [[-half, -half], [half, -half], [half, half], [-half, half]];
+            (0..4).map(move |i| SketchEntity::Line {
+                a: c[i],
+                b: c[(i + 1) % 4],
+            })
+        };
+        let mut entities: Vec<SketchEntity> = square(2.0).collect();
+        entities.push(SketchEntity::Circle {
+            center: [0.0, 0.0],
+            radius: 1.0,
+        });
+        entities.extend(square(0.25));
+        let plate = sketch(entities);
+
+        let report = detect_profiles(&plate);
+        assert_eq!(report.profiles.len(), 2);
+        assert_eq!(report.profiles[0].holes.len(), 1);
+        assert!(report.profiles[1].holes.is_empty());
+
+        let mesh = tessellate_solid(&extrude_sketch(&plate, 0.5).unwrap(), 0.01);
+        assert!(mesh.validate().is_printable());
+        assert!(signed_volume(&mesh) > 0.0);
+    }
+
+    #[test]
+    fn gaps_are_reported_for_open_chains() {
+        let almost = sketch(vec![
+            SketchEntity::Line {
+                a: [0.0, 0.0],
+                b: [1.0, 0.0],
+            },
+            SketchEntity::Line {
+                a: [1.0, 0.0],
+                b: [1.0, 1.0],
+            },
+            SketchEntity::Line {
+                a: [1.0, 1.0],
+                b: [0.0, 0.1],
+            },
+        ]);
+        let report = detect_profiles(&almost);
+        assert!(report.profiles.is_empty());
+        assert_eq!(report.open_chains.len(), 1);
+        assert_eq!(report.gaps.len(), 1);
+        assert!((report.gaps[0].distance - 0.1).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn extrudes_closed_spline() {
+        let blob = sketch(vec![SketchEntity::Spline {
+            points: vec![[0.0, 0.0], [2.0, -0.5], [3.0, 1.0], [1.5, 2.5], [-0.5, 1.5]],
+            closed: true,
+        }]);
+        let solid = extrude_sketch(&blob, 1.0).unwrap();
+        let mesh = crate::tessellate_solid(&solid, 0.01);
+        assert!(mesh.validate().is_printable());
+        assert!(signed_volume(&mesh) > 0.0);
+    }
+
+    #[test]
+    fn thickens_flat_sheet_both_ways() {
+        let disk = sketch(vec![SketchEntity::Circle {
+            center: [0.0, 0.0],
+            radius: 1.0,
+        }]);
+        let sheet: truck_modeling::Shell = sketch_faces(&disk).unwrap().into_iter().collect();
+        for thickness in [0.5, -0.5] {
+            let solid = crate::thicken(&sheet, thickness).unwrap();
+            let mesh = crate::tessellate_solid(&solid, 0.01);
+            assert!(mesh.validate().is_printable());
+            assert!(signed_volume(&mesh) > 0.0);
+        }
+    }
+
+    #[test]
+    fn open_profile_is_rejected() {
+        let open = sketch(vec![SketchEntity::Line {
+            a: [0.0, 0.0],
+            b: [1.0, 0.0],
+        }]);
+        assert!(matches!(
+            extrude_sketch(&open, 1.0),
+            Err(GeomError::NoClosedProfile)
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+//! Text outlines as sketch profiles.
+
+use crate::{extrude_sketch, GeomError};
+use cad_core::{Sketch, SketchEntity, SketchPlane};
+use truck_modeling::Solid;
+use ttf_parser::{Face, OutlineBuilder};
+
+/// Line segments used to flatten each glyph curve.
+const CURVE_SEGMENTS: usize = 8;
+
+/// Lays out `text` with a TrueType/OpenType font and returns glyph outlines
+/// as line entities in plane coordinates.
+///
+/// `height` is the em size in model units; the baseline of the first line
+/// sits on `v = 0` and `\n` starts a new line below it.
+pub fn text_entities(font: &[u8], text: &str, height: f32) -> Result<Vec<SketchEntity>, GeomError> {
+    if height <= 0.0 || !height.is_finite() {
+        return Err(GeomError::InvalidFeature("text height must be positive"));
+    }
+    let face = Face::parse(font, 0).map_err(|err| GeomError::Font(err.to_string()))?;
+    let scale = height / face.units_per_em() as f32;
+    let line_advance =
+        (face.ascender() as f32 - face.descender() as f32 + face.line_gap() as f32) * scale;
+
+    let mut outline = Outline {
+        scale,
+        origin: [0.0, 0.0],
+        start: [0.0, 0.0],
+        pen: [0.0, 0.0],
+        entities: Vec::new(),
+    };
+    for ch in text.chars() {
+        if ch == '\n' {
+            outline.origin = [0.0, outline.origin[1] - line_advance];
+            continue;
+        }
+        let Some(glyph) = face.glyph_index(ch) else {
+            continue;
+        };
+        face.outline_glyph(glyph, &mut outline);
+        let advance = face.glyph_hor_advance(glyph).unwrap_or(0) as f32 * scale;
+        outline.origin[0] += advance;
+    }
+    Ok(outline.entities)
+}
+
+/// Extrudes text outlines on a plane, e.g. to emboss a label onto a part.
+/// A negative `depth` extrudes against the plane normal (for engraving).
+pub fn extrude_text(
+    font: &[u8],
+    text: &str,
+    height: f32,
+    depth: f32,
+    plane: SketchPlane,
+) -> Result<Solid, GeomError> {
+    let sketch = Sketch {
+        id: 0,
+        name: text.to_string(),
+        plane,
+        entities: text_entities(font, text, height)?,
+        references: Vec::new(),
+    };
+    extrude_sketch(&sketch, depth)
+}
+
+struct Outline {
+    scale: f32,
+    origin: [f32; 2],
+    start: [f32; 2],
+    pen: [f32; 2],
+    entities: Vec<SketchEntity>,
+}
+
+impl Outline {
+    fn point(&self, x: f32, y: f32) -> [f32; 2] {
+        [
+            self.origin[0] + x * self.scale,
+            self.origin[1] + y * self.scale,
+        ]
+    }
+
+    fn segment_to(&mut self, p: [f32; 2]) {
+        if p != self.pen {
+            self.entities.push(SketchEntity::Line { a: self.pen, b: p });
+            self.pen = p;
+        }
+    }
+
+    fn curve(&mut self, eval: impl Fn(f32) -> [f32; 2]) {
+        for i in 1..=CURVE_SEGMENTS {
+            self.segment_to(eval(i as f32 / CURVE_SEGMENTS as f32));
+        }
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = self.point(x, y);
+        self.pen = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.segment_to(p);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (p0, p1, p2) = (self.pen, self.point(x1, y1), self.point(x, y));
+        self.curve(|t| {
+            let s = 1.0 - t;
+            std::array::from_fn(|k| s * s * p0[k] + 2.0 * s * t * p1[k] + t * t * p2[k])
+        });
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (p0, p1, p2, p3) = (
+            self.pen,
+            self.point(x1, y1),
+            self.point(x2, y2),
+            self.point(x, y),
+        );
+        self.curve(|t| {
+            let s = 1.0 - t;
+            std::array::from_fn(|k| {
+                s * s * s * p0[k]
+                    + 3.0 * s * s * t * p1[k]
+                    + 3.0 * s * t * t * p2[k]
+                    + t * t * t * p3[k]
+            })
+        });
+    }
+
+    fn close(&mut self) {
+        let start = self.start;
+        self.segment_to(start);
+    }
+}
@@ -0,0 +1,168 @@
+//! Mesh validation for printable (closed, manifold) geometry.
+
+use crate::{position_key, TriMesh};
+use glam::Vec3;
+use std::collections::HashMap;
+
+/// Problems found by [`TriMesh::validate`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshReport {
+    pub triangle_count: usize,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: usize,
+    /// Edges used by a single triangle (holes in the shell).
+    pub boundary_edges: usize,
+    /// Edges whose two triangles traverse them in the same direction.
+    pub misoriented_edges: usize,
+    /// Triangles with (near) zero area.
+    pub degenerate_triangles: usize,
+    /// Triangles whose winding disagrees with their vertex normals.
+    pub flipped_triangles: usize,
+}
+
+impl MeshReport {
+    /// Closed, manifold and consistently oriented.
+    pub fn is_watertight(&self) -> bool {
+        self.non_manifold_edges == 0 && self.boundary_edges == 0 && self.misoriented_edges == 0
+    }
+
+    /// Watertight with no degenerate or flipped triangles; safe for STL/3MF.
+    pub fn is_printable(&self) -> bool {
+        self.is_watertight() && self.degenerate_triangles == 0 && self.flipped_triangles == 0
+    }
+}
+
+impl TriMesh {
+    pub fn validate(&self) -> MeshReport {
+        let mut report = MeshReport::default();
+        // Undirected edge -> (forward uses, backward uses).
+        let mut edges: HashMap<([i32; 3], [i32; 3]), (u32, u32)> = HashMap::new();
+
+        for tri in self.indices.chunks_exact(3) {
+            report.triangle_count += 1;
+            let p = [
+                Vec3::from_array(self.positions[tri[0] as usize]),
+                Vec3::from_array(self.positions[tri[1] as usize]),
+                Vec3::from_array(self.positions[tri[2] as usize]),
+            ];
+            let keys = p.map(position_key);
+
+            let cross = (p[1] - p[0]).cross(p[2] - p[0]);
+            let longest = (p[1] - p[0])
+                .length_squared()
+                .max((p[2] - p[1]).length_squared())
+                .max((p[0] - p[2]).length_squared());
+            if keys[0] == keys[1]
+                || keys[1] == keys[2]
+                || keys[2] == keys[0]
+                || cross.length_squared() <= 1.0e-12 * longest * longest
+            {
+                report.degenerate_triangles += 1;
+                continue;
+            }
+
+            let stored: Vec3 = tri
+                .iter()
+                .filter_map(|&idx| self.normals.get(idx as usize))
+                .map(|n| Vec3::from_array(*n))
+                .sum();
+            if stored.length_squared() > 1.0e-12 && stored.dot(cross) < 0.0 {
+                report.flipped_triangles += 1;
+            }
+
+            for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+                let (a, b) = (keys[a], keys[b]);
+                if a < b {
+                    edges.entry((a, b)).or_default().0 += 1;
+                } else {
+                    edges.entry((b, a)).or_default().1 += 1;
+                }
+            }
+        }
+
+        for (forward, backward) in edges.into_values() {
+            match forward + backward {
+                1 => report.boundary_edges += 1,
+                2 if forward != 1 => report.misoriented_edges += 1,
+                2 => {}
+                _ => report.non_manifold_edges += 1,
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        make_box, make_cylinder, make_prism, make_tube, make_wedge, tessellate_solid, GeomScene,
+    };
+    use cad_core::{ModelIssue, ObjectKind, Transform};
+
+    #[test]
+    fn closed_solids_are_printable() {
+        let report = tessellate_solid(&make_box(1.0, 2.0, 3.0).unwrap(), 0.01).validate();
+        assert!(report.is_printable(), "{report:?}");
+        let report = tessellate_solid(&make_cylinder(0.5, 1.5).unwrap(), 0.01).validate();
+        assert!(report.is_printable(), "{report:?}");
+        let report = tessellate_solid(&make_wedge(2.0, 1.0, 0.5).unwrap(), 0.01).validate();
+        assert!(report.is_printable(), "{report:?}");
+        let report = tessellate_solid(&make_prism(6, 1.0, 2.0).unwrap(), 0.01).validate();
+        assert!(report.is_printable(), "{report:?}");
+        let report = tessellate_solid(&make_tube(1.0, 0.6, 2.0).unwrap(), 0.01).validate();
+        assert!(report.is_printable(), "{report:?}");
+    }
+
+    #[test]
+    fn degenerate_primitives_are_rejected() {
+        assert!(make_box(0.0, 1.0, 1.0).is_err());
+        assert!(make_cylinder(-0.5, 1.0).is_err());
+        assert!(make_tube(1.0, 1.0, 1.0).is_err());
+        assert!(make_prism(2, 1.0, 1.0).is_err());
+        assert!(make_wedge(1.0, f64::NAN, 1.0).is_err());
+
+        let mut scene = GeomScene::new();
+        assert!(scene.add_box(1.0, 0.0, 1.0).is_err());
+        assert!(scene.model().objects().is_empty());
+        let id = scene.add_cylinder(0.5, 1.0).unwrap();
+        let kind = ObjectKind::Cylinder { r: 0.5, h: -1.0 };
+        assert!(scene.update_primitive(id, kind).is_err());
+        assert!(matches!(
+            scene.model().objects()[0].kind,
+            ObjectKind::Cylinder { h, .. } if h == 1.0
+        ));
+    }
+
+    #[test]
+    fn open_and_flipped_meshes_are_reported() {
+        let mut mesh = tessellate_solid(&make_box(1.0, 1.0, 1.0).unwrap(), 0.01);
+        mesh.indices.truncate(mesh.indices.len() - 3);
+        let report = mesh.validate();
+        assert_eq!(report.boundary_edges, 3);
+        assert!(!report.is_watertight());
+
+        let mut mesh = tessellate_solid(&make_box(1.0, 1.0, 1.0).unwrap(), 0.01);
+        mesh.indices.swap(0, 1);
+        let report = mesh.validate();
+        assert_eq!(report.flipped_triangles, 1);
+        assert!(!report.is_printable());
+    }
+
+    #[test]
+    fn model_validation_reports_issues() {
+        let mut scene = GeomScene::new();
+        let id = scene.add_box(1.0, 1.0, 1.0).unwrap();
+        assert!(scene.model().validate().is_empty());
+
+        let broken = Transform {
+            translation: [f32::NAN, 0.0, 0.0],
+            ..Transform::default()
+        };
+        scene.set_object_transform(id, broken);
+        assert_eq!(
+            scene.model().validate(),
+            vec![ModelIssue::NonFiniteTransform(id)]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! Writes the protocol's JSON Schemas:
+//!
+//! ```text
+//! cargo run -p cad-protocol --features schema --example schema -- <dir>
+//! ```
+//!
+//! producing `client.schema.json` and `server.schema.json` in `<dir>`, the
+//! current directory by default.
+
+use std::path::PathBuf;
+
+fn main() -> std::io::Result<()> {
+    let dir = PathBuf::from(std::env::args().nth(1).unwrap_or_else(|| ".".to_string()));
+    let schemas = [
+        ("client.schema.json", cad_protocol::client_schema()),
+        ("server.schema.json", cad_protocol::server_schema()),
+    ];
+    for (name, schema) in schemas {
+        let path = dir.join(name);
+        let json = serde_json::to_string_pretty(&schema).map_err(std::io::Error::other)?;
+        std::fs::write(&path, json + "\n")?;
+        println!("wrote {}", path.display());
+    }
+    Ok(())
+}
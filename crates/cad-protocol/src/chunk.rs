@@ -0,0 +1,151 @@
+//! Splitting large frames into chunks and putting them back together.
+//!
+//! A frame of more than [`CHUNK_THRESHOLD`] bytes travels as a begin frame,
+//! data chunks of at most [`CHUNK_LEN`] bytes, in order, and an end frame,
+//! all binary and little-endian:
+//!
+//! | bytes | field                                               |
+//! |-------|-----------------------------------------------------|
+//! | 4     | magic `PCHK`                                        |
+//! | 1     | kind: `0` begin, `1` chunk, `2` end                 |
+//! | 1     | begin: content, `0` text or `1` binary; else zero   |
+//! | 2     | reserved, zero                                      |
+//! | 8     | transfer id, unique per sender                      |
+//! | 8     | begin: total length; chunk: offset; end: zero       |
+//! | ..    | chunk: data                                         |
+//!
+//! Chunking applies to the finished frame, so compressed frames are split
+//! after compressing and joined before decompressing.
+
+use crate::{Frame, FrameError};
+use std::collections::HashMap;
+
+pub const CHUNK_FRAME_MAGIC: [u8; 4] = *b"PCHK";
+/// Frames longer than this are chunked.
+pub const CHUNK_THRESHOLD: usize = 1024 * 1024;
+pub const CHUNK_LEN: usize = 256 * 1024;
+/// Largest transfer a [`Reassembler`] accepts.
+pub const MAX_TRANSFER_LEN: u64 = 512 * 1024 * 1024;
+const HEADER_LEN: usize = 24;
+
+const BEGIN: u8 = 0;
+const CHUNK: u8 = 1;
+const END: u8 = 2;
+
+fn header(kind: u8, content: u8, transfer: u64, value: u64) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN);
+    out.extend_from_slice(&CHUNK_FRAME_MAGIC);
+    out.extend_from_slice(&[kind, content, 0, 0]);
+    out.extend_from_slice(&transfer.to_le_bytes());
+    out.extend_from_slice(&value.to_le_bytes());
+    out
+}
+
+/// Splits outgoing frames, numbering transfers.
+#[derive(Debug, Default)]
+pub struct Chunker {
+    next_transfer: u64,
+}
+
+impl Chunker {
+    /// The frames to send for `frame`: the frame itself when it is small
+    /// enough, otherwise a whole transfer.
+    pub fn split(&mut self, frame: Frame) -> Vec<Frame> {
+        let (content, payload) = match &frame {
+            Frame::Text(text) => (0, text.as_bytes()),
+            Frame::Binary(bytes) => (1, bytes.as_slice()),
+        };
+        if payload.len() <= CHUNK_THRESHOLD {
+            return vec![frame];
+        }
+        let transfer = self.next_transfer;
+        self.next_transfer += 1;
+
+        let mut frames = Vec::with_capacity(payload.len().div_ceil(CHUNK_LEN) + 2);
+        let total = payload.len() as u64;
+        frames.push(Frame::Binary(header(BEGIN, content, transfer, total)));
+        for (i, data) in payload.chunks(CHUNK_LEN).enumerate() {
+            let mut out = header(CHUNK, 0, transfer, (i * CHUNK_LEN) as u64);
+            out.extend_from_slice(data);
+            frames.push(Frame::Binary(out));
+        }
+        frames.push(Frame::Binary(header(END, 0, transfer, 0)));
+        frames
+    }
+}
+
+#[derive(Debug)]
+struct Partial {
+    text: bool,
+    len: u64,
+    bytes: Vec<u8>,
+}
+
+/// Joins incoming transfers back into the frames they were split from.
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    partial: HashMap<u64, Partial>,
+}
+
+impl Reassembler {
+    /// Takes one received frame. Frames that are not part of a transfer come
+    /// straight back; chunk frames return `None` until their transfer ends.
+    /// A broken transfer is dropped.
+    pub fn push(&mut self, frame: Frame) -> Result<Option<Frame>, FrameError> {
+        let bytes = match &frame {
+            Frame::Binary(bytes) if bytes.starts_with(&CHUNK_FRAME_MAGIC) => bytes,
+            _ => return Ok(Some(frame)),
+        };
+        let header = bytes.get(..HEADER_LEN).ok_or(FrameError::Truncated)?;
+        let transfer = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let value = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        match header[4] {
+            BEGIN => {
+                let text = match header[5] {
+                    0 => true,
+                    1 => false,
+                    other => return Err(FrameError::UnknownContent(other)),
+                };
+                if value > MAX_TRANSFER_LEN {
+                    return Err(FrameError::TransferTooLarge(value));
+                }
+                let partial = Partial {
+                    text,
+                    len: value,
+                    bytes: Vec::new(),
+                };
+                self.partial.insert(transfer, partial);
+                Ok(None)
+            }
+            CHUNK => {
+                let partial = self
+                    .partial
+                    .get_mut(&transfer)
+                    .ok_or(FrameError::UnknownTransfer(transfer))?;
+                let data = &bytes[HEADER_LEN..];
+                if value != partial.bytes.len() as u64 || value + data.len() as u64 > partial.len {
+                    self.partial.remove(&transfer);
+                    return Err(FrameError::ChunkOutOfOrder(transfer));
+                }
+                partial.bytes.extend_from_slice(data);
+                Ok(None)
+            }
+            END => {
+                let partial = self
+                    .partial
+                    .remove(&transfer)
+                    .ok_or(FrameError::UnknownTransfer(transfer))?;
+                if partial.bytes.len() as u64 != partial.len {
+                    return Err(FrameError::Truncated);
+                }
+                if !partial.text {
+                    return Ok(Some(Frame::Binary(partial.bytes)));
+                }
+                String::from_utf8(partial.bytes)
+                    .map(|text| Some(Frame::Text(text)))
+                    .map_err(|err| FrameError::Json(err.to_string()))
+            }
+            other => Err(FrameError::UnknownChunkKind(other)),
+        }
+    }
+}
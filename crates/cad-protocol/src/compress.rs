@@ -0,0 +1,140 @@
+//! Compression of large frames, negotiated in the hello exchange.
+//!
+//! A client lists the codecs it can decode in [`crate::ClientMsg::Hello`];
+//! the server picks one with [`Compression::negotiate`], names it in
+//! [`crate::ServerMsg::HelloAck`] and from then on may wrap any frame of at
+//! least [`COMPRESS_MIN_LEN`] bytes in a binary compressed frame:
+//!
+//! | bytes | field                                         |
+//! |-------|-----------------------------------------------|
+//! | 4     | magic `PZIP`                                  |
+//! | 1     | codec: `0` deflate, `1` zstd                  |
+//! | 1     | content: `0` text frame, `1` binary frame     |
+//! | 2     | reserved, zero                                |
+//! | ..    | compressed frame                              |
+
+use crate::{Frame, FrameError};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+pub const COMPRESSED_FRAME_MAGIC: [u8; 4] = *b"PZIP";
+/// Frames shorter than this are sent as they are.
+pub const COMPRESS_MIN_LEN: usize = 16 * 1024;
+/// Upper bound on a decompressed frame, so a small frame cannot expand
+/// without limit.
+pub const MAX_DECOMPRESSED_LEN: usize = 256 * 1024 * 1024;
+const HEADER_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Compression {
+    /// Raw deflate (RFC 1951).
+    Deflate,
+    Zstd,
+}
+
+impl Compression {
+    /// Codecs the server accepts, most preferred first.
+    pub const PREFERRED: [Compression; 2] = [Compression::Zstd, Compression::Deflate];
+
+    /// The first preferred codec the peer offered; `None` leaves frames
+    /// uncompressed.
+    pub fn negotiate(offered: &[Compression]) -> Option<Compression> {
+        Self::PREFERRED
+            .into_iter()
+            .find(|codec| offered.contains(codec))
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            Self::Deflate => 0,
+            Self::Zstd => 1,
+        }
+    }
+
+    pub fn encode(self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Deflate => miniz_oxide::deflate::compress_to_vec(bytes, 6),
+            Self::Zstd => ruzstd::encoding::compress_to_vec(
+                bytes,
+                ruzstd::encoding::CompressionLevel::Fastest,
+            ),
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> Result<Vec<u8>, FrameError> {
+        let corrupt = |err: String| FrameError::Decompress(err);
+        match self {
+            Self::Deflate => {
+                miniz_oxide::inflate::decompress_to_vec_with_limit(bytes, MAX_DECOMPRESSED_LEN)
+                    .map_err(|err| corrupt(err.to_string()))
+            }
+            Self::Zstd => {
+                let decoder = ruzstd::decoding::StreamingDecoder::new(bytes)
+                    .map_err(|err| corrupt(err.to_string()))?;
+                let mut out = Vec::new();
+                decoder
+                    .take(MAX_DECOMPRESSED_LEN as u64 + 1)
+                    .read_to_end(&mut out)
+                    .map_err(|err| corrupt(err.to_string()))?;
+                if out.len() > MAX_DECOMPRESSED_LEN {
+                    return Err(corrupt("frame expands past the size limit".to_string()));
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+impl Frame {
+    fn payload(&self) -> &[u8] {
+        match self {
+            Self::Text(text) => text.as_bytes(),
+            Self::Binary(bytes) => bytes,
+        }
+    }
+
+    /// Wraps the frame in a compressed frame, leaving it as it is when it is
+    /// short or does not shrink.
+    pub fn compress(self, codec: Compression) -> Frame {
+        let payload = self.payload();
+        if payload.len() < COMPRESS_MIN_LEN {
+            return self;
+        }
+        let content = match self {
+            Self::Text(_) => 0,
+            Self::Binary(_) => 1,
+        };
+        let body = codec.encode(payload);
+        if HEADER_LEN + body.len() >= payload.len() {
+            return self;
+        }
+        let mut out = Vec::with_capacity(HEADER_LEN + body.len());
+        out.extend_from_slice(&COMPRESSED_FRAME_MAGIC);
+        out.extend_from_slice(&[codec.tag(), content, 0, 0]);
+        out.extend_from_slice(&body);
+        Frame::Binary(out)
+    }
+
+    /// Unwraps a compressed frame; other frames come back unchanged.
+    pub fn decompress(self) -> Result<Frame, FrameError> {
+        let bytes = match &self {
+            Self::Binary(bytes) if bytes.starts_with(&COMPRESSED_FRAME_MAGIC) => bytes,
+            _ => return Ok(self),
+        };
+        let header = bytes.get(..HEADER_LEN).ok_or(FrameError::Truncated)?;
+        let codec = match header[4] {
+            0 => Compression::Deflate,
+            1 => Compression::Zstd,
+            other => return Err(FrameError::UnknownCompression(other)),
+        };
+        let inner = codec.decode(&bytes[HEADER_LEN..])?;
+        match header[5] {
+            0 => String::from_utf8(inner)
+                .map(Frame::Text)
+                .map_err(|err| FrameError::Decompress(err.to_string())),
+            1 => Ok(Frame::Binary(inner)),
+            other => Err(FrameError::UnknownContent(other)),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Last-writer-wins for concurrent edits of one object, such as two users
+//! dragging it at the same time.
+//!
+//! A [`crate::ClientMsg::SetTransform`] can carry `base_seq`, the last
+//! document update its client had applied when it made the edit. With the
+//! client's peer id that makes a [`Stamp`], a Lamport timestamp: an edit
+//! made after seeing another has the greater stamp, and of two edits made
+//! without seeing each other the one from the greater peer id wins. The
+//! server stamps every edit that commits, moves, undos, features and
+//! imports alike, keeps the stamp of each object's last write in
+//! [`LastWrites`] and refuses moves behind it, so the document ends up the
+//! same whatever order concurrent edits arrive in.
+
+use crate::PeerId;
+use cad_core::ObjectId;
+use std::collections::BTreeMap;
+
+/// When an edit was made, ordered by `seq` and then by `peer`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Stamp {
+    /// The last document update the editing client had applied.
+    pub seq: u64,
+    pub peer: PeerId,
+}
+
+/// The stamp of the last write to each object of one document.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LastWrites {
+    stamps: BTreeMap<ObjectId, Stamp>,
+}
+
+impl LastWrites {
+    /// Whether an edit of `id` stamped `stamp` is no older than the last
+    /// write to it. Equal stamps come from one client sending edits faster
+    /// than it hears back, in order, so the later one wins.
+    pub fn wins(&self, id: ObjectId, stamp: Stamp) -> bool {
+        self.stamps.get(&id).is_none_or(|last| stamp >= *last)
+    }
+
+    /// Records a write to `id` that was applied.
+    pub fn record(&mut self, id: ObjectId, stamp: Stamp) {
+        self.stamps.insert(id, stamp);
+    }
+
+    /// Drops what is known of `id`, e.g. once it is deleted.
+    pub fn forget(&mut self, id: ObjectId) {
+        self.stamps.remove(&id);
+    }
+}
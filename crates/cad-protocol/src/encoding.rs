@@ -0,0 +1,73 @@
+//! Message encodings other than JSON, negotiated in the hello exchange.
+//!
+//! A client lists the encodings it can decode in [`crate::ClientMsg::Hello`];
+//! the server picks one with [`Encoding::negotiate`], names it in
+//! [`crate::ServerMsg::HelloAck`] and encodes every later message with it.
+//! Either side may send MessagePack at any time, since frames say how they
+//! are encoded: JSON travels as text, MessagePack as a binary frame:
+//!
+//! | bytes | field                                 |
+//! |-------|---------------------------------------|
+//! | 4     | magic `PMPK`                          |
+//! | ..    | MessagePack envelope, structs as maps |
+//!
+//! Both encodings carry the same serde types, so messages keep their field
+//! names and `type` tags. Meshes keep their own binary frame either way.
+
+use crate::{Frame, FrameError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub const MSGPACK_FRAME_MAGIC: [u8; 4] = *b"PMPK";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl Encoding {
+    /// Encodings the server accepts, most preferred first.
+    pub const PREFERRED: [Encoding; 2] = [Encoding::MessagePack, Encoding::Json];
+
+    /// The first preferred encoding the peer offered, or JSON, which every
+    /// peer reads.
+    pub fn negotiate(offered: &[Encoding]) -> Encoding {
+        Self::PREFERRED
+            .into_iter()
+            .find(|encoding| offered.contains(encoding))
+            .unwrap_or_default()
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Frame, FrameError> {
+        match self {
+            Self::Json => serde_json::to_string(value)
+                .map(Frame::Text)
+                .map_err(|err| FrameError::Json(err.to_string())),
+            Self::MessagePack => {
+                let mut out = MSGPACK_FRAME_MAGIC.to_vec();
+                // Named fields: the `type` tags and flattened envelopes need
+                // map-shaped structs to decode.
+                rmp_serde::encode::write_named(&mut out, value)
+                    .map_err(|err| FrameError::MessagePack(err.to_string()))?;
+                Ok(Frame::Binary(out))
+            }
+        }
+    }
+}
+
+/// Decodes a JSON text frame or a MessagePack binary frame.
+pub(crate) fn decode<T: DeserializeOwned>(frame: &Frame) -> Result<T, FrameError> {
+    match frame {
+        Frame::Text(text) => {
+            serde_json::from_str(text).map_err(|err| FrameError::Json(err.to_string()))
+        }
+        Frame::Binary(bytes) => {
+            let body = bytes
+                .strip_prefix(&MSGPACK_FRAME_MAGIC)
+                .ok_or(FrameError::BadMagic)?;
+            rmp_serde::from_slice(body).map_err(|err| FrameError::MessagePack(err.to_string()))
+        }
+    }
+}
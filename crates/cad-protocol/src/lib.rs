@@ -1,40 +1,872 @@
 //! Client <-> server message protocol.
 
+use cad_core::{
+    Anchor, CommandError, FeatureOp, Model, ModelDelta, ModelObject, ObjectId, ObjectKind,
+    Transform,
+};
 use serde::{Deserialize, Serialize};
 
+mod chunk;
+mod compress;
+mod conflict;
+mod encoding;
+mod mesh;
+mod presence;
+mod review;
+#[cfg(feature = "schema")]
+mod schema;
+mod sync;
+mod throttle;
+mod upload;
+
+pub use chunk::{
+    Chunker, Reassembler, CHUNK_FRAME_MAGIC, CHUNK_LEN, CHUNK_THRESHOLD, MAX_TRANSFER_LEN,
+};
+pub use compress::{Compression, COMPRESSED_FRAME_MAGIC, COMPRESS_MIN_LEN, MAX_DECOMPRESSED_LEN};
+pub use conflict::{LastWrites, Stamp};
+pub use encoding::{Encoding, MSGPACK_FRAME_MAGIC};
+pub use mesh::{FrameError, MeshData, MeshTarget, MESH_FRAME_MAGIC, MESH_FRAME_VERSION};
+pub use presence::{Peer, PeerId, RemotePeer, Roster};
+pub use review::{review_text, Comment, CommentId, MAX_REVIEW_TEXT_LEN};
+#[cfg(feature = "schema")]
+pub use schema::{client_schema, server_schema};
+pub use sync::{SyncAction, SyncState};
+pub use throttle::{Throttle, MAX_STREAM_RATE};
+pub use upload::{ImportFormat, UploadData, UPLOAD_FRAME_MAGIC};
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "type")]
 pub enum ClientMsg {
     Hello {
         client_version: String,
+        /// Codecs the client can decode; see [`Compression`].
+        #[serde(default)]
+        compression: Vec<Compression>,
+        /// Encodings the client can decode besides JSON; see [`Encoding`].
+        #[serde(default)]
+        encoding: Vec<Encoding>,
+        /// The [`ServerMsg::HelloAck`] session of a dropped connection, to
+        /// pick up where it left off; answered by [`ServerMsg::SessionResumed`]
+        /// or [`ErrorCode::UnknownSession`].
+        #[serde(default)]
+        resume: Option<String>,
+    },
+    /// Presents an access token, answered by [`ServerMsg::AuthResult`].
+    Authenticate {
+        token: String,
     },
+    /// Shorthand for [`ClientMsg::AddPrimitive`] with a box.
     AddBox {
         w: f32,
         h: f32,
         d: f32,
     },
+    /// Shorthand for [`ClientMsg::AddPrimitive`] with a cylinder.
     AddCylinder {
         r: f32,
         h: f32,
     },
+    AddPrimitive {
+        kind: ObjectKind,
+    },
+    SetTransform {
+        id: ObjectId,
+        transform: Transform,
+        /// The `seq` of the last document update the client had applied,
+        /// to settle concurrent moves of the object; see [`LastWrites`].
+        /// Without it the transform applies over any other.
+        #[serde(default)]
+        base_seq: Option<u64>,
+    },
+    DeleteObject {
+        id: ObjectId,
+    },
+    /// Reverts the document's last edit, whoever made it. Every client with
+    /// the document open is told with [`ServerMsg::HistoryApplied`] and a
+    /// delta.
+    Undo,
+    /// Re-applies the last undone edit; see [`ClientMsg::Undo`].
+    Redo,
+    /// Asks for the whole document, answered by [`ServerMsg::ModelSnapshot`].
+    RequestModel,
+    /// Answered by [`ServerMsg::DocumentList`] with the documents whose name
+    /// contains `query`, ignoring ASCII case; every document when it is
+    /// empty. Stored documents are listed whether or not they are open.
+    ListDocuments {
+        #[serde(default)]
+        query: String,
+    },
+    /// Creates an empty document and opens it, as [`ClientMsg::OpenDocument`].
+    CreateDocument {
+        name: String,
+    },
+    /// Switches the client to another document, answered by
+    /// [`ServerMsg::DocumentOpened`], its [`ServerMsg::ModelSnapshot`] and
+    /// comments, and the [`ServerMsg::Peers`] editing it, or
+    /// [`ErrorCode::Forbidden`] if the user has no role in it. Clients start
+    /// out in the server's first document.
+    OpenDocument {
+        id: DocumentId,
+    },
+    /// Leaves the open document; edits are refused until another is opened.
+    CloseDocument {
+        id: DocumentId,
+    },
+    /// Stores the open document as a new revision, answered by
+    /// [`ServerMsg::DocumentSaved`]. Needs [`Capabilities::storage`].
+    SaveDocument {
+        /// PNG preview replacing the stored one, served at
+        /// [`DocumentSummary::thumbnail`].
+        #[serde(default)]
+        thumbnail: Option<Vec<u8>>,
+    },
+    /// Answered by [`ServerMsg::RevisionList`] if the user has a role in
+    /// document `id`, else [`ErrorCode::Forbidden`].
+    ListRevisions {
+        id: DocumentId,
+    },
+    /// Puts the open document back as it was at `revision`, stored as its
+    /// next revision. Every client of it gets [`ServerMsg::RevisionRestored`]
+    /// and a new [`ServerMsg::ModelSnapshot`]; the undo history starts over.
+    RestoreRevision {
+        revision: RevisionId,
+    },
     RequestHeavy {
         kind: String,
         payload: Option<String>,
     },
+    /// Exports bodies server-side, every visible one when `objects` is
+    /// empty. Answered by [`ServerMsg::JobAccepted`], then
+    /// [`ServerMsg::ExportReady`] or a failed [`ServerMsg::JobResult`].
+    RequestExport {
+        format: ExportFormat,
+        #[serde(default)]
+        objects: Vec<ObjectId>,
+    },
+    /// Tessellates server-side, sparing the browser large models: every
+    /// visible body merged in world space when `objects` is empty, else each
+    /// listed body in its local space. Answered by
+    /// [`ServerMsg::JobAccepted`], progress, one [`ServerMsg::MeshData`] per
+    /// mesh as it is done, then a [`JobPayload::Text`] result.
+    RequestMesh {
+        #[serde(default)]
+        objects: Vec<ObjectId>,
+        /// Chordal tolerance in document units; the server picks a fine one
+        /// when unset.
+        #[serde(default)]
+        tolerance: Option<f64>,
+    },
+    /// Stops a job this client started, waiting or running. Answered by the
+    /// job's [`JobPayload::Canceled`] result.
+    CancelJob {
+        job_id: u64,
+    },
+    /// Adds a feature to a body, computed on the server's job worker so the
+    /// browser never waits on the kernel. Features outside
+    /// [`Capabilities::feature_ops`] are refused with
+    /// [`ErrorCode::Unsupported`] before they are queued. Answered by
+    /// [`ServerMsg::JobAccepted`], the body's new [`ServerMsg::MeshData`]
+    /// and [`ServerMsg::ModelDelta`], then a [`JobPayload::Text`] result;
+    /// other clients of the document are notified like for any edit.
+    AddFeature {
+        id: ObjectId,
+        op: FeatureOp,
+    },
+    /// Announces a file to import, sent next as an [`UploadData`] frame of
+    /// `size` bytes. Once the file has arrived it is read on the job queue:
+    /// answered by [`ServerMsg::JobAccepted`], then
+    /// [`ServerMsg::ImportResult`] or a failed [`ServerMsg::JobResult`].
+    Upload {
+        upload_id: u64,
+        name: String,
+        format: ImportFormat,
+        size: u64,
+    },
+    /// Sets how other peers see this client; `None` keeps the current color.
+    SetPresence {
+        name: String,
+        color: Option<[u8; 3]>,
+    },
+    /// Shares the client's selection and pointer with the other peers.
+    SetSelection {
+        selection: Vec<ObjectId>,
+        cursor: Option<[f32; 3]>,
+    },
+    /// Sends a chat message to every client of the open document, this one
+    /// included, as [`ServerMsg::Chat`].
+    Chat {
+        text: String,
+    },
+    /// Pins a comment to the document, announced to every client of it
+    /// with [`ServerMsg::CommentAdded`].
+    AddComment {
+        anchor: Anchor,
+        text: String,
+    },
+    /// Resolves a comment; see [`ServerMsg::CommentRemoved`]. Only its
+    /// author and the document's owners may, others get
+    /// [`ErrorCode::Forbidden`].
+    RemoveComment {
+        id: CommentId,
+    },
+    /// Gives `user` a role in the open document, announced to every client
+    /// of it with [`ServerMsg::RoleChanged`]. Only owners may, and a
+    /// document keeps at least one.
+    SetRole {
+        user: String,
+        role: Role,
+    },
+    /// Heartbeat, answered by [`ServerMsg::Pong`]; `sent_ms` is the client's
+    /// clock in milliseconds.
+    Ping {
+        sent_ms: u64,
+    },
+    /// Answers [`ServerMsg::Ping`], echoing its timestamp.
+    Pong {
+        sent_ms: u64,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "type")]
 pub enum ServerMsg {
-    HelloAck,
-    Log { text: String },
-    JobAccepted { job_id: u64 },
-    JobResult { job_id: u64, payload: String },
+    HelloAck {
+        /// The codec later frames may be compressed with.
+        #[serde(default)]
+        compression: Option<Compression>,
+        /// How later messages are encoded.
+        #[serde(default)]
+        encoding: Encoding,
+        /// Whether the client must send [`ClientMsg::Authenticate`] first.
+        #[serde(default)]
+        auth_required: bool,
+        /// Empty when the server predates capabilities.
+        #[serde(default)]
+        capabilities: Capabilities,
+        /// Names this connection's session, which a reconnecting client
+        /// resumes with [`ClientMsg::Hello`]; `None` when the server
+        /// predates resuming.
+        #[serde(default)]
+        session: Option<String>,
+    },
+    /// `user` is the name the token belongs to; `None` if it was refused.
+    AuthResult {
+        user: Option<String>,
+    },
+    /// The session was restored: the client is authenticated as before and
+    /// gets its document again next. `jobs` are those of its jobs still
+    /// queued or running, which report here as usual; replies sent while it
+    /// was away follow.
+    SessionResumed {
+        selection: Vec<ObjectId>,
+        cursor: Option<[f32; 3]>,
+        jobs: Vec<u64>,
+    },
+    Log {
+        text: String,
+    },
+    JobAccepted {
+        job_id: u64,
+    },
+    /// Where a waiting job stands: `position` jobs are ahead of it, `0`
+    /// meaning it runs next. Sent on acceptance and whenever it moves, up
+    /// as jobs ahead start or down as more urgent ones overtake it.
+    JobQueued {
+        job_id: u64,
+        position: usize,
+    },
+    /// How busy the job queue is, sent to every client whenever it changes.
+    QueueStatus {
+        pending: usize,
+        running: usize,
+    },
+    /// A running job reached `stage`: `"validating"` its input,
+    /// `"computing"`, `"tessellating"` or `"encoding"` its result.
+    JobProgress {
+        job_id: u64,
+        /// 0 to 100.
+        percent: u8,
+        stage: String,
+    },
+    JobResult {
+        job_id: u64,
+        payload: JobPayload,
+    },
+    /// The bodies an upload added to the document.
+    ImportResult {
+        upload_id: u64,
+        objects: Vec<ObjectId>,
+    },
+    /// A finished export, for the browser to offer as a download.
+    ExportReady {
+        job_id: u64,
+        /// Suggested file name, e.g. `"export.stl"`.
+        name: String,
+        mime: String,
+        content: ExportContent,
+    },
+    /// A client request was rejected.
+    Error {
+        code: ErrorCode,
+        message: String,
+        /// The request that failed, when known.
+        #[serde(default)]
+        related_request: Option<RequestId>,
+        /// The request's field at fault, e.g. `"w"` of a
+        /// [`ClientMsg::AddBox`], when one is.
+        #[serde(default)]
+        field: Option<String>,
+    },
+    /// The authoritative document, e.g. for a client that just connected,
+    /// as of update `seq`.
+    ModelSnapshot {
+        seq: u64,
+        model: Box<Model>,
+    },
+    /// Object changes producing update `seq` from update `seq - 1`; see
+    /// [`SyncState`] for handling gaps.
+    ModelDelta {
+        seq: u64,
+        delta: ModelDelta,
+    },
+    DocumentList {
+        documents: Vec<DocumentSummary>,
+    },
+    /// The document the client now has open, followed by its snapshot.
+    DocumentOpened {
+        document: DocumentSummary,
+        /// What the client may do in it; `None` when the server predates
+        /// roles.
+        #[serde(default)]
+        role: Option<Role>,
+    },
+    /// `user` now has `role` in the open document.
+    RoleChanged {
+        user: String,
+        role: Role,
+    },
+    DocumentClosed {
+        id: DocumentId,
+    },
+    DocumentSaved {
+        id: DocumentId,
+        revision: RevisionId,
+    },
+    /// The stored revisions of a document, oldest first.
+    RevisionList {
+        id: DocumentId,
+        revisions: Vec<RevisionSummary>,
+    },
+    /// `peer` put the document back as it was at `revision`, which is now
+    /// stored again as `saved`. The snapshot follows.
+    RevisionRestored {
+        id: DocumentId,
+        revision: RevisionId,
+        saved: RevisionId,
+        peer: PeerId,
+    },
+    /// An undo or redo by `peer`, sent to every client of the document ahead
+    /// of its delta.
+    HistoryApplied {
+        step: HistoryStep,
+        peer: PeerId,
+        /// What the step changed, for display.
+        text: String,
+        can_undo: bool,
+        can_redo: bool,
+    },
+    /// Another client added a body at `index` in the object list, as part
+    /// of update `seq`. Clients of the document get these notifications for
+    /// each other's edits; see [`ServerMsg::notifications`].
+    ObjectAdded {
+        seq: u64,
+        peer: PeerId,
+        index: usize,
+        object: ModelObject,
+    },
+    /// Another client changed a body; `object` replaces it whole.
+    ObjectChanged {
+        seq: u64,
+        peer: PeerId,
+        object: ModelObject,
+    },
+    ObjectRemoved {
+        seq: u64,
+        peer: PeerId,
+        id: ObjectId,
+    },
+    /// Tessellated geometry; sent as a binary frame, see [`ServerMsg::to_frame`].
+    MeshData(MeshData),
+    /// Everyone connected to the document, this client included, sent once
+    /// it joins and again whenever it opens or closes a document; later
+    /// changes arrive as the other peer messages.
+    Peers {
+        you: PeerId,
+        peers: Vec<Peer>,
+    },
+    PeerJoined {
+        peer: Peer,
+    },
+    /// A peer changed its name or color.
+    PeerUpdated {
+        peer: Peer,
+    },
+    /// A peer disconnected or switched to another document.
+    PeerLeft {
+        id: PeerId,
+    },
+    PeerSelection {
+        id: PeerId,
+        selection: Vec<ObjectId>,
+        cursor: Option<[f32; 3]>,
+    },
+    /// A chat message, sent to every client of the document.
+    Chat {
+        peer: PeerId,
+        text: String,
+        /// Server clock, in milliseconds since the Unix epoch.
+        sent_ms: u64,
+    },
+    /// Every comment on the document, sent along with its snapshot.
+    Comments {
+        comments: Vec<Comment>,
+    },
+    /// Sent to every client of the document, the author included.
+    CommentAdded {
+        comment: Comment,
+        peer: PeerId,
+    },
+    CommentRemoved {
+        id: CommentId,
+        peer: PeerId,
+    },
+    /// Heartbeat, answered by [`ClientMsg::Pong`]; `sent_ms` is the server's
+    /// clock in milliseconds.
+    Ping {
+        sent_ms: u64,
+    },
+    /// Answers [`ClientMsg::Ping`], echoing its timestamp; the round trip is
+    /// the client's clock minus `sent_ms`.
+    Pong {
+        sent_ms: u64,
+        server_ms: u64,
+    },
+    /// The client streams faster than [`MAX_STREAM_RATE`]; its streamed
+    /// messages are dropped for the next `retry_after_ms`. Sent once per
+    /// burst, in reply to the first message dropped; see [`Throttle`].
+    SlowDown {
+        retry_after_ms: u64,
+    },
+}
+
+/// How often each side pings the other.
+pub const PING_INTERVAL_MS: u64 = 15_000;
+/// A peer silent for this long is considered gone.
+pub const PEER_TIMEOUT_MS: u64 = 45_000;
+
+/// Client-chosen id correlating a request with the replies to it.
+pub type RequestId = u64;
+
+/// Server-assigned id of a hosted document.
+pub type DocumentId = u64;
+
+/// Numbers a document's stored revisions, from 1.
+pub type RevisionId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DocumentSummary {
+    pub id: DocumentId,
+    pub name: String,
+    pub objects: usize,
+    /// Clients that have the document open.
+    pub clients: usize,
+    /// The latest stored revision; `None` if the document was never saved.
+    #[serde(default)]
+    pub revision: Option<RevisionId>,
+    /// Where to fetch the document's PNG preview, if it has one.
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+}
+
+/// What a user may do in a document, least first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Role {
+    /// Follows the model and its changes, and reviews: chat and comments.
+    Viewer,
+    /// Also edits; see [`ClientMsg::is_edit`].
+    Editor,
+    /// Also decides who else is what, with [`ClientMsg::SetRole`].
+    Owner,
+}
+
+impl Role {
+    pub fn can_edit(self) -> bool {
+        self >= Self::Editor
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RevisionSummary {
+    pub revision: RevisionId,
+    /// Server clock, in milliseconds since the Unix epoch.
+    pub saved_ms: u64,
+    pub objects: usize,
+}
+
+/// A message with an optional [`RequestId`]. On the wire the id sits next to
+/// the message's own fields and may be left out, so bare messages decode as
+/// envelopes without one. The server echoes the id in every reply to the
+/// request, including job progress and results.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Envelope<M> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<RequestId>,
+    #[serde(flatten)]
+    pub msg: M,
+}
+
+impl<M> Envelope<M> {
+    pub fn new(msg: M, request_id: Option<RequestId>) -> Self {
+        Self { request_id, msg }
+    }
+}
+
+impl<M> From<M> for Envelope<M> {
+    fn from(msg: M) -> Self {
+        Self::new(msg, None)
+    }
+}
+
+impl Envelope<ServerMsg> {
+    /// Like [`ServerMsg::to_frame`], keeping the request id on text frames.
+    /// Binary mesh frames have no room for it.
+    pub fn to_frame(&self) -> Result<Frame, FrameError> {
+        self.encode(Encoding::Json)
+    }
+
+    /// Like [`Envelope::to_frame`] in the given encoding.
+    pub fn encode(&self, encoding: Encoding) -> Result<Frame, FrameError> {
+        match &self.msg {
+            ServerMsg::MeshData(_) => self.msg.to_frame(),
+            _ => encoding.encode(self),
+        }
+    }
+
+    /// Decodes any frame the server sends, compressed or not, in any
+    /// encoding.
+    pub fn from_frame(frame: Frame) -> Result<Self, FrameError> {
+        match frame.decompress()? {
+            Frame::Binary(bytes) if !bytes.starts_with(&MSGPACK_FRAME_MAGIC) => {
+                ServerMsg::from_binary(&bytes).map(Self::from)
+            }
+            frame => encoding::decode(&frame),
+        }
+    }
+}
+
+impl Envelope<ClientMsg> {
+    pub fn encode(&self, encoding: Encoding) -> Result<Frame, FrameError> {
+        encoding.encode(self)
+    }
+
+    /// Decodes a JSON or MessagePack client message.
+    pub fn from_frame(frame: &Frame) -> Result<Self, FrameError> {
+        encoding::decode(frame)
+    }
+}
+
+/// Why a request failed, for the client to decide how to react.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ErrorCode {
+    /// The message could not be decoded.
+    InvalidMessage,
+    /// A primitive whose dimensions are not finite, degenerate or out of
+    /// the server's bounds; the error names the dimension when it can.
+    InvalidPrimitive,
+    /// A [`ClientMsg::AddFeature`] whose parameters are not finite,
+    /// degenerate or out of the server's bounds, e.g. a pattern of too many
+    /// copies; the error names the parameter.
+    InvalidParameters,
+    /// A [`ClientMsg::SetTransform`] that is not finite or scales by less or
+    /// more than the server's bounds; the error names the part at fault.
+    InvalidTransform,
+    UnknownObject,
+    /// Any other edit the model refused.
+    InvalidEdit,
+    /// An undo or redo with nothing to revert.
+    EmptyHistory,
+    UnknownDocument,
+    UnknownComment,
+    /// A [`ClientMsg::RestoreRevision`] the document never had.
+    UnknownRevision,
+    /// A [`ClientMsg::CancelJob`] for a job that finished or is not the
+    /// client's.
+    UnknownJob,
+    /// A session to resume that never existed or was dropped for good.
+    UnknownSession,
+    /// The document store failed, or the server keeps none.
+    StorageFailed,
+    /// Chat or a comment that is empty or longer than
+    /// [`MAX_REVIEW_TEXT_LEN`].
+    InvalidText,
+    /// A document edit or query while the client has none open.
+    NoDocument,
+    /// A job kind, feature, format or size outside the server's
+    /// [`Capabilities`].
+    Unsupported,
+    /// The job queue is full or stopped; worth retrying later.
+    JobQueueUnavailable,
+    /// Sent before a successful [`ClientMsg::Authenticate`].
+    Unauthenticated,
+    /// Beyond the client's [`Role`] in the document, e.g. an edit by a
+    /// viewer, or the document of a user without one.
+    Forbidden,
+    /// A [`ClientMsg::SetTransform`] that lost to a concurrent edit of the
+    /// object, which the document keeps; see [`LastWrites`].
+    Superseded,
+    /// An upload was never announced, did not match its announcement, or
+    /// could not be read.
+    ImportFailed,
+    Internal,
+}
+
+impl From<&CommandError> for ErrorCode {
+    fn from(err: &CommandError) -> Self {
+        match err {
+            CommandError::UnknownObject(_) => Self::UnknownObject,
+            CommandError::InvalidPrimitive(_) => Self::InvalidPrimitive,
+            _ => Self::InvalidEdit,
+        }
+    }
+}
+
+impl ClientMsg {
+    /// Messages a server accepts before authentication; it rejects the rest
+    /// with [`ErrorCode::Unauthenticated`].
+    pub fn allowed_before_auth(&self) -> bool {
+        matches!(
+            self,
+            Self::Hello { .. } | Self::Authenticate { .. } | Self::Ping { .. } | Self::Pong { .. }
+        )
+    }
+
+    /// Messages that change the open document, which viewers may not send;
+    /// a server rejects them with [`ErrorCode::Forbidden`].
+    pub fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            Self::AddBox { .. }
+                | Self::AddCylinder { .. }
+                | Self::AddPrimitive { .. }
+                | Self::SetTransform { .. }
+                | Self::DeleteObject { .. }
+                | Self::Undo
+                | Self::Redo
+                | Self::AddFeature { .. }
+                | Self::Upload { .. }
+                | Self::SaveDocument { .. }
+                | Self::RestoreRevision { .. }
+        )
+    }
+
+    /// Messages a client may send continuously, e.g. while dragging, and
+    /// which the server rate-limits; later ones supersede earlier ones.
+    pub fn is_streamed(&self) -> bool {
+        matches!(self, Self::SetTransform { .. } | Self::SetSelection { .. })
+    }
+}
+
+/// What the server supports, so clients can disable what it cannot do
+/// rather than find out from an error.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct Capabilities {
+    /// Kinds [`ClientMsg::RequestHeavy`] accepts.
+    pub job_kinds: Vec<String>,
+    pub export_formats: Vec<ExportFormat>,
+    pub import_formats: Vec<ImportFormat>,
+    /// Largest [`ClientMsg::Upload`] accepted, in bytes.
+    pub max_upload_len: u64,
+    /// Largest mesh an import may have, in triangles.
+    pub max_triangles: u64,
+    /// Whether documents can be saved, see [`ClientMsg::SaveDocument`].
+    pub storage: bool,
+    /// Features [`ClientMsg::AddFeature`] can compute.
+    pub feature_ops: Vec<FeatureKind>,
+}
+
+/// What a [`FeatureOp`] does, without its parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum FeatureKind {
+    Subtract,
+    LinearPattern,
+    PushPull,
+}
+
+impl FeatureKind {
+    pub fn of(op: &FeatureOp) -> Self {
+        match op {
+            FeatureOp::Subtract { .. } => Self::Subtract,
+            FeatureOp::LinearPattern { .. } => Self::LinearPattern,
+            FeatureOp::PushPull { .. } => Self::PushPull,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum HistoryStep {
+    Undo,
+    Redo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ExportFormat {
+    Step,
+    Stl,
+    /// glTF binary (`.glb`).
+    Gltf,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Step => "step",
+            Self::Stl => "stl",
+            Self::Gltf => "glb",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            Self::Step => "model/step",
+            Self::Stl => "model/stl",
+            Self::Gltf => "model/gltf-binary",
+        }
+    }
+}
+
+/// Where to get an exported file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ExportContent {
+    /// Fetch it from the server.
+    Url(String),
+    Bytes(Vec<u8>),
+}
+
+/// What a finished job produced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "kind")]
+pub enum JobPayload {
+    Mesh(MeshData),
+    /// A file to offer as a download.
+    File {
+        name: String,
+        mime: String,
+        bytes: Vec<u8>,
+    },
+    Text {
+        text: String,
+    },
+    Error {
+        message: String,
+    },
+    /// Stopped by [`ClientMsg::CancelJob`]; whatever it did so far is
+    /// discarded.
+    Canceled,
+}
+
+/// One websocket frame's worth of a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl ServerMsg {
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::Error {
+            code,
+            message: message.into(),
+            related_request: None,
+            field: None,
+        }
+    }
+
+    /// An error blaming `field` of the request.
+    pub fn field_error(code: ErrorCode, field: &str, message: impl Into<String>) -> Self {
+        Self::Error {
+            code,
+            message: message.into(),
+            related_request: None,
+            field: Some(field.to_string()),
+        }
+    }
+
+    /// The notifications telling other clients about update `seq` by
+    /// `peer`: removals, then changes, then additions, as
+    /// [`Model::apply_delta`] orders them.
+    pub fn notifications(seq: u64, peer: PeerId, delta: ModelDelta) -> Vec<Self> {
+        let removed = delta
+            .removed
+            .into_iter()
+            .map(|id| Self::ObjectRemoved { seq, peer, id });
+        let changed =
+            delta
+                .changed
+                .into_iter()
+                .map(|object| Self::ObjectChanged { seq, peer, object });
+        let added = delta
+            .added
+            .into_iter()
+            .map(|(index, object)| Self::ObjectAdded {
+                seq,
+                peer,
+                index,
+                object,
+            });
+        removed.chain(changed).chain(added).collect()
+    }
+
+    /// Encodes the message for the wire: meshes as binary frames, so float
+    /// arrays skip JSON, and everything else as JSON text.
+    pub fn to_frame(&self) -> Result<Frame, FrameError> {
+        match self {
+            Self::MeshData(mesh) => mesh.to_frame().map(Frame::Binary),
+            other => serde_json::to_string(other)
+                .map(Frame::Text)
+                .map_err(|err| FrameError::Json(err.to_string())),
+        }
+    }
+
+    /// Decodes a binary frame written by [`ServerMsg::to_frame`].
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, FrameError> {
+        MeshData::from_frame(bytes).map(Self::MeshData)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cad_core::StableId;
+
+    /// `model` as a snapshot delivers it: without the client it mints as.
+    fn sent(mut model: Model) -> Box<Model> {
+        model.set_client_id(StableId::LEGACY_CLIENT);
+        Box::new(model)
+    }
 
     #[test]
     fn client_msg_roundtrip() {
@@ -48,14 +880,759 @@ mod tests {
         assert_eq!(msg, back);
     }
 
+    #[test]
+    fn envelopes_carry_optional_request_ids() {
+        let json = r#"{"type":"DeleteObject","id":3,"request_id":12}"#;
+        let envelope: Envelope<ClientMsg> = serde_json::from_str(json).unwrap();
+        assert_eq!(envelope.request_id, Some(12));
+        assert_eq!(envelope.msg, ClientMsg::DeleteObject { id: 3 });
+
+        let bare: Envelope<ClientMsg> = serde_json::from_str(r#"{"type":"RequestModel"}"#).unwrap();
+        assert_eq!(bare, Envelope::from(ClientMsg::RequestModel));
+
+        let reply = Envelope::new(ServerMsg::JobAccepted { job_id: 1 }, Some(12));
+        let Ok(Frame::Text(text)) = reply.to_frame() else {
+            panic!("job replies are text frames");
+        };
+        assert_eq!(
+            serde_json::from_str::<Envelope<ServerMsg>>(&text).unwrap(),
+            reply
+        );
+    }
+
+    #[test]
+    fn only_handshake_msgs_skip_auth() {
+        let token = ClientMsg::Authenticate {
+            token: "secret".to_string(),
+        };
+        assert!(token.allowed_before_auth());
+        assert!(ClientMsg::Ping { sent_ms: 1 }.allowed_before_auth());
+        assert!(!ClientMsg::RequestModel.allowed_before_auth());
+        assert!(!ClientMsg::DeleteObject { id: 1 }.allowed_before_auth());
+
+        let refused: ServerMsg =
+            serde_json::from_str(r#"{"type":"AuthResult","user":null}"#).unwrap();
+        assert_eq!(refused, ServerMsg::AuthResult { user: None });
+    }
+
+    #[test]
+    fn roster_tracks_peers_and_selections() {
+        let peer = |id: PeerId, name: &str| Peer {
+            id,
+            name: name.to_string(),
+            color: [200, 40, 40],
+        };
+        let mut roster = Roster::default();
+        assert!(roster.apply(&ServerMsg::Peers {
+            you: 1,
+            peers: vec![peer(1, "me"), peer(2, "ana")],
+        }));
+        assert_eq!(roster.me(), Some(1));
+        assert_eq!(roster.peers().count(), 1);
+
+        roster.apply(&ServerMsg::PeerJoined {
+            peer: peer(3, "bo"),
+        });
+        roster.apply(&ServerMsg::PeerUpdated {
+            peer: peer(1, "renamed"),
+        });
+        roster.apply(&ServerMsg::PeerSelection {
+            id: 3,
+            selection: vec![7],
+            cursor: Some([1.0, 2.0, 3.0]),
+        });
+        assert_eq!(roster.peers().count(), 2);
+        assert_eq!(roster.selected_by(7), vec![&peer(3, "bo")]);
+        assert_eq!(roster.peer(3).unwrap().cursor, Some([1.0, 2.0, 3.0]));
+
+        roster.apply(&ServerMsg::PeerLeft { id: 3 });
+        assert!(roster.selected_by(7).is_empty());
+        assert!(!roster.apply(&ServerMsg::JobAccepted { job_id: 1 }));
+    }
+
+    #[test]
+    fn switching_documents_replaces_the_roster() {
+        let peer = |id: PeerId| Peer {
+            id,
+            name: format!("peer {id}"),
+            color: [0, 0, 0],
+        };
+        let mut roster = Roster::default();
+        roster.apply(&ServerMsg::Peers {
+            you: 1,
+            peers: vec![peer(1), peer(2), peer(3)],
+        });
+        roster.apply(&ServerMsg::PeerSelection {
+            id: 2,
+            selection: vec![4],
+            cursor: None,
+        });
+        roster.apply(&ServerMsg::Peers {
+            you: 1,
+            peers: vec![peer(1), peer(5)],
+        });
+        let ids: Vec<_> = roster.peers().map(|remote| remote.peer.id).collect();
+        assert_eq!(ids, [5]);
+        assert!(roster.selected_by(4).is_empty());
+    }
+
+    #[test]
+    fn edit_msgs_use_core_types() {
+        let msgs = [
+            ClientMsg::AddPrimitive {
+                kind: ObjectKind::Prism {
+                    sides: 6,
+                    r: 1.0,
+                    h: 2.0,
+                },
+            },
+            ClientMsg::SetTransform {
+                id: 3,
+                transform: Transform::from_translation([1.0, 2.0, 3.0]),
+                base_seq: Some(7),
+            },
+            ClientMsg::DeleteObject { id: 3 },
+            ClientMsg::AddFeature {
+                id: 3,
+                op: FeatureOp::Subtract { tool: 2 },
+            },
+        ];
+        for msg in msgs {
+            let json = serde_json::to_string(&msg).unwrap();
+            let back: ClientMsg = serde_json::from_str(&json).unwrap();
+            assert_eq!(msg, back);
+        }
+    }
+
     #[test]
     fn server_msg_roundtrip() {
-        let msg = ServerMsg::JobResult {
-            job_id: 42,
-            payload: "ok".to_string(),
+        let msgs = [
+            ServerMsg::Error {
+                code: ErrorCode::UnknownObject,
+                message: "unknown object 3".to_string(),
+                related_request: Some(9),
+                field: None,
+            },
+            ServerMsg::Pong {
+                sent_ms: 1_700_000_000_000,
+                server_ms: 1_700_000_000_020,
+            },
+            ServerMsg::JobProgress {
+                job_id: 42,
+                percent: 50,
+                stage: "computing".to_string(),
+            },
+            ServerMsg::JobResult {
+                job_id: 42,
+                payload: JobPayload::File {
+                    name: "part.stl".to_string(),
+                    mime: "model/stl".to_string(),
+                    bytes: vec![1, 2, 3],
+                },
+            },
+            ServerMsg::ExportReady {
+                job_id: 44,
+                name: format!("export.{}", ExportFormat::Gltf.extension()),
+                mime: ExportFormat::Gltf.mime().to_string(),
+                content: ExportContent::Bytes(b"glTF".to_vec()),
+            },
+            ServerMsg::JobResult {
+                job_id: 43,
+                payload: JobPayload::Mesh(MeshData {
+                    target: MeshTarget::Scene,
+                    positions: vec![[0.0; 3]],
+                    normals: vec![[0.0, 0.0, 1.0]],
+                    indices: Vec::new(),
+                }),
+            },
+        ];
+        for msg in msgs {
+            let json = serde_json::to_string(&msg).unwrap();
+            let back: ServerMsg = serde_json::from_str(&json).unwrap();
+            assert_eq!(msg, back);
+        }
+    }
+
+    #[test]
+    fn jobs_can_be_canceled() {
+        let cancel: ClientMsg = serde_json::from_str(r#"{"type":"CancelJob","job_id":7}"#).unwrap();
+        assert_eq!(cancel, ClientMsg::CancelJob { job_id: 7 });
+        let canceled = ServerMsg::JobResult {
+            job_id: 7,
+            payload: JobPayload::Canceled,
+        };
+        assert_eq!(
+            serde_json::to_string(&canceled).unwrap(),
+            r#"{"type":"JobResult","job_id":7,"payload":{"kind":"Canceled"}}"#
+        );
+    }
+
+    #[test]
+    fn model_snapshot_roundtrip() {
+        let mut model = Model::default();
+        model.add_box(1.0, 2.0, 3.0);
+        let msg = ServerMsg::ModelSnapshot {
+            seq: 4,
+            model: sent(model),
         };
         let json = serde_json::to_string(&msg).unwrap();
         let back: ServerMsg = serde_json::from_str(&json).unwrap();
         assert_eq!(msg, back);
     }
+
+    #[test]
+    fn deltas_apply_in_order_and_resync_on_gaps() {
+        let mut sync = SyncState::default();
+        assert_eq!(sync.on_delta(1), SyncAction::Resync);
+        sync.on_snapshot(4);
+        assert_eq!(sync.on_delta(4), SyncAction::Skip);
+        assert_eq!(sync.on_delta(5), SyncAction::Apply);
+        assert_eq!(sync.on_delta(7), SyncAction::Resync);
+        assert_eq!(sync.seq(), None);
+        assert_eq!(sync.on_delta(8), SyncAction::Resync);
+        sync.on_snapshot(8);
+        assert_eq!(sync.on_delta(9), SyncAction::Apply);
+    }
+
+    #[test]
+    fn mesh_data_travels_as_binary() {
+        let msg = ServerMsg::MeshData(MeshData {
+            target: MeshTarget::Object(7),
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.5, -2.0]],
+            normals: vec![[0.0, 0.0, 1.0]; 3],
+            indices: vec![0, 1, 2],
+        });
+        let Ok(Frame::Binary(bytes)) = msg.to_frame() else {
+            panic!("mesh should encode as a binary frame");
+        };
+        assert_eq!(bytes.len(), 24 + 3 * 24 + 3 * 4);
+        assert_eq!(ServerMsg::from_binary(&bytes).unwrap(), msg);
+        assert_eq!(
+            ServerMsg::from_binary(&bytes[..bytes.len() - 1]),
+            Err(FrameError::Truncated)
+        );
+        let ack = ServerMsg::HelloAck {
+            compression: None,
+            encoding: Encoding::Json,
+            auth_required: false,
+            capabilities: Capabilities::default(),
+            session: None,
+        };
+        assert!(matches!(ack.to_frame(), Ok(Frame::Text(_))));
+    }
+
+    #[test]
+    fn uploads_travel_as_binary_frames() {
+        assert_eq!(
+            ImportFormat::from_file_name("Bracket.STL"),
+            Some(ImportFormat::Stl)
+        );
+        assert_eq!(
+            ImportFormat::from_file_name("part.stp"),
+            Some(ImportFormat::Step)
+        );
+        assert_eq!(ImportFormat::from_file_name("notes.txt"), None);
+
+        let upload = UploadData {
+            upload_id: 9,
+            data: vec![1, 2, 3],
+        };
+        let bytes = upload.to_frame();
+        assert_eq!(UploadData::from_frame(&bytes), Ok(upload));
+        assert_eq!(
+            UploadData::from_frame(&bytes[..8]),
+            Err(FrameError::Truncated)
+        );
+    }
+
+    #[test]
+    fn large_frames_travel_in_chunks() {
+        let mut chunker = Chunker::default();
+        let mut reassembler = Reassembler::default();
+        let small = Frame::Text("{}".to_string());
+        assert_eq!(chunker.split(small.clone()), vec![small.clone()]);
+        assert_eq!(reassembler.push(small.clone()), Ok(Some(small)));
+
+        let big = Frame::Binary((0..CHUNK_THRESHOLD * 2 + 5).map(|i| i as u8).collect());
+        let text = Frame::Text("x".repeat(CHUNK_THRESHOLD + 1));
+        let first = chunker.split(big.clone());
+        let second = chunker.split(text.clone());
+        assert_eq!(
+            first.len(),
+            2 + (CHUNK_THRESHOLD * 2 + 5).div_ceil(CHUNK_LEN)
+        );
+
+        // Transfers may interleave; the shorter one finishes first.
+        let mut done = Vec::new();
+        for i in 0..first.len() {
+            for frame in [first.get(i), second.get(i)].into_iter().flatten() {
+                done.extend(reassembler.push(frame.clone()).unwrap());
+            }
+        }
+        assert_eq!(done, vec![text, big]);
+
+        let mut gap = chunker.split(Frame::Binary(vec![0; CHUNK_THRESHOLD + 1]));
+        gap.remove(1);
+        let errors: Vec<_> = gap
+            .into_iter()
+            .filter_map(|frame| reassembler.push(frame).err())
+            .collect();
+        assert!(matches!(errors[0], FrameError::ChunkOutOfOrder(_)));
+    }
+
+    #[test]
+    fn queue_updates_carry_positions() {
+        let queued = Envelope::new(
+            ServerMsg::JobQueued {
+                job_id: 4,
+                position: 2,
+            },
+            Some(9),
+        );
+        let json = serde_json::to_string(&queued).unwrap();
+        assert!(json.contains(r#""position":2"#) && json.contains(r#""request_id":9"#));
+        assert_eq!(
+            serde_json::from_str::<Envelope<ServerMsg>>(&json).unwrap(),
+            queued
+        );
+    }
+
+    #[test]
+    fn hello_ack_advertises_capabilities() {
+        let old: ServerMsg = serde_json::from_str(r#"{"type":"HelloAck"}"#).unwrap();
+        let ServerMsg::HelloAck { capabilities, .. } = old else {
+            unreachable!()
+        };
+        assert_eq!(capabilities, Capabilities::default());
+
+        let ack = ServerMsg::HelloAck {
+            compression: None,
+            encoding: Encoding::Json,
+            auth_required: false,
+            capabilities: Capabilities {
+                job_kinds: vec!["demo".to_string()],
+                export_formats: vec![ExportFormat::Stl],
+                import_formats: vec![ImportFormat::Stl],
+                max_upload_len: 1024,
+                max_triangles: 10,
+                storage: true,
+                feature_ops: vec![FeatureKind::PushPull],
+            },
+            session: Some("0f3a".to_string()),
+        };
+        let json = serde_json::to_string(&ack).unwrap();
+        assert_eq!(serde_json::from_str::<ServerMsg>(&json).unwrap(), ack);
+
+        let cut = FeatureOp::Subtract { tool: 2 };
+        assert_eq!(FeatureKind::of(&cut), FeatureKind::Subtract);
+    }
+
+    #[test]
+    fn edits_notify_with_their_update() {
+        let mut model = Model::default();
+        let kept = model.add_box(1.0, 1.0, 1.0);
+        let gone = model.add_box(2.0, 2.0, 2.0);
+        let mut newer = model.clone();
+        newer.remove(gone);
+        newer.set_transform(
+            kept,
+            Transform {
+                translation: [1.0, 0.0, 0.0],
+                ..Transform::default()
+            },
+        );
+        let added = newer.add_box(3.0, 3.0, 3.0);
+
+        let notes = ServerMsg::notifications(6, 2, model.diff(&newer));
+        assert!(matches!(
+            notes[..],
+            [
+                ServerMsg::ObjectRemoved { seq: 6, id, .. },
+                ServerMsg::ObjectChanged { .. },
+                ServerMsg::ObjectAdded { ref object, .. },
+            ] if id == gone && object.id == added
+        ));
+
+        let mut sync = SyncState::default();
+        sync.on_snapshot(5);
+        let actions: Vec<_> = notes.iter().map(|_| sync.on_notification(6)).collect();
+        assert_eq!(actions, [SyncAction::Apply; 3]);
+        assert_eq!(sync.on_notification(5), SyncAction::Skip);
+        assert_eq!(sync.on_delta(7), SyncAction::Apply);
+        assert_eq!(sync.on_notification(9), SyncAction::Resync);
+    }
+
+    #[test]
+    fn documents_are_addressed_by_id() {
+        let open: ClientMsg = serde_json::from_str(r#"{"type":"OpenDocument","id":2}"#).unwrap();
+        assert_eq!(open, ClientMsg::OpenDocument { id: 2 });
+
+        let opened = Envelope::new(
+            ServerMsg::DocumentOpened {
+                document: DocumentSummary {
+                    id: 2,
+                    name: "Bracket".to_string(),
+                    objects: 3,
+                    clients: 1,
+                    revision: Some(4),
+                    thumbnail: Some("/documents/2/thumbnail".to_string()),
+                },
+                role: Some(Role::Editor),
+            },
+            Some(5),
+        );
+        let frame = opened.to_frame().unwrap();
+        assert_eq!(Envelope::<ServerMsg>::from_frame(frame).unwrap(), opened);
+
+        let list: ClientMsg = serde_json::from_str(r#"{"type":"ListDocuments"}"#).unwrap();
+        assert_eq!(
+            list,
+            ClientMsg::ListDocuments {
+                query: String::new()
+            }
+        );
+        let unsaved: DocumentSummary =
+            serde_json::from_str(r#"{"id":3,"name":"New","objects":0,"clients":1}"#).unwrap();
+        assert_eq!((unsaved.revision, unsaved.thumbnail), (None, None));
+    }
+
+    #[test]
+    fn history_steps_reach_every_client() {
+        let undo: ClientMsg = serde_json::from_str(r#"{"type":"Undo"}"#).unwrap();
+        assert_eq!(undo, ClientMsg::Undo);
+        assert!(!undo.allowed_before_auth());
+
+        let applied = ServerMsg::HistoryApplied {
+            step: HistoryStep::Redo,
+            peer: 2,
+            text: "added object 1".to_string(),
+            can_undo: true,
+            can_redo: false,
+        };
+        let json = serde_json::to_string(&applied).unwrap();
+        assert!(json.contains(r#""step":"Redo""#));
+        assert_eq!(serde_json::from_str::<ServerMsg>(&json).unwrap(), applied);
+    }
+
+    #[test]
+    fn messages_round_trip_as_msgpack() {
+        assert_eq!(Encoding::negotiate(&[]), Encoding::Json);
+        assert_eq!(
+            Encoding::negotiate(&[Encoding::Json, Encoding::MessagePack]),
+            Encoding::MessagePack
+        );
+
+        let mut model = Model::default();
+        let id = model.add_box(1.0, 2.0, 3.0);
+        let msgs = [
+            Envelope::new(
+                ServerMsg::ModelSnapshot {
+                    seq: 4,
+                    model: sent(model),
+                },
+                Some(7),
+            ),
+            Envelope::new(ServerMsg::error(ErrorCode::UnknownObject, "gone"), Some(8)),
+            Envelope::from(ServerMsg::ImportResult {
+                upload_id: 1,
+                objects: vec![id],
+            }),
+        ];
+        for msg in &msgs {
+            let frame = msg.encode(Encoding::MessagePack).unwrap();
+            let Frame::Binary(bytes) = &frame else {
+                panic!("msgpack frames are binary");
+            };
+            assert!(bytes.starts_with(&MSGPACK_FRAME_MAGIC));
+            assert_eq!(&Envelope::<ServerMsg>::from_frame(frame).unwrap(), msg);
+        }
+
+        let request = Envelope::new(ClientMsg::DeleteObject { id }, Some(3));
+        for encoding in [Encoding::Json, Encoding::MessagePack] {
+            let frame = request.encode(encoding).unwrap();
+            assert_eq!(Envelope::<ClientMsg>::from_frame(&frame).unwrap(), request);
+        }
+        assert_eq!(
+            Envelope::<ClientMsg>::from_frame(&Frame::Binary(b"PMSH".to_vec())),
+            Err(FrameError::BadMagic)
+        );
+    }
+
+    #[test]
+    fn large_frames_compress_after_negotiation() {
+        let hello: ClientMsg =
+            serde_json::from_str(r#"{"type":"Hello","client_version":"0.1"}"#).unwrap();
+        let ClientMsg::Hello { compression, .. } = hello else {
+            unreachable!()
+        };
+        assert_eq!(Compression::negotiate(&compression), None);
+        assert_eq!(
+            Compression::negotiate(&[Compression::Deflate, Compression::Zstd]),
+            Some(Compression::Zstd)
+        );
+
+        let mut model = Model::default();
+        for i in 0..200 {
+            model.add_box(1.0, 2.0, i as f32);
+        }
+        let snapshot = Envelope::new(
+            ServerMsg::ModelSnapshot {
+                seq: 1,
+                model: sent(model),
+            },
+            Some(5),
+        );
+        let mesh = Envelope::from(ServerMsg::MeshData(MeshData {
+            target: MeshTarget::Scene,
+            positions: vec![[1.0, 2.0, 3.0]; 2000],
+            normals: vec![[0.0, 0.0, 1.0]; 2000],
+            indices: (0..6000).map(|i| i % 2000).collect(),
+        }));
+        for codec in Compression::PREFERRED {
+            for msg in [&snapshot, &mesh] {
+                let plain = msg.to_frame().unwrap();
+                let Frame::Binary(bytes) = plain.clone().compress(codec) else {
+                    panic!("compressed frames are binary");
+                };
+                assert!(bytes.starts_with(&COMPRESSED_FRAME_MAGIC));
+                let plain_len = match &plain {
+                    Frame::Text(text) => text.len(),
+                    Frame::Binary(bytes) => bytes.len(),
+                };
+                assert!(bytes.len() < plain_len);
+                let back = Envelope::<ServerMsg>::from_frame(Frame::Binary(bytes)).unwrap();
+                assert_eq!(&back, msg);
+            }
+        }
+
+        let small = Envelope::from(ServerMsg::HelloAck {
+            compression: Some(Compression::Zstd),
+            encoding: Encoding::Json,
+            auth_required: false,
+            capabilities: Capabilities::default(),
+            session: None,
+        });
+        let frame = small.to_frame().unwrap();
+        assert_eq!(frame.clone().compress(Compression::Zstd), frame);
+        assert_eq!(Envelope::<ServerMsg>::from_frame(frame).unwrap(), small);
+    }
+
+    #[test]
+    fn comments_pin_to_points_and_objects() {
+        let add = ClientMsg::AddComment {
+            anchor: Anchor::Object {
+                object: 4,
+                local: [0.0, 0.0, 1.0],
+            },
+            text: "fillet this edge".to_string(),
+        };
+        let json = serde_json::to_string(&add).unwrap();
+        assert_eq!(serde_json::from_str::<ClientMsg>(&json).unwrap(), add);
+
+        let added = ServerMsg::CommentAdded {
+            comment: Comment {
+                id: 1,
+                anchor: Anchor::World([1.0, 2.0, 3.0]),
+                text: "too thin".to_string(),
+                author: "ana".to_string(),
+                created_ms: 1_700_000_000_000,
+            },
+            peer: 2,
+        };
+        let chat = ServerMsg::Chat {
+            peer: 2,
+            text: "looks good".to_string(),
+            sent_ms: 1_700_000_000_000,
+        };
+        for msg in [added, chat] {
+            let json = serde_json::to_string(&msg).unwrap();
+            assert_eq!(serde_json::from_str::<ServerMsg>(&json).unwrap(), msg);
+        }
+
+        assert_eq!(review_text("  ok \n"), Some("ok"));
+        assert_eq!(review_text("   "), None);
+        assert_eq!(
+            review_text(&"é".repeat(MAX_REVIEW_TEXT_LEN)).map(str::len),
+            Some(4000)
+        );
+        assert_eq!(review_text(&"x".repeat(MAX_REVIEW_TEXT_LEN + 1)), None);
+    }
+
+    #[test]
+    fn throttle_keeps_the_latest_update() {
+        let drag = |x: f32| ClientMsg::SetTransform {
+            id: 1,
+            transform: Transform::from_translation([x, 0.0, 0.0]),
+            base_seq: None,
+        };
+        assert!(drag(0.0).is_streamed());
+        assert!(!ClientMsg::DeleteObject { id: 1 }.is_streamed());
+
+        let mut throttle = Throttle::new(20);
+        assert_eq!(throttle.push(0, drag(0.0)), Some(drag(0.0)));
+        assert_eq!(throttle.push(5, drag(1.0)), None);
+        assert_eq!(throttle.push(10, drag(2.0)), None);
+        assert_eq!(throttle.delay_ms(10), Some(10));
+        assert_eq!(throttle.poll(20), Some(drag(2.0)));
+        assert_eq!(throttle.delay_ms(20), None);
+
+        let slow: ServerMsg =
+            serde_json::from_str(r#"{"type":"SlowDown","retry_after_ms":500}"#).unwrap();
+        let ServerMsg::SlowDown { retry_after_ms } = slow else {
+            panic!("expected SlowDown");
+        };
+        throttle.slow_down(40, retry_after_ms);
+        assert_eq!(throttle.push(100, drag(3.0)), None);
+        assert_eq!(throttle.poll(539), None);
+        assert_eq!(throttle.poll(540), Some(drag(3.0)));
+    }
+
+    #[test]
+    fn concurrent_moves_converge() {
+        // Peers 1 and 2 both move object 5 having seen update 10; peer 2
+        // then moves it again after seeing update 11.
+        let first = Stamp { seq: 10, peer: 1 };
+        let second = Stamp { seq: 10, peer: 2 };
+        let later = Stamp { seq: 11, peer: 1 };
+        let settle = |order: [Stamp; 2]| {
+            let mut writes = LastWrites::default();
+            order
+                .into_iter()
+                .filter(|&stamp| {
+                    let wins = writes.wins(5, stamp);
+                    if wins {
+                        writes.record(5, stamp);
+                    }
+                    wins
+                })
+                .last()
+        };
+        assert_eq!(settle([first, second]), Some(second));
+        assert_eq!(settle([second, first]), Some(second));
+        assert_eq!(settle([second, later]), Some(later));
+        assert_eq!(settle([later, second]), Some(later));
+
+        let mut writes = LastWrites::default();
+        writes.record(5, second);
+        assert!(writes.wins(5, second));
+        writes.forget(5);
+        assert!(writes.wins(5, first));
+
+        let legacy: ClientMsg = serde_json::from_str(
+            r#"{"type":"SetTransform","id":5,"transform":{"translation":[0,0,0],"rotation":[0,0,0,1],"scale":[1,1,1]}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            legacy,
+            ClientMsg::SetTransform { base_seq: None, .. }
+        ));
+    }
+    #[test]
+    fn mesh_requests_default_to_the_scene() {
+        let scene: ClientMsg = serde_json::from_str(r#"{"type":"RequestMesh"}"#).unwrap();
+        assert_eq!(
+            scene,
+            ClientMsg::RequestMesh {
+                objects: Vec::new(),
+                tolerance: None
+            }
+        );
+        let fine: ClientMsg =
+            serde_json::from_str(r#"{"type":"RequestMesh","objects":[4],"tolerance":0.001}"#)
+                .unwrap();
+        assert_eq!(
+            fine,
+            ClientMsg::RequestMesh {
+                objects: vec![4],
+                tolerance: Some(0.001)
+            }
+        );
+    }
+
+    #[test]
+    fn sessions_resume_by_token() {
+        let hello: ClientMsg =
+            serde_json::from_str(r#"{"type":"Hello","client_version":"0.1"}"#).unwrap();
+        assert!(matches!(hello, ClientMsg::Hello { resume: None, .. }));
+        let resume = ClientMsg::Hello {
+            client_version: "0.1".to_string(),
+            compression: Vec::new(),
+            encoding: Vec::new(),
+            resume: Some("0f3a".to_string()),
+        };
+        let json = serde_json::to_string(&resume).unwrap();
+        assert_eq!(serde_json::from_str::<ClientMsg>(&json).unwrap(), resume);
+
+        let resumed = ServerMsg::SessionResumed {
+            selection: vec![3],
+            cursor: None,
+            jobs: vec![12],
+        };
+        let json = serde_json::to_string(&resumed).unwrap();
+        assert_eq!(serde_json::from_str::<ServerMsg>(&json).unwrap(), resumed);
+    }
+
+    #[test]
+    fn viewers_cannot_edit() {
+        assert!(Role::Owner > Role::Editor && Role::Editor > Role::Viewer);
+        assert!(!Role::Viewer.can_edit() && Role::Editor.can_edit());
+        assert!(ClientMsg::Undo.is_edit());
+        assert!(!ClientMsg::RequestModel.is_edit());
+        let chat = ClientMsg::Chat {
+            text: "looks good".to_string(),
+        };
+        assert!(!chat.is_edit());
+
+        let set: ClientMsg =
+            serde_json::from_str(r#"{"type":"SetRole","user":"ana","role":"Viewer"}"#).unwrap();
+        assert!(matches!(
+            set,
+            ClientMsg::SetRole {
+                role: Role::Viewer,
+                ..
+            }
+        ));
+        // Servers without roles leave it out.
+        let opened: ServerMsg = serde_json::from_str(
+            r#"{"type":"DocumentOpened","document":{"id":1,"name":"A","objects":0,"clients":1}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            opened,
+            ServerMsg::DocumentOpened { role: None, .. }
+        ));
+    }
+
+    #[test]
+    fn revisions_restore_by_number() {
+        let restore: ClientMsg =
+            serde_json::from_str(r#"{"type":"RestoreRevision","revision":3}"#).unwrap();
+        assert_eq!(restore, ClientMsg::RestoreRevision { revision: 3 });
+        assert!(restore.is_edit());
+
+        let restored = Envelope::new(
+            ServerMsg::RevisionRestored {
+                id: 2,
+                revision: 3,
+                saved: 7,
+                peer: 4,
+            },
+            Some(9),
+        );
+        let frame = restored.to_frame().unwrap();
+        assert_eq!(Envelope::<ServerMsg>::from_frame(frame).unwrap(), restored);
+    }
+
+    #[test]
+    fn errors_name_the_field_at_fault() {
+        let error = ServerMsg::field_error(ErrorCode::InvalidPrimitive, "w", "w must be positive");
+        let json = serde_json::to_string(&error).unwrap();
+        assert!(json.contains(r#""field":"w""#));
+        assert_eq!(serde_json::from_str::<ServerMsg>(&json).unwrap(), error);
+
+        let older: ServerMsg =
+            serde_json::from_str(r#"{"type":"Error","code":"InvalidPrimitive","message":"no"}"#)
+                .unwrap();
+        assert!(matches!(older, ServerMsg::Error { field: None, .. }));
+    }
 }
@@ -0,0 +1,166 @@
+//! Triangle meshes sent from the server, and their binary frame layout.
+//!
+//! A mesh frame is little-endian throughout:
+//!
+//! | bytes | field                                         |
+//! |-------|-----------------------------------------------|
+//! | 4     | magic `PMSH`                                  |
+//! | 1     | layout version ([`MESH_FRAME_VERSION`])       |
+//! | 1     | target: `0` scene, `1` object                 |
+//! | 2     | reserved, zero                                |
+//! | 8     | object id (zero for the scene)                |
+//! | 4     | vertex count `n`                              |
+//! | 4     | index count `m`                               |
+//! | 12n   | positions, `f32` xyz                          |
+//! | 12n   | normals, `f32` xyz                            |
+//! | 4m    | indices, `u32`                                |
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const MESH_FRAME_MAGIC: [u8; 4] = *b"PMSH";
+pub const MESH_FRAME_VERSION: u8 = 1;
+const HEADER_LEN: usize = 24;
+
+/// What a [`MeshData`] message draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum MeshTarget {
+    /// The merged mesh of every visible body.
+    Scene,
+    /// One body, in its local space.
+    Object(u64),
+}
+
+/// Tessellation result: one normal per position, counter-clockwise
+/// triangles of three indices each.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MeshData {
+    pub target: MeshTarget,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// Shorter than its header or its declared arrays.
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnknownTarget(u8),
+    /// Positions and normals differ in length.
+    NormalCount,
+    /// A text frame failed to encode or decode.
+    Json(String),
+    /// A MessagePack frame failed to encode or decode.
+    MessagePack(String),
+    UnknownCompression(u8),
+    UnknownContent(u8),
+    /// A compressed frame is corrupt or expands past the size limit.
+    Decompress(String),
+    UnknownChunkKind(u8),
+    /// A chunk or end frame for a transfer that never began.
+    UnknownTransfer(u64),
+    /// A chunk did not continue where its transfer left off.
+    ChunkOutOfOrder(u64),
+    TransferTooLarge(u64),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "frame is truncated"),
+            Self::BadMagic => write!(f, "not a mesh frame"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported mesh frame version {v}"),
+            Self::UnknownTarget(t) => write!(f, "unknown mesh target {t}"),
+            Self::NormalCount => write!(f, "mesh needs one normal per position"),
+            Self::Json(err) => write!(f, "json: {err}"),
+            Self::MessagePack(err) => write!(f, "msgpack: {err}"),
+            Self::UnknownCompression(c) => write!(f, "unknown compression codec {c}"),
+            Self::UnknownContent(c) => write!(f, "unknown compressed frame content {c}"),
+            Self::Decompress(err) => write!(f, "decompress: {err}"),
+            Self::UnknownChunkKind(k) => write!(f, "unknown chunk frame kind {k}"),
+            Self::UnknownTransfer(id) => write!(f, "unknown transfer {id}"),
+            Self::ChunkOutOfOrder(id) => write!(f, "chunk out of order in transfer {id}"),
+            Self::TransferTooLarge(len) => write!(f, "transfer of {len} bytes is too large"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl MeshData {
+    /// Encodes the mesh as a binary frame; see the module docs for the
+    /// layout.
+    pub fn to_frame(&self) -> Result<Vec<u8>, FrameError> {
+        if self.normals.len() != self.positions.len() {
+            return Err(FrameError::NormalCount);
+        }
+        let (tag, id) = match self.target {
+            MeshTarget::Scene => (0u8, 0u64),
+            MeshTarget::Object(id) => (1, id),
+        };
+        let vertices = self.positions.len();
+        let mut out = Vec::with_capacity(HEADER_LEN + vertices * 24 + self.indices.len() * 4);
+        out.extend_from_slice(&MESH_FRAME_MAGIC);
+        out.extend_from_slice(&[MESH_FRAME_VERSION, tag, 0, 0]);
+        out.extend_from_slice(&id.to_le_bytes());
+        out.extend_from_slice(&(vertices as u32).to_le_bytes());
+        out.extend_from_slice(&(self.indices.len() as u32).to_le_bytes());
+        for v in self.positions.iter().chain(&self.normals) {
+            for c in v {
+                out.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        for i in &self.indices {
+            out.extend_from_slice(&i.to_le_bytes());
+        }
+        Ok(out)
+    }
+
+    /// Decodes a frame written by [`MeshData::to_frame`].
+    pub fn from_frame(bytes: &[u8]) -> Result<Self, FrameError> {
+        let header = bytes.get(..HEADER_LEN).ok_or(FrameError::Truncated)?;
+        if header[..4] != MESH_FRAME_MAGIC {
+            return Err(FrameError::BadMagic);
+        }
+        if header[4] != MESH_FRAME_VERSION {
+            return Err(FrameError::UnsupportedVersion(header[4]));
+        }
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let id = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let target = match header[5] {
+            0 => MeshTarget::Scene,
+            1 => MeshTarget::Object(id),
+            other => return Err(FrameError::UnknownTarget(other)),
+        };
+        let vertices = word(16) as usize;
+        let index_count = word(20) as usize;
+        let body_len = vertices
+            .checked_mul(24)
+            .and_then(|len| len.checked_add(index_count.checked_mul(4)?))
+            .ok_or(FrameError::Truncated)?;
+        if bytes.len() - HEADER_LEN < body_len {
+            return Err(FrameError::Truncated);
+        }
+
+        let float = |at: usize| f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let vec3s = |start: usize| -> Vec<[f32; 3]> {
+            (0..vertices)
+                .map(|i| std::array::from_fn(|k| float(start + (i * 3 + k) * 4)))
+                .collect()
+        };
+        let positions = vec3s(HEADER_LEN);
+        let normals = vec3s(HEADER_LEN + vertices * 12);
+        let indices_at = HEADER_LEN + vertices * 24;
+        let indices = (0..index_count).map(|i| word(indices_at + i * 4)).collect();
+        Ok(Self {
+            target,
+            positions,
+            normals,
+            indices,
+        })
+    }
+}
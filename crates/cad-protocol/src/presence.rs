@@ -0,0 +1,107 @@
+//! Who else is connected to the document, and what they have selected.
+
+use crate::ServerMsg;
+use cad_core::ObjectId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Server-assigned id of one connection.
+pub type PeerId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Peer {
+    pub id: PeerId,
+    pub name: String,
+    /// sRGB, for cursors and selection outlines.
+    pub color: [u8; 3],
+}
+
+/// A peer as the other clients see it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemotePeer {
+    pub peer: Peer,
+    pub selection: Vec<ObjectId>,
+    /// World-space point under the peer's pointer.
+    pub cursor: Option<[f32; 3]>,
+}
+
+/// A client's view of the other peers, kept current by feeding it every
+/// server message.
+#[derive(Debug, Clone, Default)]
+pub struct Roster {
+    me: Option<PeerId>,
+    peers: BTreeMap<PeerId, RemotePeer>,
+}
+
+impl Roster {
+    /// This client's own id, once the server has sent [`ServerMsg::Peers`].
+    pub fn me(&self) -> Option<PeerId> {
+        self.me
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &RemotePeer> {
+        self.peers.values()
+    }
+
+    pub fn peer(&self, id: PeerId) -> Option<&RemotePeer> {
+        self.peers.get(&id)
+    }
+
+    /// Peers that have `id` selected.
+    pub fn selected_by(&self, id: ObjectId) -> Vec<&Peer> {
+        self.peers
+            .values()
+            .filter(|remote| remote.selection.contains(&id))
+            .map(|remote| &remote.peer)
+            .collect()
+    }
+
+    /// Applies a presence message; returns `false` for any other message.
+    pub fn apply(&mut self, msg: &ServerMsg) -> bool {
+        match msg {
+            ServerMsg::Peers { you, peers } => {
+                self.me = Some(*you);
+                self.peers = peers
+                    .iter()
+                    .filter(|peer| peer.id != *you)
+                    .map(|peer| (peer.id, RemotePeer::new(peer.clone())))
+                    .collect();
+            }
+            ServerMsg::PeerJoined { peer } | ServerMsg::PeerUpdated { peer } => {
+                if Some(peer.id) == self.me {
+                    return true;
+                }
+                self.peers
+                    .entry(peer.id)
+                    .and_modify(|remote| remote.peer = peer.clone())
+                    .or_insert_with(|| RemotePeer::new(peer.clone()));
+            }
+            ServerMsg::PeerLeft { id } => {
+                self.peers.remove(id);
+            }
+            ServerMsg::PeerSelection {
+                id,
+                selection,
+                cursor,
+            } => {
+                if let Some(remote) = self.peers.get_mut(id) {
+                    remote.selection.clone_from(selection);
+                    remote.cursor = *cursor;
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl RemotePeer {
+    fn new(peer: Peer) -> Self {
+        Self {
+            peer,
+            selection: Vec::new(),
+            cursor: None,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Chat and comments for review sessions.
+//!
+//! Neither is part of the model: the server keeps a document's comments
+//! for as long as it hosts the document, but they are not undone, saved or
+//! numbered with its updates, and chat is not kept at all.
+
+use cad_core::Anchor;
+use serde::{Deserialize, Serialize};
+
+pub type CommentId = u64;
+
+/// Longest chat message or comment accepted, in characters.
+pub const MAX_REVIEW_TEXT_LEN: usize = 2000;
+
+/// A note pinned to a point in the document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Comment {
+    pub id: CommentId,
+    /// A world point, or a point on a body that moves with it. A comment
+    /// outlives its body; clients show it unpinned then.
+    pub anchor: Anchor,
+    pub text: String,
+    /// The author's name when they wrote it; peers come and go.
+    pub author: String,
+    /// Server clock, in milliseconds since the Unix epoch.
+    pub created_ms: u64,
+}
+
+/// The trimmed `text`, if it is fit to send: not empty, and not longer
+/// than [`MAX_REVIEW_TEXT_LEN`].
+pub fn review_text(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let len = text.chars().count();
+    (len > 0 && len <= MAX_REVIEW_TEXT_LEN).then_some(text)
+}
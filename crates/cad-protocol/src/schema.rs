@@ -0,0 +1,37 @@
+//! JSON Schemas of the messages, for clients and test fixtures written in
+//! other languages. Enabled by the `schema` feature.
+//!
+//! The schemas describe the JSON text frames: an [`Envelope`] around a
+//! message. Meshes, uploads and other binary frames have their layouts in
+//! their own modules' docs.
+
+use crate::{ClientMsg, Envelope, ServerMsg};
+use schemars::{schema_for, Schema};
+
+/// What clients send.
+pub fn client_schema() -> Schema {
+    schema_for!(Envelope<ClientMsg>)
+}
+
+/// What the server sends.
+pub fn server_schema() -> Schema {
+    schema_for!(Envelope<ServerMsg>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemas_name_every_message() {
+        let client = serde_json::to_string(&client_schema()).unwrap();
+        for name in ["Hello", "RequestExport", "Upload", "OpenDocument"] {
+            assert!(client.contains(&format!("\"{name}\"")), "{name}");
+        }
+        let server = serde_json::to_string(&server_schema()).unwrap();
+        for name in ["HelloAck", "ModelSnapshot", "ObjectAdded", "QueueStatus"] {
+            assert!(server.contains(&format!("\"{name}\"")), "{name}");
+        }
+        assert!(server.contains("request_id"));
+    }
+}
@@ -0,0 +1,58 @@
+//! Ordering of document updates on the receiving side.
+
+/// What to do with an incoming [`crate::ServerMsg::ModelDelta`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncAction {
+    /// The delta follows the local state; apply it.
+    Apply,
+    /// Already applied (e.g. a duplicate); drop it.
+    Skip,
+    /// A delta went missing or there is no baseline yet; drop it and send
+    /// [`crate::ClientMsg::RequestModel`].
+    Resync,
+}
+
+/// Tracks the sequence number of the last document state a client holds.
+/// Every delta carries the sequence number it produces, one past the state
+/// it applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncState {
+    seq: Option<u64>,
+}
+
+impl SyncState {
+    pub fn seq(&self) -> Option<u64> {
+        self.seq
+    }
+
+    /// Records a full snapshot as the new baseline.
+    pub fn on_snapshot(&mut self, seq: u64) {
+        self.seq = Some(seq);
+    }
+
+    /// Like [`SyncState::on_delta`] for an object notification. One update
+    /// can take several notifications, so those of the update the client is
+    /// already at apply too, and should be applied idempotently.
+    pub fn on_notification(&mut self, seq: u64) -> SyncAction {
+        if self.seq == Some(seq) {
+            return SyncAction::Apply;
+        }
+        self.on_delta(seq)
+    }
+
+    pub fn on_delta(&mut self, seq: u64) -> SyncAction {
+        match self.seq {
+            Some(current) if seq <= current => SyncAction::Skip,
+            Some(current) if seq == current + 1 => {
+                self.seq = Some(seq);
+                SyncAction::Apply
+            }
+            _ => {
+                // Wait for the snapshot rather than apply anything on top
+                // of a state with a hole in it.
+                self.seq = None;
+                SyncAction::Resync
+            }
+        }
+    }
+}
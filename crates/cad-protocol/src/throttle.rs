@@ -0,0 +1,67 @@
+//! Pacing of streamed updates, such as transforms during a drag.
+//!
+//! The server accepts [`MAX_STREAM_RATE`] streamed messages (see
+//! [`crate::ClientMsg::is_streamed`]) per second from each client. Past
+//! that it drops them and answers with [`crate::ServerMsg::SlowDown`].
+//! [`Throttle`] keeps a client under the limit: it lets one update through
+//! per interval, keeps only the latest of those in between, and backs off
+//! when the server asks.
+
+/// Streamed messages per second the server accepts from one client.
+pub const MAX_STREAM_RATE: u64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct Throttle<T> {
+    interval_ms: u64,
+    /// When the next update may go out.
+    next_ms: u64,
+    pending: Option<T>,
+}
+
+impl<T> Default for Throttle<T> {
+    /// Paced at [`MAX_STREAM_RATE`].
+    fn default() -> Self {
+        Self::new(1000_u64.div_ceil(MAX_STREAM_RATE))
+    }
+}
+
+impl<T> Throttle<T> {
+    pub fn new(interval_ms: u64) -> Self {
+        Self {
+            interval_ms,
+            next_ms: 0,
+            pending: None,
+        }
+    }
+
+    /// Offers an update at `now_ms`: returns it if it may be sent now, and
+    /// otherwise holds it in place of any update still held.
+    pub fn push(&mut self, now_ms: u64, update: T) -> Option<T> {
+        self.pending = Some(update);
+        self.poll(now_ms)
+    }
+
+    /// The held update, once it may be sent. Call it after
+    /// [`Throttle::delay_ms`] so the last update of a burst is not lost.
+    pub fn poll(&mut self, now_ms: u64) -> Option<T> {
+        if now_ms < self.next_ms {
+            return None;
+        }
+        let update = self.pending.take()?;
+        self.next_ms = now_ms + self.interval_ms;
+        Some(update)
+    }
+
+    /// How long until the held update may be sent, if one is held.
+    pub fn delay_ms(&self, now_ms: u64) -> Option<u64> {
+        self.pending
+            .as_ref()
+            .map(|_| self.next_ms.saturating_sub(now_ms))
+    }
+
+    /// Holds updates back for `retry_after_ms`, as a
+    /// [`crate::ServerMsg::SlowDown`] asks.
+    pub fn slow_down(&mut self, now_ms: u64, retry_after_ms: u64) {
+        self.next_ms = self.next_ms.max(now_ms + retry_after_ms);
+    }
+}
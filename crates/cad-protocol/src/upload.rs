@@ -0,0 +1,66 @@
+//! Files uploaded by the client for import.
+//!
+//! A [`crate::ClientMsg::Upload`] announces the file; its contents follow in
+//! one binary frame, chunked like any other large frame:
+//!
+//! | bytes | field                 |
+//! |-------|-----------------------|
+//! | 4     | magic `PUPL`          |
+//! | 4     | reserved, zero        |
+//! | 8     | upload id             |
+//! | ..    | file contents         |
+
+use crate::FrameError;
+use serde::{Deserialize, Serialize};
+
+pub const UPLOAD_FRAME_MAGIC: [u8; 4] = *b"PUPL";
+const HEADER_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ImportFormat {
+    Step,
+    /// Binary or ASCII STL.
+    Stl,
+}
+
+impl ImportFormat {
+    /// Guesses the format from a file name's extension.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let (_, ext) = name.rsplit_once('.')?;
+        match ext.to_ascii_lowercase().as_str() {
+            "step" | "stp" => Some(Self::Step),
+            "stl" => Some(Self::Stl),
+            _ => None,
+        }
+    }
+}
+
+/// The contents of an announced upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadData {
+    pub upload_id: u64,
+    pub data: Vec<u8>,
+}
+
+impl UploadData {
+    pub fn to_frame(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.data.len());
+        out.extend_from_slice(&UPLOAD_FRAME_MAGIC);
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&self.upload_id.to_le_bytes());
+        out.extend_from_slice(&self.data);
+        out
+    }
+
+    pub fn from_frame(bytes: &[u8]) -> Result<Self, FrameError> {
+        let header = bytes.get(..HEADER_LEN).ok_or(FrameError::Truncated)?;
+        if header[..4] != UPLOAD_FRAME_MAGIC {
+            return Err(FrameError::BadMagic);
+        }
+        Ok(Self {
+            upload_id: u64::from_le_bytes(header[8..16].try_into().unwrap()),
+            data: bytes[HEADER_LEN..].to_vec(),
+        })
+    }
+}
@@ -100,7 +100,7 @@ impl Renderer {
 
         let depth_texture = DepthTexture::new(&device, config.width, config.height);
 
-        let (mesh_pipeline, line_pipeline, overlay_pipeline) =
+        let (mesh_pipeline, wire_pipeline, line_pipeline, overlay_pipeline) =
             create_pipelines(&device, &camera_bind_group_layout, config.format);
         let line_settings = LineSettings::default();
         let plane_visibility = PlaneVisibility::default();
@@ -116,11 +116,14 @@ impl Renderer {
             camera_buffer,
             camera_bind_group,
             mesh_pipeline,
+            wire_pipeline,
             line_pipeline,
             overlay_pipeline,
             mesh_vertex_buffer: None,
             mesh_index_buffer: None,
             mesh_index_count: 0,
+            wire_index_buffer: None,
+            wire_index_count: 0,
             line_vertex_buffer,
             line_vertex_count,
             overlay_vertex_buffer: None,
@@ -456,11 +459,14 @@ struct RendererState {
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
     mesh_pipeline: wgpu::RenderPipeline,
+    wire_pipeline: wgpu::RenderPipeline,
     line_pipeline: wgpu::RenderPipeline,
     overlay_pipeline: wgpu::RenderPipeline,
     mesh_vertex_buffer: Option<wgpu::Buffer>,
     mesh_index_buffer: Option<wgpu::Buffer>,
     mesh_index_count: u32,
+    wire_index_buffer: Option<wgpu::Buffer>,
+    wire_index_count: u32,
     line_vertex_buffer: wgpu::Buffer,
     line_vertex_count: u32,
     overlay_vertex_buffer: Option<wgpu::Buffer>,
@@ -472,18 +478,23 @@ struct RendererState {
 
 impl RendererState {
     fn set_mesh(&mut self, mesh: TriMesh) {
-        if mesh.positions.is_empty() || mesh.indices.is_empty() {
+        self.mesh_index_buffer = None;
+        self.mesh_index_count = 0;
+        self.wire_index_buffer = None;
+        self.wire_index_count = 0;
+        if mesh.positions.is_empty() || (mesh.indices.is_empty() && mesh.line_indices.is_empty()) {
             self.mesh_vertex_buffer = None;
-            self.mesh_index_buffer = None;
-            self.mesh_index_count = 0;
             return;
         }
 
+        // Meshes without appearance channels get the default material.
         let mut vertices = Vec::with_capacity(mesh.positions.len());
-        for (pos, normal) in mesh.positions.into_iter().zip(mesh.normals.into_iter()) {
+        for (idx, (pos, normal)) in mesh.positions.into_iter().zip(mesh.normals).enumerate() {
             vertices.push(Vertex {
                 position: pos,
                 normal,
+                color: mesh.colors.get(idx).copied().unwrap_or(DEFAULT_COLOR),
+                material: mesh.materials.get(idx).copied().unwrap_or(DEFAULT_MATERIAL),
             });
         }
 
@@ -494,17 +505,27 @@ impl RendererState {
                 contents: bytemuck::cast_slice(&vertices),
                 usage: wgpu::BufferUsages::VERTEX,
             });
-        let index_buffer = self
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("mesh-index-buffer"),
-                contents: bytemuck::cast_slice(&mesh.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
         self.mesh_vertex_buffer = Some(vertex_buffer);
-        self.mesh_index_buffer = Some(index_buffer);
-        self.mesh_index_count = mesh.indices.len() as u32;
+        if !mesh.indices.is_empty() {
+            self.mesh_index_buffer = Some(self.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("mesh-index-buffer"),
+                    contents: bytemuck::cast_slice(&mesh.indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                },
+            ));
+            self.mesh_index_count = mesh.indices.len() as u32;
+        }
+        if !mesh.line_indices.is_empty() {
+            self.wire_index_buffer = Some(self.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("wire-index-buffer"),
+                    contents: bytemuck::cast_slice(&mesh.line_indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                },
+            ));
+            self.wire_index_count = mesh.line_indices.len() as u32;
+        }
     }
 
     fn set_plane_visibility(&mut self, xy: bool, yz: bool, zx: bool) {
@@ -645,6 +666,16 @@ impl RendererState {
                 pass.draw_indexed(0..self.mesh_index_count, 0, 0..1);
             }
 
+            // Wireframe bodies
+            if let (Some(vertex_buffer), Some(index_buffer)) =
+                (&self.mesh_vertex_buffer, &self.wire_index_buffer)
+            {
+                pass.set_pipeline(&self.wire_pipeline);
+                pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..self.wire_index_count, 0, 0..1);
+            }
+
             // Grid + axes
             pass.set_pipeline(&self.line_pipeline);
             pass.set_vertex_buffer(0, self.line_vertex_buffer.slice(..));
@@ -677,12 +708,15 @@ fn canvas_size(canvas: &HtmlCanvasElement) -> (u32, u32) {
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 struct CameraUniform {
     view_proj: [[f32; 4]; 4],
+    /// Eye position; `w` is padding.
+    eye: [f32; 4],
 }
 
 impl CameraUniform {
     fn from_camera(camera: &Camera) -> Self {
         Self {
             view_proj: camera.view_proj().to_cols_array_2d(),
+            eye: camera.eye().extend(1.0).to_array(),
         }
     }
 }
@@ -841,11 +875,17 @@ fn arcball_vector(x: f32, y: f32, width: f32, height: f32) -> Vec3 {
     }
 }
 
+const DEFAULT_COLOR: [f32; 4] = [0.78, 0.8, 0.84, 1.0];
+const DEFAULT_MATERIAL: [f32; 2] = [0.5, 0.0];
+
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 struct Vertex {
     position: [f32; 3],
     normal: [f32; 3],
+    color: [f32; 4],
+    /// `[roughness, metalness]`.
+    material: [f32; 2],
 }
 
 impl Vertex {
@@ -864,6 +904,16 @@ impl Vertex {
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
             ],
         }
     }
@@ -905,6 +955,7 @@ fn create_pipelines(
     wgpu::RenderPipeline,
     wgpu::RenderPipeline,
     wgpu::RenderPipeline,
+    wgpu::RenderPipeline,
 ) {
     let mesh_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("mesh-shader"),
@@ -936,7 +987,7 @@ fn create_pipelines(
             compilation_options: wgpu::PipelineCompilationOptions::default(),
             targets: &[Some(wgpu::ColorTargetState {
                 format: color_format,
-                blend: Some(wgpu::BlendState::REPLACE),
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
         }),
@@ -961,6 +1012,46 @@ fn create_pipelines(
         cache: None,
     });
 
+    let wire_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("wire-pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &mesh_shader,
+            entry_point: Some("vs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[Vertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &mesh_shader,
+            entry_point: Some("fs_wire"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    });
+
     let line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("line-pipeline"),
         layout: Some(&pipeline_layout),
@@ -1041,7 +1132,12 @@ fn create_pipelines(
         cache: None,
     });
 
-    (mesh_pipeline, line_pipeline, overlay_pipeline)
+    (
+        mesh_pipeline,
+        wire_pipeline,
+        line_pipeline,
+        overlay_pipeline,
+    )
 }
 
 fn create_line_buffers(
@@ -1203,6 +1299,7 @@ impl DepthTexture {
 const MESH_SHADER: &str = r#"
 struct Camera {
   view_proj: mat4x4<f32>,
+  eye: vec4<f32>,
 };
 
 @group(0) @binding(0)
@@ -1211,11 +1308,16 @@ var<uniform> camera: Camera;
 struct VertexInput {
   @location(0) position: vec3<f32>,
   @location(1) normal: vec3<f32>,
+  @location(2) color: vec4<f32>,
+  @location(3) material: vec2<f32>,
 };
 
 struct VertexOutput {
   @builtin(position) position: vec4<f32>,
   @location(0) normal: vec3<f32>,
+  @location(1) color: vec4<f32>,
+  @location(2) material: vec2<f32>,
+  @location(3) view_dir: vec3<f32>,
 };
 
 @vertex
@@ -1223,16 +1325,35 @@ fn vs_main(input: VertexInput) -> VertexOutput {
   var out: VertexOutput;
   out.position = camera.view_proj * vec4<f32>(input.position, 1.0);
   out.normal = normalize(input.normal);
+  out.color = input.color;
+  out.material = input.material;
+  out.view_dir = camera.eye.xyz - input.position;
   return out;
 }
 
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
   let light_dir = normalize(vec3<f32>(0.4, 0.7, 1.0));
-  let diffuse = max(dot(input.normal, light_dir), 0.0);
-  let base = vec3<f32>(0.78, 0.8, 0.84);
-  let color = base * (0.2 + 0.8 * diffuse);
-  return vec4<f32>(color, 1.0);
+  let n = normalize(input.normal);
+  let v = normalize(input.view_dir);
+  let h = normalize(light_dir + v);
+  let roughness = clamp(input.material.x, 0.04, 1.0);
+  let metalness = clamp(input.material.y, 0.0, 1.0);
+  let diffuse = max(dot(n, light_dir), 0.0);
+  // Blinn-Phong lobe whose width follows roughness.
+  let shininess = 2.0 / (roughness * roughness * roughness * roughness) - 2.0;
+  let spec = pow(max(dot(n, h), 0.0), max(shininess, 1.0)) * (1.0 - roughness);
+  let base = input.color.rgb;
+  let spec_color = mix(vec3<f32>(0.04), base, metalness);
+  let color = base * (1.0 - metalness) * (0.2 + 0.8 * diffuse)
+    + base * metalness * 0.2
+    + spec_color * spec;
+  return vec4<f32>(color, input.color.a);
+}
+
+@fragment
+fn fs_wire(input: VertexOutput) -> @location(0) vec4<f32> {
+  return input.color;
 }
 "#;
 
@@ -0,0 +1,365 @@
+//! HTTP API over the documents, for scripts and CI that would rather not
+//! speak the websocket protocol. When [`crate::TOKENS_VAR`] is
+//! set, requests carry one of its tokens as `Authorization: Bearer <token>`,
+//! or as `?token=` where a header cannot be set, e.g. in a download link.
+
+use crate::{
+    document_summaries, queue_job, restore_revision, AppState, DocumentHandle, JobTask, API_PEER,
+    EXPORT_FORMATS, IMPORT_FORMATS, LOCAL_USER, MAX_UPLOAD_LEN,
+};
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use cad_core::ObjectId;
+use cad_protocol::{
+    DocumentId, ErrorCode, ExportContent, ExportFormat, ImportFormat, JobPayload, RevisionId, Role,
+    ServerMsg,
+};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+type Params = Query<HashMap<String, String>>;
+
+pub fn routes() -> Router<AppState> {
+    let mut router = Router::new()
+        .route("/api/documents", get(documents))
+        .route("/api/documents/:id/model", get(model))
+        .route("/api/documents/:id/objects/:oid", get(object))
+        .route("/api/documents/:id/revisions", get(revisions))
+        .route(
+            "/api/documents/:id/revisions/:revision/restore",
+            post(restore),
+        )
+        .route(
+            "/api/documents/:id/import",
+            post(import).layer(DefaultBodyLimit::max(MAX_UPLOAD_LEN as usize)),
+        );
+    for format in [ExportFormat::Stl, ExportFormat::Step, ExportFormat::Gltf] {
+        let path = format!("/api/documents/:id/export.{}", format.extension());
+        let handler = move |state: State<AppState>, headers, params: Params, id| {
+            export(format, state, headers, params, id)
+        };
+        router = router.route(&path, get(handler));
+    }
+    router
+}
+
+/// Hosted and stored documents, those whose name contains `?query=` only
+/// when given.
+async fn documents(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Params,
+) -> Response {
+    if !authorized(&state, &headers, &params) {
+        return error(StatusCode::UNAUTHORIZED, "missing or unknown token");
+    }
+    let query = params.get("query").map_or("", String::as_str);
+    Json(document_summaries(&state, query)).into_response()
+}
+
+/// The whole model as the versioned JSON documents are saved in.
+async fn model(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Params,
+    Path(id): Path<DocumentId>,
+) -> Response {
+    let snapshot = match viewable(&state, &headers, &params, id) {
+        Ok((_, handle)) => handle.lock().unwrap().model.snapshot(),
+        Err(refused) => return *refused,
+    };
+    match snapshot.to_json() {
+        Ok(json) => ([(header::CONTENT_TYPE, "application/json")], json).into_response(),
+        Err(err) => {
+            warn!("encoding document {id}: {err}");
+            error(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+        }
+    }
+}
+
+async fn object(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Params,
+    Path((id, oid)): Path<(DocumentId, ObjectId)>,
+) -> Response {
+    let handle = match viewable(&state, &headers, &params, id) {
+        Ok((_, handle)) => handle,
+        Err(refused) => return *refused,
+    };
+    let document = handle.lock().unwrap();
+    match document.model.object(oid) {
+        Some(object) => Json(object).into_response(),
+        None => error(
+            StatusCode::NOT_FOUND,
+            format!("no object {oid} in document {id}"),
+        ),
+    }
+}
+
+/// The document's stored revisions, oldest first.
+async fn revisions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Params,
+    Path(id): Path<DocumentId>,
+) -> Response {
+    if let Err(refused) = viewable(&state, &headers, &params, id) {
+        return *refused;
+    }
+    let revisions = match &state.store {
+        Some(store) => store.revisions(id),
+        None => Ok(Vec::new()),
+    };
+    match revisions {
+        Ok(revisions) => Json(revisions).into_response(),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+    }
+}
+
+/// Puts the document back as it was at a revision, like
+/// [`cad_protocol::ClientMsg::RestoreRevision`]. Replies with the revision
+/// that stores it as `{"revision": n}`; needs a token whose user may edit
+/// the document.
+async fn restore(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Params,
+    Path((id, revision)): Path<(DocumentId, RevisionId)>,
+) -> Response {
+    let (user, document) = match viewable(&state, &headers, &params, id) {
+        Ok(viewable) => viewable,
+        Err(refused) => return *refused,
+    };
+    if !document
+        .lock()
+        .unwrap()
+        .role(&user)
+        .is_some_and(Role::can_edit)
+    {
+        return error(
+            StatusCode::FORBIDDEN,
+            format!("{user} cannot change document {id}"),
+        );
+    }
+    match restore_revision(&state, id, &document, revision, API_PEER).first() {
+        Some(ServerMsg::RevisionRestored { saved, .. }) => {
+            Json(serde_json::json!({ "revision": saved })).into_response()
+        }
+        Some(ServerMsg::Error {
+            code: ErrorCode::UnknownRevision,
+            message,
+            ..
+        }) => error(StatusCode::NOT_FOUND, message.clone()),
+        Some(ServerMsg::Error { message, .. }) => {
+            error(StatusCode::INTERNAL_SERVER_ERROR, message.clone())
+        }
+        _ => error(StatusCode::INTERNAL_SERVER_ERROR, "restore failed"),
+    }
+}
+
+/// Exports every visible body of the document as a download, on the job
+/// queue like [`cad_protocol::ClientMsg::RequestExport`].
+async fn export(
+    format: ExportFormat,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Params,
+    Path(id): Path<DocumentId>,
+) -> Response {
+    let model = match viewable(&state, &headers, &params, id) {
+        Ok((_, handle)) => handle.lock().unwrap().model.snapshot(),
+        Err(refused) => return *refused,
+    };
+    if !EXPORT_FORMATS.contains(&format) {
+        return error(
+            StatusCode::NOT_IMPLEMENTED,
+            format!("cannot export {format:?}"),
+        );
+    }
+    let task = JobTask::Export {
+        format,
+        objects: Vec::new(),
+        model,
+    };
+    // The job reports to this request as it would to a client.
+    let (tx, mut rx) = mpsc::channel(16);
+    for reply in queue_job(&state, task, None, &tx) {
+        if let ServerMsg::Error { message, .. } = reply {
+            return error(StatusCode::SERVICE_UNAVAILABLE, message);
+        }
+    }
+    drop(tx);
+    while let Some(envelope) = rx.recv().await {
+        let message = match envelope.msg {
+            ServerMsg::ExportReady {
+                name,
+                mime,
+                content: ExportContent::Bytes(bytes),
+                ..
+            } => {
+                let disposition = format!("attachment; filename=\"{name}\"");
+                let headers = [
+                    (header::CONTENT_TYPE, mime),
+                    (header::CONTENT_DISPOSITION, disposition),
+                ];
+                return (headers, bytes).into_response();
+            }
+            ServerMsg::JobResult {
+                payload: JobPayload::Error { message },
+                ..
+            } => message,
+            ServerMsg::JobResult {
+                payload: JobPayload::Canceled,
+                ..
+            } => "export canceled".to_string(),
+            _ => continue,
+        };
+        return error(StatusCode::INTERNAL_SERVER_ERROR, message);
+    }
+    error(StatusCode::INTERNAL_SERVER_ERROR, "export produced no file")
+}
+
+/// Adds the bodies of the first file in a multipart form to the document,
+/// on the job queue like an upload over the websocket. Replies with the new
+/// object ids as `{"objects": [...]}`. Needs a token whose user may edit
+/// the document.
+async fn import(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Params,
+    Path(id): Path<DocumentId>,
+    mut form: Multipart,
+) -> Response {
+    let Some(user) = token_user(&state, &headers, &params) else {
+        return error(StatusCode::UNAUTHORIZED, "missing or unknown token");
+    };
+    let (name, data) = loop {
+        let field = match form.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => return error(StatusCode::BAD_REQUEST, "no file in the form"),
+            Err(err) => return error(StatusCode::BAD_REQUEST, err.to_string()),
+        };
+        let Some(name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        match field.bytes().await {
+            Ok(data) => break (name, data.to_vec()),
+            Err(err) => return error(StatusCode::BAD_REQUEST, err.to_string()),
+        }
+    };
+    let format = match ImportFormat::from_file_name(&name) {
+        Some(format) if IMPORT_FORMATS.contains(&format) => format,
+        _ => {
+            return error(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("cannot import {name}"),
+            )
+        }
+    };
+    let document = match viewable(&state, &headers, &params, id) {
+        Ok((_, document)) => document,
+        Err(refused) => return *refused,
+    };
+    if !document
+        .lock()
+        .unwrap()
+        .role(&user)
+        .is_some_and(Role::can_edit)
+    {
+        return error(
+            StatusCode::FORBIDDEN,
+            format!("{user} cannot change document {id}"),
+        );
+    }
+    let task = JobTask::Import {
+        upload_id: 0,
+        name,
+        format,
+        data,
+        document: (id, document),
+        peer: API_PEER,
+        peers: state.peers.clone(),
+    };
+    let (tx, mut rx) = mpsc::channel(16);
+    for reply in queue_job(&state, task, None, &tx) {
+        if let ServerMsg::Error { message, .. } = reply {
+            return error(StatusCode::SERVICE_UNAVAILABLE, message);
+        }
+    }
+    drop(tx);
+    while let Some(envelope) = rx.recv().await {
+        match envelope.msg {
+            ServerMsg::ImportResult { objects, .. } => {
+                return Json(serde_json::json!({ "objects": objects })).into_response()
+            }
+            ServerMsg::JobResult {
+                payload: JobPayload::Error { message },
+                ..
+            } => return error(StatusCode::UNPROCESSABLE_ENTITY, message),
+            ServerMsg::JobResult { .. } => break,
+            _ => {}
+        }
+    }
+    error(StatusCode::INTERNAL_SERVER_ERROR, "import did not finish")
+}
+
+fn authorized(state: &AppState, headers: &HeaderMap, params: &HashMap<String, String>) -> bool {
+    token_user(state, headers, params).is_some()
+}
+
+/// The request's user and the document `id`, if they may view it, or the
+/// response to send instead.
+fn viewable(
+    state: &AppState,
+    headers: &HeaderMap,
+    params: &HashMap<String, String>,
+    id: DocumentId,
+) -> Result<(String, DocumentHandle), Box<Response>> {
+    let Some(user) = token_user(state, headers, params) else {
+        return Err(Box::new(error(
+            StatusCode::UNAUTHORIZED,
+            "missing or unknown token",
+        )));
+    };
+    match crate::viewable(state, &user, id) {
+        Ok(document) => Ok((user, document)),
+        Err((code, message)) => {
+            let status = match code {
+                ErrorCode::UnknownDocument => StatusCode::NOT_FOUND,
+                ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err(Box::new(error(status, message)))
+        }
+    }
+}
+
+/// Who the request's token belongs to.
+fn token_user(
+    state: &AppState,
+    headers: &HeaderMap,
+    params: &HashMap<String, String>,
+) -> Option<String> {
+    let Some(tokens) = &state.tokens else {
+        return Some(LOCAL_USER.to_string());
+    };
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let token = bearer.or(params.get("token").map(String::as_str))?;
+    tokens.get(token.trim()).cloned()
+}
+
+/// A failed request, as `{"error": message}`.
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    let body = serde_json::json!({ "error": message.into() });
+    (status, Json(body)).into_response()
+}
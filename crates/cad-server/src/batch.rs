@@ -0,0 +1,150 @@
+//! `cad-server batch`: one operation on one document, without a browser or
+//! a listening server, for CI pipelines and scripted conversions.
+//!
+//! `cad-server batch <document> <operation> [<output>]` reads the document,
+//! a stored document's id (from the database at [`crate::STORE_VAR`]) or
+//! the path of a model saved as JSON, and writes the operation's result to
+//! `<output>`, or to stdout when it is `-` or left out. The operations are
+//! the export formats by extension (`stl`, `glb`) and `mass`, the mass
+//! properties of the visible bodies as JSON. STEP joins them once the kernel
+//! can write it.
+
+use crate::{open_store, replay, Document, STORE_VAR};
+use cad_core::Model;
+use cad_geom::GeomScene;
+use cad_protocol::DocumentId;
+use std::{io::Write, path::PathBuf};
+use tracing::warn;
+
+#[derive(Debug)]
+pub struct Batch {
+    document: String,
+    operation: Operation,
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Operation {
+    Stl,
+    Glb,
+    Mass,
+}
+
+impl Batch {
+    /// The arguments after `batch`.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let usage = "usage: cad-server batch <document> <stl|glb|mass> [<output>]";
+        let (Some(document), Some(operation)) = (args.next(), args.next()) else {
+            return Err(usage.to_string());
+        };
+        let operation = match operation.as_str() {
+            "stl" => Operation::Stl,
+            "glb" => Operation::Glb,
+            "mass" => Operation::Mass,
+            name => return Err(format!("unknown operation {name:?}; {usage}")),
+        };
+        let output = args.next().filter(|path| path != "-").map(PathBuf::from);
+        if let Some(arg) = args.next() {
+            return Err(format!("unexpected argument {arg:?}; {usage}"));
+        }
+        Ok(Self {
+            document,
+            operation,
+            output,
+        })
+    }
+
+    pub fn run(self) -> Result<(), String> {
+        let model = self.model()?;
+        let unit = model.unit();
+        let scene = GeomScene::from_model(model).map_err(|err| err.to_string())?;
+        let bytes = match self.operation {
+            Operation::Stl => scene.export_stl(&[]),
+            Operation::Glb => scene.export_glb(&[]),
+            Operation::Mass => scene.export_mesh(&[]).map(|mesh| {
+                let props = mesh.mass_properties();
+                let json = serde_json::json!({
+                    "unit": unit.symbol(),
+                    "watertight": mesh.validate().is_watertight(),
+                    "volume": props.volume,
+                    "surface_area": props.surface_area,
+                    "centroid": props.centroid,
+                    "inertia": props.inertia,
+                });
+                format!("{json}\n").into_bytes()
+            }),
+        }
+        .map_err(|err| err.to_string())?;
+        match &self.output {
+            Some(path) => std::fs::write(path, bytes)
+                .map_err(|err| format!("cannot write {}: {err}", path.display())),
+            None => std::io::stdout()
+                .write_all(&bytes)
+                .map_err(|err| format!("cannot write to stdout: {err}")),
+        }
+    }
+
+    /// The stored document as the server would open it, its logged edits
+    /// included, or the model in the file.
+    fn model(&self) -> Result<Model, String> {
+        let Ok(id) = self.document.parse::<DocumentId>() else {
+            let json = std::fs::read_to_string(&self.document)
+                .map_err(|err| format!("cannot read {}: {err}", self.document))?;
+            return Model::from_json(&json).map_err(|err| format!("{}: {err}", self.document));
+        };
+        let store =
+            open_store()?.ok_or_else(|| format!("set {STORE_VAR} to open stored documents"))?;
+        let (name, _, model) = store
+            .load(id)
+            .map_err(|err| err.to_string())?
+            .ok_or_else(|| format!("no document {id}"))?;
+        let journal = match store.journal(id) {
+            Ok(Some(journal)) => journal,
+            Ok(None) => return Ok(model),
+            Err(err) => {
+                warn!("reading the log of document {id}, using its last save: {err}");
+                return Ok(model);
+            }
+        };
+        let mut document = Document {
+            name,
+            ..Document::default()
+        };
+        replay(&mut document, journal);
+        Ok(document.model.snapshot().into_model())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Batch, String> {
+        Batch::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn arguments_parse_or_explain_usage() {
+        let batch = parse(&["7", "glb", "out.glb"]).unwrap();
+        assert_eq!(batch.document, "7");
+        assert_eq!(batch.operation, Operation::Glb);
+        assert_eq!(batch.output, Some(PathBuf::from("out.glb")));
+        assert_eq!(
+            parse(&["part.json", "mass"]).unwrap().operation,
+            Operation::Mass
+        );
+        assert_eq!(parse(&["7", "stl", "-"]).unwrap().output, None);
+        assert_eq!(parse(&["7", "stl"]).unwrap().output, None);
+
+        for args in [
+            &[][..],
+            &["7"],
+            &["7", "step"],
+            &["7", "obj"],
+            &["7", "stl", "a", "b"],
+        ] {
+            let err = parse(args).unwrap_err();
+            assert!(err.contains("usage: cad-server batch"), "{args:?}: {err}");
+        }
+    }
+}
@@ -1,50 +1,477 @@
 use axum::{
-    extract::{ws::Message, ws::WebSocket, ws::WebSocketUpgrade, State},
-    response::{IntoResponse, Redirect},
+    extract::{ws::Message, ws::WebSocket, ws::WebSocketUpgrade, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
     routing::get,
     Router,
 };
-use cad_protocol::{ClientMsg, ServerMsg};
+use cad_core::{
+    fresh_client_id, Anchor, FeatureOp, Model, ModelCommand, ModelDelta, ModelSnapshot, ObjectId,
+    ObjectKind, SharedModel, UndoStack,
+};
+use cad_geom::{GeomError, GeomScene, TriMesh};
+use cad_protocol::{
+    review_text, Capabilities, Chunker, ClientMsg, Comment, CommentId, Compression, DocumentId,
+    DocumentSummary, Encoding, Envelope, ErrorCode, ExportContent, ExportFormat, FeatureKind,
+    Frame, HistoryStep, ImportFormat, JobPayload, LastWrites, MeshData, MeshTarget, Peer, PeerId,
+    Reassembler, RequestId, RevisionId, Role, ServerMsg, Stamp, UploadData, MAX_REVIEW_TEXT_LEN,
+    MAX_STREAM_RATE, MSGPACK_FRAME_MAGIC, PEER_TIMEOUT_MS, PING_INTERVAL_MS,
+};
 use futures_util::{SinkExt, StreamExt};
 use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap, VecDeque},
     path::PathBuf,
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc,
+        Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio_util::sync::CancellationToken;
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::trace::TraceLayer;
 use tracing::{info, warn};
 
+mod api;
+mod batch;
+mod metrics;
+mod primitive;
+mod record;
+mod store;
+
+use metrics::Metrics;
+use record::Recorder;
+use store::{DocumentStore, Journal, LoggedOp, SqliteStore};
+
 #[derive(Clone)]
 struct AppState {
-    job_tx: mpsc::Sender<HeavyJob>,
+    /// Wakes an idle worker when a job is queued.
+    job_ready: Arc<Notify>,
     next_job_id: Arc<AtomicU64>,
+    jobs: Arc<Mutex<JobQueue>>,
+    /// Every hosted document; clients start out in [`FIRST_DOCUMENT`].
+    documents: Arc<Mutex<BTreeMap<DocumentId, DocumentHandle>>>,
+    next_document_id: Arc<AtomicU64>,
+    /// Access tokens and the users they belong to; `None` turns
+    /// authentication off.
+    tokens: Option<Arc<HashMap<String, String>>>,
+    /// Authenticated clients. Those with the same document open form its
+    /// room: they see each other's presence and edits.
+    peers: Arc<Mutex<BTreeMap<PeerId, PeerEntry>>>,
+    next_peer_id: Arc<AtomicU64>,
+    /// Sessions of dropped connections by token, until they are resumed or
+    /// [`RESUME_WINDOW`] passes.
+    parked: Arc<Mutex<HashMap<String, ParkedSession>>>,
+    /// Saved documents, loaded into [`AppState::documents`] when opened;
+    /// `None` when [`STORE_VAR`] is unset, which keeps documents in memory
+    /// only.
+    store: Option<Arc<dyn DocumentStore>>,
+    metrics: Arc<Metrics>,
+    /// Where each session's client messages are recorded; see
+    /// [`RECORD_VAR`].
+    recordings: Option<PathBuf>,
+}
+
+struct PeerEntry {
+    peer: Peer,
+    /// The document the peer has open, which scopes what it is sent.
+    document: Option<DocumentId>,
+    tx: mpsc::Sender<Envelope<ServerMsg>>,
+}
+
+/// Per-connection state.
+struct Session {
+    /// Names the session for [`ClientMsg::Hello`] to resume.
+    token: String,
+    peer_id: PeerId,
+    /// Who the client authenticated as; set from the start when
+    /// authentication is off.
+    user: Option<String>,
+    /// Whether the client is in [`AppState::peers`], which happens once it
+    /// is authenticated.
+    joined: bool,
+    /// The document the client edits; authoritative state lives here.
+    document: Option<(DocumentId, DocumentHandle)>,
+    /// What the client last shared with [`ClientMsg::SetSelection`].
+    selection: Vec<ObjectId>,
+    cursor: Option<[f32; 3]>,
+    /// Announced uploads whose data has not arrived yet.
+    uploads: HashMap<u64, PendingUpload>,
+    stream: StreamLimit,
+}
+
+/// Counts the client's streamed messages in one-second windows, enforcing
+/// [`MAX_STREAM_RATE`].
+struct StreamLimit {
+    window: Instant,
+    count: u64,
+    /// Whether the client was sent [`ServerMsg::SlowDown`] this window.
+    warned: bool,
+}
+
+impl StreamLimit {
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    fn new() -> Self {
+        Self {
+            window: Instant::now(),
+            count: 0,
+            warned: false,
+        }
+    }
+
+    /// Counts a message arriving at `now`, or refuses it with the time left
+    /// until the window ends.
+    fn admit(&mut self, now: Instant) -> Result<(), Duration> {
+        if now.duration_since(self.window) >= Self::WINDOW {
+            *self = Self {
+                window: now,
+                count: 0,
+                warned: false,
+            };
+        }
+        if self.count >= MAX_STREAM_RATE {
+            return Err(Self::WINDOW.saturating_sub(now.duration_since(self.window)));
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+struct PendingUpload {
+    name: String,
+    format: ImportFormat,
+    size: u64,
+    /// The announcing request, echoed in the import's replies.
+    request_id: Option<RequestId>,
+}
+
+/// The session of a dropped connection, waiting for the client to come
+/// back.
+struct ParkedSession {
+    session: Session,
+    /// The dropped connection's channel, which the session's jobs still
+    /// reply on.
+    out_tx: mpsc::Sender<Envelope<ServerMsg>>,
+    resume: Resume,
+    since: Instant,
+}
+
+/// Hands [`hold_replies`] the channel of the connection that resumes a
+/// session.
+type Resume = oneshot::Sender<mpsc::Sender<Envelope<ServerMsg>>>;
+
+const PEER_COLORS: [[u8; 3]; 6] = [
+    [230, 90, 70],
+    [70, 150, 230],
+    [90, 190, 110],
+    [220, 170, 50],
+    [170, 100, 220],
+    [60, 190, 190],
+];
+
+/// A document and the number of updates applied to it, which numbers the
+/// deltas sent to clients.
+#[derive(Default)]
+struct Document {
+    name: String,
+    model: SharedModel,
+    seq: u64,
+    /// Shared by every client, so an undo reverts the last edit whoever
+    /// made it.
+    history: UndoStack,
+    /// Review comments, oldest first; not part of the model or its history.
+    comments: Vec<Comment>,
+    /// Who wrote each comment, as authenticated rather than as named, so
+    /// only they and owners resolve it.
+    comment_users: BTreeMap<CommentId, String>,
+    next_comment_id: CommentId,
+    /// The latest revision in the store, if it was ever saved.
+    revision: Option<RevisionId>,
+    /// The `seq` that revision was stored at; behind `seq` while the
+    /// document has unsaved changes.
+    saved_seq: u64,
+    /// `None` until the document is saved; see [`OpLog`].
+    log: Option<OpLog>,
+    /// Users' roles by name; empty for documents nobody owns, see
+    /// [`Document::role`].
+    roles: BTreeMap<String, Role>,
+    /// Settles concurrent moves of one object; not stored, so the first
+    /// move after a restart wins.
+    last_writes: LastWrites,
+}
+
+type DocumentHandle = Arc<Mutex<Document>>;
+
+/// Where a stored document logs what is applied to it, so its edits since
+/// the last save and its history outlive the server. The log is compacted
+/// into a checkpoint every [`CHECKPOINT_EVERY`] ops.
+struct OpLog {
+    store: Arc<dyn DocumentStore>,
+    id: DocumentId,
+    /// Ops logged since the last checkpoint.
+    len: usize,
+}
+
+impl Document {
+    /// An empty document whose model mints stable ids as a client of its
+    /// own.
+    fn new(name: String) -> Self {
+        let mut document = Self {
+            name,
+            ..Self::default()
+        };
+        document.model.edit().set_client_id(fresh_client_id());
+        document
+    }
+
+    /// What `user` may do here, if anything: in documents nobody owns, the
+    /// first one and those made before roles, everyone edits; in the others
+    /// only those given a role may as much as view.
+    fn role(&self, user: &str) -> Option<Role> {
+        if self.roles.is_empty() {
+            return Some(Role::Editor);
+        }
+        self.roles.get(user).copied()
+    }
+
+    /// The stamp of an edit by `peer` made after it applied update
+    /// `base_seq`; without one, after every update so far, as for edits
+    /// the server makes itself.
+    fn stamp(&self, peer: PeerId, base_seq: Option<u64>) -> Stamp {
+        Stamp {
+            seq: base_seq.map_or(self.seq, |seq| seq.min(self.seq)),
+            peer,
+        }
+    }
+
+    /// Notes `stamp` as the last write to every object `delta` adds or
+    /// changes. Every edit that commits calls it, so a move based on an
+    /// update from before any of them loses.
+    fn wrote(&mut self, delta: &ModelDelta, stamp: Stamp) {
+        let written = delta.added.iter().map(|(_, object)| object);
+        for object in written.chain(&delta.changed) {
+            self.last_writes.record(object.id, stamp);
+        }
+        for &id in &delta.removed {
+            self.last_writes.forget(id);
+        }
+    }
+
+    /// Logs an op just applied. Called under the document's lock, so ops
+    /// are logged in the order they apply.
+    fn record(&mut self, op: LoggedOp) {
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        if log.len + 1 >= CHECKPOINT_EVERY {
+            // The model already holds the op, so the checkpoint does too.
+            return self.checkpoint();
+        }
+        log.len += 1;
+        if let Err(err) = log.store.log_op(log.id, &op) {
+            warn!("logging an edit to document {}: {err}", log.id);
+        }
+    }
+
+    /// Replaces the log with the document as it is, also for changes that
+    /// are not ops.
+    fn checkpoint(&mut self) {
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        log.len = 0;
+        if let Err(err) = log.store.checkpoint(log.id, &self.model, &self.history) {
+            warn!("checkpointing document {}: {err}", log.id);
+        }
+    }
+}
+
+/// Stands for edits made over the HTTP API; no connected peer has it.
+const API_PEER: PeerId = 0;
+/// Who everyone is when [`TOKENS_VAR`] is unset.
+const LOCAL_USER: &str = "local";
+
+/// Created at startup, so clients have a document to edit right away.
+const FIRST_DOCUMENT: DocumentId = 1;
+
+/// Kinds of [`JobTask::Demo`] the server runs.
+const JOB_KINDS: [&str; 1] = ["demo"];
+const EXPORT_FORMATS: [ExportFormat; 2] = [ExportFormat::Stl, ExportFormat::Gltf];
+/// Features the kernel computes; booleans and fillets are not implemented.
+const FEATURE_OPS: [FeatureKind; 2] = [FeatureKind::LinearPattern, FeatureKind::PushPull];
+const IMPORT_FORMATS: [ImportFormat; 1] = [ImportFormat::Stl];
+/// Well under [`cad_protocol::MAX_TRANSFER_LEN`], leaving room for the
+/// upload frame's header.
+const MAX_UPLOAD_LEN: u64 = 256 * 1024 * 1024;
+const MAX_TRIANGLES: u64 = 2_000_000;
+const MAX_THUMBNAIL_LEN: usize = 1024 * 1024;
+/// Side, in pixels, of the thumbnails the server draws itself.
+const THUMBNAIL_SIZE: u32 = 256;
+/// Beyond this many waiting jobs, new ones are refused.
+const MAX_PENDING_JOBS: usize = 64;
+/// Tolerance of [`ClientMsg::RequestMesh`] when the client names none, a
+/// fifth of the browser's.
+const MESH_TOLERANCE: f64 = 0.002;
+/// Finer requests could take the worker down with them.
+const MIN_MESH_TOLERANCE: f64 = 1.0e-4;
+/// How long the session of a dropped connection can be resumed.
+const RESUME_WINDOW: Duration = Duration::from_secs(60);
+/// Ops a document logs before the log is compacted.
+const CHECKPOINT_EVERY: usize = 100;
+
+/// Advertised in every [`ServerMsg::HelloAck`].
+fn capabilities(state: &AppState) -> Capabilities {
+    Capabilities {
+        job_kinds: JOB_KINDS.map(String::from).to_vec(),
+        export_formats: EXPORT_FORMATS.to_vec(),
+        import_formats: IMPORT_FORMATS.to_vec(),
+        max_upload_len: MAX_UPLOAD_LEN,
+        max_triangles: MAX_TRIANGLES,
+        storage: state.store.is_some(),
+        feature_ops: FEATURE_OPS.to_vec(),
+    }
+}
+
+/// Jobs waiting for a worker, most urgent first, and those running.
+#[derive(Default)]
+struct JobQueue {
+    pending: VecDeque<HeavyJob>,
+    /// Started jobs, until their worker reports them.
+    running: Vec<RunningJob>,
+}
+
+struct RunningJob {
+    id: u64,
+    respond_to: mpsc::Sender<Envelope<ServerMsg>>,
+    cancel: CancellationToken,
+}
+
+/// Interactive jobs, which someone is waiting on, run before batch ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum JobPriority {
+    Batch,
+    Interactive,
 }
 
 struct HeavyJob {
     id: u64,
-    kind: String,
-    payload: Option<String>,
-    respond_to: mpsc::Sender<ServerMsg>,
+    task: JobTask,
+    /// The request that started the job, echoed in its progress and result.
+    request_id: Option<RequestId>,
+    respond_to: mpsc::Sender<Envelope<ServerMsg>>,
+    /// Fired by [`ClientMsg::CancelJob`] or the worker's timeout.
+    cancel: CancellationToken,
+}
+
+enum JobTask {
+    /// A stand-in job from [`ClientMsg::RequestHeavy`].
+    Demo {
+        kind: String,
+        payload: Option<String>,
+    },
+    Export {
+        format: ExportFormat,
+        objects: Vec<ObjectId>,
+        /// The document as of the request.
+        model: ModelSnapshot,
+    },
+    Mesh {
+        objects: Vec<ObjectId>,
+        tolerance: f64,
+        model: ModelSnapshot,
+    },
+    /// Computes a feature on a snapshot, then commits it to `document`.
+    Feature {
+        id: ObjectId,
+        op: FeatureOp,
+        model: ModelSnapshot,
+        document: (DocumentId, DocumentHandle),
+        peer: PeerId,
+        /// To notify the document's other clients of the commit.
+        peers: Arc<Mutex<BTreeMap<PeerId, PeerEntry>>>,
+    },
+    /// Reads a file, then adds its bodies to `document`.
+    Import {
+        upload_id: u64,
+        name: String,
+        format: ImportFormat,
+        data: Vec<u8>,
+        document: (DocumentId, DocumentHandle),
+        peer: PeerId,
+        peers: Arc<Mutex<BTreeMap<PeerId, PeerEntry>>>,
+    },
+}
+
+impl JobTask {
+    /// How the job is labeled in `/metrics`.
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Demo { .. } => "demo",
+            Self::Export { .. } => "export",
+            Self::Mesh { .. } => "mesh",
+            Self::Feature { .. } => "feature",
+            Self::Import { .. } => "import",
+        }
+    }
+
+    fn priority(&self) -> JobPriority {
+        match self {
+            Self::Mesh { .. } | Self::Feature { .. } | Self::Import { .. } => {
+                JobPriority::Interactive
+            }
+            Self::Demo { .. } | Self::Export { .. } => JobPriority::Batch,
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
-
-    let (job_tx, job_rx) = mpsc::channel(64);
-    tokio::spawn(job_worker(job_rx));
+    let mode = mode();
+    let logs = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env());
+    // Replays and batch runs print their output on stdout.
+    match mode {
+        Mode::Serve => logs.init(),
+        Mode::Replay(_) | Mode::Batch(_) => logs.with_writer(std::io::stderr).init(),
+    }
 
-    let state = AppState {
-        job_tx,
-        next_job_id: Arc::new(AtomicU64::new(1)),
+    let replay = match mode {
+        Mode::Serve => None,
+        Mode::Replay(path) => Some(path),
+        Mode::Batch(batch) => {
+            if let Err(err) = batch.run() {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+    };
+    if let Some(path) = replay {
+        let store = match replay_store() {
+            Ok(store) => store,
+            Err(err) => {
+                eprintln!("cannot copy the document store: {err}");
+                std::process::exit(1);
+            }
+        };
+        let state = start(Some(store));
+        if let Err(err) = record::replay(state, &path).await {
+            eprintln!("cannot read {}: {err}", path.display());
+            std::process::exit(1);
+        }
+        return;
+    }
+    let store = match open_store() {
+        Ok(store) => store,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
     };
+    let state = start(store);
 
     let dist_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../web/dist");
     let index_file = dist_dir.join("index.html");
@@ -55,6 +482,9 @@ async fn main() {
             get(|| async { Redirect::temporary("/icon.svg") }),
         )
         .route("/ws", get(ws_handler))
+        .route("/documents/:id/thumbnail", get(thumbnail_handler))
+        .merge(api::routes())
+        .merge(metrics::routes())
         .nest_service(
             "/",
             ServeDir::new(dist_dir.clone()).append_index_html_on_directories(true),
@@ -64,113 +494,2600 @@ async fn main() {
         .layer(TraceLayer::new_for_http());
 
     let addr = "0.0.0.0:8080";
+    if let Some((cert, key)) = tls_paths() {
+        return serve_tls(addr, app, cert, key).await;
+    }
     info!("listening on http://{addr}");
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
 
+/// Starts the job workers and sets up the documents clients start with,
+/// keeping them in `store` if there is one.
+fn start(store: Option<Arc<dyn DocumentStore>>) -> AppState {
+    let jobs = Arc::<Mutex<JobQueue>>::default();
+    let job_ready = Arc::new(Notify::new());
+    let peers = Arc::<Mutex<BTreeMap<PeerId, PeerEntry>>>::default();
+    let metrics = Arc::<Metrics>::default();
+    let (workers, timeout) = (job_workers(), job_timeout());
+    for _ in 0..workers {
+        let worker = job_worker(
+            jobs.clone(),
+            job_ready.clone(),
+            peers.clone(),
+            metrics.clone(),
+            timeout,
+        );
+        tokio::spawn(worker);
+    }
+    info!("running {workers} job workers");
+
+    let stored = match &store {
+        Some(store) => store.list("").expect("cannot list stored documents"),
+        None => Vec::new(),
+    };
+    let last_stored = stored.iter().map(|doc| doc.id).max().unwrap_or(0);
+    let state = AppState {
+        job_ready,
+        next_job_id: Arc::new(AtomicU64::new(1)),
+        jobs,
+        documents: Arc::default(),
+        next_document_id: Arc::new(AtomicU64::new(last_stored.max(FIRST_DOCUMENT) + 1)),
+        tokens: load_tokens().map(Arc::new),
+        peers,
+        next_peer_id: Arc::new(AtomicU64::new(1)),
+        parked: Arc::default(),
+        store,
+        metrics,
+        recordings: recordings_dir(),
+    };
+    if state.tokens.is_none() {
+        info!("{TOKENS_VAR} not set; clients need no token");
+    }
+    if let (Some(store), Some(period)) = (&state.store, autosave_period()) {
+        info!("autosaving changed documents every {period:?}");
+        tokio::spawn(autosave(state.clone(), store.clone(), period));
+    }
+    match load_document(&state, FIRST_DOCUMENT) {
+        Ok(Some(_)) => info!("{} stored documents", stored.len()),
+        Ok(None) => {
+            let document = Document::new("Untitled".to_string());
+            let handle = Arc::new(Mutex::new(document));
+            state
+                .documents
+                .lock()
+                .unwrap()
+                .insert(FIRST_DOCUMENT, handle);
+        }
+        Err(err) => panic!("cannot load document {FIRST_DOCUMENT}: {err}"),
+    }
+    state
+}
+
+enum Mode {
+    Serve,
+    /// `--replay <recording>`; see [`record::replay`].
+    Replay(PathBuf),
+    /// `batch ...`; see [`batch`].
+    Batch(batch::Batch),
+}
+
+fn mode() -> Mode {
+    let mut args = std::env::args().skip(1);
+    let Some(arg) = args.next() else {
+        return Mode::Serve;
+    };
+    match arg.as_str() {
+        "--replay" => Mode::Replay(args.next().expect("--replay needs a recording").into()),
+        "batch" => match batch::Batch::parse(args) {
+            Ok(batch) => Mode::Batch(batch),
+            Err(usage) => {
+                eprintln!("{usage}");
+                std::process::exit(2);
+            }
+        },
+        arg => panic!("unknown argument {arg:?}"),
+    }
+}
+
+const TLS_CERT_VAR: &str = "PHYSALIS_TLS_CERT";
+const TLS_KEY_VAR: &str = "PHYSALIS_TLS_KEY";
+
+/// The PEM certificate chain and private key to serve HTTPS and WSS with,
+/// from `PHYSALIS_TLS_CERT` and `PHYSALIS_TLS_KEY`.
+fn tls_paths() -> Option<(PathBuf, PathBuf)> {
+    match (
+        std::env::var_os(TLS_CERT_VAR),
+        std::env::var_os(TLS_KEY_VAR),
+    ) {
+        (Some(cert), Some(key)) => Some((cert.into(), key.into())),
+        (None, None) => None,
+        _ => panic!("set both {TLS_CERT_VAR} and {TLS_KEY_VAR}, or neither"),
+    }
+}
+
+#[cfg(feature = "tls")]
+async fn serve_tls(addr: &str, app: Router, cert: PathBuf, key: PathBuf) {
+    use axum_server::tls_rustls::RustlsConfig;
+
+    let config = match RustlsConfig::from_pem_file(&cert, &key).await {
+        Ok(config) => config,
+        Err(err) => panic!(
+            "cannot load {} and {}: {err}",
+            cert.display(),
+            key.display()
+        ),
+    };
+    let addr = addr.parse().unwrap();
+    info!("listening on https://{addr}");
+    axum_server::bind_rustls(addr, config)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
+
+#[cfg(not(feature = "tls"))]
+async fn serve_tls(_addr: &str, _app: Router, _cert: PathBuf, _key: PathBuf) {
+    panic!("{TLS_CERT_VAR} is set, but the server was built without the `tls` feature");
+}
+
+const TOKENS_VAR: &str = "PHYSALIS_TOKENS";
+
+/// Reads access tokens from `PHYSALIS_TOKENS`, a comma-separated list of
+/// `token=user` pairs; a bare token stands for a user of the same name.
+fn load_tokens() -> Option<HashMap<String, String>> {
+    let value = std::env::var(TOKENS_VAR).ok()?;
+    let tokens = value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((token, user)) => (token.trim().to_string(), user.trim().to_string()),
+            None => (entry.to_string(), entry.to_string()),
+        })
+        .collect();
+    Some(tokens)
+}
+
+const STORE_VAR: &str = "PHYSALIS_DB";
+const JOB_WORKERS_VAR: &str = "PHYSALIS_JOB_WORKERS";
+const DEFAULT_JOB_WORKERS: usize = 2;
+
+/// How many jobs run at once, from `PHYSALIS_JOB_WORKERS`.
+fn job_workers() -> usize {
+    let Ok(value) = std::env::var(JOB_WORKERS_VAR) else {
+        return DEFAULT_JOB_WORKERS;
+    };
+    match value.trim().parse() {
+        Ok(workers) if workers > 0 => workers,
+        _ => panic!("{JOB_WORKERS_VAR} must be a positive number, not {value:?}"),
+    }
+}
+
+const JOB_TIMEOUT_VAR: &str = "PHYSALIS_JOB_TIMEOUT";
+const DEFAULT_JOB_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long a job may run, in seconds from `PHYSALIS_JOB_TIMEOUT`, before
+/// the worker gives up on it and moves on.
+fn job_timeout() -> Duration {
+    let Ok(value) = std::env::var(JOB_TIMEOUT_VAR) else {
+        return DEFAULT_JOB_TIMEOUT;
+    };
+    match value.trim().parse() {
+        Ok(secs) if secs > 0.0 => Duration::from_secs_f64(secs),
+        _ => panic!("{JOB_TIMEOUT_VAR} must be a positive number of seconds, not {value:?}"),
+    }
+}
+
+const AUTOSAVE_VAR: &str = "PHYSALIS_AUTOSAVE";
+const DEFAULT_AUTOSAVE: Duration = Duration::from_secs(60);
+
+/// How often changed documents are stored, in seconds from
+/// `PHYSALIS_AUTOSAVE`; `None` when it is `0`.
+fn autosave_period() -> Option<Duration> {
+    let Ok(value) = std::env::var(AUTOSAVE_VAR) else {
+        return Some(DEFAULT_AUTOSAVE);
+    };
+    match value.trim().parse() {
+        Ok(0.0) => None,
+        Ok(secs) if secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+        _ => panic!("{AUTOSAVE_VAR} must be a number of seconds, not {value:?}"),
+    }
+}
+
+const RECORD_VAR: &str = "PHYSALIS_RECORD";
+
+/// The directory at `PHYSALIS_RECORD`, created if need be.
+fn recordings_dir() -> Option<PathBuf> {
+    let dir = PathBuf::from(std::env::var(RECORD_VAR).ok()?);
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("cannot create {}: {err}", dir.display());
+    }
+    info!("recording sessions in {}", dir.display());
+    Some(dir)
+}
+
+/// Opens the SQLite database at `PHYSALIS_DB`, creating it if need be;
+/// `None` when it is not set.
+fn open_store() -> Result<Option<Arc<dyn DocumentStore>>, String> {
+    let Ok(path) = std::env::var(STORE_VAR) else {
+        return Ok(None);
+    };
+    let store = SqliteStore::open(&path).map_err(|err| format!("cannot open {path}: {err}"))?;
+    info!("storing documents in {path}");
+    Ok(Some(Arc::new(store)))
+}
+
+/// A store for replays: an in-memory copy of the database at
+/// `PHYSALIS_DB` if set, so they open the stored documents but never
+/// change them, else an empty one.
+fn replay_store() -> Result<Arc<dyn DocumentStore>, store::StoreError> {
+    let store = match std::env::var(STORE_VAR) {
+        Ok(path) => SqliteStore::copy_of(path)?,
+        Err(_) => SqliteStore::in_memory()?,
+    };
+    Ok(Arc::new(store))
+}
+
+async fn thumbnail_handler(Path(id): Path<DocumentId>, State(state): State<AppState>) -> Response {
+    let Some(store) = &state.store else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match store.thumbnail(id) {
+        Ok(Some(png)) => ([(header::CONTENT_TYPE, "image/png")], png).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            warn!("thumbnail of document {id}: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
 async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
     ws.on_upgrade(move |socket| handle_socket(socket, state))
 }
 
 async fn handle_socket(socket: WebSocket, state: AppState) {
+    state.metrics.connected();
     let (mut ws_tx, mut ws_rx) = socket.split();
-    let (out_tx, mut out_rx) = mpsc::channel::<ServerMsg>(32);
+    let (out_tx, mut out_rx) = mpsc::channel::<Envelope<ServerMsg>>(32);
 
+    // Stopped once the socket closes, handing back what is left to send.
+    let stop = CancellationToken::new();
+    let stop_sending = stop.clone();
     let send_task = tokio::spawn(async move {
-        while let Some(msg) = out_rx.recv().await {
-            if let Ok(text) = serde_json::to_string(&msg) {
-                if ws_tx.send(Message::Text(text)).await.is_err() {
-                    break;
+        // Set by the ack to the client's hello; the ack itself goes out plain.
+        let mut compression = None;
+        let mut encoding = Encoding::Json;
+        let mut chunker = Chunker::default();
+        'send: loop {
+            let msg = tokio::select! {
+                msg = out_rx.recv() => msg,
+                () = stop_sending.cancelled() => None,
+            };
+            let Some(msg) = msg else {
+                break;
+            };
+            let frame = msg.encode(encoding).map(|frame| match compression {
+                Some(codec) => frame.compress(codec),
+                None => frame,
+            });
+            if let ServerMsg::HelloAck {
+                compression: codec,
+                encoding: agreed,
+                ..
+            } = msg.msg
+            {
+                compression = codec;
+                encoding = agreed;
+            }
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(err) => {
+                    warn!("dropping unencodable message: {err}");
+                    continue;
+                }
+            };
+            for frame in chunker.split(frame) {
+                let frame = match frame {
+                    Frame::Text(text) => Message::Text(text),
+                    Frame::Binary(bytes) => Message::Binary(bytes),
+                };
+                if ws_tx.send(frame).await.is_err() {
+                    break 'send;
                 }
             }
         }
+        out_rx
     });
 
-    let _ = out_tx.send(ServerMsg::HelloAck).await;
-
-    while let Some(Ok(msg)) = ws_rx.next().await {
-        match msg {
-            Message::Text(text) => {
-                if let Ok(client_msg) = serde_json::from_str::<ClientMsg>(&text) {
-                    match client_msg {
-                        ClientMsg::Hello { client_version } => {
-                            let _ = out_tx.send(ServerMsg::HelloAck).await;
-                            let _ = out_tx
-                                .send(ServerMsg::Log {
-                                    text: format!("client hello: {client_version}"),
-                                })
-                                .await;
-                        }
-                        ClientMsg::AddBox { .. } | ClientMsg::AddCylinder { .. } => {
-                            let _ = out_tx
-                                .send(ServerMsg::Log {
-                                    text: "received add-primitive".to_string(),
-                                })
-                                .await;
-                        }
-                        ClientMsg::RequestHeavy { kind, payload } => {
-                            let job_id = state.next_job_id.fetch_add(1, Ordering::Relaxed);
-                            let job = HeavyJob {
-                                id: job_id,
-                                kind,
-                                payload,
-                                respond_to: out_tx.clone(),
-                            };
-                            if state.job_tx.send(job).await.is_ok() {
-                                let _ = out_tx.send(ServerMsg::JobAccepted { job_id }).await;
-                            } else {
-                                let _ = out_tx
-                                    .send(ServerMsg::Log {
-                                        text: "job queue unavailable".to_string(),
-                                    })
-                                    .await;
-                            }
-                        }
-                    }
-                } else {
-                    let _ = out_tx
-                        .send(ServerMsg::Log {
-                            text: format!("unrecognized payload: {text}"),
-                        })
-                        .await;
+    let mut session = open_session(&state, &out_tx).await;
+    let mut recorder = state.recordings.as_ref().and_then(|dir| {
+        let path = dir.join(format!("{}-{}.rec", unix_ms(), session.peer_id));
+        Recorder::create(&path)
+            .map_err(|err| warn!("cannot record to {}: {err}", path.display()))
+            .ok()
+    });
+
+    let mut reassembler = Reassembler::default();
+    let mut heartbeat = tokio::time::interval(Duration::from_millis(PING_INTERVAL_MS));
+    let mut last_seen = Instant::now();
+    loop {
+        let msg = tokio::select! {
+            msg = ws_rx.next() => match msg {
+                Some(Ok(msg)) => msg,
+                _ => break,
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > Duration::from_millis(PEER_TIMEOUT_MS) {
+                    warn!("client stopped responding");
+                    break;
                 }
+                let ping = ServerMsg::Ping { sent_ms: unix_ms() };
+                let _ = out_tx.send(ping.into()).await;
+                continue;
             }
-            Message::Binary(_) => {
-                let _ = out_tx
-                    .send(ServerMsg::Log {
-                        text: "binary message ignored".to_string(),
-                    })
-                    .await;
-            }
+        };
+        last_seen = Instant::now();
+        let frame = match msg {
+            Message::Text(text) => Frame::Text(text),
+            Message::Binary(bytes) => Frame::Binary(bytes),
             Message::Close(_) => break,
-            _ => {}
+            _ => continue,
+        };
+        let frame = match reassembler.push(frame) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => continue,
+            Err(err) => {
+                let reply = ServerMsg::error(ErrorCode::InvalidMessage, err.to_string());
+                let _ = out_tx.send(reply.into()).await;
+                continue;
+            }
+        };
+        if let Some(Err(err)) = recorder.as_mut().map(|recorder| recorder.record(&frame)) {
+            warn!("stopped recording: {err}");
+            recorder = None;
         }
+        handle_frame(&state, &mut session, frame, &out_tx).await;
     }
 
-    drop(out_tx);
-    let _ = send_task.await;
+    if session.joined {
+        leave(&state, &mut session);
+    }
+    stop.cancel();
+    // Only an authenticated session is worth resuming.
+    if let (Some(_), Ok(out_rx)) = (&session.user, send_task.await) {
+        park(&state, session, out_tx, out_rx);
+    }
+    state.metrics.disconnected();
     warn!("websocket closed");
 }
 
-async fn job_worker(mut rx: mpsc::Receiver<HeavyJob>) {
-    while let Some(job) = rx.recv().await {
-        let respond_to = job.respond_to.clone();
-        let job_id = job.id;
-        let kind = job.kind.clone();
-        let payload = job.payload.clone();
+/// Starts a client's session, sending it the [`ServerMsg::HelloAck`] and,
+/// if no token is needed, adding it to the peers of [`FIRST_DOCUMENT`].
+async fn open_session(state: &AppState, out_tx: &mpsc::Sender<Envelope<ServerMsg>>) -> Session {
+    let mut session = Session {
+        token: format!("{:032x}", rand::random::<u128>()),
+        peer_id: state.next_peer_id.fetch_add(1, Ordering::Relaxed),
+        user: state.tokens.is_none().then(|| LOCAL_USER.to_string()),
+        joined: false,
+        document: None,
+        selection: Vec::new(),
+        cursor: None,
+        uploads: HashMap::new(),
+        stream: StreamLimit::new(),
+    };
+    let ack = ServerMsg::HelloAck {
+        compression: None,
+        encoding: Encoding::Json,
+        auth_required: state.tokens.is_some(),
+        capabilities: capabilities(state),
+        session: Some(session.token.clone()),
+    };
+    let _ = out_tx.send(ack.into()).await;
 
-        let result = tokio::task::spawn_blocking(move || {
-            std::thread::sleep(Duration::from_millis(300));
-            let details = payload.unwrap_or_else(|| "no-payload".to_string());
-            format!("heavy job done: {kind} ({details})")
-        })
-        .await;
+    let first = state
+        .documents
+        .lock()
+        .unwrap()
+        .get(&FIRST_DOCUMENT)
+        .cloned();
+    session.document = first.map(|handle| (FIRST_DOCUMENT, handle));
+    if session.user.is_some() {
+        let peers = join(state, &mut session, out_tx);
+        let _ = out_tx.send(peers.into()).await;
+    }
+    session
+}
+
+/// Handles one whole frame from the client: an upload's data or a message.
+async fn handle_frame(
+    state: &AppState,
+    session: &mut Session,
+    frame: Frame,
+    out_tx: &mpsc::Sender<Envelope<ServerMsg>>,
+) {
+    let (request_id, replies) = match frame {
+        Frame::Binary(bytes) if !bytes.starts_with(&MSGPACK_FRAME_MAGIC) => {
+            receive_upload(state, session, &bytes, out_tx)
+        }
+        frame => match Envelope::<ClientMsg>::from_frame(&frame) {
+            Ok(Envelope { request_id, msg }) => (
+                request_id,
+                handle_client_msg(state, session, msg, request_id, out_tx).await,
+            ),
+            Err(err) => (
+                None,
+                vec![ServerMsg::error(
+                    ErrorCode::InvalidMessage,
+                    format!("unrecognized payload: {err}"),
+                )],
+            ),
+        },
+    };
+    for reply in replies {
+        let _ = out_tx.send(in_reply_to(reply, request_id)).await;
+    }
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Adds the client to the peers, telling the others in its document, and
+/// returns the [`ServerMsg::Peers`] to send it.
+fn join(
+    state: &AppState,
+    session: &mut Session,
+    out_tx: &mpsc::Sender<Envelope<ServerMsg>>,
+) -> ServerMsg {
+    let id = session.peer_id;
+    let peer = Peer {
+        id,
+        name: session.user.clone().unwrap_or_default(),
+        color: PEER_COLORS[id as usize % PEER_COLORS.len()],
+    };
+    let document = session.document.as_ref().map(|(id, _)| *id);
+    let mut peers = state.peers.lock().unwrap();
+    if let Some(document) = document {
+        broadcast_document(
+            &peers,
+            document,
+            id,
+            ServerMsg::PeerJoined { peer: peer.clone() },
+        );
+    }
+    let entry = PeerEntry {
+        peer,
+        document,
+        tx: out_tx.clone(),
+    };
+    peers.insert(id, entry);
+    session.joined = true;
+    room_peers(&peers, id)
+}
+
+/// Removes the client from the peers, telling the others in its document.
+fn leave(state: &AppState, session: &mut Session) {
+    let id = session.peer_id;
+    let mut peers = state.peers.lock().unwrap();
+    let left = peers.remove(&id);
+    if let Some(document) = left.and_then(|entry| entry.document) {
+        broadcast_document(&peers, document, id, ServerMsg::PeerLeft { id });
+    }
+    session.joined = false;
+}
+
+/// Keeps the session of a dropped connection for [`RESUME_WINDOW`], holding
+/// the replies its jobs send meanwhile.
+fn park(
+    state: &AppState,
+    session: Session,
+    out_tx: mpsc::Sender<Envelope<ServerMsg>>,
+    out_rx: mpsc::Receiver<Envelope<ServerMsg>>,
+) {
+    let (resume, resumed) = oneshot::channel();
+    tokio::spawn(hold_replies(out_rx, resumed));
+    let token = session.token.clone();
+    let since = Instant::now();
+    let parked = ParkedSession {
+        session,
+        out_tx,
+        resume,
+        since,
+    };
+    state.parked.lock().unwrap().insert(token.clone(), parked);
+    let sessions = state.parked.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(RESUME_WINDOW).await;
+        let mut sessions = sessions.lock().unwrap();
+        // Unless it was resumed and parked again since.
+        if sessions
+            .get(&token)
+            .is_some_and(|parked| parked.since == since)
+        {
+            sessions.remove(&token);
+        }
+    });
+}
+
+/// Holds the replies to a parked session until a connection resumes it,
+/// then forwards them and those that follow there. Dropping the session
+/// drops them.
+async fn hold_replies(
+    mut rx: mpsc::Receiver<Envelope<ServerMsg>>,
+    mut resumed: oneshot::Receiver<mpsc::Sender<Envelope<ServerMsg>>>,
+) {
+    let mut held = Vec::new();
+    let tx = loop {
+        tokio::select! {
+            Some(msg) = rx.recv() => held.push(msg),
+            tx = &mut resumed => match tx {
+                Ok(tx) => break tx,
+                Err(_) => return,
+            },
+        }
+    };
+    for msg in held {
+        if tx.send(msg).await.is_err() {
+            return;
+        }
+    }
+    while let Some(msg) = rx.recv().await {
+        if tx.send(msg).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Swaps the client's new session for the parked one named `token`, if
+/// there is one: the client rejoins its document as the same peer, and the
+/// session's jobs answer it from now on. Returns the replies that restore
+/// it, and the channel to hand [`hold_replies`] once they are sent.
+fn resume_session(
+    state: &AppState,
+    session: &mut Session,
+    token: &str,
+    out_tx: &mpsc::Sender<Envelope<ServerMsg>>,
+) -> Option<(Vec<ServerMsg>, Resume)> {
+    let parked = state.parked.lock().unwrap().remove(token)?;
+    if session.joined {
+        leave(state, session);
+    }
+    *session = Session {
+        joined: false,
+        stream: StreamLimit::new(),
+        ..parked.session
+    };
+
+    let mut jobs = Vec::new();
+    let mut guard = state.jobs.lock().unwrap();
+    let queue = &mut *guard;
+    let pending = queue
+        .pending
+        .iter_mut()
+        .map(|job| (job.id, &mut job.respond_to));
+    let running = queue
+        .running
+        .iter_mut()
+        .map(|job| (job.id, &mut job.respond_to));
+    for (id, respond_to) in pending.chain(running) {
+        // Running jobs keep replying on the parked channel, which forwards.
+        if respond_to.same_channel(&parked.out_tx) {
+            *respond_to = out_tx.clone();
+            jobs.push(id);
+        }
+    }
+    drop(guard);
+
+    let mut replies = vec![ServerMsg::SessionResumed {
+        selection: session.selection.clone(),
+        cursor: session.cursor,
+        jobs,
+    }];
+    let peers = join(state, session, out_tx);
+    if let Some((id, _)) = &session.document {
+        let selection = ServerMsg::PeerSelection {
+            id: session.peer_id,
+            selection: session.selection.clone(),
+            cursor: session.cursor,
+        };
+        broadcast_document(
+            &state.peers.lock().unwrap(),
+            *id,
+            session.peer_id,
+            selection,
+        );
+        replies.extend(opened(state, session));
+    }
+    replies.push(peers);
+    Some((replies, parked.resume))
+}
+
+/// Moves the client's presence to document `to`: the clients of the
+/// document it leaves see it leave, and those of `to` see it join. Returns
+/// the [`ServerMsg::Peers`] of `to` for the client, if it has joined.
+fn change_room(state: &AppState, session: &Session, to: Option<DocumentId>) -> Option<ServerMsg> {
+    let id = session.peer_id;
+    let mut peers = state.peers.lock().unwrap();
+    let entry = peers.get_mut(&id)?;
+    let from = std::mem::replace(&mut entry.document, to);
+    let peer = entry.peer.clone();
+    if from != to {
+        if let Some(from) = from {
+            broadcast_document(&peers, from, id, ServerMsg::PeerLeft { id });
+        }
+        if let Some(to) = to {
+            broadcast_document(&peers, to, id, ServerMsg::PeerJoined { peer });
+        }
+    }
+    Some(room_peers(&peers, id))
+}
+
+/// [`ServerMsg::Peers`] for `you`: the clients of its document.
+fn room_peers(peers: &BTreeMap<PeerId, PeerEntry>, you: PeerId) -> ServerMsg {
+    let document = peers.get(&you).and_then(|entry| entry.document);
+    let peers = peers
+        .iter()
+        .filter(|(&id, entry)| id == you || (document.is_some() && entry.document == document))
+        .map(|(_, entry)| entry.peer.clone())
+        .collect();
+    ServerMsg::Peers { you, peers }
+}
+
+/// Sends `msg` to the peers that have `document` open, but not `from`.
+/// Presence is best effort: a peer whose queue is full misses the update
+/// rather than stall the sender.
+fn broadcast_document(
+    peers: &BTreeMap<PeerId, PeerEntry>,
+    document: DocumentId,
+    from: PeerId,
+    msg: ServerMsg,
+) {
+    for (id, entry) in peers {
+        if *id != from && entry.document == Some(document) {
+            let _ = entry.tx.try_send(msg.clone().into());
+        }
+    }
+}
+
+/// Tags a reply with the request it answers.
+fn in_reply_to(mut msg: ServerMsg, request_id: Option<RequestId>) -> Envelope<ServerMsg> {
+    if let ServerMsg::Error {
+        related_request, ..
+    } = &mut msg
+    {
+        *related_request = related_request.or(request_id);
+    }
+    Envelope::new(msg, request_id)
+}
 
-        if let Ok(payload) = result {
-            let _ = respond_to
-                .send(ServerMsg::JobResult { job_id, payload })
-                .await;
+/// Handles one client message, returning the replies to send back.
+async fn handle_client_msg(
+    state: &AppState,
+    session: &mut Session,
+    msg: ClientMsg,
+    request_id: Option<RequestId>,
+    out_tx: &mpsc::Sender<Envelope<ServerMsg>>,
+) -> Vec<ServerMsg> {
+    if session.user.is_none() && !msg.allowed_before_auth() {
+        return vec![ServerMsg::error(
+            ErrorCode::Unauthenticated,
+            "authenticate first",
+        )];
+    }
+    if msg.is_streamed() {
+        if let Err(retry_after) = session.stream.admit(Instant::now()) {
+            // Drop the update; a later one supersedes it anyway.
+            if std::mem::replace(&mut session.stream.warned, true) {
+                return Vec::new();
+            }
+            return vec![ServerMsg::SlowDown {
+                retry_after_ms: retry_after.as_millis() as u64,
+            }];
+        }
+    }
+    if msg.is_edit() && session_role(session).is_some_and(|role| !role.can_edit()) {
+        return vec![ServerMsg::error(
+            ErrorCode::Forbidden,
+            "viewers cannot change the document",
+        )];
+    }
+    match msg {
+        ClientMsg::Hello {
+            client_version,
+            compression,
+            encoding,
+            resume,
+        } => {
+            let resuming = resume.is_some();
+            let resumed = resume.and_then(|token| resume_session(state, session, &token, out_tx));
+            let mut replies = vec![
+                ServerMsg::HelloAck {
+                    compression: Compression::negotiate(&compression),
+                    encoding: Encoding::negotiate(&encoding),
+                    auth_required: state.tokens.is_some(),
+                    capabilities: capabilities(state),
+                    session: Some(session.token.clone()),
+                },
+                ServerMsg::Log {
+                    text: format!("client hello: {client_version}"),
+                },
+            ];
+            match resumed {
+                Some((restored, held)) => {
+                    replies.extend(restored);
+                    for reply in replies {
+                        let _ = out_tx.send(in_reply_to(reply, request_id)).await;
+                    }
+                    // Replies held while the client was away come after.
+                    let _ = held.send(out_tx.clone());
+                    Vec::new()
+                }
+                None if resuming => {
+                    replies.push(ServerMsg::error(
+                        ErrorCode::UnknownSession,
+                        "no session to resume",
+                    ));
+                    replies
+                }
+                None => replies,
+            }
+        }
+        ClientMsg::Authenticate { token } => {
+            let user = match &state.tokens {
+                Some(tokens) => tokens.get(&token).cloned(),
+                None => session.user.clone(),
+            };
+            let Some(name) = user.clone() else {
+                warn!("client presented an unknown token");
+                return vec![ServerMsg::AuthResult { user }];
+            };
+            info!("client authenticated as {name}");
+            session.user = Some(name);
+            let mut replies = vec![ServerMsg::AuthResult { user }];
+            if !session.joined {
+                replies.push(join(state, session, out_tx));
+            }
+            replies
+        }
+        ClientMsg::SetPresence { name, color } => {
+            let mut peers = state.peers.lock().unwrap();
+            let Some(entry) = peers.get_mut(&session.peer_id) else {
+                return Vec::new();
+            };
+            let name = name.trim();
+            if !name.is_empty() {
+                entry.peer.name = name.to_string();
+            }
+            if let Some(color) = color {
+                entry.peer.color = color;
+            }
+            let update = ServerMsg::PeerUpdated {
+                peer: entry.peer.clone(),
+            };
+            if let Some(document) = entry.document {
+                broadcast_document(&peers, document, session.peer_id, update.clone());
+            }
+            vec![update]
+        }
+        ClientMsg::SetSelection { selection, cursor } => {
+            let Some((document, _)) = &session.document else {
+                return vec![no_document()];
+            };
+            session.selection.clone_from(&selection);
+            session.cursor = cursor;
+            let peers = state.peers.lock().unwrap();
+            let msg = ServerMsg::PeerSelection {
+                id: session.peer_id,
+                selection,
+                cursor,
+            };
+            broadcast_document(&peers, *document, session.peer_id, msg);
+            Vec::new()
+        }
+        ClientMsg::AddBox { w, h, d } => add_primitive(state, session, ObjectKind::Box { w, h, d }),
+        ClientMsg::AddCylinder { r, h } => {
+            add_primitive(state, session, ObjectKind::Cylinder { r, h })
+        }
+        ClientMsg::AddPrimitive { kind } => add_primitive(state, session, kind),
+        ClientMsg::SetTransform {
+            id,
+            transform,
+            base_seq,
+        } => {
+            if let Err(rejected) = primitive::check_transform(&transform) {
+                return vec![rejected.into()];
+            }
+            let command = ModelCommand::SetTransform { id, transform };
+            apply_edit(state, session, command, base_seq)
+        }
+        ClientMsg::DeleteObject { id } => {
+            apply_edit(state, session, ModelCommand::Delete { id }, None)
+        }
+        ClientMsg::Undo => step_history(state, session, HistoryStep::Undo),
+        ClientMsg::Redo => step_history(state, session, HistoryStep::Redo),
+        ClientMsg::RequestModel => match &session.document {
+            Some((_, document)) => vec![model_snapshot(document), comments(document)],
+            None => vec![no_document()],
+        },
+        ClientMsg::Chat { text } => {
+            let Some((document, _)) = &session.document else {
+                return vec![no_document()];
+            };
+            let Some(text) = review_text(&text) else {
+                return vec![invalid_text()];
+            };
+            let msg = ServerMsg::Chat {
+                peer: session.peer_id,
+                text: text.to_string(),
+                sent_ms: unix_ms(),
+            };
+            notify_document(state, *document, session.peer_id, [msg.clone()]);
+            vec![msg]
+        }
+        ClientMsg::AddComment { anchor, text } => add_comment(state, session, anchor, &text),
+        ClientMsg::RemoveComment { id } => {
+            let Some((document_id, document)) = &session.document else {
+                return vec![no_document()];
+            };
+            {
+                let mut document = document.lock().unwrap();
+                let Some(at) = document
+                    .comments
+                    .iter()
+                    .position(|comment| comment.id == id)
+                else {
+                    return vec![ServerMsg::error(
+                        ErrorCode::UnknownComment,
+                        format!("no comment {id}"),
+                    )];
+                };
+                let user = session.user.as_deref().unwrap_or_default();
+                let wrote = document.comment_users.get(&id).map(String::as_str) == Some(user);
+                if !wrote && document.role(user) != Some(Role::Owner) {
+                    return vec![ServerMsg::error(
+                        ErrorCode::Forbidden,
+                        "only its author or an owner resolves a comment",
+                    )];
+                }
+                document.comments.remove(at);
+                document.comment_users.remove(&id);
+            }
+            let msg = ServerMsg::CommentRemoved {
+                id,
+                peer: session.peer_id,
+            };
+            notify_document(state, *document_id, session.peer_id, [msg.clone()]);
+            vec![msg]
+        }
+        ClientMsg::SetRole { user, role } => set_role(state, session, &user, role),
+        ClientMsg::ListDocuments { query } => vec![ServerMsg::DocumentList {
+            documents: document_summaries(state, &query),
+        }],
+        ClientMsg::CreateDocument { name } => {
+            let id = state.next_document_id.fetch_add(1, Ordering::Relaxed);
+            let name = match name.trim() {
+                "" => "Untitled".to_string(),
+                name => name.to_string(),
+            };
+            let owner = session.user.clone().unwrap_or_default();
+            let document = Document {
+                roles: BTreeMap::from([(owner, Role::Owner)]),
+                ..Document::new(name)
+            };
+            let handle = Arc::new(Mutex::new(document));
+            state.documents.lock().unwrap().insert(id, handle);
+            open_document(state, session, id)
+        }
+        ClientMsg::OpenDocument { id } => open_document(state, session, id),
+        ClientMsg::SaveDocument { thumbnail } => save_document(state, session, thumbnail),
+        ClientMsg::RestoreRevision { revision } => {
+            let Some((document_id, document)) = &session.document else {
+                return vec![no_document()];
+            };
+            restore_revision(state, *document_id, document, revision, session.peer_id)
+        }
+        ClientMsg::ListRevisions { id } => {
+            let user = session.user.as_deref().unwrap_or_default();
+            if let Err((code, message)) = viewable(state, user, id) {
+                return vec![ServerMsg::error(code, message)];
+            }
+            let revisions = match &state.store {
+                Some(store) => store.revisions(id),
+                None => Ok(Vec::new()),
+            };
+            match revisions {
+                Ok(revisions) => vec![ServerMsg::RevisionList { id, revisions }],
+                Err(err) => vec![ServerMsg::error(ErrorCode::StorageFailed, err.to_string())],
+            }
+        }
+        ClientMsg::CloseDocument { id } => {
+            if session.document.as_ref().map(|(open, _)| *open) != Some(id) {
+                return vec![ServerMsg::error(
+                    ErrorCode::UnknownDocument,
+                    format!("document {id} is not open"),
+                )];
+            }
+            session.document = None;
+            let mut replies = vec![ServerMsg::DocumentClosed { id }];
+            replies.extend(change_room(state, session, None));
+            replies
+        }
+        ClientMsg::Ping { sent_ms } => vec![ServerMsg::Pong {
+            sent_ms,
+            server_ms: unix_ms(),
+        }],
+        // Receiving it already counts as a sign of life.
+        ClientMsg::Pong { .. } => Vec::new(),
+        ClientMsg::RequestHeavy { kind, payload } => {
+            if !JOB_KINDS.contains(&kind.as_str()) {
+                return vec![ServerMsg::error(
+                    ErrorCode::Unsupported,
+                    format!("no job kind {kind:?}"),
+                )];
+            }
+            let task = JobTask::Demo { kind, payload };
+            queue_job(state, task, request_id, out_tx)
+        }
+        ClientMsg::Upload {
+            upload_id,
+            name,
+            format,
+            size,
+        } => {
+            if !IMPORT_FORMATS.contains(&format) {
+                return vec![ServerMsg::error(
+                    ErrorCode::Unsupported,
+                    format!("cannot import {format:?}"),
+                )];
+            }
+            if size > MAX_UPLOAD_LEN {
+                return vec![ServerMsg::error(
+                    ErrorCode::Unsupported,
+                    format!("upload of {size} bytes is too large"),
+                )];
+            }
+            let upload = PendingUpload {
+                name,
+                format,
+                size,
+                request_id,
+            };
+            session.uploads.insert(upload_id, upload);
+            Vec::new()
+        }
+        ClientMsg::RequestExport { format, objects } => {
+            if !EXPORT_FORMATS.contains(&format) {
+                return vec![ServerMsg::error(
+                    ErrorCode::Unsupported,
+                    format!("cannot export {format:?}"),
+                )];
+            }
+            let Some((_, document)) = &session.document else {
+                return vec![no_document()];
+            };
+            let model = document.lock().unwrap().model.snapshot();
+            let task = JobTask::Export {
+                format,
+                objects,
+                model,
+            };
+            queue_job(state, task, request_id, out_tx)
+        }
+        ClientMsg::RequestMesh { objects, tolerance } => {
+            let tolerance = tolerance.unwrap_or(MESH_TOLERANCE);
+            if !(tolerance >= MIN_MESH_TOLERANCE && tolerance.is_finite()) {
+                return vec![ServerMsg::error(
+                    ErrorCode::Unsupported,
+                    format!("tolerance must be at least {MIN_MESH_TOLERANCE}"),
+                )];
+            }
+            let Some((_, document)) = &session.document else {
+                return vec![no_document()];
+            };
+            let model = document.lock().unwrap().model.snapshot();
+            let task = JobTask::Mesh {
+                objects,
+                tolerance,
+                model,
+            };
+            queue_job(state, task, request_id, out_tx)
+        }
+        ClientMsg::CancelJob { job_id } => cancel_job(state, job_id, out_tx),
+        ClientMsg::AddFeature { id, op } => {
+            let Some((document_id, document)) = &session.document else {
+                return vec![no_document()];
+            };
+            let kind = FeatureKind::of(&op);
+            if !FEATURE_OPS.contains(&kind) {
+                return vec![ServerMsg::error(
+                    ErrorCode::Unsupported,
+                    format!("the server cannot compute {kind:?} features"),
+                )];
+            }
+            if let Err(rejected) = primitive::check_feature(&op) {
+                return vec![rejected.into()];
+            }
+            let model = document.lock().unwrap().model.snapshot();
+            if model.object(id).is_none() {
+                return vec![ServerMsg::error(
+                    ErrorCode::UnknownObject,
+                    format!("unknown object {id}"),
+                )];
+            }
+            let task = JobTask::Feature {
+                id,
+                op,
+                model,
+                document: (*document_id, document.clone()),
+                peer: session.peer_id,
+                peers: state.peers.clone(),
+            };
+            queue_job(state, task, request_id, out_tx)
+        }
+    }
+}
+
+/// Handles a binary frame that is not a message, which carries the data of
+/// an announced upload. Returns the upload's request with the replies.
+fn receive_upload(
+    state: &AppState,
+    session: &mut Session,
+    bytes: &[u8],
+    out_tx: &mpsc::Sender<Envelope<ServerMsg>>,
+) -> (Option<RequestId>, Vec<ServerMsg>) {
+    if session.user.is_none() {
+        let reply = ServerMsg::error(ErrorCode::Unauthenticated, "authenticate first");
+        return (None, vec![reply]);
+    }
+    let data = match UploadData::from_frame(bytes) {
+        Ok(data) => data,
+        Err(err) => {
+            let reply = ServerMsg::error(ErrorCode::InvalidMessage, err.to_string());
+            return (None, vec![reply]);
+        }
+    };
+    let Some(upload) = session.uploads.remove(&data.upload_id) else {
+        let reply = ServerMsg::error(
+            ErrorCode::ImportFailed,
+            format!("upload {} was not announced", data.upload_id),
+        );
+        return (None, vec![reply]);
+    };
+    let request_id = upload.request_id;
+    if data.data.len() as u64 != upload.size {
+        let reply = ServerMsg::error(
+            ErrorCode::ImportFailed,
+            format!(
+                "upload {} has {} bytes, announced {}",
+                data.upload_id,
+                data.data.len(),
+                upload.size
+            ),
+        );
+        return (request_id, vec![reply]);
+    }
+    let Some((document_id, document)) = &session.document else {
+        return (request_id, vec![no_document()]);
+    };
+    let task = JobTask::Import {
+        upload_id: data.upload_id,
+        name: upload.name,
+        format: upload.format,
+        data: data.data,
+        document: (*document_id, document.clone()),
+        peer: session.peer_id,
+        peers: state.peers.clone(),
+    };
+    (request_id, queue_job(state, task, request_id, out_tx))
+}
+
+/// Queues a job behind those at least as urgent.
+fn queue_job(
+    state: &AppState,
+    task: JobTask,
+    request_id: Option<RequestId>,
+    out_tx: &mpsc::Sender<Envelope<ServerMsg>>,
+) -> Vec<ServerMsg> {
+    let mut jobs = state.jobs.lock().unwrap();
+    if jobs.pending.len() >= MAX_PENDING_JOBS {
+        return vec![ServerMsg::error(
+            ErrorCode::JobQueueUnavailable,
+            "job queue full",
+        )];
+    }
+    let job_id = state.next_job_id.fetch_add(1, Ordering::Relaxed);
+    let priority = task.priority();
+    let position = jobs
+        .pending
+        .iter()
+        .position(|queued| queued.task.priority() < priority)
+        .unwrap_or(jobs.pending.len());
+    jobs.pending.insert(
+        position,
+        HeavyJob {
+            id: job_id,
+            task,
+            request_id,
+            respond_to: out_tx.clone(),
+            cancel: CancellationToken::new(),
+        },
+    );
+    announce_positions(&jobs, position + 1);
+    announce_queue(&jobs, &state.peers.lock().unwrap());
+    state.job_ready.notify_one();
+    vec![
+        ServerMsg::JobAccepted { job_id },
+        ServerMsg::JobQueued { job_id, position },
+    ]
+}
+
+/// Cancels one of the client's jobs. A waiting job is dropped from the queue
+/// here; a running one is reported by the worker once it notices.
+fn cancel_job(
+    state: &AppState,
+    job_id: u64,
+    out_tx: &mpsc::Sender<Envelope<ServerMsg>>,
+) -> Vec<ServerMsg> {
+    let mut jobs = state.jobs.lock().unwrap();
+    let owned = |id, respond_to: &mpsc::Sender<_>| id == job_id && respond_to.same_channel(out_tx);
+    let waiting = jobs
+        .pending
+        .iter()
+        .position(|queued| owned(queued.id, &queued.respond_to));
+    if let Some(idx) = waiting {
+        jobs.pending.remove(idx);
+        announce_positions(&jobs, idx);
+        announce_queue(&jobs, &state.peers.lock().unwrap());
+        return vec![ServerMsg::JobResult {
+            job_id,
+            payload: JobPayload::Canceled,
+        }];
+    }
+    let running = jobs
+        .running
+        .iter()
+        .find(|job| owned(job.id, &job.respond_to));
+    match running {
+        Some(running) => {
+            running.cancel.cancel();
+            Vec::new()
+        }
+        None => vec![ServerMsg::error(
+            ErrorCode::UnknownJob,
+            format!("no job {job_id} of yours to cancel"),
+        )],
+    }
+}
+
+/// Sends every client the queue's size. Best effort like
+/// [`broadcast_document`].
+fn announce_queue(jobs: &JobQueue, peers: &BTreeMap<PeerId, PeerEntry>) {
+    let status = ServerMsg::QueueStatus {
+        pending: jobs.pending.len(),
+        running: jobs.running.len(),
+    };
+    for entry in peers.values() {
+        let _ = entry.tx.try_send(status.clone().into());
+    }
+}
+
+/// Tells the owners of waiting jobs from position `from` on where they now
+/// stand.
+fn announce_positions(jobs: &JobQueue, from: usize) {
+    for (position, queued) in jobs.pending.iter().enumerate().skip(from) {
+        let msg = ServerMsg::JobQueued {
+            job_id: queued.id,
+            position,
+        };
+        let _ = queued
+            .respond_to
+            .try_send(in_reply_to(msg, queued.request_id));
+    }
+}
+
+/// Adds a primitive to the client's document, unless
+/// [`primitive::check`] refuses it.
+fn add_primitive(state: &AppState, session: &Session, kind: ObjectKind) -> Vec<ServerMsg> {
+    if let Err(rejected) = primitive::check(&kind) {
+        return vec![rejected.into()];
+    }
+    apply_edit(state, session, ModelCommand::AddObject { kind }, None)
+}
+
+/// Applies an edit to the shared document, replying with what it did and
+/// the resulting delta. `base_seq` is the update the client made a move
+/// against; without one the edit is as recent as the document.
+fn apply_edit(
+    state: &AppState,
+    session: &Session,
+    command: ModelCommand,
+    base_seq: Option<u64>,
+) -> Vec<ServerMsg> {
+    let Some((document_id, document)) = &session.document else {
+        return vec![no_document()];
+    };
+    let (text, seq, delta) = {
+        let mut guard = document.lock().unwrap();
+        let document = &mut *guard;
+        let stamp = document.stamp(session.peer_id, base_seq);
+        if let ModelCommand::SetTransform { id, .. } = command {
+            if !document.last_writes.wins(id, stamp) {
+                return vec![ServerMsg::error(
+                    ErrorCode::Superseded,
+                    format!("object {id} was changed by a concurrent edit"),
+                )];
+            }
+        }
+        let before = document.model.snapshot();
+        let op = LoggedOp::Edit(command.clone());
+        if let Err(err) = document.history.execute(document.model.edit(), command) {
+            return vec![ServerMsg::error(ErrorCode::from(&err), err.to_string())];
+        }
+        document.record(op);
+        let text = document
+            .history
+            .next_undo()
+            .map_or_else(String::new, describe);
+        let delta = before.diff(&document.model);
+        document.wrote(&delta, stamp);
+        document.seq += 1;
+        (text, document.seq, delta)
+    };
+    let notes = ServerMsg::notifications(seq, session.peer_id, delta.clone());
+    notify_document(state, *document_id, session.peer_id, notes);
+    vec![
+        ServerMsg::Log { text },
+        ServerMsg::ModelDelta { seq, delta },
+    ]
+}
+
+/// Undoes or redoes the document's last edit, telling every client of the
+/// document what it reverted.
+fn step_history(state: &AppState, session: &Session, step: HistoryStep) -> Vec<ServerMsg> {
+    let Some((document_id, document)) = &session.document else {
+        return vec![no_document()];
+    };
+    let (applied, seq, delta) = {
+        let mut guard = document.lock().unwrap();
+        let document = &mut *guard;
+        let available = match step {
+            HistoryStep::Undo => document.history.can_undo(),
+            HistoryStep::Redo => document.history.can_redo(),
+        };
+        if !available {
+            return vec![ServerMsg::error(
+                ErrorCode::EmptyHistory,
+                format!("nothing to {step:?}").to_lowercase(),
+            )];
+        }
+        let before = document.model.snapshot();
+        let model = document.model.edit();
+        let result = match step {
+            HistoryStep::Undo => document.history.undo(model),
+            HistoryStep::Redo => document.history.redo(model),
+        };
+        if let Err(err) = result {
+            return vec![ServerMsg::error(ErrorCode::from(&err), err.to_string())];
+        }
+        document.record(match step {
+            HistoryStep::Undo => LoggedOp::Undo,
+            HistoryStep::Redo => LoggedOp::Redo,
+        });
+        // The step's inverse is now on the other stack and says what it did.
+        let inverse = match step {
+            HistoryStep::Undo => document.history.next_redo(),
+            HistoryStep::Redo => document.history.next_undo(),
+        };
+        let text = inverse.map_or_else(String::new, describe);
+        let delta = before.diff(&document.model);
+        let stamp = document.stamp(session.peer_id, None);
+        document.wrote(&delta, stamp);
+        document.seq += 1;
+        let applied = ServerMsg::HistoryApplied {
+            step,
+            peer: session.peer_id,
+            text,
+            can_undo: document.history.can_undo(),
+            can_redo: document.history.can_redo(),
+        };
+        (applied, document.seq, delta)
+    };
+    let notes = ServerMsg::notifications(seq, session.peer_id, delta.clone());
+    let others = std::iter::once(applied.clone()).chain(notes);
+    notify_document(state, *document_id, session.peer_id, others);
+    vec![applied, ServerMsg::ModelDelta { seq, delta }]
+}
+
+/// Sends `msgs` to the other clients of `document`, best effort like
+/// [`broadcast_document`].
+fn notify_document(
+    state: &AppState,
+    document: DocumentId,
+    from: PeerId,
+    msgs: impl IntoIterator<Item = ServerMsg>,
+) {
+    let peers = state.peers.lock().unwrap();
+    for msg in msgs {
+        broadcast_document(&peers, document, from, msg);
+    }
+}
+
+fn no_document() -> ServerMsg {
+    ServerMsg::error(ErrorCode::NoDocument, "no document is open")
+}
+
+/// The client's role in its document, if it has one open.
+fn session_role(session: &Session) -> Option<Role> {
+    let (_, document) = session.document.as_ref()?;
+    let user = session.user.as_deref()?;
+    document.lock().unwrap().role(user)
+}
+
+/// Gives `user` `role` in the client's document, if the client owns it,
+/// telling every client of the document.
+fn set_role(state: &AppState, session: &Session, user: &str, role: Role) -> Vec<ServerMsg> {
+    let Some((document_id, document)) = &session.document else {
+        return vec![no_document()];
+    };
+    let user = user.trim();
+    if user.is_empty() {
+        return vec![ServerMsg::error(ErrorCode::InvalidMessage, "no user named")];
+    }
+    let stored_roles = {
+        let mut document = document.lock().unwrap();
+        if document.role(session.user.as_deref().unwrap_or_default()) != Some(Role::Owner) {
+            return vec![ServerMsg::error(
+                ErrorCode::Forbidden,
+                "only owners assign roles",
+            )];
+        }
+        let owners = document
+            .roles
+            .values()
+            .filter(|&&r| r == Role::Owner)
+            .count();
+        if role != Role::Owner && document.roles.get(user) == Some(&Role::Owner) && owners == 1 {
+            return vec![ServerMsg::error(
+                ErrorCode::Forbidden,
+                "a document keeps at least one owner",
+            )];
+        }
+        document.roles.insert(user.to_string(), role);
+        // Unsaved documents store their roles when first saved.
+        document.revision.map(|_| document.roles.clone())
+    };
+    if let (Some(store), Some(roles)) = (&state.store, stored_roles) {
+        if let Err(err) = store.set_roles(*document_id, &roles) {
+            warn!("storing the roles of document {document_id}: {err}");
+        }
+    }
+    let msg = ServerMsg::RoleChanged {
+        user: user.to_string(),
+        role,
+    };
+    notify_document(state, *document_id, session.peer_id, [msg.clone()]);
+    vec![msg]
+}
+
+fn model_snapshot(document: &DocumentHandle) -> ServerMsg {
+    // Snapshot under the lock; copy outside it.
+    let (seq, snapshot) = {
+        let document = document.lock().unwrap();
+        (document.seq, document.model.snapshot())
+    };
+    let model = Box::new(snapshot.into_model());
+    ServerMsg::ModelSnapshot { seq, model }
+}
+
+/// Switches the client to document `id`, replying with the document and its
+/// snapshot.
+fn open_document(state: &AppState, session: &mut Session, id: DocumentId) -> Vec<ServerMsg> {
+    let user = session.user.as_deref().unwrap_or_default();
+    let handle = match viewable(state, user, id) {
+        Ok(handle) => handle,
+        Err((code, message)) => return vec![ServerMsg::error(code, message)],
+    };
+    // Uploads and selections belong to the document they were made in.
+    session.uploads.clear();
+    session.selection.clear();
+    session.cursor = None;
+    session.document = Some((id, handle));
+    let peers = change_room(state, session, Some(id));
+    let mut replies = opened(state, session);
+    replies.extend(peers);
+    replies
+}
+
+/// The document `id`, if `user` may view it, or why not.
+fn viewable(
+    state: &AppState,
+    user: &str,
+    id: DocumentId,
+) -> Result<DocumentHandle, (ErrorCode, String)> {
+    let handle = match load_document(state, id) {
+        Ok(Some(handle)) => handle,
+        Ok(None) => return Err((ErrorCode::UnknownDocument, format!("no document {id}"))),
+        Err(err) => return Err((ErrorCode::StorageFailed, err.to_string())),
+    };
+    if handle.lock().unwrap().role(user).is_none() {
+        return Err((
+            ErrorCode::Forbidden,
+            format!("{user} has no role in document {id}"),
+        ));
+    }
+    Ok(handle)
+}
+
+/// [`ServerMsg::DocumentOpened`] and the snapshot and comments of the
+/// client's document.
+fn opened(state: &AppState, session: &Session) -> Vec<ServerMsg> {
+    let Some((id, handle)) = &session.document else {
+        return Vec::new();
+    };
+    let role = session_role(session);
+    let document = document_summaries(state, "")
+        .into_iter()
+        .find(|summary| summary.id == *id);
+    let mut replies: Vec<ServerMsg> = document
+        .map(|document| ServerMsg::DocumentOpened { document, role })
+        .into_iter()
+        .collect();
+    replies.push(model_snapshot(handle));
+    replies.push(comments(handle));
+    replies
+}
+
+fn comments(document: &DocumentHandle) -> ServerMsg {
+    ServerMsg::Comments {
+        comments: document.lock().unwrap().comments.clone(),
+    }
+}
+
+fn invalid_text() -> ServerMsg {
+    ServerMsg::error(
+        ErrorCode::InvalidText,
+        format!("text must be 1 to {MAX_REVIEW_TEXT_LEN} characters"),
+    )
+}
+
+/// Pins a comment by the client to its document, telling every client of
+/// the document.
+fn add_comment(state: &AppState, session: &Session, anchor: Anchor, text: &str) -> Vec<ServerMsg> {
+    let Some((document_id, document)) = &session.document else {
+        return vec![no_document()];
+    };
+    let Some(text) = review_text(text) else {
+        return vec![invalid_text()];
+    };
+    let author = match state.peers.lock().unwrap().get(&session.peer_id) {
+        Some(entry) => entry.peer.name.clone(),
+        None => session.user.clone().unwrap_or_default(),
+    };
+    let comment = {
+        let mut document = document.lock().unwrap();
+        if let Some(object) = anchor.object() {
+            if document.model.object(object).is_none() {
+                return vec![ServerMsg::error(
+                    ErrorCode::UnknownObject,
+                    format!("no object {object}"),
+                )];
+            }
+        }
+        document.next_comment_id += 1;
+        let comment = Comment {
+            id: document.next_comment_id,
+            anchor,
+            text: text.to_string(),
+            author,
+            created_ms: unix_ms(),
+        };
+        document.comments.push(comment.clone());
+        let user = session.user.clone().unwrap_or_default();
+        document.comment_users.insert(comment.id, user);
+        comment
+    };
+    let msg = ServerMsg::CommentAdded {
+        comment,
+        peer: session.peer_id,
+    };
+    notify_document(state, *document_id, session.peer_id, [msg.clone()]);
+    vec![msg]
+}
+
+/// The hosted document `id`, loading it from the store if need be.
+fn load_document(
+    state: &AppState,
+    id: DocumentId,
+) -> Result<Option<DocumentHandle>, store::StoreError> {
+    if let Some(handle) = state.documents.lock().unwrap().get(&id) {
+        return Ok(Some(handle.clone()));
+    }
+    let Some(store) = &state.store else {
+        return Ok(None);
+    };
+    let Some((name, revision, mut model)) = store.load(id)? else {
+        return Ok(None);
+    };
+    let log = OpLog {
+        store: store.clone(),
+        id,
+        len: 0,
+    };
+    let mut document = Document {
+        name,
+        revision: Some(revision),
+        log: Some(log),
+        roles: store.roles(id)?,
+        ..Document::default()
+    };
+    match store.journal(id) {
+        Ok(Some(journal)) => {
+            replay(&mut document, journal);
+            // Revisions are saved without the client id.
+            model.set_client_id(document.model.client_id());
+            // Edits logged since the revision are not saved in it.
+            if *document.model != model {
+                document.seq += 1;
+            }
+        }
+        // Saved before documents kept a log, or by a build whose log this
+        // one cannot read: start one from the revision.
+        result => {
+            if let Err(err) = result {
+                warn!("opening revision {revision} of document {id}: {err}");
+            }
+            model.set_client_id(fresh_client_id());
+            document.model = SharedModel::new(model);
+            document.checkpoint();
+        }
+    }
+    // Another client may have loaded it meanwhile; keep theirs.
+    let mut documents = state.documents.lock().unwrap();
+    let handle = documents
+        .entry(id)
+        .or_insert_with(|| Arc::new(Mutex::new(document)));
+    Ok(Some(handle.clone()))
+}
+
+/// Restores the document its log describes, the last checkpoint with the
+/// ops since applied again.
+fn replay(document: &mut Document, journal: Journal) {
+    let Journal {
+        model,
+        mut history,
+        ops,
+    } = journal;
+    let mut model = SharedModel::new(model);
+    let len = ops.len();
+    for op in ops {
+        let result = match op {
+            LoggedOp::Edit(command) => history.execute(model.edit(), command).map(|()| true),
+            LoggedOp::Undo => history.undo(model.edit()),
+            LoggedOp::Redo => history.redo(model.edit()),
+        };
+        if let Err(err) = result {
+            warn!("replaying the log of document {}: {err}", document.name);
+            break;
+        }
+    }
+    document.model = model;
+    document.history = history;
+    if let Some(log) = &mut document.log {
+        log.len = len;
+    }
+}
+
+/// Hosted and stored documents whose name contains `query`, ignoring ASCII
+/// case.
+fn document_summaries(state: &AppState, query: &str) -> Vec<DocumentSummary> {
+    let query = query.to_ascii_lowercase();
+    let thumbnail = |id: DocumentId| format!("/documents/{id}/thumbnail");
+    let mut summaries = BTreeMap::new();
+    let stored = match &state.store {
+        Some(store) => store.list(&query).unwrap_or_else(|err| {
+            warn!("listing stored documents: {err}");
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    for doc in stored {
+        let summary = DocumentSummary {
+            id: doc.id,
+            name: doc.name,
+            objects: doc.latest.objects,
+            clients: 0,
+            revision: Some(doc.latest.revision),
+            thumbnail: doc.has_thumbnail.then(|| thumbnail(doc.id)),
+        };
+        summaries.insert(doc.id, summary);
+    }
+
+    let mut clients: HashMap<DocumentId, usize> = HashMap::new();
+    for entry in state.peers.lock().unwrap().values() {
+        if let Some(id) = entry.document {
+            *clients.entry(id).or_default() += 1;
+        }
+    }
+    let documents = state.documents.lock().unwrap();
+    for (&id, handle) in documents.iter() {
+        let document = handle.lock().unwrap();
+        if !document.name.to_ascii_lowercase().contains(&query) {
+            continue;
+        }
+        // The hosted document is newer than its stored revisions.
+        let stored = summaries.remove(&id);
+        let summary = DocumentSummary {
+            id,
+            name: document.name.clone(),
+            objects: document.model.objects().len(),
+            clients: clients.get(&id).copied().unwrap_or(0),
+            revision: document.revision,
+            thumbnail: stored.and_then(|stored| stored.thumbnail),
+        };
+        summaries.insert(id, summary);
+    }
+    summaries.into_values().collect()
+}
+
+/// Stores the client's document as a new revision, telling every client
+/// of the document.
+fn save_document(
+    state: &AppState,
+    session: &Session,
+    thumbnail: Option<Vec<u8>>,
+) -> Vec<ServerMsg> {
+    let Some(store) = &state.store else {
+        return vec![ServerMsg::error(
+            ErrorCode::StorageFailed,
+            format!("the server keeps no documents; set {STORE_VAR}"),
+        )];
+    };
+    let Some((document_id, document)) = &session.document else {
+        return vec![no_document()];
+    };
+    if thumbnail
+        .as_ref()
+        .is_some_and(|png| png.len() > MAX_THUMBNAIL_LEN)
+    {
+        return vec![ServerMsg::error(
+            ErrorCode::InvalidMessage,
+            format!("thumbnail larger than {MAX_THUMBNAIL_LEN} bytes"),
+        )];
+    }
+    let revision = match store_revision(store, *document_id, document, thumbnail.as_deref()) {
+        Ok(revision) => revision,
+        Err(err) => return vec![ServerMsg::error(ErrorCode::StorageFailed, err.to_string())],
+    };
+    let saved = ServerMsg::DocumentSaved {
+        id: *document_id,
+        revision,
+    };
+    notify_document(state, *document_id, session.peer_id, [saved.clone()]);
+    vec![saved]
+}
+
+/// Stores the document as its next revision, with its roles and a
+/// `thumbnail` replacing the stored one, and starts its log. Without a
+/// `thumbnail` the server draws one in the background; see
+/// [`render_thumbnail`].
+fn store_revision(
+    store: &Arc<dyn DocumentStore>,
+    id: DocumentId,
+    document: &DocumentHandle,
+    thumbnail: Option<&[u8]>,
+) -> Result<RevisionId, store::StoreError> {
+    // Snapshot under the lock; write outside it.
+    let (name, seq, snapshot, roles) = {
+        let document = document.lock().unwrap();
+        let roles = document.roles.clone();
+        let name = document.name.clone();
+        (name, document.seq, document.model.snapshot(), roles)
+    };
+    let model = snapshot.into_model();
+    let revision = store.save(id, &name, &model, thumbnail)?;
+    store.set_roles(id, &roles)?;
+    if thumbnail.is_none() {
+        let store = store.clone();
+        tokio::task::spawn_blocking(move || render_thumbnail(&*store, id, revision, model));
+    }
+    let mut document = document.lock().unwrap();
+    document.revision = document.revision.max(Some(revision));
+    document.saved_seq = document.saved_seq.max(seq);
+    if document.log.is_none() {
+        document.log = Some(OpLog {
+            store: store.clone(),
+            id,
+            len: 0,
+        });
+        document.checkpoint();
+    }
+    Ok(revision)
+}
+
+/// Draws `model`, the document's `revision`, as its thumbnail: an
+/// isometric view of its visible bodies, tessellated and rasterized on the
+/// CPU so no GPU is needed.
+fn render_thumbnail(store: &dyn DocumentStore, id: DocumentId, revision: RevisionId, model: Model) {
+    let png = match GeomScene::from_model(model) {
+        Ok(scene) => scene.render_preview(THUMBNAIL_SIZE),
+        Err(err) => {
+            warn!("thumbnail of document {id} revision {revision}: {err}");
+            return;
+        }
+    };
+    // Left out when a later save has drawn or sent a newer one.
+    if let Err(err) = store.set_thumbnail(id, revision, &png) {
+        warn!("storing the thumbnail of document {id}: {err}");
+    }
+}
+
+/// Puts document `id` back as it was at `revision`, storing that as its
+/// next revision, on behalf of `peer`. Every other client of the document
+/// is sent what the replies hold: [`ServerMsg::RevisionRestored`] and the
+/// new snapshot.
+fn restore_revision(
+    state: &AppState,
+    id: DocumentId,
+    document: &DocumentHandle,
+    revision: RevisionId,
+    peer: PeerId,
+) -> Vec<ServerMsg> {
+    let Some(store) = &state.store else {
+        return vec![ServerMsg::error(
+            ErrorCode::StorageFailed,
+            format!("the server keeps no documents; set {STORE_VAR}"),
+        )];
+    };
+    let mut model = match store.load_revision(id, revision) {
+        Ok(Some(model)) => model,
+        Ok(None) => {
+            return vec![ServerMsg::error(
+                ErrorCode::UnknownRevision,
+                format!("document {id} has no revision {revision}"),
+            )]
+        }
+        Err(err) => return vec![ServerMsg::error(ErrorCode::StorageFailed, err.to_string())],
+    };
+    {
+        let mut document = document.lock().unwrap();
+        // Go on minting as the document's own client.
+        model.set_client_id(document.model.client_id());
+        document.model = SharedModel::new(model);
+        // The history's commands were made against another model.
+        document.history = UndoStack::default();
+        document.last_writes = LastWrites::default();
+        document.seq += 1;
+        document.checkpoint();
+    }
+    let saved = match store_revision(store, id, document, None) {
+        Ok(saved) => saved,
+        Err(err) => return vec![ServerMsg::error(ErrorCode::StorageFailed, err.to_string())],
+    };
+    info!("document {id} restored to revision {revision} as {saved}");
+    let restored = ServerMsg::RevisionRestored {
+        id,
+        revision,
+        saved,
+        peer,
+    };
+    let replies = vec![restored, model_snapshot(document)];
+    notify_document(state, id, peer, replies.clone());
+    replies
+}
+
+/// Stores every hosted document changed since it was last stored, each
+/// `period`, telling the clients of each.
+async fn autosave(state: AppState, store: Arc<dyn DocumentStore>, period: Duration) {
+    let mut ticks = tokio::time::interval(period);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first tick is immediate; nothing has changed yet.
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        let documents: Vec<_> = state
+            .documents
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, handle)| (id, handle.clone()))
+            .collect();
+        for (id, document) in documents {
+            let changed = {
+                let document = document.lock().unwrap();
+                document.seq != document.saved_seq
+            };
+            if !changed {
+                continue;
+            }
+            match store_revision(&store, id, &document, None) {
+                Ok(revision) => {
+                    info!("autosaved document {id} as revision {revision}");
+                    let saved = ServerMsg::DocumentSaved { id, revision };
+                    notify_document(&state, id, API_PEER, [saved]);
+                }
+                Err(err) => warn!("autosaving document {id}: {err}"),
+            }
+        }
+    }
+}
+
+/// What applying a command did, told from its inverse: an add is undone by
+/// deleting it.
+fn describe(inverse: &ModelCommand) -> String {
+    match inverse {
+        ModelCommand::Delete { id } => format!("added object {id}"),
+        ModelCommand::Restore { id } => format!("deleted object {id}"),
+        ModelCommand::SetTransform { id, .. } => format!("moved object {id}"),
+        ModelCommand::SetFeatures { id, .. } => format!("changed the features of object {id}"),
+        _ => "edit applied".to_string(),
+    }
+}
+
+/// Takes the most urgent waiting job and marks it running.
+fn next_job(
+    jobs: &Mutex<JobQueue>,
+    peers: &Mutex<BTreeMap<PeerId, PeerEntry>>,
+) -> Option<HeavyJob> {
+    let mut queue = jobs.lock().unwrap();
+    let job = queue.pending.pop_front()?;
+    queue.running.push(RunningJob {
+        id: job.id,
+        respond_to: job.respond_to.clone(),
+        cancel: job.cancel.clone(),
+    });
+    announce_positions(&queue, 0);
+    announce_queue(&queue, &peers.lock().unwrap());
+    Some(job)
+}
+
+/// One of [`job_workers`] workers, running jobs as they are queued.
+async fn job_worker(
+    jobs: Arc<Mutex<JobQueue>>,
+    ready: Arc<Notify>,
+    peers: Arc<Mutex<BTreeMap<PeerId, PeerEntry>>>,
+    metrics: Arc<Metrics>,
+    timeout: Duration,
+) {
+    loop {
+        let Some(job) = next_job(&jobs, &peers) else {
+            ready.notified().await;
+            continue;
+        };
+        let respond_to = job.respond_to.clone();
+        let job_id = job.id;
+        let task = job.task;
+        let kind = task.kind();
+        let cancel = job.cancel;
+        let (send_to, request_id) = (respond_to.clone(), job.request_id);
+        let ctx = JobContext {
+            job_id,
+            cancel: cancel.clone(),
+            send: Box::new(move |msg| {
+                let _ = send_to.blocking_send(in_reply_to(msg, request_id));
+            }),
+            last: Cell::new(None),
+        };
+        let started = Instant::now();
+        let running = tokio::task::spawn_blocking(move || run_job(task, &ctx));
+        // A blocking job cannot be interrupted: past its deadline it is left
+        // to finish on its own thread, its result unused.
+        let payload = tokio::select! {
+            biased;
+            result = running => match result {
+                Ok(result) => Ok(result),
+                Err(err) => Err(JobPayload::Error {
+                    message: format!("job failed: {err}"),
+                }),
+            },
+            () = cancel.cancelled() => Err(JobPayload::Canceled),
+            () = tokio::time::sleep(timeout) => {
+                cancel.cancel();
+                Err(JobPayload::Error {
+                    message: format!("job timed out after {}s", timeout.as_secs_f64()),
+                })
+            }
+        };
+        metrics.job_finished(kind, started.elapsed());
+        let result = payload.unwrap_or_else(|payload| ServerMsg::JobResult { job_id, payload });
+        let _ = respond_to.send(in_reply_to(result, job.request_id)).await;
+        let mut queue = jobs.lock().unwrap();
+        queue.running.retain(|queued| queued.id != job_id);
+        announce_queue(&queue, &peers.lock().unwrap());
+    }
+}
+
+/// What a running job reports through: the stages it reaches, its partial
+/// results, and whether it is still wanted.
+struct JobContext {
+    job_id: u64,
+    cancel: CancellationToken,
+    send: Box<dyn Fn(ServerMsg) + Send>,
+    /// The last progress sent, so repeats are not.
+    last: Cell<Option<(u8, &'static str)>>,
+}
+
+impl JobContext {
+    /// Tells the job's owner it reached `stage`, e.g. `"tessellating"`,
+    /// `percent` of the way through.
+    fn progress(&self, percent: u8, stage: &'static str) {
+        if self.last.replace(Some((percent, stage))) == Some((percent, stage)) {
+            return;
+        }
+        self.send(ServerMsg::JobProgress {
+            job_id: self.job_id,
+            percent,
+            stage: stage.to_string(),
+        });
+    }
+
+    /// Sends the job's owner a partial result; nothing more goes out once
+    /// the job is canceled or timed out.
+    fn send(&self, msg: ServerMsg) {
+        if !self.cancel.is_cancelled() {
+            (self.send)(msg);
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+}
+
+/// Runs a job to completion, returning the message that reports it;
+/// progress and partial results go through `ctx` on the way. Jobs that
+/// commit to a document do not once they are canceled.
+fn run_job(task: JobTask, ctx: &JobContext) -> ServerMsg {
+    let job_id = ctx.job_id;
+    match task {
+        JobTask::Demo { kind, payload } => {
+            ctx.progress(0, "computing");
+            std::thread::sleep(Duration::from_millis(300));
+            let details = payload.unwrap_or_else(|| "no-payload".to_string());
+            let text = format!("heavy job done: {kind} ({details})");
+            ServerMsg::JobResult {
+                job_id,
+                payload: JobPayload::Text { text },
+            }
+        }
+        JobTask::Export {
+            format,
+            objects,
+            model,
+        } => match export(format, &objects, model, ctx) {
+            Ok(bytes) => ServerMsg::ExportReady {
+                job_id,
+                name: format!("export.{}", format.extension()),
+                mime: format.mime().to_string(),
+                content: ExportContent::Bytes(bytes),
+            },
+            Err(err) => ServerMsg::JobResult {
+                job_id,
+                payload: JobPayload::Error {
+                    message: format!("export failed: {err}"),
+                },
+            },
+        },
+        JobTask::Mesh {
+            objects,
+            tolerance,
+            model,
+        } => {
+            let payload = match mesh(&objects, tolerance, model, ctx) {
+                Ok(triangles) => JobPayload::Text {
+                    text: format!("meshed {triangles} triangles"),
+                },
+                Err(err) => JobPayload::Error {
+                    message: format!("meshing failed: {err}"),
+                },
+            };
+            ServerMsg::JobResult { job_id, payload }
+        }
+        JobTask::Feature {
+            id,
+            op,
+            model,
+            document,
+            peer,
+            peers,
+        } => {
+            let commit = Commit {
+                document: &document,
+                peer,
+                peers: &peers,
+            };
+            let payload = match add_feature(id, op, model, commit, ctx) {
+                Ok(text) => JobPayload::Text { text },
+                Err(message) => JobPayload::Error { message },
+            };
+            ServerMsg::JobResult { job_id, payload }
+        }
+        JobTask::Import {
+            upload_id,
+            name,
+            format,
+            data,
+            document,
+            peer,
+            peers,
+        } => {
+            let commit = Commit {
+                document: &document,
+                peer,
+                peers: &peers,
+            };
+            match import(&name, format, data, commit, ctx) {
+                Ok(objects) => ServerMsg::ImportResult { upload_id, objects },
+                Err(err) => ServerMsg::JobResult {
+                    job_id,
+                    payload: JobPayload::Error {
+                        message: format!("{name}: {err}"),
+                    },
+                },
+            }
+        }
+    }
+}
+
+/// Where a job commits its edit, and who made it.
+struct Commit<'a> {
+    document: &'a (DocumentId, DocumentHandle),
+    peer: PeerId,
+    peers: &'a Mutex<BTreeMap<PeerId, PeerEntry>>,
+}
+
+/// Computes a feature for [`ClientMsg::AddFeature`] and commits it to the
+/// document, unless the body's features changed while it was computed.
+/// Sends the edit and the body's new mesh through `ctx`.
+fn add_feature(
+    id: ObjectId,
+    op: FeatureOp,
+    model: ModelSnapshot,
+    commit: Commit<'_>,
+    ctx: &JobContext,
+) -> Result<String, String> {
+    let Commit {
+        document: (document_id, document),
+        peer,
+        peers,
+    } = commit;
+    ctx.progress(0, "validating");
+    let Some(base) = model.object(id).map(|obj| obj.features.clone()) else {
+        return Err(format!("unknown object {id}"));
+    };
+    ctx.progress(10, "computing");
+    let mut scene = GeomScene::from_model(model.into_model()).map_err(|err| err.to_string())?;
+    let feature = scene
+        .add_feature(id, op)
+        .map_err(|err| format!("feature failed: {err}"))?;
+    let features = scene
+        .model()
+        .object(id)
+        .map_or_else(Vec::new, |obj| obj.features.clone());
+
+    let (text, seq, delta) = {
+        let mut guard = document.lock().unwrap();
+        let document = &mut *guard;
+        if ctx.is_cancelled() {
+            return Err("job canceled".to_string());
+        }
+        if document.model.object(id).map(|obj| &obj.features) != Some(&base) {
+            return Err(format!(
+                "object {id} changed while its feature was computed"
+            ));
+        }
+        let before = document.model.snapshot();
+        let command = ModelCommand::SetFeatures { id, features };
+        let op = LoggedOp::Edit(command.clone());
+        if let Err(err) = document.history.execute(document.model.edit(), command) {
+            return Err(err.to_string());
+        }
+        document.record(op);
+        let text = document
+            .history
+            .next_undo()
+            .map_or_else(String::new, describe);
+        let delta = before.diff(&document.model);
+        let stamp = document.stamp(peer, None);
+        document.wrote(&delta, stamp);
+        document.seq += 1;
+        (text, document.seq, delta)
+    };
+    {
+        let peers = peers.lock().unwrap();
+        for note in ServerMsg::notifications(seq, peer, delta.clone()) {
+            broadcast_document(&peers, *document_id, peer, note);
+        }
+    }
+    ctx.send(ServerMsg::Log { text });
+    ctx.send(ServerMsg::ModelDelta { seq, delta });
+    if let Some(mesh) = scene.object_mesh(id) {
+        ctx.progress(90, "encoding");
+        ctx.send(ServerMsg::MeshData(MeshData {
+            target: MeshTarget::Object(id),
+            positions: mesh.positions.clone(),
+            normals: mesh.normals.clone(),
+            indices: mesh.indices.clone(),
+        }));
+    }
+    Ok(format!("added feature {feature} to object {id}"))
+}
+
+/// Reads an uploaded file and adds its bodies to the document. Sends
+/// everyone a full snapshot rather than a delta, since deltas do not carry
+/// the mesh assets the new bodies draw.
+fn import(
+    name: &str,
+    format: ImportFormat,
+    data: Vec<u8>,
+    commit: Commit<'_>,
+    ctx: &JobContext,
+) -> Result<Vec<ObjectId>, String> {
+    let Commit {
+        document: (document_id, document),
+        peer,
+        peers,
+    } = commit;
+    ctx.progress(0, "validating");
+    let meshes = match format {
+        ImportFormat::Stl => {
+            let (positions, indices) = cad_geom::parse_stl(&data).map_err(|err| err.to_string())?;
+            let triangles = indices.len() as u64 / 3;
+            if triangles > MAX_TRIANGLES {
+                return Err(format!(
+                    "{triangles} triangles, at most {MAX_TRIANGLES} allowed"
+                ));
+            }
+            ctx.progress(30, "computing");
+            cad_geom::mesh_solid(&positions, &indices).map_err(|err| err.to_string())?;
+            vec![(positions, indices)]
+        }
+        ImportFormat::Step => return Err(GeomError::NotImplemented("import_step").to_string()),
+    };
+
+    let objects: Vec<ObjectId> = {
+        let mut guard = document.lock().unwrap();
+        let document = &mut *guard;
+        if ctx.is_cancelled() {
+            return Err("job canceled".to_string());
+        }
+        let before = document.model.snapshot();
+        for (positions, indices) in meshes {
+            let model = document.model.edit();
+            let handle = model.add_mesh_asset(name.to_string(), positions, indices);
+            // Through the history, so the import can be undone like any add.
+            let kind = ObjectKind::Mesh { handle };
+            if let Err(err) = document
+                .history
+                .execute(model, ModelCommand::AddObject { kind })
+            {
+                warn!("import of {name} failed: {err}");
+            }
+        }
+        // Mesh assets are no ops, and too large to log anyway.
+        document.checkpoint();
+        let delta = before.diff(&document.model);
+        let stamp = document.stamp(peer, None);
+        document.wrote(&delta, stamp);
+        document.seq += 1;
+        delta.added.into_iter().map(|(_, obj)| obj.id).collect()
+    };
+    info!("imported {name} as {objects:?}");
+    ctx.progress(90, "encoding");
+    let snapshot = model_snapshot(document);
+    broadcast_document(&peers.lock().unwrap(), *document_id, peer, snapshot.clone());
+    ctx.send(snapshot);
+    Ok(objects)
+}
+
+/// Tessellates for [`ClientMsg::RequestMesh`], sending each mesh as it is
+/// done. Returns the number of triangles sent.
+fn mesh(
+    objects: &[ObjectId],
+    tolerance: f64,
+    model: ModelSnapshot,
+    ctx: &JobContext,
+) -> Result<usize, GeomError> {
+    ctx.progress(0, "validating");
+    if let Some(&id) = objects.iter().find(|&&id| model.object(id).is_none()) {
+        return Err(GeomError::UnknownObject(id));
+    }
+    ctx.progress(5, "tessellating");
+    let mut scene = GeomScene::from_model_with_tolerance(model.into_model(), tolerance)?;
+    let send_mesh = |target, mesh: &TriMesh| {
+        ctx.send(ServerMsg::MeshData(MeshData {
+            target,
+            positions: mesh.positions.clone(),
+            normals: mesh.normals.clone(),
+            indices: mesh.indices.clone(),
+        }));
+        mesh.indices.len() / 3
+    };
+    if objects.is_empty() {
+        let mesh = scene.mesh()?;
+        ctx.progress(90, "encoding");
+        return Ok(send_mesh(MeshTarget::Scene, &mesh));
+    }
+    let mut triangles = 0;
+    for (done, &id) in objects.iter().enumerate() {
+        ctx.progress((50 + done * 50 / objects.len()) as u8, "encoding");
+        let mesh = scene.object_mesh(id).ok_or(GeomError::UnknownObject(id))?;
+        triangles += send_mesh(MeshTarget::Object(id), &mesh);
+    }
+    Ok(triangles)
+}
+
+fn export(
+    format: ExportFormat,
+    objects: &[ObjectId],
+    model: ModelSnapshot,
+    ctx: &JobContext,
+) -> Result<Vec<u8>, GeomError> {
+    ctx.progress(0, "validating");
+    if let Some(&id) = objects.iter().find(|&&id| model.object(id).is_none()) {
+        return Err(GeomError::UnknownObject(id));
+    }
+    ctx.progress(10, "tessellating");
+    let scene = GeomScene::from_model(model.into_model())?;
+    ctx.progress(70, "encoding");
+    match format {
+        ExportFormat::Stl => scene.export_stl(objects),
+        ExportFormat::Gltf => scene.export_glb(objects),
+        ExportFormat::Step => Err(GeomError::NotImplemented("export_step")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cad_core::Transform;
+    use cad_protocol::CHUNK_THRESHOLD;
+
+    const ID: DocumentId = 7;
+
+    /// A server with an in-memory store, no workers and no tokens.
+    fn server() -> AppState {
+        AppState {
+            job_ready: Arc::new(Notify::new()),
+            next_job_id: Arc::new(AtomicU64::new(1)),
+            jobs: Arc::default(),
+            documents: Arc::default(),
+            next_document_id: Arc::new(AtomicU64::new(FIRST_DOCUMENT + 1)),
+            tokens: None,
+            peers: Arc::default(),
+            next_peer_id: Arc::new(AtomicU64::new(1)),
+            parked: Arc::default(),
+            store: Some(Arc::new(SqliteStore::in_memory().unwrap())),
+            metrics: Arc::default(),
+            recordings: None,
+        }
+    }
+
+    /// Hosts document `ID`, owned by `ana`, who made `bo` a viewer.
+    fn owned_document(state: &AppState) -> DocumentHandle {
+        let document = Document {
+            roles: BTreeMap::from([
+                ("ana".to_string(), Role::Owner),
+                ("bo".to_string(), Role::Viewer),
+            ]),
+            ..Document::new("Bracket".to_string())
+        };
+        let handle = Arc::new(Mutex::new(document));
+        let mut documents = state.documents.lock().unwrap();
+        documents.insert(ID, handle.clone());
+        handle
+    }
+
+    /// An authenticated client of `user`, with `document` open.
+    fn client(state: &AppState, user: &str, document: Option<&DocumentHandle>) -> Session {
+        Session {
+            token: String::new(),
+            peer_id: state.next_peer_id.fetch_add(1, Ordering::Relaxed),
+            user: Some(user.to_string()),
+            joined: false,
+            document: document.map(|handle| (ID, handle.clone())),
+            selection: Vec::new(),
+            cursor: None,
+            uploads: HashMap::new(),
+            stream: StreamLimit::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn comments_are_resolved_by_their_authors_or_owners() {
+        let state = server();
+        let document = owned_document(&state);
+        let mut owner = client(&state, "ana", Some(&document));
+        let mut viewer = client(&state, "bo", Some(&document));
+        let comment = |text: &str| ClientMsg::AddComment {
+            anchor: Anchor::World([0.0; 3]),
+            text: text.to_string(),
+        };
+        let id = |replies: Vec<ServerMsg>| match &replies[..] {
+            [ServerMsg::CommentAdded { comment, .. }] => comment.id,
+            other => panic!("{other:?}"),
+        };
+        let theirs = id(send(&state, &mut owner, comment("too thin")).await);
+        let own = id(send(&state, &mut viewer, comment("why?")).await);
+        let also_own = id(send(&state, &mut viewer, comment("never mind")).await);
+
+        let remove = |id| ClientMsg::RemoveComment { id };
+        let refused = send(&state, &mut viewer, remove(theirs)).await;
+        assert_eq!(error_code(&refused), Some(ErrorCode::Forbidden));
+        for (session, id) in [(&mut viewer, own), (&mut owner, also_own)] {
+            let removed = send(&state, session, remove(id)).await;
+            assert!(matches!(removed[..], [ServerMsg::CommentRemoved { .. }]));
+        }
+        let left: Vec<_> = document
+            .lock()
+            .unwrap()
+            .comments
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(left, [theirs]);
+    }
+
+    #[test]
+    fn concurrent_moves_settle_on_one_winner() {
+        let moved = |x: f32| Transform {
+            translation: [x, 0.0, 0.0],
+            ..Transform::default()
+        };
+        // Either order of arrival ends with the greater peer's move.
+        for later_peer_first in [false, true] {
+            let state = server();
+            let document = owned_document(&state);
+            let editor = ("bo".to_string(), Role::Editor);
+            document.lock().unwrap().roles.extend([editor]);
+            let ana = client(&state, "ana", Some(&document));
+            let bo = client(&state, "bo", Some(&document));
+            assert!(ana.peer_id < bo.peer_id);
+            let add = ModelCommand::AddObject {
+                kind: ObjectKind::Box {
+                    w: 1.0,
+                    h: 1.0,
+                    d: 1.0,
+                },
+            };
+            apply_edit(&state, &ana, add, None);
+            let id = document.lock().unwrap().model.objects()[0].id;
+            // Both drag the box having seen it added, neither the other's
+            // move.
+            let base = Some(document.lock().unwrap().seq);
+            let mut moves = [(&ana, 1.0), (&bo, 2.0)];
+            if later_peer_first {
+                moves.reverse();
+            }
+            let replies: Vec<_> = moves
+                .into_iter()
+                .map(|(session, x)| {
+                    let command = ModelCommand::SetTransform {
+                        id,
+                        transform: moved(x),
+                    };
+                    apply_edit(&state, session, command, base)
+                })
+                .collect();
+            let refused = replies.iter().filter(|r| error_code(r).is_some()).count();
+            assert_eq!(refused, usize::from(later_peer_first));
+            let object = document.lock().unwrap().model.object(id).cloned();
+            assert_eq!(object.unwrap().transform, moved(2.0));
+
+            // A move made after seeing the winner goes through, but one
+            // from before the undo that followed it does not.
+            let seen = Some(document.lock().unwrap().seq);
+            let command = ModelCommand::SetTransform {
+                id,
+                transform: moved(3.0),
+            };
+            assert!(error_code(&apply_edit(&state, &ana, command.clone(), seen)).is_none());
+            step_history(&state, &bo, HistoryStep::Undo);
+            let refused = apply_edit(&state, &ana, command, seen);
+            assert_eq!(error_code(&refused), Some(ErrorCode::Superseded));
+        }
+    }
+
+    #[tokio::test]
+    async fn binary_frames_carry_messages_and_uploads() {
+        let state = server();
+        let document = owned_document(&state);
+        let mut session = client(&state, "ana", Some(&document));
+        let (out_tx, mut out_rx) = mpsc::channel(16);
+        // As the connection reads them: reassembled, then handled.
+        let mut reassembler = Reassembler::default();
+        let mut receive = async |frames: Vec<Frame>| {
+            for frame in frames {
+                if let Some(frame) = reassembler.push(frame).unwrap() {
+                    handle_frame(&state, &mut session, frame, &out_tx).await;
+                }
+            }
+            let mut replies = Vec::new();
+            while let Ok(reply) = out_rx.try_recv() {
+                replies.push(reply);
+            }
+            replies
+        };
+
+        // MessagePack and, for older clients, JSON text alike.
+        for (encoding, request_id) in [(Encoding::MessagePack, 1), (Encoding::Json, 2)] {
+            let ping = Envelope::new(ClientMsg::Ping { sent_ms: 5 }, Some(request_id));
+            let replies = receive(vec![ping.encode(encoding).unwrap()]).await;
+            assert!(
+                matches!(
+                    &replies[..],
+                    [Envelope { request_id: Some(id), msg: ServerMsg::Pong { sent_ms: 5, .. } }]
+                        if *id == request_id
+                ),
+                "{encoding:?}: {replies:?}"
+            );
+        }
+
+        // An upload too large for one frame, announced in MessagePack.
+        let data = UploadData {
+            upload_id: 9,
+            data: vec![0; CHUNK_THRESHOLD + 1],
+        };
+        let announce = Envelope::new(
+            ClientMsg::Upload {
+                upload_id: 9,
+                name: "part.stl".to_string(),
+                format: ImportFormat::Stl,
+                size: data.data.len() as u64,
+            },
+            Some(3),
+        );
+        let mut frames = vec![announce.encode(Encoding::MessagePack).unwrap()];
+        let chunks = Chunker::default().split(Frame::Binary(data.to_frame()));
+        assert!(chunks.len() > 1);
+        frames.extend(chunks);
+        let replies = receive(frames).await;
+        assert!(
+            matches!(
+                replies.first(),
+                Some(Envelope {
+                    request_id: Some(3),
+                    msg: ServerMsg::JobAccepted { .. }
+                })
+            ),
+            "{replies:?}"
+        );
+    }
+
+    async fn send(state: &AppState, session: &mut Session, msg: ClientMsg) -> Vec<ServerMsg> {
+        let (out_tx, _out_rx) = mpsc::channel(16);
+        handle_client_msg(state, session, msg, None, &out_tx).await
+    }
+
+    fn error_code(replies: &[ServerMsg]) -> Option<ErrorCode> {
+        match replies {
+            [ServerMsg::Error { code, .. }] => Some(*code),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn documents_are_viewed_by_their_roles_only() {
+        let state = server();
+        owned_document(&state);
+        for (user, allowed) in [("ana", true), ("bo", true), ("cy", false)] {
+            let mut session = client(&state, user, None);
+            let listed = send(&state, &mut session, ClientMsg::ListRevisions { id: ID }).await;
+            let opened = send(&state, &mut session, ClientMsg::OpenDocument { id: ID }).await;
+            if allowed {
+                assert!(
+                    matches!(listed[..], [ServerMsg::RevisionList { .. }]),
+                    "{user}"
+                );
+                assert!(error_code(&opened).is_none(), "{user}");
+            } else {
+                assert_eq!(error_code(&listed), Some(ErrorCode::Forbidden));
+                assert_eq!(error_code(&opened), Some(ErrorCode::Forbidden));
+                assert!(session.document.is_none());
+            }
+        }
+        let mut session = client(&state, "cy", None);
+        let listed = send(
+            &state,
+            &mut session,
+            ClientMsg::ListRevisions { id: ID + 1 },
+        )
+        .await;
+        assert_eq!(error_code(&listed), Some(ErrorCode::UnknownDocument));
+    }
+
+    fn logged(store: &Arc<SqliteStore>) -> Option<OpLog> {
+        Some(OpLog {
+            store: store.clone(),
+            id: ID,
+            len: 0,
+        })
+    }
+
+    /// Applies `op` to `document` as a connection would, then logs it.
+    fn apply(document: &mut Document, op: LoggedOp) {
+        let model = document.model.edit();
+        let applied = match op.clone() {
+            LoggedOp::Edit(command) => document.history.execute(model, command).map(|()| true),
+            LoggedOp::Undo => document.history.undo(model),
+            LoggedOp::Redo => document.history.redo(model),
+        };
+        assert!(applied.unwrap(), "{op:?}");
+        document.record(op);
+    }
+
+    fn reload(store: &Arc<SqliteStore>) -> Document {
+        let mut document = Document {
+            log: logged(store),
+            ..Document::default()
+        };
+        replay(&mut document, store.journal(ID).unwrap().unwrap());
+        document
+    }
+
+    #[test]
+    fn replayed_log_matches_the_live_document() {
+        // Short of, at and past one checkpoint, and past two.
+        for count in [
+            1,
+            CHECKPOINT_EVERY - 1,
+            CHECKPOINT_EVERY,
+            2 * CHECKPOINT_EVERY + 7,
+        ] {
+            let store = Arc::new(SqliteStore::in_memory().unwrap());
+            let mut live = Document {
+                log: logged(&store),
+                ..Document::new("Part".to_string())
+            };
+            // A document is logged once it is saved.
+            store.save(ID, &live.name, &live.model, None).unwrap();
+            live.checkpoint();
+            for i in 0..count {
+                let op = match i % 4 {
+                    0 => LoggedOp::Edit(ModelCommand::AddObject {
+                        kind: ObjectKind::Box {
+                            w: 1.0 + i as f32,
+                            h: 1.0,
+                            d: 1.0,
+                        },
+                    }),
+                    1 => LoggedOp::Edit(ModelCommand::SetTransform {
+                        id: live.model.objects().last().unwrap().id,
+                        transform: Transform {
+                            translation: [i as f32, 0.0, 0.0],
+                            ..Transform::default()
+                        },
+                    }),
+                    2 => LoggedOp::Undo,
+                    _ => LoggedOp::Redo,
+                };
+                apply(&mut live, op);
+            }
+
+            let mut reloaded = reload(&store);
+            assert_eq!(*reloaded.model, *live.model, "after {count} ops");
+            assert_eq!(
+                reloaded.log.as_ref().unwrap().len,
+                live.log.as_ref().unwrap().len
+            );
+            let uids = |document: &Document| {
+                let objects = document.model.objects();
+                objects.iter().map(|o| o.uid()).collect::<Vec<_>>()
+            };
+            assert_eq!(uids(&reloaded), uids(&live));
+
+            // The history came back too: both undo and redo alike. Only the
+            // reloaded document logs now, as after a restart.
+            live.log = None;
+            for op in [LoggedOp::Undo, LoggedOp::Redo, LoggedOp::Undo] {
+                apply(&mut live, op.clone());
+                apply(&mut reloaded, op);
+                assert_eq!(*reloaded.model, *live.model, "after {count} ops");
+            }
+            assert_eq!(*reload(&store).model, *live.model);
         }
     }
 }
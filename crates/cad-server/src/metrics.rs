@@ -0,0 +1,122 @@
+//! Health and Prometheus endpoints for whoever runs the server.
+
+use crate::AppState;
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// Upper bounds of the job duration buckets, in seconds.
+const JOB_BUCKETS: [f64; 10] = [0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+/// What the server counts as it runs; the rest of `/metrics` is read off
+/// [`AppState`] when scraped.
+#[derive(Default)]
+pub struct Metrics {
+    /// Open websockets, authenticated or not.
+    connections: AtomicU64,
+    /// Finished jobs by kind.
+    jobs: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Jobs within each of [`JOB_BUCKETS`], not cumulative.
+    buckets: [u64; JOB_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Metrics {
+    pub fn connected(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn disconnected(&self) {
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Records a job of `kind` that took `elapsed` from starting to its
+    /// result, whether it succeeded, failed or was canceled.
+    pub fn job_finished(&self, kind: &'static str, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut jobs = self.jobs.lock().unwrap();
+        let histogram = jobs.entry(kind).or_default();
+        if let Some(bucket) = JOB_BUCKETS.iter().position(|&le| seconds <= le) {
+            histogram.buckets[bucket] += 1;
+        }
+        histogram.count += 1;
+        histogram.sum += seconds;
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/metrics", get(metrics))
+}
+
+/// The Prometheus text format, version 0.0.4.
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let (queued, running) = {
+        let jobs = state.jobs.lock().unwrap();
+        (jobs.pending.len(), jobs.running.len())
+    };
+    let mut gauges = vec![
+        (
+            "physalis_connections",
+            "Open websockets.",
+            state.metrics.connections.load(Ordering::Relaxed) as usize,
+        ),
+        (
+            "physalis_peers",
+            "Authenticated clients.",
+            state.peers.lock().unwrap().len(),
+        ),
+        ("physalis_jobs_queued", "Jobs waiting for a worker.", queued),
+        ("physalis_jobs_running", "Jobs being run.", running),
+        (
+            "physalis_documents_hosted",
+            "Documents in memory.",
+            state.documents.lock().unwrap().len(),
+        ),
+    ];
+    // A failed listing leaves the metric out rather than reporting zero.
+    if let Some(Ok(stored)) = state.store.as_ref().map(|store| store.list("")) {
+        let help = "Documents with a saved revision.";
+        gauges.push(("physalis_documents_stored", help, stored.len()));
+    }
+    let mut out = String::new();
+    for (name, help, value) in gauges {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        let _ = writeln!(out, "{name} {value}");
+    }
+
+    let name = "physalis_job_duration_seconds";
+    let _ = writeln!(out, "# HELP {name} Time from a job starting to its result.");
+    let _ = writeln!(out, "# TYPE {name} histogram");
+    for (kind, histogram) in state.metrics.jobs.lock().unwrap().iter() {
+        let mut cumulative = 0;
+        for (le, count) in JOB_BUCKETS.iter().zip(histogram.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{kind=\"{kind}\",le=\"{le}\"}} {cumulative}"
+            );
+        }
+        let count = histogram.count;
+        let _ = writeln!(out, "{name}_bucket{{kind=\"{kind}\",le=\"+Inf\"}} {count}");
+        let _ = writeln!(out, "{name}_sum{{kind=\"{kind}\"}} {}", histogram.sum);
+        let _ = writeln!(out, "{name}_count{{kind=\"{kind}\"}} {count}");
+    }
+
+    let content_type = "text/plain; version=0.0.4; charset=utf-8";
+    ([(header::CONTENT_TYPE, content_type)], out)
+}
@@ -0,0 +1,340 @@
+//! Checks on the primitives, features and transforms clients send, made
+//! before they reach the model or a job worker so that no solid is ever
+//! built from them.
+
+use cad_core::{DerivedOp, FeatureOp, ObjectKind, Transform};
+use cad_protocol::{ErrorCode, ServerMsg};
+
+/// Largest length a primitive or feature may have, in document units.
+pub const MAX_DIMENSION: f32 = 1.0e6;
+/// Smallest, well clear of the kernel's tolerance.
+pub const MIN_DIMENSION: f32 = 1.0e-4;
+/// Beyond this a prism is a cylinder with a very large mesh.
+pub const MAX_PRISM_SIDES: u32 = 1024;
+/// Copies a linear pattern may make, the original included.
+pub const MAX_PATTERN_COUNT: u32 = 1000;
+
+/// Why a primitive, feature or transform was refused.
+#[derive(Debug)]
+pub struct Rejected {
+    code: ErrorCode,
+    /// The parameter at fault, if the reason is about one.
+    field: Option<&'static str>,
+    reason: String,
+}
+
+impl Rejected {
+    fn primitive(field: &'static str, reason: impl Into<String>) -> Self {
+        Self {
+            code: ErrorCode::InvalidPrimitive,
+            field: Some(field),
+            reason: reason.into(),
+        }
+    }
+
+    fn feature(field: &'static str, reason: impl Into<String>) -> Self {
+        Self {
+            code: ErrorCode::InvalidParameters,
+            field: Some(field),
+            reason: reason.into(),
+        }
+    }
+
+    fn transform(field: &'static str, reason: impl Into<String>) -> Self {
+        Self {
+            code: ErrorCode::InvalidTransform,
+            field: Some(field),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl From<Rejected> for ServerMsg {
+    fn from(rejected: Rejected) -> Self {
+        let what = match rejected.code {
+            ErrorCode::InvalidPrimitive => "primitive",
+            ErrorCode::InvalidTransform => "transform",
+            _ => "feature",
+        };
+        let message = format!("invalid {what}: {}", rejected.reason);
+        match rejected.field {
+            Some(field) => ServerMsg::field_error(rejected.code, field, message),
+            None => ServerMsg::error(rejected.code, message),
+        }
+    }
+}
+
+/// Checks `kind`'s dimensions against the server's bounds, then against
+/// [`ObjectKind::validate`].
+pub fn check(kind: &ObjectKind) -> Result<(), Rejected> {
+    // Each length, and whether it may be negative, e.g. an extrusion
+    // pointing away from its sketch's normal.
+    let lengths = match *kind {
+        ObjectKind::Box { w, h, d } | ObjectKind::Wedge { w, h, d } => {
+            vec![("w", w, false), ("h", h, false), ("d", d, false)]
+        }
+        ObjectKind::Cylinder { r, h } => vec![("r", r, false), ("h", h, false)],
+        ObjectKind::Tube {
+            outer_r,
+            inner_r,
+            h,
+        } => {
+            // Checked first, as the inner radius is bounded by it.
+            if let Some(reason) = length_fault(outer_r, false) {
+                return Err(Rejected::primitive("outer_r", format!("outer_r {reason}")));
+            }
+            // Zero is a solid tube.
+            if !(inner_r.is_finite() && (0.0..outer_r).contains(&inner_r)) {
+                return Err(Rejected::primitive(
+                    "inner_r",
+                    "inner radius must be from 0 to below the outer radius",
+                ));
+            }
+            vec![("h", h, false)]
+        }
+        ObjectKind::Prism { sides, r, h } => {
+            if !(3..=MAX_PRISM_SIDES).contains(&sides) {
+                return Err(Rejected::primitive(
+                    "sides",
+                    format!("a prism has 3 to {MAX_PRISM_SIDES} sides"),
+                ));
+            }
+            vec![("r", r, false), ("h", h, false)]
+        }
+        ObjectKind::Derived {
+            op: DerivedOp::Extrude { distance },
+            ..
+        } => vec![("distance", distance, true)],
+        // Angles, operands and meshes are the model's to check.
+        ObjectKind::Derived { .. } | ObjectKind::Mesh { .. } | ObjectKind::Reference { .. } => {
+            Vec::new()
+        }
+    };
+    for (field, value, signed) in lengths {
+        if let Some(reason) = length_fault(value, signed) {
+            return Err(Rejected::primitive(field, format!("{field} {reason}")));
+        }
+    }
+    kind.validate().map_err(|reason| Rejected {
+        code: ErrorCode::InvalidPrimitive,
+        field: None,
+        reason: reason.to_string(),
+    })
+}
+
+/// Checks `op`'s parameters against the server's bounds, so a worker never
+/// starts on a feature the kernel would choke on or take forever over.
+pub fn check_feature(op: &FeatureOp) -> Result<(), Rejected> {
+    match *op {
+        // The tool is the kernel's to check.
+        FeatureOp::Subtract { .. } => Ok(()),
+        FeatureOp::LinearPattern {
+            direction,
+            spacing,
+            count,
+        } => {
+            if !(1..=MAX_PATTERN_COUNT).contains(&count) {
+                return Err(Rejected::feature(
+                    "count",
+                    format!("a pattern has 1 to {MAX_PATTERN_COUNT} copies"),
+                ));
+            }
+            feature_direction("direction", direction)?;
+            feature_length("spacing", spacing)
+        }
+        FeatureOp::PushPull {
+            point,
+            normal,
+            distance,
+        } => {
+            if !point
+                .iter()
+                .all(|c| c.is_finite() && c.abs() <= MAX_DIMENSION)
+            {
+                return Err(Rejected::feature(
+                    "point",
+                    format!("point must be finite and within {MAX_DIMENSION} of the origin"),
+                ));
+            }
+            feature_direction("normal", normal)?;
+            feature_length("distance", distance)
+        }
+    }
+}
+
+/// Feature lengths may point either way, so only zero is refused.
+fn feature_length(field: &'static str, value: f32) -> Result<(), Rejected> {
+    match length_fault(value, true) {
+        Some(reason) => Err(Rejected::feature(field, format!("{field} {reason}"))),
+        None => Ok(()),
+    }
+}
+
+fn feature_direction(field: &'static str, direction: [f32; 3]) -> Result<(), Rejected> {
+    let finite = direction.iter().all(|c| c.is_finite());
+    if finite && direction.iter().any(|&c| c != 0.0) {
+        return Ok(());
+    }
+    Err(Rejected::feature(
+        field,
+        format!("{field} must be a finite, non-zero vector"),
+    ))
+}
+
+/// Checks that `transform` is finite, and that its scale neither flattens
+/// a body below [`MIN_DIMENSION`] nor blows it up past [`MAX_DIMENSION`]
+/// times. Negative scales mirror, and are fine.
+pub fn check_transform(transform: &Transform) -> Result<(), Rejected> {
+    if !transform.translation.iter().all(|c| c.is_finite()) {
+        return Err(Rejected::transform(
+            "translation",
+            "translation must be finite",
+        ));
+    }
+    if !transform.rotation.iter().all(|c| c.is_finite()) {
+        return Err(Rejected::transform("rotation", "rotation must be finite"));
+    }
+    for factor in transform.scale {
+        if let Some(reason) = length_fault(factor, true) {
+            return Err(Rejected::transform("scale", format!("scale {reason}")));
+        }
+    }
+    Ok(())
+}
+
+/// What is wrong with a length, if anything; `signed` ones may be negative
+/// but not zero.
+fn length_fault(value: f32, signed: bool) -> Option<String> {
+    if !value.is_finite() {
+        Some("must be a finite number".to_string())
+    } else if !signed && value <= 0.0 {
+        Some("must be positive".to_string())
+    } else if value.abs() < MIN_DIMENSION {
+        Some(format!("must be at least {MIN_DIMENSION} in size"))
+    } else if value.abs() > MAX_DIMENSION {
+        Some(format!("must be at most {MAX_DIMENSION} in size"))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_bounds_parameters_are_refused() {
+        let boxed = |w| ObjectKind::Box { w, h: 1.0, d: 1.0 };
+        let prism = |sides| ObjectKind::Prism {
+            sides,
+            r: 1.0,
+            h: 1.0,
+        };
+        let primitives = [
+            (boxed(1.0), None),
+            (boxed(f32::NAN), Some("w")),
+            (boxed(f32::INFINITY), Some("w")),
+            (boxed(0.0), Some("w")),
+            (boxed(-1.0), Some("w")),
+            (boxed(MIN_DIMENSION / 2.0), Some("w")),
+            (boxed(MAX_DIMENSION * 2.0), Some("w")),
+            (prism(3), None),
+            (prism(MAX_PRISM_SIDES), None),
+            (prism(2), Some("sides")),
+            (prism(MAX_PRISM_SIDES + 1), Some("sides")),
+            (
+                ObjectKind::Tube {
+                    outer_r: 1.0,
+                    inner_r: 1.0,
+                    h: 1.0,
+                },
+                Some("inner_r"),
+            ),
+            (
+                ObjectKind::Tube {
+                    outer_r: -1.0,
+                    inner_r: 0.5,
+                    h: 1.0,
+                },
+                Some("outer_r"),
+            ),
+        ];
+        for (kind, field) in primitives {
+            let result = check(&kind);
+            assert_eq!(
+                result.as_ref().err().and_then(|r| r.field),
+                field,
+                "{kind:?}"
+            );
+            if let Err(rejected) = result {
+                assert_eq!(rejected.code, ErrorCode::InvalidPrimitive);
+            }
+        }
+
+        let pattern = |spacing, count| FeatureOp::LinearPattern {
+            direction: [1.0, 0.0, 0.0],
+            spacing,
+            count,
+        };
+        let features = [
+            (pattern(2.0, 3), None),
+            (pattern(-2.0, 3), None),
+            (pattern(2.0, 0), Some("count")),
+            (pattern(2.0, MAX_PATTERN_COUNT + 1), Some("count")),
+            (pattern(0.0, 3), Some("spacing")),
+            (pattern(f32::NAN, 3), Some("spacing")),
+            (
+                FeatureOp::LinearPattern {
+                    direction: [0.0; 3],
+                    spacing: 1.0,
+                    count: 2,
+                },
+                Some("direction"),
+            ),
+            (FeatureOp::Subtract { tool: 2 }, None),
+            (
+                FeatureOp::PushPull {
+                    point: [0.0, f32::NAN, 0.0],
+                    normal: [0.0, 0.0, 1.0],
+                    distance: 1.0,
+                },
+                Some("point"),
+            ),
+        ];
+        for (op, field) in features {
+            let result = check_feature(&op);
+            assert_eq!(result.as_ref().err().and_then(|r| r.field), field, "{op:?}");
+            if let Err(rejected) = result {
+                assert_eq!(rejected.code, ErrorCode::InvalidParameters);
+            }
+        }
+
+        let scaled = |factor| Transform {
+            scale: [1.0, factor, 1.0],
+            ..Transform::default()
+        };
+        let transforms = [
+            (Transform::default(), None),
+            (scaled(-1.0), None),
+            (scaled(0.0), Some("scale")),
+            (scaled(MIN_DIMENSION / 2.0), Some("scale")),
+            (scaled(MAX_DIMENSION * 2.0), Some("scale")),
+            (scaled(f32::NAN), Some("scale")),
+            (
+                Transform::from_translation([0.0, f32::INFINITY, 0.0]),
+                Some("translation"),
+            ),
+        ];
+        for (transform, field) in transforms {
+            let result = check_transform(&transform);
+            assert_eq!(
+                result.as_ref().err().and_then(|r| r.field),
+                field,
+                "{transform:?}"
+            );
+            if let Err(rejected) = result {
+                assert_eq!(rejected.code, ErrorCode::InvalidTransform);
+            }
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Recording what clients send, and replaying a recording against a fresh
+//! server, to reproduce what a user saw.
+//!
+//! A recording is [`MAGIC`] and a version byte, then each frame the client
+//! sent, after reassembly: the milliseconds since the session started, a
+//! kind byte (`0` text, `1` binary) and the frame's length, as
+//! little-endian `u64`, `u8` and `u32`, then the frame itself. Access
+//! tokens and session resume tokens are written as [`REDACTED`], so
+//! recordings can be passed around without handing out access.
+
+use crate::{handle_frame, leave, open_session, AppState};
+use cad_protocol::{ClientMsg, Encoding, Envelope, Frame, MSGPACK_FRAME_MAGIC};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+const MAGIC: &[u8; 4] = b"PREC";
+const VERSION: u8 = 1;
+/// A frame's time, kind and length.
+const HEADER_LEN: usize = 8 + 1 + 4;
+/// What recordings hold in place of tokens.
+pub const REDACTED: &str = "redacted";
+
+/// Appends a session's frames to its recording as they arrive.
+pub struct Recorder {
+    file: File,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&[VERSION])?;
+        Ok(Self {
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, frame: &Frame) -> io::Result<()> {
+        let redacted = redact(frame);
+        let (kind, bytes) = match redacted.as_ref().unwrap_or(frame) {
+            Frame::Text(text) => (0, text.as_bytes()),
+            Frame::Binary(bytes) => (1, bytes.as_slice()),
+        };
+        let millis = self.started.elapsed().as_millis() as u64;
+        let mut record = Vec::with_capacity(HEADER_LEN + bytes.len());
+        record.extend(millis.to_le_bytes());
+        record.push(kind);
+        record.extend((bytes.len() as u32).to_le_bytes());
+        record.extend(bytes);
+        // In one write, so a crash is unlikely to leave half a frame.
+        self.file.write_all(&record)
+    }
+}
+
+/// `frame` with the tokens in it replaced by [`REDACTED`], in the same
+/// encoding; `None` if it carries none.
+fn redact(frame: &Frame) -> Option<Frame> {
+    if matches!(frame, Frame::Binary(bytes) if !bytes.starts_with(&MSGPACK_FRAME_MAGIC)) {
+        // Upload data.
+        return None;
+    }
+    let mut envelope = Envelope::<ClientMsg>::from_frame(frame).ok()?;
+    match &mut envelope.msg {
+        ClientMsg::Authenticate { token }
+        | ClientMsg::Hello {
+            resume: Some(token),
+            ..
+        } => *token = REDACTED.to_string(),
+        _ => return None,
+    }
+    let encoding = match frame {
+        Frame::Text(_) => Encoding::Json,
+        Frame::Binary(_) => Encoding::MessagePack,
+    };
+    // Left out rather than written with its token.
+    Some(
+        envelope
+            .encode(encoding)
+            .unwrap_or_else(|_| Frame::Text(String::new())),
+    )
+}
+
+/// The frames of a recording, each with when it arrived.
+pub fn read(path: &Path) -> io::Result<Vec<(Duration, Frame)>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let data = std::fs::read(path)?;
+    let Some([version, rest @ ..]) = data.strip_prefix(MAGIC.as_slice()) else {
+        return Err(invalid("not a recording".to_string()));
+    };
+    if *version != VERSION {
+        return Err(invalid(format!("unknown recording version {version}")));
+    }
+    let mut frames = Vec::new();
+    let mut rest = rest;
+    while !rest.is_empty() {
+        let Some((header, body)) = rest.split_first_chunk::<HEADER_LEN>() else {
+            warn!("recording ends in a partial frame");
+            break;
+        };
+        let millis = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let len = u32::from_le_bytes(header[9..].try_into().unwrap()) as usize;
+        let Some(bytes) = body.get(..len) else {
+            warn!("recording ends in a partial frame");
+            break;
+        };
+        let frame = match header[8] {
+            0 => String::from_utf8(bytes.to_vec())
+                .map(Frame::Text)
+                .map_err(|_| invalid("text frame is not UTF-8".to_string()))?,
+            1 => Frame::Binary(bytes.to_vec()),
+            kind => return Err(invalid(format!("unknown frame kind {kind}"))),
+        };
+        frames.push((Duration::from_millis(millis), frame));
+        rest = &body[len..];
+    }
+    Ok(frames)
+}
+
+/// Plays the recording at `path` to `state` as its client would have, at
+/// the pace it was recorded, printing each reply as a line of JSON. Returns
+/// once the jobs it started are done, or at once if the recording cannot be
+/// read.
+pub async fn replay(state: AppState, path: &Path) -> io::Result<()> {
+    let frames = read(path)?;
+    let (out_tx, mut out_rx) = mpsc::channel(32);
+    let printer = tokio::spawn(async move {
+        while let Some(msg) = out_rx.recv().await {
+            match serde_json::to_string(&msg) {
+                Ok(json) => println!("{json}"),
+                Err(err) => warn!("cannot print a reply: {err}"),
+            }
+        }
+    });
+
+    let mut session = open_session(&state, &out_tx).await;
+    let started = tokio::time::Instant::now();
+    for (at, frame) in frames {
+        tokio::time::sleep_until(started + at).await;
+        handle_frame(&state, &mut session, frame, &out_tx).await;
+    }
+    loop {
+        {
+            let jobs = state.jobs.lock().unwrap();
+            if jobs.pending.is_empty() && jobs.running.is_empty() {
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    if session.joined {
+        leave(&state, &mut session);
+    }
+    drop(out_tx);
+    let _ = printer.await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A file of the temporary directory this test alone writes.
+    fn scratch(name: &str) -> PathBuf {
+        let name = format!("physalis-{}-{name}.rec", std::process::id());
+        std::env::temp_dir().join(name)
+    }
+
+    /// A recording of one text and one binary frame.
+    fn recording(name: &str) -> (PathBuf, Vec<Frame>) {
+        let path = scratch(name);
+        let frames = vec![
+            Frame::Text(r#"{"type":"hello"}"#.to_string()),
+            Frame::Binary(vec![0, 1, 2, 255]),
+        ];
+        let mut recorder = Recorder::create(&path).unwrap();
+        for frame in &frames {
+            recorder.record(frame).unwrap();
+        }
+        (path, frames)
+    }
+
+    #[test]
+    fn recorded_frames_read_back() {
+        let (path, frames) = recording("round-trip");
+        let read_back = read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let times: Vec<_> = read_back.iter().map(|(at, _)| *at).collect();
+        assert!(times.is_sorted());
+        let read_back: Vec<_> = read_back.into_iter().map(|(_, frame)| frame).collect();
+        assert_eq!(read_back, frames);
+    }
+
+    #[test]
+    fn partial_last_frame_is_dropped() {
+        let (path, frames) = recording("partial");
+        let data = std::fs::read(&path).unwrap();
+        // Cut into the last frame's body, then into its header.
+        for cut in [1, 4 + HEADER_LEN] {
+            std::fs::write(&path, &data[..data.len() - cut]).unwrap();
+            let read_back = read(&path).unwrap();
+            assert_eq!(read_back.len(), 1, "cut {cut}");
+            assert_eq!(read_back[0].1, frames[0]);
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn unknown_recordings_are_refused() {
+        let (path, _) = recording("unknown");
+        let data = std::fs::read(&path).unwrap();
+        let kind_at = MAGIC.len() + 1 + 8;
+        let mut version = data.clone();
+        version[MAGIC.len()] = VERSION + 1;
+        let mut kind = data.clone();
+        kind[kind_at] = 7;
+        let mut text = data;
+        // The first frame's body, no longer UTF-8.
+        text[kind_at + 5] = 0xff;
+        for (what, data) in [
+            ("magic", b"GIF89a".to_vec()),
+            ("version", version),
+            ("kind", kind),
+            ("text", text),
+        ] {
+            std::fs::write(&path, data).unwrap();
+            let err = read(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{what}");
+        }
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read(&path).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn tokens_are_redacted() {
+        let path = scratch("redacted");
+        let hello = ClientMsg::Hello {
+            client_version: "0.1".to_string(),
+            compression: Vec::new(),
+            encoding: vec![Encoding::MessagePack],
+            resume: Some("0f3a".to_string()),
+        };
+        let authenticate = ClientMsg::Authenticate {
+            token: "s3cret".to_string(),
+        };
+        let frames = [
+            Envelope::from(hello).encode(Encoding::MessagePack).unwrap(),
+            Envelope::new(authenticate, Some(2))
+                .encode(Encoding::Json)
+                .unwrap(),
+            Envelope::from(ClientMsg::Undo)
+                .encode(Encoding::Json)
+                .unwrap(),
+        ];
+        let mut recorder = Recorder::create(&path).unwrap();
+        for frame in &frames {
+            recorder.record(frame).unwrap();
+        }
+        let data = std::fs::read(&path).unwrap();
+        let read_back = read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let leaked = |token: &[u8]| data.windows(token.len()).any(|w| w == token);
+        assert!(!leaked(b"s3cret") && !leaked(b"0f3a"));
+        let msgs: Vec<_> = read_back
+            .iter()
+            .map(|(_, frame)| Envelope::<ClientMsg>::from_frame(frame).unwrap())
+            .collect();
+        assert!(matches!(
+            &msgs[0].msg,
+            ClientMsg::Hello { resume: Some(token), .. } if token == REDACTED
+        ));
+        assert!(matches!(
+            &msgs[1].msg,
+            ClientMsg::Authenticate { token } if token == REDACTED
+        ));
+        assert_eq!(msgs[1].request_id, Some(2));
+        assert_eq!(read_back[2].1, frames[2]);
+    }
+}
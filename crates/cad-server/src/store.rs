@@ -0,0 +1,537 @@
+//! Where documents outlive the server: their saved revisions, a thumbnail
+//! each, and the log of their edits since.
+
+use cad_core::{ClientId, LoadError, Model, ModelCommand, UndoStack};
+use cad_protocol::{DocumentId, RevisionId, RevisionSummary, Role};
+use rusqlite::{backup::Progress, params, Connection, OptionalExtension, MAIN_DB};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::Path, sync::Mutex};
+
+/// A stored document as listed, without its model.
+pub struct StoredDocument {
+    pub id: DocumentId,
+    pub name: String,
+    pub latest: RevisionSummary,
+    pub has_thumbnail: bool,
+}
+
+/// A change to a document's model and history, as logged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LoggedOp {
+    /// A new edit, recorded in the history.
+    Edit(ModelCommand),
+    Undo,
+    Redo,
+}
+
+/// A document as its log left it: the model and history at the last
+/// checkpoint, and the ops applied since, oldest first. The model keeps its
+/// client id, so replayed ops mint the stable ids they did before.
+pub struct Journal {
+    pub model: Model,
+    pub history: UndoStack,
+    pub ops: Vec<LoggedOp>,
+}
+
+pub trait DocumentStore: Send + Sync {
+    /// Stores `model` as the document's next revision and returns its
+    /// number; a `thumbnail` replaces the stored one.
+    fn save(
+        &self,
+        id: DocumentId,
+        name: &str,
+        model: &Model,
+        thumbnail: Option<&[u8]>,
+    ) -> Result<RevisionId, StoreError>;
+
+    /// The document's name and latest revision.
+    fn load(&self, id: DocumentId) -> Result<Option<(String, RevisionId, Model)>, StoreError>;
+
+    /// Documents whose name contains `query`, ignoring ASCII case, by id.
+    fn list(&self, query: &str) -> Result<Vec<StoredDocument>, StoreError>;
+
+    /// The document's revisions, oldest first.
+    fn revisions(&self, id: DocumentId) -> Result<Vec<RevisionSummary>, StoreError>;
+
+    /// The model the document had at `revision`.
+    fn load_revision(
+        &self,
+        id: DocumentId,
+        revision: RevisionId,
+    ) -> Result<Option<Model>, StoreError>;
+
+    fn thumbnail(&self, id: DocumentId) -> Result<Option<Vec<u8>>, StoreError>;
+
+    /// Replaces the document's thumbnail with `png`, a picture of
+    /// `revision`, unless a later revision was saved since.
+    fn set_thumbnail(
+        &self,
+        id: DocumentId,
+        revision: RevisionId,
+        png: &[u8],
+    ) -> Result<(), StoreError>;
+
+    /// Appends `op` to the document's log.
+    fn log_op(&self, id: DocumentId, op: &LoggedOp) -> Result<(), StoreError>;
+
+    /// Replaces the document's log with a checkpoint of `model` and
+    /// `history`.
+    fn checkpoint(
+        &self,
+        id: DocumentId,
+        model: &Model,
+        history: &UndoStack,
+    ) -> Result<(), StoreError>;
+
+    /// The document's log; `None` if it was never checkpointed.
+    fn journal(&self, id: DocumentId) -> Result<Option<Journal>, StoreError>;
+
+    /// Who has which role in the document.
+    fn roles(&self, id: DocumentId) -> Result<BTreeMap<String, Role>, StoreError>;
+
+    /// Replaces the document's roles.
+    fn set_roles(&self, id: DocumentId, roles: &BTreeMap<String, Role>) -> Result<(), StoreError>;
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+    Encode(serde_json::Error),
+    /// A stored revision this build cannot read.
+    Load(LoadError),
+    /// A logged op, history or role this build cannot read.
+    Log(serde_json::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sqlite(err) => write!(f, "document store: {err}"),
+            Self::Encode(err) => write!(f, "could not encode document: {err}"),
+            Self::Load(err) => write!(f, "stored {err}"),
+            Self::Log(err) => write!(f, "unreadable document log: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::Sqlite(err)
+    }
+}
+
+/// Models are kept as versioned JSON, which later builds migrate; see
+/// [`Model::from_json`].
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// A store that lives as long as it does, for tests and replays.
+    pub fn in_memory() -> Result<Self, StoreError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    /// An in-memory copy of the database at `path`, empty if there is none,
+    /// for work that must leave the database as it is.
+    pub fn copy_of(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let mut conn = Connection::open_in_memory()?;
+        if path.as_ref().exists() {
+            conn.restore(MAIN_DB, path, None::<fn(Progress)>)?;
+        }
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, StoreError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS documents (
+                 id INTEGER PRIMARY KEY,
+                 name TEXT NOT NULL,
+                 thumbnail BLOB
+             );
+             CREATE TABLE IF NOT EXISTS revisions (
+                 document INTEGER NOT NULL REFERENCES documents (id),
+                 revision INTEGER NOT NULL,
+                 saved_ms INTEGER NOT NULL,
+                 objects INTEGER NOT NULL,
+                 model TEXT NOT NULL,
+                 PRIMARY KEY (document, revision)
+             );
+             CREATE TABLE IF NOT EXISTS checkpoints (
+                 document INTEGER PRIMARY KEY REFERENCES documents (id),
+                 model TEXT NOT NULL,
+                 history TEXT NOT NULL,
+                 client INTEGER NOT NULL DEFAULT 0
+             );
+             CREATE TABLE IF NOT EXISTS ops (
+                 document INTEGER NOT NULL REFERENCES documents (id),
+                 seq INTEGER NOT NULL,
+                 op TEXT NOT NULL,
+                 PRIMARY KEY (document, seq)
+             );
+             CREATE TABLE IF NOT EXISTS roles (
+                 document INTEGER NOT NULL REFERENCES documents (id),
+                 user TEXT NOT NULL,
+                 role TEXT NOT NULL,
+                 PRIMARY KEY (document, user)
+             );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl DocumentStore for SqliteStore {
+    fn save(
+        &self,
+        id: DocumentId,
+        name: &str,
+        model: &Model,
+        thumbnail: Option<&[u8]>,
+    ) -> Result<RevisionId, StoreError> {
+        let json = model.to_json().map_err(StoreError::Encode)?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO documents (id, name, thumbnail) VALUES (?1, ?2, ?3)
+             ON CONFLICT (id) DO UPDATE
+             SET name = excluded.name, thumbnail = coalesce(excluded.thumbnail, thumbnail)",
+            params![id as i64, name, thumbnail],
+        )?;
+        let revision: i64 = tx.query_row(
+            "SELECT coalesce(max(revision), 0) + 1 FROM revisions WHERE document = ?1",
+            [id as i64],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "INSERT INTO revisions (document, revision, saved_ms, objects, model)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id as i64,
+                revision,
+                crate::unix_ms() as i64,
+                model.objects().len() as i64,
+                json
+            ],
+        )?;
+        tx.commit()?;
+        Ok(revision as RevisionId)
+    }
+
+    fn load(&self, id: DocumentId) -> Result<Option<(String, RevisionId, Model)>, StoreError> {
+        let stored = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT d.name, r.revision, r.model
+                 FROM documents d JOIN revisions r ON r.document = d.id
+                 WHERE d.id = ?1 ORDER BY r.revision DESC LIMIT 1",
+                [id as i64],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((name, revision, json)) = stored else {
+            return Ok(None);
+        };
+        let model = Model::from_json(&json).map_err(StoreError::Load)?;
+        Ok(Some((name, revision as RevisionId, model)))
+    }
+
+    fn list(&self, query: &str) -> Result<Vec<StoredDocument>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT d.id, d.name, d.thumbnail IS NOT NULL, r.revision, r.saved_ms, r.objects
+             FROM documents d JOIN revisions r ON r.document = d.id
+             WHERE r.revision = (SELECT max(revision) FROM revisions WHERE document = d.id)
+               AND instr(lower(d.name), lower(?1)) > 0
+             ORDER BY d.id",
+        )?;
+        let rows = stmt.query_map([query], |row| {
+            Ok(StoredDocument {
+                id: row.get::<_, i64>(0)? as DocumentId,
+                name: row.get(1)?,
+                has_thumbnail: row.get(2)?,
+                latest: RevisionSummary {
+                    revision: row.get::<_, i64>(3)? as RevisionId,
+                    saved_ms: row.get::<_, i64>(4)? as u64,
+                    objects: row.get::<_, i64>(5)? as usize,
+                },
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn revisions(&self, id: DocumentId) -> Result<Vec<RevisionSummary>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT revision, saved_ms, objects FROM revisions
+             WHERE document = ?1 ORDER BY revision",
+        )?;
+        let rows = stmt.query_map([id as i64], |row| {
+            Ok(RevisionSummary {
+                revision: row.get::<_, i64>(0)? as RevisionId,
+                saved_ms: row.get::<_, i64>(1)? as u64,
+                objects: row.get::<_, i64>(2)? as usize,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn load_revision(
+        &self,
+        id: DocumentId,
+        revision: RevisionId,
+    ) -> Result<Option<Model>, StoreError> {
+        let json = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT model FROM revisions WHERE document = ?1 AND revision = ?2",
+                params![id as i64, revision as i64],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        json.map(|json| Model::from_json(&json).map_err(StoreError::Load))
+            .transpose()
+    }
+
+    fn thumbnail(&self, id: DocumentId) -> Result<Option<Vec<u8>>, StoreError> {
+        let thumbnail = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT thumbnail FROM documents WHERE id = ?1",
+                [id as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(thumbnail.flatten())
+    }
+
+    fn set_thumbnail(
+        &self,
+        id: DocumentId,
+        revision: RevisionId,
+        png: &[u8],
+    ) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE documents SET thumbnail = ?3
+             WHERE id = ?1
+               AND ?2 = (SELECT max(revision) FROM revisions WHERE document = ?1)",
+            params![id as i64, revision as i64, png],
+        )?;
+        Ok(())
+    }
+
+    fn log_op(&self, id: DocumentId, op: &LoggedOp) -> Result<(), StoreError> {
+        let json = serde_json::to_string(op).map_err(StoreError::Encode)?;
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO ops (document, seq, op)
+             SELECT ?1, coalesce(max(seq), 0) + 1, ?2 FROM ops WHERE document = ?1",
+            params![id as i64, json],
+        )?;
+        Ok(())
+    }
+
+    fn checkpoint(
+        &self,
+        id: DocumentId,
+        model: &Model,
+        history: &UndoStack,
+    ) -> Result<(), StoreError> {
+        let client = model.client_id();
+        let model = model.to_json().map_err(StoreError::Encode)?;
+        let history = serde_json::to_string(history).map_err(StoreError::Encode)?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO checkpoints (document, model, history, client) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (document) DO UPDATE
+             SET model = excluded.model, history = excluded.history, client = excluded.client",
+            params![id as i64, model, history, client as i64],
+        )?;
+        tx.execute("DELETE FROM ops WHERE document = ?1", [id as i64])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn journal(&self, id: DocumentId) -> Result<Option<Journal>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let checkpoint = conn
+            .query_row(
+                "SELECT model, history, client FROM checkpoints WHERE document = ?1",
+                [id as i64],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((model, history, client)) = checkpoint else {
+            return Ok(None);
+        };
+        let mut model = Model::from_json(&model).map_err(StoreError::Load)?;
+        model.set_client_id(client as ClientId);
+        let mut stmt = conn.prepare("SELECT op FROM ops WHERE document = ?1 ORDER BY seq")?;
+        let ops = stmt
+            .query_map([id as i64], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(Journal {
+            model,
+            history: serde_json::from_str(&history).map_err(StoreError::Log)?,
+            ops: ops
+                .iter()
+                .map(|op| serde_json::from_str(op))
+                .collect::<Result<_, _>>()
+                .map_err(StoreError::Log)?,
+        }))
+    }
+
+    fn roles(&self, id: DocumentId) -> Result<BTreeMap<String, Role>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT user, role FROM roles WHERE document = ?1")?;
+        let rows = stmt
+            .query_map([id as i64], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(|(user, role)| {
+                let role = serde_json::from_value(role.into()).map_err(StoreError::Log)?;
+                Ok((user, role))
+            })
+            .collect()
+    }
+
+    fn set_roles(&self, id: DocumentId, roles: &BTreeMap<String, Role>) -> Result<(), StoreError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM roles WHERE document = ?1", [id as i64])?;
+        for (user, role) in roles {
+            let role = serde_json::to_value(role).map_err(StoreError::Encode)?;
+            tx.execute(
+                "INSERT INTO roles (document, user, role) VALUES (?1, ?2, ?3)",
+                params![id as i64, user, role.as_str()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cad_core::StableId;
+
+    /// A model of `boxes` boxes, minting as no client, as revisions load.
+    fn model(boxes: usize) -> Model {
+        let mut model = Model::default();
+        for i in 0..boxes {
+            model.add_box(1.0 + i as f32, 1.0, 1.0);
+        }
+        model.set_client_id(StableId::LEGACY_CLIENT);
+        model
+    }
+
+    #[test]
+    fn revisions_load_back_newest_first() {
+        let store = SqliteStore::in_memory().unwrap();
+        assert!(store.load(1).unwrap().is_none());
+        assert!(store.load_revision(1, 1).unwrap().is_none());
+        assert!(store.revisions(1).unwrap().is_empty());
+
+        let (first, second) = (model(1), model(2));
+        assert_eq!(store.save(1, "Bracket", &first, None).unwrap(), 1);
+        assert_eq!(store.save(1, "Bracket v2", &second, None).unwrap(), 2);
+        assert_eq!(store.save(2, "Other", &model(3), None).unwrap(), 1);
+
+        let (name, revision, latest) = store.load(1).unwrap().unwrap();
+        assert_eq!((name.as_str(), revision), ("Bracket v2", 2));
+        assert_eq!(latest, second);
+        assert_eq!(store.load_revision(1, 1).unwrap(), Some(first));
+        assert!(store.load_revision(1, 3).unwrap().is_none());
+        let revisions = store.revisions(1).unwrap();
+        let numbers: Vec<_> = revisions.iter().map(|r| (r.revision, r.objects)).collect();
+        assert_eq!(numbers, [(1, 1), (2, 2)]);
+        assert!(revisions[0].saved_ms <= revisions[1].saved_ms);
+    }
+
+    #[test]
+    fn documents_are_listed_by_name() {
+        let store = SqliteStore::in_memory().unwrap();
+        store.save(1, "Bracket", &model(1), None).unwrap();
+        store.save(2, "Gear housing", &model(1), None).unwrap();
+        store.save(2, "Gear housing", &model(2), None).unwrap();
+
+        let ids = |query| -> Vec<_> {
+            let listed = store.list(query).unwrap();
+            listed.iter().map(|doc| doc.id).collect()
+        };
+        assert_eq!(ids(""), [1, 2]);
+        assert_eq!(ids("GEAR"), [2]);
+        assert!(ids("shaft").is_empty());
+        let gear = &store.list("gear").unwrap()[0];
+        assert_eq!((gear.latest.revision, gear.latest.objects), (2, 2));
+    }
+
+    #[test]
+    fn thumbnails_follow_the_latest_revision() {
+        let store = SqliteStore::in_memory().unwrap();
+        assert!(store.thumbnail(1).unwrap().is_none());
+        store.save(1, "Part", &model(1), Some(b"first")).unwrap();
+        // A save without one keeps the stored thumbnail.
+        store.save(1, "Part", &model(1), None).unwrap();
+        assert_eq!(store.thumbnail(1).unwrap().as_deref(), Some(&b"first"[..]));
+        assert!(store.list("").unwrap()[0].has_thumbnail);
+
+        store.set_thumbnail(1, 2, b"drawn").unwrap();
+        assert_eq!(store.thumbnail(1).unwrap().as_deref(), Some(&b"drawn"[..]));
+        // Drawn for a revision that is no longer the latest.
+        store.set_thumbnail(1, 1, b"stale").unwrap();
+        assert_eq!(store.thumbnail(1).unwrap().as_deref(), Some(&b"drawn"[..]));
+    }
+    #[test]
+    fn copies_leave_the_database_alone() {
+        let path = std::env::temp_dir().join(format!("physalis-copy-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert!(SqliteStore::copy_of(&path)
+            .unwrap()
+            .list("")
+            .unwrap()
+            .is_empty());
+
+        SqliteStore::open(&path)
+            .unwrap()
+            .save(1, "Part", &model(1), None)
+            .unwrap();
+        let copy = SqliteStore::copy_of(&path).unwrap();
+        copy.save(1, "Part", &model(2), None).unwrap();
+        copy.save(2, "Bracket", &model(1), None).unwrap();
+        let original = SqliteStore::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(copy.revisions(1).unwrap().len(), 2);
+        assert_eq!(original.revisions(1).unwrap().len(), 1);
+        assert_eq!(original.list("").unwrap().len(), 1);
+    }
+}
@@ -57,6 +57,8 @@ pub enum IconName {
     Play,
     SkipForward,
     ChevronLeft,
+    Lock,
+    LockOpen,
 }
 
 fn icon_svg_body(name: IconName) -> &'static str {
@@ -299,6 +301,14 @@ fn icon_svg_body(name: IconName) -> &'static str {
 <path d="M6.029 4.285A2 2 0 0 0 3 6v12a2 2 0 0 0 3.029 1.715l9.997-5.998a2 2 0 0 0 .003-3.432z" />"#
         }
         IconName::ChevronLeft => r#"<path d="m15 18-6-6 6-6" />"#,
+        IconName::Lock => {
+            r#"<rect width="18" height="11" x="3" y="11" rx="2" ry="2" />
+<path d="M7 11V7a5 5 0 0 1 10 0v4" />"#
+        }
+        IconName::LockOpen => {
+            r#"<rect width="18" height="11" x="3" y="11" rx="2" ry="2" />
+<path d="M7 11V7a5 5 0 0 1 9.9-1" />"#
+        }
     }
 }
 
@@ -1,19 +1,24 @@
 use crate::ui_icons::{IconName, UiIcon};
-use cad_core::{ObjectId, Transform};
-use cad_geom::{GeomScene, SurfaceHit};
-use cad_protocol::{ClientMsg, ServerMsg};
+use cad_core::{Anchor, ComponentId, DocumentInfo, Model, ObjectId, SketchEntity, Transform};
+use cad_geom::{GeomError, GeomScene, Hatch, SurfaceHit, TriMesh};
+use cad_protocol::{
+    review_text, Capabilities, Chunker, ClientMsg, Compression, Encoding, Envelope, ErrorCode,
+    ExportContent, ExportFormat, Frame, ImportFormat, Reassembler, Role, Roster, ServerMsg,
+    SyncAction, SyncState, Throttle, UploadData, PEER_TIMEOUT_MS, PING_INTERVAL_MS,
+};
 use cad_render::{OverlayLine, Renderer};
 use glam::{EulerRot, Mat3, Quat, Vec3};
 use js_sys::Date;
 use leptos::html::Canvas;
 use leptos::prelude::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::{closure::Closure, JsCast};
 use wasm_bindgen_futures::spawn_local;
 use web_sys::{
-    CanvasRenderingContext2d, HtmlInputElement, KeyboardEvent, MessageEvent, MouseEvent, WebSocket,
+    BinaryType, CanvasRenderingContext2d, HtmlInputElement, KeyboardEvent, MessageEvent,
+    MouseEvent, WebSocket,
 };
 
 #[wasm_bindgen(start)]
@@ -53,7 +58,7 @@ struct UiShortcut {
 
 const TOP_TABS: [&str; 5] = ["Model", "Surface", "Mesh", "Sheet", "Tools"];
 
-const UI_COMMANDS: [UiCommand; 10] = [
+const UI_COMMANDS: [UiCommand; 15] = [
     UiCommand {
         id: "box",
         label: "Create Box",
@@ -90,6 +95,18 @@ const UI_COMMANDS: [UiCommand; 10] = [
         category: "Modify",
         shortcut: Some("Ctrl+S"),
     },
+    UiCommand {
+        id: "undo",
+        label: "Undo",
+        category: "Edit",
+        shortcut: Some("Ctrl+Z"),
+    },
+    UiCommand {
+        id: "redo",
+        label: "Redo",
+        category: "Edit",
+        shortcut: Some("Ctrl+Y"),
+    },
     UiCommand {
         id: "measure",
         label: "Measure Distance",
@@ -102,6 +119,30 @@ const UI_COMMANDS: [UiCommand; 10] = [
         category: "Inspect",
         shortcut: None,
     },
+    UiCommand {
+        id: "server_mesh",
+        label: "Fine Mesh on Server",
+        category: "Inspect",
+        shortcut: None,
+    },
+    UiCommand {
+        id: "comment",
+        label: "Add Comment",
+        category: "Review",
+        shortcut: None,
+    },
+    UiCommand {
+        id: "new_document",
+        label: "New Document",
+        category: "File",
+        shortcut: Some("Ctrl+N"),
+    },
+    UiCommand {
+        id: "save_document",
+        label: "Save Document",
+        category: "File",
+        shortcut: None,
+    },
     UiCommand {
         id: "import",
         label: "Import File",
@@ -202,6 +243,22 @@ fn ui_time_hms() -> String {
     )
 }
 
+/// `Feb 16, 2026 10:23`-style local time for Unix milliseconds.
+fn ui_date_time(ms: u64) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let date = Date::new(&JsValue::from_f64(ms as f64));
+    format!(
+        "{} {}, {} {:02}:{:02}",
+        MONTHS[date.get_month() as usize % 12],
+        date.get_date(),
+        date.get_full_year(),
+        date.get_hours(),
+        date.get_minutes()
+    )
+}
+
 fn command_icon(id: &str) -> IconName {
     match id {
         "box" => IconName::Box,
@@ -223,6 +280,16 @@ fn App() -> impl IntoView {
     let canvas_ref = NodeRef::<Canvas>::new();
     let viewcube_ref = NodeRef::<Canvas>::new();
     let scene = Rc::new(RefCell::new(GeomScene::new()));
+    {
+        let mut scene = scene.borrow_mut();
+        scene.set_client_id(random_client_id());
+        let now = Date::now() as u64;
+        scene.set_document_info(DocumentInfo {
+            created_ms: now,
+            modified_ms: now,
+            ..DocumentInfo::default()
+        });
+    }
     let renderer = Rc::new(RefCell::new(None::<Renderer>));
     let ws_handle = Rc::new(RefCell::new(None::<WebSocket>));
     let (renderer_ready, set_renderer_ready) = signal(false);
@@ -230,6 +297,11 @@ fn App() -> impl IntoView {
     let (plane_yz, set_plane_yz) = signal(false);
     let (plane_zx, set_plane_zx) = signal(false);
     let (object_count, set_object_count) = signal(0usize);
+    let (latency_ms, set_latency_ms) = signal(None::<u64>);
+    // What the server advertised; empty until it has said.
+    let (capabilities, set_capabilities) = signal(Capabilities::default());
+    // Server job queue: waiting and running jobs.
+    let (job_queue, set_job_queue) = signal((0usize, 0usize));
     let (object_ids, set_object_ids) = signal(Vec::<ObjectId>::new());
 
     let (tool_mode, set_tool_mode) = signal(EditorTool::None);
@@ -259,7 +331,8 @@ fn App() -> impl IntoView {
     let (expand_sketches, set_expand_sketches) = signal(true);
     let (expand_bodies, set_expand_bodies) = signal(true);
     let (expand_components, set_expand_components) = signal(true);
-    let (expand_component_1, set_expand_component_1) = signal(true);
+    // Bumped when per-object flags change so the browser tree redraws.
+    let (tree_revision, set_tree_revision) = signal(0u32);
     let (log_entries, set_log_entries) = signal(vec![
         UiLogEntry {
             level: UiLogLevel::Success,
@@ -353,7 +426,26 @@ fn App() -> impl IntoView {
         let ws_handle = ws_handle.clone();
         Effect::new(move |_| {
             if ws_handle.borrow().is_none() {
-                connect_ws(ws_handle.clone());
+                connect_ws(
+                    ws_handle.clone(),
+                    set_latency_ms,
+                    set_capabilities,
+                    set_job_queue,
+                    set_selected_id,
+                );
+            }
+        });
+    }
+    {
+        // Show other peers what this client has selected.
+        let ws_handle = ws_handle.clone();
+        Effect::new(move |_| {
+            let msg = ClientMsg::SetSelection {
+                selection: selected_id.get().into_iter().collect(),
+                cursor: None,
+            };
+            if let Some(ws) = ws_handle.borrow().as_ref() {
+                send_streamed(ws, msg);
             }
         });
     }
@@ -371,6 +463,7 @@ fn App() -> impl IntoView {
     {
         let scene = scene.clone();
         let renderer = renderer.clone();
+        let ws_handle = ws_handle.clone();
         let editor_attached = editor_attached.clone();
         let enter_sketch_draw_for_controls = enter_sketch_draw.clone();
         Effect::new(move |_| {
@@ -392,6 +485,7 @@ fn App() -> impl IntoView {
                 viewcube_canvas.clone(),
                 scene.clone(),
                 renderer.clone(),
+                ws_handle.clone(),
                 tool_mode,
                 set_tool_mode,
                 set_selected_id,
@@ -425,7 +519,16 @@ fn App() -> impl IntoView {
         Rc::new(move || {
             let id = {
                 let mut scene = scene.borrow_mut();
-                let id = scene.add_box(1.0, 1.0, 1.0);
+                let id = match scene.add_box(1.0, 1.0, 1.0) {
+                    Ok(id) => id,
+                    Err(err) => {
+                        (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            format!("Failed to create box: {err}"),
+                        );
+                        return;
+                    }
+                };
                 set_object_count.set(scene.model().objects().len());
                 id
             };
@@ -456,7 +559,16 @@ fn App() -> impl IntoView {
         Rc::new(move || {
             let id = {
                 let mut scene = scene.borrow_mut();
-                let id = scene.add_cylinder(0.5, 1.5);
+                let id = match scene.add_cylinder(0.5, 1.5) {
+                    Ok(id) => id,
+                    Err(err) => {
+                        (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            format!("Failed to create cylinder: {err}"),
+                        );
+                        return;
+                    }
+                };
                 set_object_count.set(scene.model().objects().len());
                 id
             };
@@ -473,6 +585,83 @@ fn App() -> impl IntoView {
         })
     };
 
+    let copy_selected_action: Rc<dyn Fn()> = {
+        let scene = scene.clone();
+        let renderer = renderer.clone();
+        let set_object_count = set_object_count;
+        let set_object_ids = set_object_ids;
+        let set_selected_id = set_selected_id;
+        let set_transform_ui = set_transform_ui;
+        let set_baseline_transform = set_baseline_transform;
+        let set_browser_selected = set_browser_selected;
+        let set_active_tool = set_active_tool;
+        let push_log = push_log.clone();
+        Rc::new(move || {
+            set_active_tool.set("copy".to_string());
+            let Some(source) = selected_id.get_untracked() else {
+                (push_log.as_ref())(UiLogLevel::Warning, "Select a body to copy".to_string());
+                return;
+            };
+            let copied = {
+                let mut scene = scene.borrow_mut();
+                let step = scene.bounds_radius(source).unwrap_or(0.5).max(0.25) * 2.0;
+                let copied = scene.duplicate_with_offset(source, [step, 0.0, 0.0]);
+                set_object_count.set(scene.model().objects().len());
+                copied
+            };
+            let Some(id) = copied else {
+                return;
+            };
+            set_object_ids.update(|ids| ids.push(id));
+            update_mesh(&scene, &renderer);
+            set_selected_id.set(Some(id));
+            set_browser_selected.set(format!("body-{}", id.saturating_add(1)));
+            if let Some(transform) = scene.borrow().object_transform(id) {
+                set_baseline_transform.set(Some(transform));
+                set_transform_ui.set(TransformUi::from_transform(transform));
+            }
+            (push_log.as_ref())(
+                UiLogLevel::Success,
+                format!("Body {} copied to Body {}", source + 1, id + 1),
+            );
+        })
+    };
+
+    let delete_selected_action: Rc<dyn Fn()> = {
+        let scene = scene.clone();
+        let renderer = renderer.clone();
+        let set_object_count = set_object_count;
+        let set_object_ids = set_object_ids;
+        let set_selected_id = set_selected_id;
+        let set_baseline_transform = set_baseline_transform;
+        let set_active_tool = set_active_tool;
+        let push_log = push_log.clone();
+        Rc::new(move || {
+            set_active_tool.set("delete".to_string());
+            let Some(id) = selected_id.get_untracked() else {
+                (push_log.as_ref())(UiLogLevel::Warning, "Select a body to delete".to_string());
+                return;
+            };
+            let removed = {
+                let mut scene = scene.borrow_mut();
+                let removed = scene.delete_object(id);
+                set_object_count.set(scene.model().objects().len());
+                removed
+            };
+            if !removed {
+                return;
+            }
+            set_object_ids.update(|ids| ids.retain(|other| *other != id));
+            set_selected_id.set(None);
+            set_baseline_transform.set(None);
+            update_mesh(&scene, &renderer);
+            (push_log.as_ref())(
+                UiLogLevel::Success,
+                format!("Body {} moved to trash", id + 1),
+            );
+        })
+    };
+
     let activate_move_tool: Rc<dyn Fn()> = {
         let set_active_tool = set_active_tool;
         let set_tool_mode = set_tool_mode;
@@ -538,19 +727,29 @@ fn App() -> impl IntoView {
         let next_sketch_id = next_sketch_id;
         let set_next_sketch_id = set_next_sketch_id;
         let set_browser_selected = set_browser_selected;
+        let scene = scene.clone();
         let push_log = push_log.clone();
         Rc::new(move || {
-            if sketch_plane.get_untracked().is_some() {
+            if let Some(plane) = sketch_plane.get_untracked() {
                 let sketch_id = next_sketch_id.get_untracked();
                 let name = format!("Sketch {sketch_id}");
                 let plane_label = sketch_plane_name.get_untracked();
                 let segments = sketch_segments.get_untracked();
+                let plane = plane.to_core();
+                let entities = segments
+                    .iter()
+                    .map(|seg| SketchEntity::Line {
+                        a: plane.to_plane(seg.a.to_array()),
+                        b: plane.to_plane(seg.b.to_array()),
+                    })
+                    .collect();
+                scene.borrow_mut().add_sketch(name.clone(), plane, entities);
                 set_saved_sketches.update(|items| {
                     items.push(SavedSketch {
                         id: sketch_id,
                         name: name.clone(),
                         plane_label: plane_label.clone(),
-                        segments: segments.clone(),
+                        segment_count: segments.len(),
                     });
                 });
                 set_next_sketch_id.set(sketch_id + 1);
@@ -615,15 +814,33 @@ fn App() -> impl IntoView {
         }
     };
 
+    let show_section_action: Rc<dyn Fn()> = {
+        let scene = scene.clone();
+        let renderer = renderer.clone();
+        let set_active_tool = set_active_tool;
+        let push_log = push_log.clone();
+        Rc::new(move || {
+            set_active_tool.set("section".to_string());
+            let loops = show_section(&scene, &renderer);
+            if loops == 0 {
+                (push_log.as_ref())(UiLogLevel::Warning, "Nothing to section".to_string());
+            } else {
+                (push_log.as_ref())(UiLogLevel::Info, format!("Section: {loops} loop(s)"));
+            }
+        })
+    };
+
     {
         let add_box_action = add_box_action.clone();
         let add_cylinder_action = add_cylinder_action.clone();
+        let show_section_action = show_section_action.clone();
         let activate_move_tool = activate_move_tool.clone();
         let activate_select_tool = activate_select_tool.clone();
         let set_show_palette = set_show_palette;
         let set_pending_command = set_pending_command;
         let set_active_tool = set_active_tool;
         let push_log = push_log.clone();
+        let ws_handle = ws_handle.clone();
         Effect::new(move |_| {
             let Some(command_id) = pending_command.get() else {
                 return;
@@ -640,24 +857,162 @@ fn App() -> impl IntoView {
                 }
                 "export" => {
                     set_active_tool.set("export".to_string());
-                    (push_log.as_ref())(
-                        UiLogLevel::Warning,
-                        "Export command is not implemented yet".to_string(),
-                    );
+                    let supported = capabilities
+                        .with_untracked(|caps| caps.export_formats.contains(&ExportFormat::Stl));
+                    if !supported {
+                        (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            "The server cannot export STL".to_string(),
+                        );
+                        set_show_palette.set(false);
+                        set_pending_command.set(None);
+                        return;
+                    }
+                    // The server exports the selection, or every visible
+                    // body, and sends the file back as a download.
+                    let msg = ClientMsg::RequestExport {
+                        format: ExportFormat::Stl,
+                        objects: selected_id.get_untracked().into_iter().collect(),
+                    };
+                    let ws = ws_handle.borrow();
+                    let sent = match (ws.as_ref(), serde_json::to_string(&msg)) {
+                        (Some(ws), Ok(text)) => ws.send_with_str(&text).is_ok(),
+                        _ => false,
+                    };
+                    if sent {
+                        (push_log.as_ref())(
+                            UiLogLevel::Info,
+                            "STL export requested from the server".to_string(),
+                        );
+                    } else {
+                        (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            "Export needs a server connection".to_string(),
+                        );
+                    }
                 }
-                "section" => {
-                    set_active_tool.set("section".to_string());
-                    (push_log.as_ref())(
-                        UiLogLevel::Info,
-                        "Section mode is not connected yet".to_string(),
-                    );
+                "server_mesh" => {
+                    // Large models tessellate on the server instead of the
+                    // main thread; meshes come back as binary frames.
+                    let msg = ClientMsg::RequestMesh {
+                        objects: selected_id.get_untracked().into_iter().collect(),
+                        tolerance: None,
+                    };
+                    let ws = ws_handle.borrow();
+                    let sent = match (ws.as_ref(), serde_json::to_string(&msg)) {
+                        (Some(ws), Ok(text)) => ws.send_with_str(&text).is_ok(),
+                        _ => false,
+                    };
+                    if !sent {
+                        (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            "Server meshing needs a server connection".to_string(),
+                        );
+                    }
+                }
+                "new_document" => {
+                    // The server opens it for this client straight away.
+                    let msg = ClientMsg::CreateDocument {
+                        name: "Untitled".to_string(),
+                    };
+                    let ws = ws_handle.borrow();
+                    let sent = match (ws.as_ref(), serde_json::to_string(&msg)) {
+                        (Some(ws), Ok(text)) => ws.send_with_str(&text).is_ok(),
+                        _ => false,
+                    };
+                    if !sent {
+                        (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            "New documents need a server connection".to_string(),
+                        );
+                    }
+                }
+                "save_document" => {
+                    if !capabilities.with_untracked(|caps| caps.storage) {
+                        (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            "The server does not store documents".to_string(),
+                        );
+                        set_show_palette.set(false);
+                        set_pending_command.set(None);
+                        return;
+                    }
+                    let msg = ClientMsg::SaveDocument { thumbnail: None };
+                    let ws = ws_handle.borrow();
+                    let sent = match (ws.as_ref(), serde_json::to_string(&msg)) {
+                        (Some(ws), Ok(text)) => ws.send_with_str(&text).is_ok(),
+                        _ => false,
+                    };
+                    if !sent {
+                        (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            "Saving needs a server connection".to_string(),
+                        );
+                    }
+                }
+                "undo" | "redo" => {
+                    // History lives on the server, shared by every client.
+                    let msg = if command_id == "undo" {
+                        ClientMsg::Undo
+                    } else {
+                        ClientMsg::Redo
+                    };
+                    let ws = ws_handle.borrow();
+                    let sent = match (ws.as_ref(), serde_json::to_string(&msg)) {
+                        (Some(ws), Ok(text)) => ws.send_with_str(&text).is_ok(),
+                        _ => false,
+                    };
+                    if !sent {
+                        (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            "Undo needs a server connection".to_string(),
+                        );
+                    }
+                }
+                "section" => (show_section_action.as_ref())(),
+                "comment" => {
+                    // Pinned to the selected body, so it follows the body
+                    // around, or else to the origin.
+                    let anchor = match selected_id.get_untracked() {
+                        Some(object) => Anchor::Object {
+                            object,
+                            local: [0.0; 3],
+                        },
+                        None => Anchor::World([0.0; 3]),
+                    };
+                    let text = web_sys::window()
+                        .and_then(|window| window.prompt_with_message("Comment").ok().flatten());
+                    if let Some(text) = text.filter(|text| review_text(text).is_some()) {
+                        let msg = ClientMsg::AddComment { anchor, text };
+                        let ws = ws_handle.borrow();
+                        let sent = match (ws.as_ref(), serde_json::to_string(&msg)) {
+                            (Some(ws), Ok(text)) => ws.send_with_str(&text).is_ok(),
+                            _ => false,
+                        };
+                        if !sent {
+                            (push_log.as_ref())(
+                                UiLogLevel::Warning,
+                                "Comments need a server connection".to_string(),
+                            );
+                        }
+                    }
                 }
                 "import" => {
                     set_active_tool.set("import".to_string());
-                    (push_log.as_ref())(
-                        UiLogLevel::Info,
-                        "Import is not connected yet".to_string(),
-                    );
+                    // The server reads the file and adds its bodies to the
+                    // document.
+                    let ws = ws_handle.borrow().clone();
+                    match ws.map(pick_and_upload) {
+                        Some(Ok(())) => {}
+                        Some(Err(err)) => (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            format!("Import failed: {err:?}"),
+                        ),
+                        None => (push_log.as_ref())(
+                            UiLogLevel::Warning,
+                            "Import needs a server connection".to_string(),
+                        ),
+                    }
                 }
                 "rotate" => {
                     set_active_tool.set("rotate".to_string());
@@ -748,6 +1103,24 @@ fn App() -> impl IntoView {
         });
     }
 
+    let browser_scene = scene.clone();
+    let browser_renderer = renderer.clone();
+    let component_scene = scene.clone();
+    let info_scene = scene.clone();
+    // Re-read the document info whenever the model visibly changes.
+    let document_info = move || {
+        object_ids.track();
+        tree_revision.track();
+        info_scene.borrow().model().info().clone()
+    };
+    let stats_scene = scene.clone();
+    let document_stats = move || {
+        object_ids.track();
+        tree_revision.track();
+        stats_scene.borrow().model().stats()
+    };
+    let status_stats = document_stats.clone();
+
     view! {
         <div class="cad-shell">
             <div class="cad-topbar">
@@ -877,23 +1250,15 @@ fn App() -> impl IntoView {
                             <span class="ribbon-label">"Scale"</span>
                         </button>
                         <button class="ribbon-tool" class:active=move || active_tool.get() == "copy" on:click={
-                            let set_active_tool = set_active_tool;
-                            let push_log = push_log.clone();
-                            move |_| {
-                                set_active_tool.set("copy".to_string());
-                                (push_log.as_ref())(UiLogLevel::Info, "Copy tool is not connected yet".to_string());
-                            }
+                            let copy_selected_action = copy_selected_action.clone();
+                            move |_| (copy_selected_action.as_ref())()
                         }>
                             <UiIcon name=IconName::Copy size=20 class="ribbon-icon" />
                             <span class="ribbon-label">"Copy"</span>
                         </button>
                         <button class="ribbon-tool" class:active=move || active_tool.get() == "delete" on:click={
-                            let set_active_tool = set_active_tool;
-                            let push_log = push_log.clone();
-                            move |_| {
-                                set_active_tool.set("delete".to_string());
-                                (push_log.as_ref())(UiLogLevel::Warning, "Delete tool is not connected yet".to_string());
-                            }
+                            let delete_selected_action = delete_selected_action.clone();
+                            move |_| (delete_selected_action.as_ref())()
                         }>
                             <UiIcon name=IconName::Trash2 size=20 class="ribbon-icon" />
                             <span class="ribbon-label">"Delete"</span>
@@ -979,8 +1344,8 @@ fn App() -> impl IntoView {
                             <span class="ribbon-label">"Analyze"</span>
                         </button>
                         <button class="ribbon-tool" class:active=move || active_tool.get() == "section" on:click={
-                            let set_active_tool = set_active_tool;
-                            move |_| set_active_tool.set("section".to_string())
+                            let show_section_action = show_section_action.clone();
+                            move |_| (show_section_action.as_ref())()
                         }>
                             <UiIcon name=IconName::Eye size=20 class="ribbon-icon" />
                             <span class="ribbon-label">"Section"</span>
@@ -990,10 +1355,12 @@ fn App() -> impl IntoView {
                 <div class="ribbon-group">
                     <div class="ribbon-title">"INSERT"</div>
                     <div class="ribbon-tools">
-                        <button class="ribbon-tool" class:active=move || active_tool.get() == "import" on:click={
-                            let set_active_tool = set_active_tool;
-                            move |_| set_active_tool.set("import".to_string())
-                        }>
+                        <button
+                            class="ribbon-tool"
+                            class:active=move || active_tool.get() == "import"
+                            disabled=move || capabilities.with(|caps| caps.import_formats.is_empty())
+                            on:click=move |_| set_pending_command.set(Some("import".to_string()))
+                        >
                             <UiIcon name=IconName::File size=20 class="ribbon-icon" />
                             <span class="ribbon-label">"Import"</span>
                         </button>
@@ -1048,7 +1415,7 @@ fn App() -> impl IntoView {
                         <input
                             class="browser-input"
                             type="text"
-                            placeholder="Search browser..."
+                            placeholder="Search bodies, tag:, kind:, layer:"
                             prop:value=move || browser_search.get()
                             on:input=move |ev| set_browser_search.set(event_target_value(&ev))
                         />
@@ -1138,7 +1505,7 @@ fn App() -> impl IntoView {
                                             let label = format!(
                                                 "{} · {} seg · {}",
                                                 item.name,
-                                                item.segments.len(),
+                                                item.segment_count,
                                                 item.plane_label
                                             );
                                             view! {
@@ -1179,33 +1546,104 @@ fn App() -> impl IntoView {
                         </div>
                         <Show when=move || expand_bodies.get()>
                             <div class="tree-children">
-                                {move || {
+                                {
+                                    let scene = browser_scene.clone();
+                                    let renderer = browser_renderer.clone();
+                                    move || {
+                                    tree_revision.track();
+                                    let search = browser_search.get();
+                                    let matches = (!search.trim().is_empty()).then(|| {
+                                        let scene = scene.borrow();
+                                        let model = scene.model();
+                                        model.query(&model.parse_query(&search))
+                                    });
                                     object_ids
                                         .get()
                                         .into_iter()
                                         .enumerate()
+                                        .filter(|(_, object_id)| {
+                                            matches.as_ref().map_or(true, |ids| ids.contains(object_id))
+                                        })
                                         .map(|(idx, object_id)| {
                                             let row_id = format!("body-{}", idx + 1);
+                                            let (name, visible, locked) = scene
+                                                .borrow()
+                                                .model()
+                                                .object(object_id)
+                                                .map(|obj| (obj.display_name(), obj.visible, obj.locked))
+                                                .unwrap_or_else(|| (format!("Body {}", idx + 1), true, false));
                                             let row_id_for_class = row_id.clone();
+                                            let visible_title = if visible { "Hide" } else { "Show" };
+                                            let locked_title = if locked { "Unlock" } else { "Lock" };
+                                            let toggle_visible = {
+                                                let scene = scene.clone();
+                                                let renderer = renderer.clone();
+                                                move |_| {
+                                                    scene.borrow_mut().set_object_visible(object_id, !visible);
+                                                    if visible && selected_id.get_untracked() == Some(object_id) {
+                                                        set_selected_id.set(None);
+                                                    }
+                                                    update_mesh(&scene, &renderer);
+                                                    set_tree_revision.update(|rev| *rev += 1);
+                                                }
+                                            };
+                                            let toggle_locked = {
+                                                let scene = scene.clone();
+                                                move |_| {
+                                                    scene.borrow_mut().set_object_locked(object_id, !locked);
+                                                    if !locked && selected_id.get_untracked() == Some(object_id) {
+                                                        set_selected_id.set(None);
+                                                    }
+                                                    set_tree_revision.update(|rev| *rev += 1);
+                                                }
+                                            };
                                             view! {
-                                                <button
+                                                <div
                                                     class="tree-row tree-leaf"
                                                     class:selected=move || browser_selected.get() == row_id_for_class
-                                                    on:click={
-                                                        let row_id = row_id.clone();
-                                                        move |_| {
-                                                            set_browser_selected.set(row_id.clone());
-                                                            set_selected_id.set(Some(object_id));
-                                                        }
-                                                    }
+                                                    class:hidden-body=move || !visible
                                                 >
-                                                    <UiIcon name=IconName::Box size=16 class="tree-icon" />
-                                                    <span class="tree-text">{format!("Body {}", idx + 1)}</span>
-                                                </button>
+                                                    <button
+                                                        class="tree-main-btn"
+                                                        on:click={
+                                                            let row_id = row_id.clone();
+                                                            move |_| {
+                                                                set_browser_selected.set(row_id.clone());
+                                                                set_selected_id.set(Some(object_id));
+                                                            }
+                                                        }
+                                                    >
+                                                        <UiIcon name=IconName::Box size=16 class="tree-icon" />
+                                                        <span class="tree-text">{name}</span>
+                                                    </button>
+                                                    <button
+                                                        class="small-icon-btn"
+                                                        title=visible_title
+                                                        on:click=toggle_visible
+                                                    >
+                                                        {if visible {
+                                                            view! { <UiIcon name=IconName::Eye size=14 class="small-icon" /> }
+                                                        } else {
+                                                            view! { <UiIcon name=IconName::EyeOff size=14 class="small-icon" /> }
+                                                        }}
+                                                    </button>
+                                                    <button
+                                                        class="small-icon-btn"
+                                                        title=locked_title
+                                                        on:click=toggle_locked
+                                                    >
+                                                        {if locked {
+                                                            view! { <UiIcon name=IconName::Lock size=14 class="small-icon" /> }
+                                                        } else {
+                                                            view! { <UiIcon name=IconName::LockOpen size=14 class="small-icon" /> }
+                                                        }}
+                                                    </button>
+                                                </div>
                                             }
                                         })
                                         .collect_view()
-                                }}
+                                    }
+                                }
                             </div>
                         </Show>
 
@@ -1226,26 +1664,47 @@ fn App() -> impl IntoView {
                         </div>
                         <Show when=move || expand_components.get()>
                             <div class="tree-children">
-                                <div class="tree-row tree-group">
-                                    <button class="tree-toggle" on:click=move |_| set_expand_component_1.update(|v| *v = !*v)>
-                                        {move || {
-                                            if expand_component_1.get() {
-                                                view! { <UiIcon name=IconName::ChevronDown size=14 class="tree-toggle-icon" /> }
-                                            } else {
-                                                view! { <UiIcon name=IconName::ChevronRight size=14 class="tree-toggle-icon" /> }
+                                {
+                                    let scene = component_scene.clone();
+                                    move || {
+                                    object_ids.track();
+                                    tree_revision.track();
+                                    let rows = component_rows(scene.borrow().model());
+                                    if rows.is_empty() {
+                                        return view! {
+                                            <div class="tree-empty">"No components"</div>
+                                        }
+                                            .into_any();
+                                    }
+                                    rows.into_iter()
+                                        .map(|row| {
+                                            let indent = format!("{}px", row.depth * 16);
+                                            match row.object {
+                                                Some(object_id) => view! {
+                                                    <button
+                                                        class="tree-row tree-leaf"
+                                                        style:margin-left=indent
+                                                        class:selected=move || selected_id.get() == Some(object_id)
+                                                        on:click=move |_| set_selected_id.set(Some(object_id))
+                                                    >
+                                                        <UiIcon name=IconName::Box size=16 class="tree-icon" />
+                                                        <span class="tree-text">{row.label}</span>
+                                                    </button>
+                                                }
+                                                    .into_any(),
+                                                None => view! {
+                                                    <div class="tree-row tree-group" style:margin-left=indent>
+                                                        <UiIcon name=IconName::Folder size=16 class="tree-icon" />
+                                                        <span class="tree-text">{row.label}</span>
+                                                    </div>
+                                                }
+                                                    .into_any(),
                                             }
-                                        }}
-                                    </button>
-                                    <UiIcon name=IconName::Folder size=16 class="tree-icon" />
-                                    <span class="tree-text">"Component 1"</span>
-                                </div>
-                                <Show when=move || expand_component_1.get()>
-                                    <div class="tree-children">
-                                        <button class="tree-row tree-leaf">"Part A"</button>
-                                        <button class="tree-row tree-leaf">"Part B"</button>
-                                    </div>
-                                </Show>
-                                <button class="tree-row tree-leaf">"Component 2"</button>
+                                        })
+                                        .collect_view()
+                                        .into_any()
+                                    }
+                                }
                             </div>
                         </Show>
                     </div>
@@ -1279,7 +1738,11 @@ fn App() -> impl IntoView {
                         <button class="nav-tool" title="Zoom Out">
                             <UiIcon name=IconName::ZoomOut size=20 class="nav-icon" />
                         </button>
-                        <button class="nav-tool" title="Fit View">
+                        <button class="nav-tool" title="Fit View" on:click={
+                            let scene = scene.clone();
+                            let renderer = renderer.clone();
+                            move |_| fit_view(&scene, &renderer)
+                        }>
                             <UiIcon name=IconName::Maximize2 size=20 class="nav-icon" />
                         </button>
                     </div>
@@ -1326,6 +1789,34 @@ fn App() -> impl IntoView {
                             }>
                                 "YZ Plane"
                             </button>
+                            {
+                                let scene = browser_scene.clone();
+                                let enter_sketch_draw = enter_sketch_draw.clone();
+                                move || {
+                                    tree_revision.track();
+                                    // The origin planes come first and have their own buttons.
+                                    scene
+                                        .borrow()
+                                        .model()
+                                        .sketch_planes()
+                                        .into_iter()
+                                        .skip(3)
+                                        .map(|(name, plane)| {
+                                            let enter_sketch_draw = enter_sketch_draw.clone();
+                                            let plane = SketchPlane::from_core(&plane);
+                                            let label = name.clone();
+                                            view! {
+                                                <button
+                                                    class="sketch-plane-btn"
+                                                    on:click=move |_| (enter_sketch_draw.as_ref())(plane, label.clone())
+                                                >
+                                                    {name}
+                                                </button>
+                                            }
+                                        })
+                                        .collect_view()
+                                }
+                            }
                         </div>
                         <div class="sketch-prompt-foot">
                             <button class="sketch-cancel-btn" on:click={
@@ -1385,11 +1876,12 @@ fn App() -> impl IntoView {
                             on_change={
                                 let scene = scene.clone();
                                 let renderer = renderer.clone();
+                                let ws_handle = ws_handle.clone();
                                 Rc::new(move |ui| {
                                     set_transform_ui.set(ui);
                                     if let Some(id) = selected_id.get_untracked() {
                                         let t = ui.to_transform();
-                                        apply_transform(&scene, &renderer, id, t);
+                                        apply_transform(&scene, &renderer, &ws_handle, id, t);
                                         update_overlay(
                                             &scene,
                                             &renderer,
@@ -1414,6 +1906,7 @@ fn App() -> impl IntoView {
                             on_cancel={
                                 let scene = scene.clone();
                                 let renderer = renderer.clone();
+                                let ws_handle = ws_handle.clone();
                                 let activate_select_tool = activate_select_tool.clone();
                                 Rc::new(move || {
                                     let Some(id) = selected_id.get_untracked() else {
@@ -1422,7 +1915,7 @@ fn App() -> impl IntoView {
                                     let Some(base) = baseline_transform.get_untracked() else {
                                         return;
                                     };
-                                    apply_transform(&scene, &renderer, id, base);
+                                    apply_transform(&scene, &renderer, &ws_handle, id, base);
                                     set_transform_ui.set(TransformUi::from_transform(base));
                                     update_overlay(
                                         &scene,
@@ -1442,11 +1935,25 @@ fn App() -> impl IntoView {
                             <span>"•"</span>
                             <span class="status-ok">"Snap: On"</span>
                             <span>"•"</span>
-                            <span>"Units: mm"</span>
+                            <span>{format!("Units: {}", scene.borrow().unit().symbol())}</span>
                         </div>
                         <div class="status-right">
                             <span>{move || format!("Objects: {}", object_count.get())}</span>
                             <span>"•"</span>
+                            <span>{move || match latency_ms.get() {
+                                Some(ms) => format!("Ping: {ms} ms"),
+                                None => "Ping: –".to_string(),
+                            }}</span>
+                            <span>"•"</span>
+                            <span>{move || match job_queue.get() {
+                                (0, 0) => "Jobs: idle".to_string(),
+                                (pending, running) => {
+                                    format!("Jobs: {running} running, {pending} waiting")
+                                }
+                            }}</span>
+                            <span>"•"</span>
+                            <span>{move || format!("Triangles: ~{}", status_stats().triangles)}</span>
+                            <span>"•"</span>
                             <span>{move || {
                                 match tool_mode.get() {
                                     EditorTool::Move => "Tool: Move".to_string(),
@@ -1752,24 +2259,43 @@ fn App() -> impl IntoView {
                     <div class="project-row">
                         <UiIcon name=IconName::Package size=14 class="project-row-icon" />
                         <span class="project-row-label">"Project Name"</span>
-                        <span class="project-row-value">"Mechanical Assembly v2"</span>
+                        <span class="project-row-value">
+                            {let document_info = document_info.clone(); move || document_info().title}
+                        </span>
                     </div>
                     <div class="project-row">
                         <UiIcon name=IconName::User size=14 class="project-row-icon" />
                         <span class="project-row-label">"Created by"</span>
-                        <span class="project-row-value">"Design Engineer"</span>
+                        <span class="project-row-value">
+                            {let document_info = document_info.clone();
+                            move || {
+                                let author = document_info().author;
+                                if author.is_empty() { "Unknown".to_string() } else { author }
+                            }}
+                        </span>
                     </div>
                     <div class="project-row">
                         <UiIcon name=IconName::Calendar size=14 class="project-row-icon" />
                         <span class="project-row-label">"Last Modified"</span>
-                        <span class="project-row-value">"Feb 16, 2026 10:23"</span>
+                        <span class="project-row-value">
+                            {let document_info = document_info.clone();
+                            move || {
+                                let info = document_info();
+                                format!("{} (rev {})", ui_date_time(info.modified_ms), info.revision)
+                            }}
+                        </span>
                     </div>
                     <div class="project-foot">
-                        <span>"10 Features"</span>
-                        <span>"•"</span>
-                        <span>"3 Components"</span>
-                        <span>"•"</span>
-                        <span>"2 Bodies"</span>
+                        {move || {
+                            let stats = document_stats();
+                            view! {
+                                <span>{format!("{} Features", stats.features)}</span>
+                                <span>"•"</span>
+                                <span>{format!("{} Components", stats.components)}</span>
+                                <span>"•"</span>
+                                <span>{format!("{} Bodies", stats.objects)}</span>
+                            }
+                        }}
                     </div>
                 </div>
             </Show>
@@ -1777,6 +2303,58 @@ fn App() -> impl IntoView {
     }
 }
 
+/// Fresh non-zero client id for stamping stable object ids this session.
+fn random_client_id() -> u64 {
+    let half = || (js_sys::Math::random() * u32::MAX as f64) as u64;
+    ((half() << 32) | half()).max(1)
+}
+
+/// One line of the browser's component tree.
+struct ComponentRow {
+    depth: usize,
+    label: String,
+    /// The body shown on this line; `None` for component lines.
+    object: Option<ObjectId>,
+}
+
+/// Components depth-first, each followed by its bodies.
+fn component_rows(model: &Model) -> Vec<ComponentRow> {
+    let mut rows = Vec::new();
+    push_component_rows(model, None, 0, &mut rows);
+    rows
+}
+
+fn push_component_rows(
+    model: &Model,
+    parent: Option<ComponentId>,
+    depth: usize,
+    rows: &mut Vec<ComponentRow>,
+) {
+    // Guards against parent cycles in hand-edited files.
+    if depth > model.components().len() {
+        return;
+    }
+    for component in model.components().iter().filter(|c| c.parent == parent) {
+        rows.push(ComponentRow {
+            depth,
+            label: component.name.clone(),
+            object: None,
+        });
+        push_component_rows(model, Some(component.id), depth + 1, rows);
+        for obj in model
+            .objects()
+            .iter()
+            .filter(|obj| obj.parent == Some(component.id))
+        {
+            rows.push(ComponentRow {
+                depth: depth + 1,
+                label: obj.display_name(),
+                object: Some(obj.id),
+            });
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum EditorTool {
     None,
@@ -1800,6 +2378,21 @@ struct SketchPlane {
     v: Vec3,
 }
 
+impl SketchPlane {
+    fn from_core(plane: &cad_core::SketchPlane) -> Self {
+        Self {
+            origin: Vec3::from_array(plane.origin),
+            normal: Vec3::from_array(plane.normal),
+            u: Vec3::from_array(plane.u),
+            v: Vec3::from_array(plane.v),
+        }
+    }
+
+    fn to_core(self) -> cad_core::SketchPlane {
+        cad_core::SketchPlane::new(self.origin.to_array(), self.u.to_array(), self.v.to_array())
+    }
+}
+
 #[derive(Clone, Copy)]
 struct SketchSegment {
     a: Vec3,
@@ -1811,7 +2404,7 @@ struct SavedSketch {
     id: usize,
     name: String,
     plane_label: String,
-    segments: Vec<SketchSegment>,
+    segment_count: usize,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -1851,6 +2444,7 @@ struct TransformUi {
     rx_deg: f32,
     ry_deg: f32,
     rz_deg: f32,
+    scale: [f32; 3],
 }
 
 impl Default for TransformUi {
@@ -1862,13 +2456,14 @@ impl Default for TransformUi {
             rx_deg: 0.0,
             ry_deg: 0.0,
             rz_deg: 0.0,
+            scale: [1.0, 1.0, 1.0],
         }
     }
 }
 
 impl TransformUi {
     fn from_transform(transform: Transform) -> Self {
-        let q = quat_from_transform(transform);
+        let q = transform.rotation_quat();
         let (rx, ry, rz) = q.to_euler(EulerRot::XYZ);
         Self {
             tx: transform.translation[0],
@@ -1877,6 +2472,7 @@ impl TransformUi {
             rx_deg: rx.to_degrees(),
             ry_deg: ry.to_degrees(),
             rz_deg: rz.to_degrees(),
+            scale: transform.scale,
         }
     }
 
@@ -1890,7 +2486,8 @@ impl TransformUi {
         .normalize();
         Transform {
             translation: [self.tx, self.ty, self.tz],
-            rotation: [q.x, q.y, q.z, q.w],
+            rotation: q.to_array(),
+            scale: self.scale,
         }
     }
 }
@@ -2604,6 +3201,67 @@ fn update_sketch_overlay(
     renderer.render();
 }
 
+/// Camera distance per unit of bounding-sphere radius when fitting the view;
+/// enough to frame the sphere in the 45 degree field of view with a margin.
+const FIT_VIEW_DISTANCE: f32 = 2.8;
+
+fn fit_view(scene: &Rc<RefCell<GeomScene>>, renderer: &Rc<RefCell<Option<Renderer>>>) {
+    let Some(sphere) = scene.borrow().bounding_sphere() else {
+        return;
+    };
+    let center = Vec3::from_array(sphere.center);
+    let radius = sphere.radius.max(0.25);
+
+    let mut renderer = renderer.borrow_mut();
+    let Some(r) = renderer.as_mut() else {
+        return;
+    };
+    let rotation = r.camera_rotation();
+    r.set_camera_view(center.to_array(), rotation, radius * FIT_VIEW_DISTANCE);
+    r.render();
+}
+
+/// Cuts every body with the horizontal plane through the scene center and
+/// draws the hatched section as overlay lines. Returns the loop count.
+fn show_section(scene: &Rc<RefCell<GeomScene>>, renderer: &Rc<RefCell<Option<Renderer>>>) -> usize {
+    let scene = scene.borrow();
+    let Some(sphere) = scene.bounding_sphere() else {
+        return 0;
+    };
+    let plane = cad_core::SketchPlane {
+        origin: sphere.center,
+        ..cad_core::SketchPlane::XZ
+    };
+    let hatch = Hatch {
+        spacing: (sphere.radius / 20.0).max(0.01),
+        ..Hatch::default()
+    };
+    let section = scene.section(&plane, &[], hatch);
+
+    let mut lines = Vec::new();
+    for points in &section.loops {
+        for (i, a) in points.iter().enumerate() {
+            lines.push(OverlayLine {
+                a: *a,
+                b: points[(i + 1) % points.len()],
+                color: [1.0, 0.55, 0.2],
+            });
+        }
+    }
+    lines.extend(section.hatch.iter().map(|[a, b]| OverlayLine {
+        a: *a,
+        b: *b,
+        color: [0.85, 0.5, 0.3],
+    }));
+
+    let mut renderer = renderer.borrow_mut();
+    if let Some(r) = renderer.as_mut() {
+        r.set_overlay_lines(lines);
+        r.render();
+    }
+    section.loops.len()
+}
+
 fn animate_camera_to_sketch_plane(renderer: Rc<RefCell<Option<Renderer>>>, plane: SketchPlane) {
     let (start_target, start_radius, start_rot) = {
         let mut renderer_borrow = renderer.borrow_mut();
@@ -2665,6 +3323,7 @@ fn attach_editor_controls(
     viewcube_el: web_sys::HtmlCanvasElement,
     scene: Rc<RefCell<GeomScene>>,
     renderer: Rc<RefCell<Option<Renderer>>>,
+    ws_handle: Rc<RefCell<Option<WebSocket>>>,
     tool_mode: ReadSignal<EditorTool>,
     set_tool_mode: WriteSignal<EditorTool>,
     set_selected_id: WriteSignal<Option<ObjectId>>,
@@ -2950,6 +3609,7 @@ fn attach_editor_controls(
             let canvas_el = canvas_el.clone();
             let scene = scene.clone();
             let renderer = renderer.clone();
+            let ws_handle = ws_handle.clone();
             let drag_state = drag_state.clone();
             let viewcube_state = viewcube_state.clone();
             let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
@@ -2985,7 +3645,7 @@ fn attach_editor_controls(
                     }
                 };
 
-                apply_transform(&scene, &renderer, ds.object_id, new_t);
+                apply_transform(&scene, &renderer, &ws_handle, ds.object_id, new_t);
                 set_transform_ui.set(TransformUi::from_transform(new_t));
                 update_overlay(
                     &scene,
@@ -3016,6 +3676,9 @@ fn attach_editor_controls(
 
         // Keyboard shortcuts
         {
+            let scene = scene.clone();
+            let renderer = renderer.clone();
+            let request_overlay_refresh = request_overlay_refresh.clone();
             let set_sketch_anchor = set_sketch_anchor;
             let set_sketch_cursor = set_sketch_cursor;
             let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
@@ -3040,6 +3703,10 @@ fn attach_editor_controls(
                     set_tool_mode.set(EditorTool::Move);
                     set_sketch_anchor.set(None);
                     set_sketch_cursor.set(None);
+                } else if key == "f" || key == "F" {
+                    event.prevent_default();
+                    fit_view(&scene, &renderer);
+                    (request_overlay_refresh.as_ref())();
                 } else if key == "Escape" {
                     event.prevent_default();
                     set_tool_mode.set(EditorTool::None);
@@ -3090,24 +3757,16 @@ fn attach_editor_controls(
 fn apply_transform(
     scene: &Rc<RefCell<GeomScene>>,
     renderer: &Rc<RefCell<Option<Renderer>>>,
+    ws_handle: &Rc<RefCell<Option<WebSocket>>>,
     id: ObjectId,
     transform: Transform,
 ) {
-    let mesh = {
-        let mut scene = scene.borrow_mut();
-        let _ = scene.set_object_transform(id, transform);
-        match scene.mesh() {
-            Ok(mesh) => mesh,
-            Err(err) => {
-                log(&format!("tessellation failed: {err}"));
-                return;
-            }
-        }
-    };
-    if let Some(renderer) = renderer.borrow_mut().as_mut() {
-        renderer.set_mesh(mesh);
-        renderer.render();
+    if let Some(ws) = ws_handle.borrow().as_ref() {
+        send_transform(ws, id, transform);
     }
+    let mut scene = scene.borrow_mut();
+    let _ = scene.set_object_transform(id, transform);
+    draw_scene(&scene, renderer);
 }
 
 fn gizmo_dimensions(base_r: f32, dist_to_obj: f32) -> (f32, f32) {
@@ -3140,14 +3799,15 @@ fn update_overlay(
     };
 
     let origin = Vec3::from_array(t.translation);
-    let rot = quat_from_transform(t);
+    let rot = t.rotation_quat();
     let (eye, _target) = renderer.camera_eye_target();
     let eye = Vec3::from_array(eye);
     let to_camera = (eye - origin).normalize_or_zero();
     let mut lines = Vec::new();
     // Selection highlight (oriented local AABB).
     if let Some(aabb) = scene_ref.local_aabb(id) {
-        add_aabb_wireframe(&mut lines, origin, rot, aabb, [1.0, 0.85, 0.25]);
+        let scale = Vec3::from_array(t.scale);
+        add_aabb_wireframe(&mut lines, origin, rot, scale, aabb, [1.0, 0.85, 0.25]);
     }
 
     if show_gizmo {
@@ -3346,6 +4006,7 @@ fn add_aabb_wireframe(
     lines: &mut Vec<OverlayLine>,
     origin: Vec3,
     rot: Quat,
+    scale: Vec3,
     aabb: cad_geom::Aabb,
     color: [f32; 3],
 ) {
@@ -3361,7 +4022,7 @@ fn add_aabb_wireframe(
         Vec3::new(max.x, max.y, max.z),
         Vec3::new(min.x, max.y, max.z),
     ]
-    .map(|p| origin + rot * p);
+    .map(|p| origin + rot * (p * scale));
 
     let edges = [
         (0, 1),
@@ -3437,7 +4098,7 @@ fn hit_gizmo(
         return None;
     };
     let origin = Vec3::from_array(t.translation);
-    let rot = quat_from_transform(t);
+    let rot = t.rotation_quat();
     let axis_x = (rot * Vec3::X).normalize();
     let axis_y = (rot * Vec3::Y).normalize();
     let axis_z = (rot * Vec3::Z).normalize();
@@ -3550,7 +4211,7 @@ fn drag_rotate(ds: DragState, axis: Axis, ray_o: Vec3, ray_d: Vec3) -> Option<Tr
         delta += std::f32::consts::TAU;
     }
 
-    let start_q = quat_from_transform(ds.start_transform);
+    let start_q = ds.start_transform.rotation_quat();
     let axis_local = match axis {
         Axis::X => Vec3::X,
         Axis::Y => Vec3::Y,
@@ -3560,7 +4221,7 @@ fn drag_rotate(ds: DragState, axis: Axis, ray_o: Vec3, ray_d: Vec3) -> Option<Tr
     let q = (start_q * q_local).normalize();
 
     let mut out = ds.start_transform;
-    out.rotation = [q.x, q.y, q.z, q.w];
+    out.rotation = q.to_array();
     Some(out)
 }
 
@@ -3643,28 +4304,45 @@ fn canvas_cursor(canvas: &web_sys::HtmlCanvasElement, event: &MouseEvent) -> (f3
     )
 }
 
-fn quat_from_transform(transform: Transform) -> Quat {
-    Quat::from_xyzw(
-        transform.rotation[0],
-        transform.rotation[1],
-        transform.rotation[2],
-        transform.rotation[3],
-    )
-    .normalize()
+fn update_mesh(scene: &Rc<RefCell<GeomScene>>, renderer: &Rc<RefCell<Option<Renderer>>>) {
+    let mut scene = scene.borrow_mut();
+    scene.touch_document(Date::now() as u64);
+    draw_scene(&scene, renderer);
 }
 
-fn update_mesh(scene: &Rc<RefCell<GeomScene>>, renderer: &Rc<RefCell<Option<Renderer>>>) {
-    let mesh = match scene.borrow_mut().mesh() {
-        Ok(mesh) => mesh,
+thread_local! {
+    /// Counts the scene meshes started by [`draw_scene`], so that one
+    /// finishing late never replaces a newer one.
+    static MESH_GENERATION: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Combines the scene mesh without freezing the page, then draws it. The
+/// scene is free to change meanwhile; the mesh shows it as it was.
+fn draw_scene(scene: &GeomScene, renderer: &Rc<RefCell<Option<Renderer>>>) {
+    let parts = match scene.mesh_parts() {
+        Ok(parts) => Some(parts),
+        Err(GeomError::EmptyScene) => None,
         Err(err) => {
             log(&format!("tessellation failed: {err}"));
             return;
         }
     };
-    if let Some(renderer) = renderer.borrow_mut().as_mut() {
-        renderer.set_mesh(mesh);
-        renderer.render();
-    }
+    let generation = MESH_GENERATION.get() + 1;
+    MESH_GENERATION.set(generation);
+    let renderer = renderer.clone();
+    spawn_local(async move {
+        let mesh = match parts {
+            Some(parts) => parts.mesh_async().await,
+            None => TriMesh::default(),
+        };
+        if MESH_GENERATION.get() != generation {
+            return;
+        }
+        if let Some(renderer) = renderer.borrow_mut().as_mut() {
+            renderer.set_mesh(mesh);
+            renderer.render();
+        }
+    });
 }
 
 fn schedule_renderer_init(
@@ -3716,7 +4394,16 @@ fn schedule_renderer_init(
     });
 }
 
-fn connect_ws(handle: Rc<RefCell<Option<WebSocket>>>) {
+/// How long to wait before reconnecting a dropped socket.
+const RECONNECT_DELAY_MS: i32 = 2000;
+
+fn connect_ws(
+    handle: Rc<RefCell<Option<WebSocket>>>,
+    set_latency_ms: WriteSignal<Option<u64>>,
+    set_capabilities: WriteSignal<Capabilities>,
+    set_job_queue: WriteSignal<(usize, usize)>,
+    set_selected_id: WriteSignal<Option<ObjectId>>,
+) {
     let window = match web_sys::window() {
         Some(window) => window,
         None => return,
@@ -3747,32 +4434,315 @@ fn connect_ws(handle: Rc<RefCell<Option<WebSocket>>>) {
         }
     };
 
+    // Set while the server restores the previous connection's session, which
+    // brings back its document and sign-in.
+    let resuming = Rc::new(Cell::new(false));
     let ws_open = ws.clone();
+    let resuming_open = resuming.clone();
     let onopen = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+        let resume = SESSION.with_borrow(Clone::clone);
+        resuming_open.set(resume.is_some());
         let msg = ClientMsg::Hello {
             client_version: env!("CARGO_PKG_VERSION").to_string(),
+            compression: Compression::PREFERRED.to_vec(),
+            encoding: Encoding::PREFERRED.to_vec(),
+            resume,
         };
         if let Ok(text) = serde_json::to_string(&msg) {
             let _ = ws_open.send_with_str(&text);
         }
+        if !resuming_open.get() {
+            request_document(&ws_open);
+        }
     }) as Box<dyn FnMut(_)>);
     ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
     onopen.forget();
 
+    // Ping the server, and give up on it once it falls silent.
+    let last_seen = Rc::new(Cell::new(Date::now()));
+    let ws_ping = ws.clone();
+    let last_seen_ping = last_seen.clone();
+    let heartbeat = Closure::wrap(Box::new(move || {
+        if ws_ping.ready_state() != WebSocket::OPEN {
+            return;
+        }
+        let now = Date::now();
+        if now - last_seen_ping.get() > PEER_TIMEOUT_MS as f64 {
+            log("server stopped responding");
+            let _ = ws_ping.close();
+            return;
+        }
+        let ping = ClientMsg::Ping {
+            sent_ms: now as u64,
+        };
+        if let Ok(text) = serde_json::to_string(&ping) {
+            let _ = ws_ping.send_with_str(&text);
+        }
+    }) as Box<dyn FnMut()>);
+    let heartbeat_id = window
+        .set_interval_with_callback_and_timeout_and_arguments_0(
+            heartbeat.as_ref().unchecked_ref(),
+            PING_INTERVAL_MS as i32,
+        )
+        .ok();
+    heartbeat.forget();
+
+    ws.set_binary_type(BinaryType::Arraybuffer);
+    let ws_message = ws.clone();
+    // The new connection starts over from a snapshot.
+    SYNC.set(SyncState::default());
+    let mut reassembler = Reassembler::default();
+    let mut roster = Roster::default();
     let onmessage = Closure::wrap(Box::new(move |event: MessageEvent| {
-        if let Some(text) = event.data().as_string() {
-            if let Ok(msg) = serde_json::from_str::<ServerMsg>(&text) {
-                log(&format!("server: {msg:?}"));
-            } else {
-                log(&format!("ws message: {text}"));
+        last_seen.set(Date::now());
+        let data = event.data();
+        let frame = if let Some(text) = data.as_string() {
+            Frame::Text(text)
+        } else if let Ok(buffer) = data.dyn_into::<js_sys::ArrayBuffer>() {
+            Frame::Binary(js_sys::Uint8Array::new(&buffer).to_vec())
+        } else {
+            return;
+        };
+        let frame = match reassembler.push(frame) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return,
+            Err(err) => {
+                log(&format!("bad server frame: {err}"));
+                return;
             }
+        };
+        match Envelope::<ServerMsg>::from_frame(frame) {
+            Ok(Envelope { msg, .. }) if roster.apply(&msg) => {
+                let names: Vec<_> = roster
+                    .peers()
+                    .map(|remote| remote.peer.name.as_str())
+                    .collect();
+                log(&format!("peers: {}", names.join(", ")));
+            }
+            Ok(Envelope { msg, .. }) => match msg {
+                ServerMsg::ModelSnapshot { seq, model } => {
+                    SYNC.with_borrow_mut(|sync| sync.on_snapshot(seq));
+                    log(&format!(
+                        "server document: {} objects, revision {}",
+                        model.objects().len(),
+                        model.info().revision
+                    ));
+                }
+                ServerMsg::ModelDelta { seq, delta } => {
+                    match SYNC.with_borrow_mut(|sync| sync.on_delta(seq)) {
+                        SyncAction::Apply => log(&format!(
+                            "server delta {seq}: {} added, {} changed, {} removed",
+                            delta.added.len(),
+                            delta.changed.len(),
+                            delta.removed.len()
+                        )),
+                        SyncAction::Skip => {}
+                        SyncAction::Resync => {
+                            if let Ok(text) = serde_json::to_string(&ClientMsg::RequestModel) {
+                                let _ = ws_message.send_with_str(&text);
+                            }
+                        }
+                    }
+                }
+                ServerMsg::ObjectAdded { seq, peer, .. }
+                | ServerMsg::ObjectChanged { seq, peer, .. }
+                | ServerMsg::ObjectRemoved { seq, peer, .. } => {
+                    match SYNC.with_borrow_mut(|sync| sync.on_notification(seq)) {
+                        SyncAction::Apply => {
+                            let by = roster
+                                .peer(peer)
+                                .map_or("another client", |remote| remote.peer.name.as_str());
+                            let change = match &msg {
+                                ServerMsg::ObjectAdded { object, .. } => {
+                                    format!("added object {}", object.id)
+                                }
+                                ServerMsg::ObjectChanged { object, .. } => {
+                                    format!("changed object {}", object.id)
+                                }
+                                ServerMsg::ObjectRemoved { id, .. } => {
+                                    format!("removed object {id}")
+                                }
+                                _ => unreachable!(),
+                            };
+                            log(&format!("{by} {change}"));
+                        }
+                        SyncAction::Skip => {}
+                        SyncAction::Resync => {
+                            if let Ok(text) = serde_json::to_string(&ClientMsg::RequestModel) {
+                                let _ = ws_message.send_with_str(&text);
+                            }
+                        }
+                    }
+                }
+                ServerMsg::HelloAck {
+                    auth_required,
+                    capabilities,
+                    session,
+                    ..
+                } => {
+                    set_capabilities.set(capabilities);
+                    if session.is_some() {
+                        SESSION.set(session);
+                    }
+                    if !auth_required || resuming.get() {
+                        return;
+                    }
+                    let Some(token) = url_param("token") else {
+                        log("server wants a token; add ?token=... to the page URL");
+                        return;
+                    };
+                    if let Ok(text) = serde_json::to_string(&ClientMsg::Authenticate { token }) {
+                        let _ = ws_message.send_with_str(&text);
+                    }
+                }
+                ServerMsg::AuthResult { user: Some(user) } => {
+                    log(&format!("signed in as {user}"));
+                    // Anything asked for before signing in was refused.
+                    request_document(&ws_message);
+                }
+                ServerMsg::AuthResult { user: None } => log("server refused the token"),
+                ServerMsg::SessionResumed {
+                    selection, jobs, ..
+                } => {
+                    resuming.set(false);
+                    set_selected_id.set(selection.first().copied());
+                    log(&format!("reconnected; jobs still pending: {jobs:?}"));
+                }
+                ServerMsg::Error {
+                    code: ErrorCode::UnknownSession,
+                    ..
+                } => {
+                    // Too late to resume; start over on a fresh connection.
+                    log("session expired");
+                    SESSION.set(None);
+                    let _ = ws_message.close();
+                }
+                ServerMsg::Ping { sent_ms } => {
+                    if let Ok(text) = serde_json::to_string(&ClientMsg::Pong { sent_ms }) {
+                        let _ = ws_message.send_with_str(&text);
+                    }
+                }
+                ServerMsg::Pong { sent_ms, .. } => {
+                    set_latency_ms.set(Some((Date::now() as u64).saturating_sub(sent_ms)));
+                }
+                ServerMsg::ExportReady {
+                    name,
+                    mime,
+                    content,
+                    ..
+                } => {
+                    let result = match content {
+                        ExportContent::Bytes(bytes) => download_bytes(&name, &mime, &bytes),
+                        ExportContent::Url(url) => download_url(&name, &url),
+                    };
+                    match result {
+                        Ok(()) => log(&format!("downloaded {name}")),
+                        Err(err) => log(&format!("download of {name} failed: {err:?}")),
+                    }
+                }
+                ServerMsg::DocumentOpened { document, role } => {
+                    let access = match role {
+                        Some(Role::Viewer) => ", view only",
+                        _ => "",
+                    };
+                    log(&format!(
+                        "opened document {} \"{}\" ({} objects{access})",
+                        document.id, document.name, document.objects
+                    ))
+                }
+                ServerMsg::RoleChanged { user, role } => {
+                    log(&format!("{user} is now {role:?} of the document"))
+                }
+                ServerMsg::DocumentSaved { id, revision } => {
+                    log(&format!("document {id} saved as revision {revision}"))
+                }
+                ServerMsg::RevisionRestored {
+                    id,
+                    revision,
+                    saved,
+                    ..
+                } => log(&format!(
+                    "document {id} restored to revision {revision}, saved as {saved}"
+                )),
+                ServerMsg::QueueStatus { pending, running } => {
+                    set_job_queue.set((pending, running));
+                }
+                ServerMsg::Chat { peer, text, .. } => {
+                    let by = match roster.peer(peer) {
+                        Some(remote) => remote.peer.name.clone(),
+                        None => "you".to_string(),
+                    };
+                    log(&format!("{by}: {text}"));
+                }
+                ServerMsg::Comments { comments } => {
+                    log(&format!("{} comments on the document", comments.len()));
+                }
+                ServerMsg::CommentAdded { comment, .. } => log(&format!(
+                    "comment {} by {} at {:?}: {}",
+                    comment.id, comment.author, comment.anchor, comment.text
+                )),
+                ServerMsg::CommentRemoved { id, .. } => log(&format!("comment {id} resolved")),
+                ServerMsg::SlowDown { retry_after_ms } => {
+                    STREAM.with_borrow_mut(|throttle| {
+                        throttle.slow_down(Date::now() as u64, retry_after_ms)
+                    });
+                    log(&format!(
+                        "server asked to slow down for {retry_after_ms} ms"
+                    ));
+                }
+                ServerMsg::JobQueued { job_id, position } => match position {
+                    0 => log(&format!("job {job_id} runs next")),
+                    ahead => log(&format!("job {job_id} waits behind {ahead} others")),
+                },
+                ServerMsg::HistoryApplied {
+                    step, peer, text, ..
+                } => {
+                    let by = match roster.peer(peer) {
+                        Some(remote) => remote.peer.name.clone(),
+                        None => "you".to_string(),
+                    };
+                    log(&format!("{step:?} by {by}: {text}"));
+                }
+                ServerMsg::ImportResult { upload_id, objects } => log(&format!(
+                    "upload {upload_id} imported as objects {objects:?}"
+                )),
+                ServerMsg::MeshData(mesh) => log(&format!(
+                    "server mesh: {:?}, {} triangles",
+                    mesh.target,
+                    mesh.indices.len() / 3
+                )),
+                msg => log(&format!("server: {msg:?}")),
+            },
+            Err(err) => log(&format!("bad server frame: {err}")),
         }
     }) as Box<dyn FnMut(_)>);
     ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
     onmessage.forget();
 
+    let reconnect_handle = handle.clone();
     let onclose = Closure::wrap(Box::new(move |_event: web_sys::CloseEvent| {
         log("ws closed");
+        set_latency_ms.set(None);
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+        if let Some(id) = heartbeat_id {
+            window.clear_interval_with_handle(id);
+        }
+        let handle = reconnect_handle.clone();
+        let reconnect = Closure::once_into_js(move || {
+            connect_ws(
+                handle,
+                set_latency_ms,
+                set_capabilities,
+                set_job_queue,
+                set_selected_id,
+            );
+        });
+        let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+            reconnect.unchecked_ref(),
+            RECONNECT_DELAY_MS,
+        );
     }) as Box<dyn FnMut(_)>);
     ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
     onclose.forget();
@@ -3780,6 +4750,177 @@ fn connect_ws(handle: Rc<RefCell<Option<WebSocket>>>) {
     *handle.borrow_mut() = Some(ws);
 }
 
+thread_local! {
+    /// Numbers the client's uploads and splits them; uploads are the only
+    /// binary frames it sends.
+    static UPLOADS: RefCell<(u64, Chunker)> = RefCell::default();
+    /// Paces the messages the server rate-limits; see [`send_streamed`].
+    static STREAM: RefCell<Throttle<ClientMsg>> = RefCell::default();
+    /// The server's name for this client's session, to resume it after a
+    /// reconnect.
+    static SESSION: RefCell<Option<String>> = RefCell::default();
+    /// The last document update applied, which the client's moves are based
+    /// on; see [`send_transform`].
+    static SYNC: RefCell<SyncState> = RefCell::default();
+}
+
+/// Tells the server of a move of `id`, stamped with the last update applied
+/// so that concurrent moves of it settle the same way for everyone.
+fn send_transform(ws: &WebSocket, id: ObjectId, transform: Transform) {
+    let base_seq = SYNC.with_borrow(SyncState::seq);
+    send_streamed(
+        ws,
+        ClientMsg::SetTransform {
+            id,
+            transform,
+            base_seq,
+        },
+    );
+}
+
+/// Sends a [`ClientMsg::is_streamed`] message within the server's rate,
+/// holding it back if need be; of the messages held, only the latest goes
+/// out.
+fn send_streamed(ws: &WebSocket, msg: ClientMsg) {
+    let ready = STREAM.with_borrow_mut(|throttle| throttle.push(Date::now() as u64, msg));
+    match ready {
+        Some(msg) => send_open(ws, &msg),
+        None => schedule_stream_flush(ws.clone()),
+    }
+}
+
+/// Sends the held streamed message once [`STREAM`] lets it through.
+fn schedule_stream_flush(ws: WebSocket) {
+    let Some(delay) = STREAM.with_borrow(|throttle| throttle.delay_ms(Date::now() as u64)) else {
+        return;
+    };
+    let flush = Closure::once_into_js(move || {
+        match STREAM.with_borrow_mut(|throttle| throttle.poll(Date::now() as u64)) {
+            Some(msg) => send_open(&ws, &msg),
+            // Held back further by a SlowDown in the meantime.
+            None => schedule_stream_flush(ws),
+        }
+    });
+    if let Some(window) = web_sys::window() {
+        let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+            flush.unchecked_ref(),
+            delay as i32,
+        );
+    }
+}
+
+/// Sends `msg` if the socket is open, dropping it otherwise.
+fn send_open(ws: &WebSocket, msg: &ClientMsg) {
+    if ws.ready_state() != WebSocket::OPEN {
+        return;
+    }
+    if let Ok(text) = serde_json::to_string(msg) {
+        let _ = ws.send_with_str(&text);
+    }
+}
+
+/// Asks the user for a STEP or STL file and uploads it for import.
+fn pick_and_upload(ws: WebSocket) -> Result<(), JsValue> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| JsValue::from_str("no document"))?;
+    let input: HtmlInputElement = document.create_element("input")?.dyn_into()?;
+    input.set_type("file");
+    input.set_accept(".stl,.step,.stp");
+    let picked = input.clone();
+    let onchange = Closure::once(move |_event: web_sys::Event| {
+        let Some(file) = picked.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        spawn_local(async move {
+            if let Err(err) = upload_file(&ws, &file).await {
+                log(&format!("upload of {} failed: {err:?}", file.name()));
+            }
+        });
+    });
+    input.set_onchange(Some(onchange.as_ref().unchecked_ref()));
+    onchange.forget();
+    input.click();
+    Ok(())
+}
+
+/// Announces `file` with [`ClientMsg::Upload`], then sends its contents.
+async fn upload_file(ws: &WebSocket, file: &web_sys::File) -> Result<(), JsValue> {
+    let name = file.name();
+    let format = ImportFormat::from_file_name(&name)
+        .ok_or_else(|| JsValue::from_str("not a STEP or STL file"))?;
+    let buffer = wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await?;
+    let data = js_sys::Uint8Array::new(&buffer).to_vec();
+
+    UPLOADS.with_borrow_mut(|(next_upload, chunker)| {
+        let upload_id = *next_upload;
+        *next_upload += 1;
+        let msg = ClientMsg::Upload {
+            upload_id,
+            name: name.clone(),
+            format,
+            size: data.len() as u64,
+        };
+        let text =
+            serde_json::to_string(&msg).map_err(|err| JsValue::from_str(&err.to_string()))?;
+        ws.send_with_str(&text)?;
+        let upload = UploadData { upload_id, data };
+        for frame in chunker.split(Frame::Binary(upload.to_frame())) {
+            match frame {
+                Frame::Text(text) => ws.send_with_str(&text)?,
+                Frame::Binary(bytes) => ws.send_with_u8_array(&bytes)?,
+            }
+        }
+        log(&format!("uploading {name}"));
+        Ok(())
+    })
+}
+
+/// Offers `bytes` to the user as a file download.
+fn download_bytes(name: &str, mime: &str, bytes: &[u8]) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+    let result = download_url(name, &url);
+    web_sys::Url::revoke_object_url(&url)?;
+    result
+}
+
+fn download_url(name: &str, url: &str) -> Result<(), JsValue> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| JsValue::from_str("no document"))?;
+    let anchor: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(url);
+    anchor.set_download(name);
+    anchor.click();
+    Ok(())
+}
+
+/// Asks for the document named by the `document` query parameter of the
+/// page URL, or else for the one the server started the client in.
+fn request_document(ws: &WebSocket) {
+    let msg = match url_param("document").and_then(|id| id.parse().ok()) {
+        Some(id) => ClientMsg::OpenDocument { id },
+        None => ClientMsg::RequestModel,
+    };
+    if let Ok(text) = serde_json::to_string(&msg) {
+        let _ = ws.send_with_str(&text);
+    }
+}
+
+/// A query parameter of the page URL.
+fn url_param(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    let value = search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))?;
+    js_sys::decode_uri_component(value).ok()?.as_string()
+}
+
 fn log(text: &str) {
     web_sys::console::log_1(&text.into());
 }